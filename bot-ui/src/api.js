@@ -63,6 +63,24 @@ export class Api {
     return this.fetch("after-streams");
   }
 
+  /**
+   * Get the songs queued up after the current song.
+   */
+  queue() {
+    return fetch(`${this.url}/queue`).then(r => {
+      if (!r.ok) {
+        throw Error(`got bad status code: ${r.status}`);
+      }
+
+      // NB: no content means there is no player.
+      if (r.status === 204) {
+        return [];
+      }
+
+      return r.json();
+    });
+  }
+
   /**
    * Delete an after stream.
    *
@@ -231,6 +249,26 @@ export class Api {
     });
   }
 
+  commandsEditLimits(key, limits) {
+    return this.fetch(["commands", key.channel, key.name, "limits"], {
+      method: "PUT",
+      headers: {
+        "Content-Type": "application/json",
+      },
+      body: JSON.stringify(limits),
+    });
+  }
+
+  commandsEditHelp(key, help) {
+    return this.fetch(["commands", key.channel, key.name, "help"], {
+      method: "PUT",
+      headers: {
+        "Content-Type": "application/json",
+      },
+      body: JSON.stringify(help),
+    });
+  }
+
   promotions(channel) {
     return this.fetch(["promotions", channel]);
   }
@@ -69,6 +69,62 @@ export default class Commands extends React.Component {
     }
   }
 
+  /**
+   * Prompt for a new cooldown of the given kind for the command.
+   */
+  async editCooldown(c, what, label) {
+    let value = window.prompt(`${label} for ${c.key.name} (e.g. 30s or 1m), leave empty for none:`, c.limits[what] || "");
+
+    if (value === null) {
+      return;
+    }
+
+    this.setState({
+      loading: true,
+      error: null,
+    });
+
+    try {
+      let limits = Object.assign({}, c.limits);
+      limits[what] = value.trim() === "" ? null : value.trim();
+      await this.api.commandsEditLimits(c.key, limits);
+      await this.list();
+    } catch (e) {
+      this.setState({
+        loading: false,
+        error: `Failed to set cooldown: ${e}`,
+      });
+    }
+  }
+
+  /**
+   * Prompt for the description or usage of the command, as shown by !help.
+   */
+  async editHelp(c, what, label) {
+    let value = window.prompt(`${label} for ${c.key.name}, leave empty for none:`, c[what] || "");
+
+    if (value === null) {
+      return;
+    }
+
+    this.setState({
+      loading: true,
+      error: null,
+    });
+
+    try {
+      let help = {description: c.description, usage: c.usage};
+      help[what] = value.trim() === "" ? null : value.trim();
+      await this.api.commandsEditHelp(c.key, help);
+      await this.list();
+    } catch (e) {
+      this.setState({
+        loading: false,
+        error: `Failed to set ${label.toLowerCase()}: ${e}`,
+      });
+    }
+  }
+
   render() {
     let content = null;
 
@@ -87,6 +143,10 @@ export default class Commands extends React.Component {
                 <th>Name</th>
                 <th>Group</th>
                 <th className="table-fill">Text</th>
+                <th>Description</th>
+                <th>Usage</th>
+                <th>Cooldown</th>
+                <th>Moderator Cooldown</th>
                 <th></th>
               </tr>
             </thead>
@@ -111,6 +171,26 @@ export default class Commands extends React.Component {
                     <td className="command-name">{c.key.name}</td>
                     <td className="command-group"><b>{c.group}</b></td>
                     <td className="command-template">{c.template}</td>
+                    <td>
+                      <Button size="sm" variant="secondary" onClick={_ => this.editHelp(c, "description", "Description")}>
+                        {c.description || "none"}
+                      </Button>
+                    </td>
+                    <td>
+                      <Button size="sm" variant="secondary" onClick={_ => this.editHelp(c, "usage", "Usage")}>
+                        {c.usage || "none"}
+                      </Button>
+                    </td>
+                    <td>
+                      <Button size="sm" variant="secondary" onClick={_ => this.editCooldown(c, "cooldown", "Cooldown")}>
+                        {c.limits.cooldown || "none"}
+                      </Button>
+                    </td>
+                    <td>
+                      <Button size="sm" variant="secondary" onClick={_ => this.editCooldown(c, "moderator_cooldown", "Moderator cooldown")}>
+                        {c.limits.moderator_cooldown || "none"}
+                      </Button>
+                    </td>
                     <td>{disabled}</td>
                   </tr>
                 );
@@ -0,0 +1,107 @@
+import React from "react";
+import {Alert, Table} from "react-bootstrap";
+import * as utils from "../utils.js";
+import {Api} from "../api.js";
+import {Loading, Error} from 'shared-ui/components';
+
+/**
+ * How often the queue is refreshed, in milliseconds.
+ */
+const REFRESH_INTERVAL = 5000;
+
+export default class Queue extends React.Component {
+  constructor(props) {
+    super(props);
+    this.api = new Api(utils.apiUrl());
+
+    this.state = {
+      loading: true,
+      error: null,
+      data: null,
+    };
+  }
+
+  async componentDidMount() {
+    await this.list();
+    this.interval = setInterval(() => this.list(), REFRESH_INTERVAL);
+  }
+
+  componentWillUnmount() {
+    clearInterval(this.interval);
+  }
+
+  /**
+   * Refresh the song queue.
+   */
+  async list() {
+    try {
+      let data = await this.api.queue();
+
+      this.setState({
+        loading: false,
+        error: null,
+        data,
+      });
+    } catch(e) {
+      this.setState({
+        loading: false,
+        error: `failed to request song queue: ${e}`,
+        data: null,
+      });
+    }
+  }
+
+  render() {
+    let content = null;
+
+    if (this.state.data) {
+      if (this.state.data.length === 0) {
+        content = (
+          <Alert variant="info">
+            No songs are queued!
+          </Alert>
+        );
+      } else {
+        content = (
+          <Table responsive="sm">
+            <thead>
+              <tr>
+                <th>#</th>
+                <th className="table-fill">Song</th>
+                <th>Requested By</th>
+                <th>Duration</th>
+              </tr>
+            </thead>
+            <tbody>
+              {this.state.data.map((item, index) => {
+                let name = item.name;
+
+                if (item.artists) {
+                  name = `${name} by ${item.artists}`;
+                }
+
+                return (
+                  <tr key={index}>
+                    <td>{index + 1}</td>
+                    <td>{name}</td>
+                    <td>{item.user || <em>unknown</em>}</td>
+                    <td>{item.duration}</td>
+                  </tr>
+                );
+              })}
+            </tbody>
+          </Table>
+        );
+      }
+    }
+
+    return (
+      <div id="queue" className="container p-4">
+        <h1 className='oxi-page-title'>Song Queue</h1>
+        <Loading isLoading={this.state.loading} />
+        <Error error={this.state.error} />
+        {content}
+      </div>
+    );
+  }
+}
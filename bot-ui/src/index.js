@@ -19,6 +19,7 @@ import Aliases from "./components/Aliases";
 import Themes from "./components/Themes";
 import YouTube from "./components/YouTube";
 import Chat from "./components/Chat";
+import Queue from "./components/Queue";
 import Authorization from "./components/Authorization";
 import ConfigurationPrompt from "./components/ConfigurationPrompt";
 import * as semver from "semver";
@@ -406,6 +407,7 @@ function AppRouter() {
       <Route path="/overlay/" component={Overlay} />
       <Route path="/youtube" component={YouTube} />
       <Route path="/chat" component={Chat} />
+      <Route path="/queue" component={Queue} />
     </Router>
   );
 }
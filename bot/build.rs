@@ -79,6 +79,34 @@ fn file_version() -> Option<(String, u64)> {
     Some((version, info))
 }
 
+/// Find the version of the latest migration in the given directory, using
+/// the same naming scheme as diesel.
+fn latest_migration(dir: &Path) -> Result<String> {
+    let mut latest = String::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+
+        let version = match name.split('_').next() {
+            Some(version) => version.replace('-', ""),
+            None => continue,
+        };
+
+        if version > latest {
+            latest = version;
+        }
+    }
+
+    Ok(latest)
+}
+
 fn main() -> Result<()> {
     if cfg!(target_os = "windows") {
         use winres::VersionInfo::*;
@@ -123,6 +151,9 @@ fn main() -> Result<()> {
     fs::write(out_dir.join("version.txt"), &version).context("writing version.txt")?;
     fs::write(out_dir.join("user_agent.txt"), &user_agent).context("writing user_agent.txt")?;
 
+    let migration = latest_migration(Path::new("migrations")).context("reading migrations")?;
+    fs::write(out_dir.join("migration.txt"), migration).context("writing migration.txt")?;
+
     // backtrace compile probe
     match backtrace_compile_probe() {
         Some(status) if status.success() => println!("cargo:rustc-cfg=backtrace"),
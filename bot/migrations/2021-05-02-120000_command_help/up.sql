@@ -0,0 +1,2 @@
+ALTER TABLE commands ADD COLUMN description VARCHAR DEFAULT NULL;
+ALTER TABLE commands ADD COLUMN usage VARCHAR DEFAULT NULL;
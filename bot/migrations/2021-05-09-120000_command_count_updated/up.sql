@@ -0,0 +1,2 @@
+ALTER TABLE commands ADD COLUMN count_updated_at TIMESTAMP DEFAULT NULL;
+ALTER TABLE commands ADD COLUMN count_updated_by VARCHAR DEFAULT NULL;
@@ -0,0 +1,8 @@
+CREATE TABLE timers (
+    channel VARCHAR NOT NULL,
+    name VARCHAR NOT NULL,
+    interval INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    paused BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (channel, name)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE commands ADD COLUMN games VARCHAR DEFAULT NULL;
+ALTER TABLE aliases ADD COLUMN games VARCHAR DEFAULT NULL;
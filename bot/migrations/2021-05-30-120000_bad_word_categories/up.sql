@@ -0,0 +1,7 @@
+ALTER TABLE bad_words ADD COLUMN category VARCHAR DEFAULT NULL;
+
+CREATE TABLE bad_word_categories (
+    name VARCHAR NOT NULL PRIMARY KEY,
+    action VARCHAR NOT NULL DEFAULT 'delete',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE
+);
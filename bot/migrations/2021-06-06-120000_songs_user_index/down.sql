@@ -0,0 +1 @@
+DROP INDEX idx_songs_user_played;
@@ -0,0 +1 @@
+CREATE INDEX idx_songs_user_played ON songs(user, played);
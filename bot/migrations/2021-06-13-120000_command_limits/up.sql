@@ -0,0 +1,3 @@
+ALTER TABLE commands ADD COLUMN cooldown INTEGER DEFAULT NULL;
+ALTER TABLE commands ADD COLUMN user_cooldown INTEGER DEFAULT NULL;
+ALTER TABLE commands ADD COLUMN stream_limit INTEGER DEFAULT NULL;
@@ -0,0 +1 @@
+ALTER TABLE commands ADD COLUMN moderator_cooldown INTEGER DEFAULT NULL;
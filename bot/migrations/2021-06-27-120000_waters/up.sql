@@ -0,0 +1,11 @@
+CREATE TABLE waters (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    channel VARCHAR NOT NULL,
+    stream_started_at TIMESTAMP NOT NULL,
+    rewarded_at TIMESTAMP NOT NULL,
+    user VARCHAR NOT NULL,
+    amount BIGINT NOT NULL,
+    undone BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX idx_waters_channel ON waters(channel, stream_started_at);
@@ -0,0 +1 @@
+DROP TABLE bad_word_allowed;
@@ -0,0 +1,5 @@
+CREATE TABLE bad_word_allowed (
+    channel VARCHAR NOT NULL,
+    word VARCHAR NOT NULL,
+    PRIMARY KEY (channel, word)
+);
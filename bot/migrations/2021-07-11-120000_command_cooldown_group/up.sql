@@ -0,0 +1 @@
+ALTER TABLE commands ADD COLUMN cooldown_group TEXT DEFAULT NULL;
@@ -0,0 +1,10 @@
+CREATE TABLE quotes (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    channel VARCHAR NOT NULL,
+    number INTEGER NOT NULL,
+    added_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    user VARCHAR NOT NULL,
+    text TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX idx_quotes_channel_number ON quotes(channel, number);
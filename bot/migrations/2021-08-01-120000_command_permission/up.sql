@@ -0,0 +1 @@
+ALTER TABLE commands ADD COLUMN permission TEXT DEFAULT NULL;
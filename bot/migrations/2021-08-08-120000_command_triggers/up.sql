@@ -0,0 +1 @@
+ALTER TABLE commands ADD COLUMN triggers TEXT DEFAULT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN paid_channel TEXT DEFAULT NULL;
+ALTER TABLE songs ADD COLUMN paid INTEGER DEFAULT NULL;
@@ -2,7 +2,10 @@ use crate::oauth2;
 use anyhow::{bail, Result};
 use bytes::Bytes;
 use reqwest::{header, Client, Method, StatusCode, Url};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
 use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
 #[derive(Debug, Error)]
 #[error("error when sending request")]
@@ -40,6 +43,81 @@ impl BodyHelper for Option<Bytes> {
     }
 }
 
+/// Limits how many requests can be in flight at the same time, queueing the
+/// rest.
+#[derive(Debug, Clone, Default)]
+pub struct Limiter {
+    inner: Arc<LimiterInner>,
+}
+
+#[derive(Debug, Default)]
+struct LimiterInner {
+    /// NB: replaced when the limit changes. Requests holding a permit from an
+    /// old semaphore are allowed to finish.
+    semaphore: parking_lot::RwLock<Option<Arc<Semaphore>>>,
+    limit: AtomicUsize,
+    /// The total number of requests which had to wait for another request to
+    /// finish.
+    queued: AtomicU64,
+}
+
+impl Limiter {
+    /// Set the maximum number of requests in flight, where `0` means there is
+    /// no limit.
+    pub fn set_limit(&self, limit: usize) {
+        let semaphore = match limit {
+            0 => None,
+            limit => Some(Arc::new(Semaphore::new(limit))),
+        };
+
+        *self.inner.semaphore.write() = semaphore;
+        self.inner.limit.store(limit, Ordering::SeqCst);
+    }
+
+    /// Statistics on requests which have been limited.
+    pub fn stats(&self) -> LimiterStats {
+        let in_flight = match self.inner.semaphore.read().as_ref() {
+            Some(semaphore) => {
+                let limit = self.inner.limit.load(Ordering::SeqCst);
+                limit.saturating_sub(semaphore.available_permits())
+            }
+            None => 0,
+        };
+
+        LimiterStats {
+            limit: self.inner.limit.load(Ordering::SeqCst),
+            in_flight,
+            queued: self.inner.queued.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Wait until a request is allowed to be sent.
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let semaphore = self.inner.semaphore.read().clone()?;
+
+        match semaphore.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(..) => {
+                self.inner.queued.fetch_add(1, Ordering::SeqCst);
+                log::trace!("Request queued, too many requests in flight");
+                semaphore.acquire_owned().await.ok()
+            }
+        }
+    }
+}
+
+/// Statistics on requests which have been limited.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct LimiterStats {
+    /// The maximum number of requests in flight, `0` if there is no limit.
+    pub limit: usize,
+    /// The number of requests currently in flight, if they're limited.
+    pub in_flight: usize,
+    /// The total number of requests which had to wait for another request to
+    /// finish.
+    pub queued: u64,
+}
+
 #[derive(Clone)]
 pub struct RequestBuilder {
     token: Option<oauth2::SyncToken>,
@@ -53,6 +131,7 @@ pub struct RequestBuilder {
     /// Add the client id to the specified header if configured.
     client_id_header: Option<&'static str>,
     absent_body: bool,
+    limiter: Option<Limiter>,
 }
 
 impl RequestBuilder {
@@ -68,9 +147,16 @@ impl RequestBuilder {
             use_bearer: true,
             client_id_header: None,
             absent_body: false,
+            limiter: None,
         }
     }
 
+    /// Limit how many requests are in flight with the given limiter.
+    pub fn limiter(mut self, limiter: Limiter) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
     /// Use the OAuth2 header instead of Bearer when sending authentication.
     pub fn use_oauth2_header(mut self) -> Self {
         self.use_bearer = false;
@@ -189,9 +275,15 @@ impl RequestBuilder {
 
         req = req.header(header::USER_AGENT, USER_AGENT);
 
+        let permit = match &self.limiter {
+            Some(limiter) => limiter.acquire().await,
+            None => None,
+        };
+
         let res = req.send().await.map_err(SendRequestError)?;
         let status = res.status();
         let body = res.bytes().await.map_err(ReceiveResponseError)?;
+        drop(permit);
 
         if log::log_enabled!(log::Level::Trace) {
             let response = String::from_utf8_lossy(&body);
@@ -362,3 +454,35 @@ where
         self.empty_on_status(StatusCode::NOT_FOUND)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Limiter;
+
+    #[tokio::test]
+    async fn test_limiter() {
+        let limiter = Limiter::default();
+        assert!(limiter.acquire().await.is_none());
+
+        limiter.set_limit(1);
+        let permit = limiter.acquire().await.expect("permit");
+        assert_eq!(limiter.stats().in_flight, 1);
+
+        let task = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.is_some() }
+        });
+
+        while limiter.stats().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        drop(permit);
+        assert!(task.await.expect("task"));
+
+        let stats = limiter.stats();
+        assert_eq!(stats.limit, 1);
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.queued, 1);
+    }
+}
@@ -12,7 +12,7 @@ pub mod tduva;
 pub mod twitch;
 pub mod youtube;
 
-pub use self::base::RequestBuilder;
+pub use self::base::{Limiter, LimiterStats, RequestBuilder};
 pub use self::bttv::BetterTTV;
 pub use self::ffz::FrankerFaceZ;
 pub use self::github::GitHub;
@@ -32,6 +32,10 @@ pub struct Token {
     pub expires_in: Option<u64>,
     /// Scopes associated with token.
     pub scopes: Vec<String>,
+    /// Refresh token, only present for tokens which are refreshed locally
+    /// instead of through the remote API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
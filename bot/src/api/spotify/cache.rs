@@ -0,0 +1,92 @@
+//! Cache for track metadata, to avoid asking Spotify about the same tracks
+//! over and over.
+
+use crate::api::spotify::FullTrack;
+use crate::utils::Lru;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The default number of tracks to cache.
+pub(super) const DEFAULT_CAPACITY: usize = 1000;
+/// The default time tracks are cached for.
+pub(super) const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Cache of tracks by ID and market, since playability depends on the
+/// market.
+#[derive(Debug, Clone)]
+pub struct TrackCache {
+    inner: Arc<TrackCacheInner>,
+}
+
+#[derive(Debug)]
+struct TrackCacheInner {
+    cache: Mutex<Lru<(String, Option<String>), FullTrack>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Default for TrackCache {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(TrackCacheInner {
+                cache: Mutex::new(Lru::new(DEFAULT_CAPACITY, Some(DEFAULT_TTL))),
+                hits: AtomicU64::default(),
+                misses: AtomicU64::default(),
+            }),
+        }
+    }
+}
+
+impl TrackCache {
+    /// Set how many tracks are cached, where `0` disables the cache.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.inner.cache.lock().set_capacity(capacity);
+    }
+
+    /// Set how long tracks are cached for.
+    pub fn set_ttl(&self, ttl: Duration) {
+        self.inner.cache.lock().set_ttl(Some(ttl));
+    }
+
+    /// Statistics on how well the cache works.
+    pub fn stats(&self) -> TrackCacheStats {
+        TrackCacheStats {
+            len: self.inner.cache.lock().len(),
+            hits: self.inner.hits.load(Ordering::SeqCst),
+            misses: self.inner.misses.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Get the cached track with the given ID.
+    pub(super) fn get(&self, id: &str, market: Option<&str>) -> Option<FullTrack> {
+        let key = (id.to_string(), market.map(String::from));
+        let track = self.inner.cache.lock().get(&key, Instant::now());
+
+        let counter = match track {
+            Some(..) => &self.inner.hits,
+            None => &self.inner.misses,
+        };
+
+        counter.fetch_add(1, Ordering::SeqCst);
+        track
+    }
+
+    /// Cache the track with the given ID.
+    pub(super) fn insert(&self, id: String, market: Option<&str>, track: FullTrack) {
+        let key = (id, market.map(String::from));
+        self.inner.cache.lock().insert(key, track, Instant::now());
+    }
+}
+
+/// Statistics on the track cache.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct TrackCacheStats {
+    /// The number of cached tracks.
+    pub len: usize,
+    /// The number of lookups which were answered by the cache.
+    pub hits: u64,
+    /// The number of lookups which had to ask Spotify.
+    pub misses: u64,
+}
@@ -1,18 +1,22 @@
 //! Spotify API helpers.
 
+pub use self::cache::{TrackCache, TrackCacheStats};
 pub use self::model::artist::SimplifiedArtist;
+pub use self::model::audio::AudioFeatures;
 pub use self::model::context::FullPlayingContext;
 pub use self::model::device::Device;
 pub use self::model::page::Page;
 pub use self::model::playlist::{FullPlaylist, SimplifiedPlaylist};
 pub use self::model::search::SearchTracks;
 pub use self::model::senum::DeviceType;
+pub use self::model::show::{FullEpisode, SimplifiedShow};
 pub use self::model::track::{FullTrack, SavedTrack};
 pub use self::model::user::PrivateUser;
-use crate::api::RequestBuilder;
+use crate::api::{Limiter, RequestBuilder};
 use crate::oauth2;
 use crate::prelude::*;
 use crate::spotify_id::SpotifyId;
+use crate::utils;
 use anyhow::Result;
 use bytes::Bytes;
 use reqwest::{header, Client, Method, StatusCode};
@@ -20,10 +24,68 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 use url::Url;
 
+mod cache;
 mod model;
 
 const API_URL: &str = "https://api.spotify.com/v1";
 const DEFAULT_LIMIT: usize = 50;
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// Keep the limit of concurrent requests against the API up to date with the
+/// `spotify/max-concurrent-requests` setting.
+pub async fn limit_requests(
+    spotify: Arc<Spotify>,
+    settings: &crate::Settings,
+) -> Result<impl Future<Output = Result<()>>> {
+    let (mut limit_stream, limit) = settings
+        .stream("spotify/max-concurrent-requests")
+        .or_with(DEFAULT_MAX_CONCURRENT_REQUESTS)
+        .await?;
+
+    spotify.limiter.set_limit(limit);
+
+    Ok(async move {
+        loop {
+            let limit = limit_stream.recv().await;
+            spotify.limiter.set_limit(limit);
+        }
+    })
+}
+
+/// Keep the track cache up to date with the `spotify/track-cache/capacity`
+/// and `spotify/track-cache/ttl` settings.
+pub async fn cache_tracks(
+    spotify: Arc<Spotify>,
+    settings: &crate::Settings,
+) -> Result<impl Future<Output = Result<()>>> {
+    let settings = settings.scoped("spotify/track-cache");
+
+    let (mut capacity_stream, capacity) = settings
+        .stream("capacity")
+        .or_with(cache::DEFAULT_CAPACITY)
+        .await?;
+
+    let (mut ttl_stream, ttl) = settings
+        .stream("ttl")
+        .or_with(utils::Duration::seconds(cache::DEFAULT_TTL.as_secs()))
+        .await?;
+
+    spotify.track_cache.set_capacity(capacity);
+    spotify.track_cache.set_ttl(ttl.as_std());
+
+    Ok(async move {
+        loop {
+            tokio::select! {
+                capacity = capacity_stream.recv() => {
+                    spotify.track_cache.set_capacity(capacity);
+                }
+                ttl = ttl_stream.recv() => {
+                    spotify.track_cache.set_ttl(ttl.as_std());
+                }
+            }
+        }
+    })
+}
 
 /// API integration.
 #[derive(Clone, Debug)]
@@ -31,6 +93,8 @@ pub struct Spotify {
     client: Client,
     api_url: Url,
     pub token: oauth2::SyncToken,
+    limiter: Limiter,
+    track_cache: TrackCache,
 }
 
 impl Spotify {
@@ -40,14 +104,28 @@ impl Spotify {
             client: Client::new(),
             api_url: str::parse::<Url>(API_URL)?,
             token,
+            limiter: Limiter::default(),
+            track_cache: TrackCache::default(),
         })
     }
 
+    /// Access the limiter for how many requests are in flight against the API.
+    pub fn limiter(&self) -> &Limiter {
+        &self.limiter
+    }
+
+    /// Access the cache of track metadata.
+    pub fn track_cache(&self) -> &TrackCache {
+        &self.track_cache
+    }
+
     /// Get request against API.
     fn request(&self, method: Method, path: &[&str]) -> RequestBuilder {
         let mut url = self.api_url.clone();
         url.path_segments_mut().expect("bad base").extend(path);
-        RequestBuilder::new(self.client.clone(), method, url).token(self.token.clone())
+        RequestBuilder::new(self.client.clone(), method, url)
+            .token(self.token.clone())
+            .limiter(self.limiter.clone())
     }
 
     /// Get user info.
@@ -174,6 +252,29 @@ impl Spotify {
         r.json_map(device_control).await
     }
 
+    /// Transfer playback to the given device, keeping the current playback state.
+    pub async fn me_player_transfer(&self, device_id: &str) -> Result<bool> {
+        let request = Request {
+            device_ids: [device_id],
+        };
+
+        let body = Bytes::from(serde_json::to_vec(&request)?);
+
+        let r = self
+            .request(Method::PUT, &["me", "player"])
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json")
+            .absent_body(true)
+            .body(body);
+
+        return r.json_map(device_control).await;
+
+        #[derive(serde::Serialize)]
+        struct Request<'a> {
+            device_ids: [&'a str; 1],
+        }
+    }
+
     /// Skip to the next song.
     pub async fn me_player_next(&self, device_id: Option<&str>) -> Result<bool> {
         let r = self
@@ -185,6 +286,18 @@ impl Spotify {
         r.json_map(device_control).await
     }
 
+    /// Seek to the given position in the currently playing track.
+    pub async fn me_player_seek(&self, device_id: Option<&str>, position_ms: u64) -> Result<bool> {
+        self.request(Method::PUT, &["me", "player", "seek"])
+            .optional_query_param("device_id", device_id)
+            .query_param("position_ms", &position_ms.to_string())
+            .header(header::ACCEPT, "application/json")
+            .header(header::CONTENT_LENGTH, "0")
+            .absent_body(true)
+            .json_map(device_control)
+            .await
+    }
+
     /// Get my playlists.
     pub async fn my_playlists(&self) -> Result<Page<SimplifiedPlaylist>> {
         let req = self.request(Method::GET, &["me", "playlists"]);
@@ -200,21 +313,54 @@ impl Spotify {
         req.execute().await?.json()
     }
 
-    /// Get the full track by ID.
+    /// Get the full track by ID, which is cached.
     pub async fn track(&self, id: String, market: Option<&str>) -> Result<FullTrack> {
+        if let Some(track) = self.track_cache.get(&id, market) {
+            return Ok(track);
+        }
+
         let req = self
             .request(Method::GET, &["tracks", id.as_str()])
             .optional_query_param("market", market);
 
+        let track = req.execute().await?.json::<FullTrack>()?;
+        self.track_cache.insert(id, market, track.clone());
+        Ok(track)
+    }
+
+    /// Get audio features for the given tracks, at most 100 at a time.
+    ///
+    /// Tracks without audio features are left out.
+    pub async fn audio_features(&self, ids: &[String]) -> Result<Vec<AudioFeatures>> {
+        let req = self
+            .request(Method::GET, &["audio-features"])
+            .query_param("ids", &ids.join(","));
+
+        let response = req.execute().await?.json::<Response>()?;
+        return Ok(response.audio_features.into_iter().flatten().collect());
+
+        #[derive(serde::Deserialize)]
+        struct Response {
+            audio_features: Vec<Option<AudioFeatures>>,
+        }
+    }
+
+    /// Get the full episode by ID.
+    pub async fn episode(&self, id: String, market: Option<&str>) -> Result<FullEpisode> {
+        let req = self
+            .request(Method::GET, &["episodes", id.as_str()])
+            .optional_query_param("market", market);
+
         req.execute().await?.json()
     }
 
     /// Search for tracks.
-    pub async fn search_track(&self, q: &str) -> Result<Page<FullTrack>> {
+    pub async fn search_track(&self, q: &str, market: Option<&str>) -> Result<Page<FullTrack>> {
         let req = self
             .request(Method::GET, &["search"])
             .query_param("type", "track")
-            .query_param("q", q);
+            .query_param("q", q)
+            .optional_query_param("market", market);
 
         req.execute()
             .await?
@@ -238,6 +384,7 @@ impl Spotify {
         PageStream {
             client: self.client.clone(),
             token: self.token.clone(),
+            limiter: self.limiter.clone(),
             next: Some(Box::pin(future)),
         }
     }
@@ -256,6 +403,7 @@ fn device_control<C>(status: StatusCode, _: &C) -> Result<Option<bool>> {
 pub struct PageStream<T> {
     client: Client,
     token: oauth2::SyncToken,
+    limiter: Limiter,
     next: Option<BoxFuture<'static, Result<Page<T>>>>,
 }
 
@@ -265,8 +413,9 @@ where
 {
     /// Get the next page for a type.
     pub fn next_page(&self, url: Url) -> impl Future<Output = Result<Page<T>>> {
-        let req =
-            RequestBuilder::new(self.client.clone(), Method::GET, url).token(self.token.clone());
+        let req = RequestBuilder::new(self.client.clone(), Method::GET, url)
+            .token(self.token.clone())
+            .limiter(self.limiter.clone());
 
         async move { req.execute().await?.json() }
     }
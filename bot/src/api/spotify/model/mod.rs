@@ -19,5 +19,6 @@ pub mod playlist;
 pub mod recommend;
 pub mod search;
 pub mod senum;
+pub mod show;
 pub mod track;
 pub mod user;
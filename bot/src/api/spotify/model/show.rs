@@ -0,0 +1,105 @@
+//! All objects related to shows and episodes.
+use std::collections::HashMap;
+
+use super::album::Restrictions;
+use super::image::Image;
+use serde::{Deserialize, Serialize};
+
+///[simplified show object](https://developer.spotify.com/documentation/web-api/reference/#object-simplifiedshowobject)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SimplifiedShow {
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub available_markets: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    pub explicit: bool,
+    pub external_urls: HashMap<String, String>,
+    pub href: String,
+    pub id: String,
+    #[serde(default)]
+    pub images: Vec<Image>,
+    pub name: String,
+    pub publisher: String,
+    pub uri: String,
+}
+
+///[episode object full](https://developer.spotify.com/documentation/web-api/reference/#object-episodeobject)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FullEpisode {
+    #[serde(default)]
+    pub description: String,
+    pub duration_ms: u32,
+    pub explicit: bool,
+    pub external_urls: HashMap<String, String>,
+    pub href: String,
+    pub id: String,
+    #[serde(default)]
+    pub images: Vec<Image>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_playable: Option<bool>,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restrictions: Option<Restrictions>,
+    pub show: SimplifiedShow,
+    pub uri: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FullEpisode;
+
+    #[test]
+    fn test_deserialize_episode() {
+        let episode = serde_json::from_str::<FullEpisode>(
+            r#"{
+                "audio_preview_url": "https://p.scdn.co/mp3-preview/2f37da1d4221f40b9d1a98cd191f4d6f1646ad17",
+                "description": "A Spotify podcast sharing fresh insights on important topics of the moment.",
+                "duration_ms": 1502795,
+                "explicit": false,
+                "external_urls": {
+                    "spotify": "https://open.spotify.com/episode/512ojhOuo1ktJprKbVcKyQ"
+                },
+                "href": "https://api.spotify.com/v1/episodes/512ojhOuo1ktJprKbVcKyQ",
+                "id": "512ojhOuo1ktJprKbVcKyQ",
+                "images": [],
+                "is_externally_hosted": false,
+                "is_playable": true,
+                "language": "en",
+                "languages": ["en"],
+                "name": "Following the Money",
+                "release_date": "2019-05-01",
+                "release_date_precision": "day",
+                "show": {
+                    "available_markets": ["SE", "US"],
+                    "copyrights": [],
+                    "description": "Spotify's podcast about the stories behind the stories.",
+                    "explicit": false,
+                    "external_urls": {
+                        "spotify": "https://open.spotify.com/show/38bS44xjbVVZ3No3ByF1dJ"
+                    },
+                    "href": "https://api.spotify.com/v1/shows/38bS44xjbVVZ3No3ByF1dJ",
+                    "id": "38bS44xjbVVZ3No3ByF1dJ",
+                    "images": [],
+                    "is_externally_hosted": false,
+                    "languages": ["en"],
+                    "media_type": "audio",
+                    "name": "Spotify Originals",
+                    "publisher": "Spotify",
+                    "type": "show",
+                    "uri": "spotify:show:38bS44xjbVVZ3No3ByF1dJ"
+                },
+                "type": "episode",
+                "uri": "spotify:episode:512ojhOuo1ktJprKbVcKyQ"
+            }"#,
+        )
+        .expect("valid episode");
+
+        assert_eq!(episode.name, "Following the Money");
+        assert_eq!(episode.duration_ms, 1502795);
+        assert_eq!(episode.is_playable, Some(true));
+        assert_eq!(episode.show.name, "Spotify Originals");
+        assert_eq!(episode.uri, "spotify:episode:512ojhOuo1ktJprKbVcKyQ");
+    }
+}
@@ -0,0 +1,201 @@
+//! Coalescing of concurrent user lookups into batched requests.
+//!
+//! A lookup is sent right away if no request is in flight. Lookups which
+//! arrive while one is are sent together once it has completed.
+
+use crate::api::twitch::new;
+use anyhow::Error;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+/// The maximum number of logins Twitch accepts in a single request.
+pub(super) const LIMIT: usize = 100;
+
+type Lookup = Result<Option<new::User>, SharedError>;
+type Waiter = oneshot::Sender<Lookup>;
+
+/// Lookups waiting for the next batch, along with everyone waiting for them.
+pub(super) type Batch = Vec<(String, Vec<Waiter>)>;
+
+/// An error shared by everyone waiting for the same batch.
+#[derive(Debug, Clone)]
+pub(super) struct SharedError(Arc<Error>);
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, fmt)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// If a request is in flight.
+    busy: bool,
+    pending: HashMap<String, Vec<Waiter>>,
+}
+
+/// User lookups which are waiting to be sent.
+#[derive(Debug, Default, Clone)]
+pub(super) struct Users {
+    state: Arc<parking_lot::Mutex<State>>,
+}
+
+impl Users {
+    /// Queue a lookup of the given login.
+    ///
+    /// Returns `true` if no request was in flight, in which case the caller
+    /// is responsible for sending batches until [take][Users::take] comes up
+    /// empty.
+    pub(super) fn queue(&self, login: &str) -> (oneshot::Receiver<Lookup>, bool) {
+        let (tx, rx) = oneshot::channel();
+        let mut state = self.state.lock();
+
+        state
+            .pending
+            .entry(login.to_lowercase())
+            .or_default()
+            .push(tx);
+
+        let idle = !std::mem::replace(&mut state.busy, true);
+        (rx, idle)
+    }
+
+    /// Take all queued lookups, split into batches of at most [LIMIT] logins.
+    ///
+    /// Once there is nothing left to take, no request is considered to be in
+    /// flight.
+    pub(super) fn take(&self) -> Vec<Batch> {
+        let mut state = self.state.lock();
+
+        if state.pending.is_empty() {
+            state.busy = false;
+            return Vec::new();
+        }
+
+        let mut batches = Vec::new();
+        let mut batch = Vec::new();
+
+        for entry in std::mem::take(&mut state.pending) {
+            if batch.len() == LIMIT {
+                batches.push(std::mem::take(&mut batch));
+            }
+
+            batch.push(entry);
+        }
+
+        if !batch.is_empty() {
+            batches.push(batch);
+        }
+
+        batches
+    }
+}
+
+/// Fan out the result of a batched request to everyone waiting for it.
+pub(super) fn resolve(batch: Batch, result: Result<Vec<new::User>, Error>) {
+    let users = match result {
+        Ok(users) => users,
+        Err(e) => {
+            let e = SharedError(Arc::new(e));
+
+            for (_, waiters) in batch {
+                for waiter in waiters {
+                    let _ = waiter.send(Err(e.clone()));
+                }
+            }
+
+            return;
+        }
+    };
+
+    let mut users = users
+        .into_iter()
+        .map(|u| (u.login.to_lowercase(), u))
+        .collect::<HashMap<_, _>>();
+
+    for (login, waiters) in batch {
+        let user = users.remove(&login);
+
+        for waiter in waiters {
+            let _ = waiter.send(Ok(user.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve, Users, LIMIT};
+    use crate::api::twitch::new;
+
+    fn user(login: &str) -> new::User {
+        new::User {
+            id: login.to_string(),
+            login: login.to_string(),
+            display_name: login.to_string(),
+            ty: String::new(),
+            broadcaster_type: String::new(),
+            description: String::new(),
+            profile_image_url: String::new(),
+            offline_image_url: String::new(),
+            view_count: 0,
+            email: None,
+        }
+    }
+
+    #[test]
+    fn test_batch() {
+        let users = Users::default();
+
+        let (mut a, idle) = users.queue("foo");
+        assert!(idle);
+        let batch = users.take().pop().unwrap();
+        assert_eq!(batch.len(), 1);
+
+        // NB: lookups while a request is in flight are queued for the next one.
+        let (mut b, idle) = users.queue("Foo");
+        assert!(!idle);
+        let (mut c, _) = users.queue("bar");
+
+        resolve(batch, Ok(vec![user("foo")]));
+        assert_eq!(a.try_recv().unwrap().unwrap().unwrap().login, "foo");
+
+        let mut batches = users.take();
+        assert_eq!(batches.len(), 1);
+        let batch = batches.pop().unwrap();
+        assert_eq!(batch.len(), 2);
+        resolve(batch, Ok(vec![user("foo")]));
+
+        assert_eq!(b.try_recv().unwrap().unwrap().unwrap().login, "foo");
+        assert!(c.try_recv().unwrap().unwrap().is_none());
+
+        assert!(users.take().is_empty());
+
+        let (mut d, idle) = users.queue("foo");
+        assert!(idle);
+        let batch = users.take().pop().unwrap();
+        resolve(batch, Err(anyhow::anyhow!("failed")));
+        assert_eq!(d.try_recv().unwrap().unwrap_err().to_string(), "failed");
+    }
+
+    #[test]
+    fn test_batch_limit() {
+        let users = Users::default();
+
+        let _receivers = (0..=LIMIT)
+            .map(|n| users.queue(&n.to_string()).0)
+            .collect::<Vec<_>>();
+
+        let batches = users.take();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].len(), LIMIT);
+        assert_eq!(batches[1].len(), 1);
+    }
+}
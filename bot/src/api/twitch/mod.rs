@@ -2,7 +2,7 @@
 
 use crate::api::RequestBuilder;
 use crate::oauth2;
-use anyhow::{Context as _, Result};
+use anyhow::{anyhow, Context as _, Result};
 use bytes::Bytes;
 use reqwest::{header, Client, Method, StatusCode, Url};
 
@@ -15,6 +15,7 @@ const GQL_URL: &str = "https://gql.twitch.tv/gql";
 
 const GQL_CLIENT_ID: &str = "kimne78kx3ncx6brgo4mv6wki5h1ko";
 
+mod batch;
 mod gql;
 mod model;
 pub mod pubsub;
@@ -29,6 +30,8 @@ pub struct Twitch {
     id_url: Url,
     badges_url: Url,
     gql_url: Url,
+    /// User lookups waiting to be batched.
+    users: batch::Users,
     pub token: oauth2::SyncToken,
 }
 
@@ -41,6 +44,7 @@ impl Twitch {
             id_url: str::parse::<Url>(ID_TWITCH_URL)?,
             badges_url: str::parse::<Url>(BADGES_TWITCH_URL)?,
             gql_url: str::parse::<Url>(GQL_URL)?,
+            users: batch::Users::default(),
             token,
         })
     }
@@ -128,13 +132,46 @@ impl Twitch {
     }
 
     /// Get information on a user.
+    ///
+    /// Concurrent lookups are combined into a single request.
     pub async fn new_user_by_login(&self, login: &str) -> Result<Option<new::User>> {
-        let req = self
-            .new_api(Method::GET, &["users"])
-            .query_param("login", login);
+        let (rx, idle) = self.users.queue(login);
+
+        if idle {
+            let twitch = self.clone();
+
+            tokio::spawn(async move {
+                loop {
+                    let batches = twitch.users.take();
+
+                    if batches.is_empty() {
+                        break;
+                    }
+
+                    for batch in batches {
+                        let result = twitch.new_users_by_logins(&batch).await;
+                        batch::resolve(batch, result);
+                    }
+                }
+            });
+        }
+
+        match rx.await {
+            Ok(result) => Ok(result?),
+            Err(..) => Err(anyhow!("user lookup was cancelled")),
+        }
+    }
+
+    /// Get information on all users in the given batch.
+    async fn new_users_by_logins(&self, batch: &batch::Batch) -> Result<Vec<new::User>> {
+        let mut req = self.new_api(Method::GET, &["users"]);
+
+        for (login, _) in batch {
+            req = req.query_param("login", login);
+        }
 
         let res = req.execute().await?.json::<Data<Vec<new::User>>>()?;
-        Ok(res.data.into_iter().next())
+        Ok(res.data)
     }
 
     /// Get information on a user.
@@ -161,6 +198,39 @@ impl Twitch {
         }
     }
 
+    /// Get the game the given broadcaster last streamed, or `None` if they
+    /// have never streamed.
+    pub async fn new_last_game(&self, broadcaster_id: &str) -> Result<Option<String>> {
+        let req = self
+            .new_api(Method::GET, &["channels"])
+            .query_param("broadcaster_id", broadcaster_id);
+
+        let res = req.execute().await?.json::<Data<Vec<new::Channel>>>()?;
+
+        Ok(res
+            .data
+            .into_iter()
+            .next()
+            .map(|c| c.game_name)
+            .filter(|game| !game.is_empty()))
+    }
+
+    /// Get the total number of followers of the given user.
+    pub async fn new_followers_total(&self, to_id: &str) -> Result<u64> {
+        let req = self
+            .new_api(Method::GET, &["users", "follows"])
+            .query_param("to_id", to_id)
+            .query_param("first", "1");
+
+        let res = req.execute().await?.json::<Response>()?;
+        return Ok(res.total);
+
+        #[derive(serde::Deserialize)]
+        struct Response {
+            total: u64,
+        }
+    }
+
     /// Create a clip for the given broadcaster.
     pub async fn new_create_clip(&self, broadcaster_id: &str) -> Result<Option<new::Clip>> {
         let req = self
@@ -171,6 +241,16 @@ impl Twitch {
         Ok(res.data.into_iter().next())
     }
 
+    /// Get a clip by id.
+    ///
+    /// Newly created clips aren't available until Twitch has processed them.
+    pub async fn new_clip_by_id(&self, id: &str) -> Result<Option<new::ClipInfo>> {
+        let req = self.new_api(Method::GET, &["clips"]).query_param("id", id);
+
+        let res = req.execute().await?.json::<Data<Vec<new::ClipInfo>>>()?;
+        Ok(res.data.into_iter().next())
+    }
+
     /// Get stream information.
     pub async fn new_stream_by_id(&self, id: &str) -> Result<Option<new::Stream>> {
         let req = self
@@ -25,6 +25,12 @@ pub struct Clip {
     pub edit_url: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClipInfo {
+    pub id: String,
+    pub url: String,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct User {
     pub id: String,
@@ -52,6 +58,18 @@ pub struct Subscription {
     pub user_name: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Channel {
+    pub broadcaster_id: String,
+    pub broadcaster_name: String,
+    #[serde(default)]
+    pub game_id: String,
+    #[serde(default)]
+    pub game_name: String,
+    #[serde(default)]
+    pub title: String,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Stream {
     pub id: String,
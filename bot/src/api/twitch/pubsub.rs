@@ -1,4 +1,5 @@
 use crate::api;
+use crate::bus;
 use crate::injector::{Injector, Key};
 use crate::prelude::BoxStream;
 use crate::tags;
@@ -103,16 +104,20 @@ impl Client {
 }
 
 /// Connect to the pub/sub websocket once available.
+///
+/// Follows and subscriptions are sent as notifications on the `global_bus`.
 pub fn connect(
     settings: &crate::Settings,
     injector: &Injector,
+    global_bus: &bus::Bus<bus::Global>,
 ) -> impl Future<Output = Result<()>> {
-    task(settings.clone(), injector.clone())
+    task(settings.clone(), injector.clone(), global_bus.clone())
 }
 
 struct State {
     enabled: bool,
     ws: TwitchPubSub,
+    global_bus: bus::Bus<bus::Global>,
     client: Fuse<Client>,
     streamer: Option<api::TwitchAndUser>,
     ping_interval: Fuse<Interval>,
@@ -204,6 +209,7 @@ impl State {
                         format!("channel-points-channel-v1.{}", streamer.user.id),
                         format!("channel-bits-events-v2.{}", streamer.user.id),
                         format!("channel-subscribe-events-v1.{}", streamer.user.id),
+                        format!("following.{}", streamer.user.id),
                     ],
                     auth_token: self::transport::SecretString(auth_token),
                 },
@@ -248,8 +254,27 @@ impl State {
                 self.pong_deadline.clear();
             }
             self::transport::Frame::Message(message) => {
-                let m: Message = serde_json::from_str(&message.data.message)?;
-                self.handle_message(m).await?;
+                let topic = message.data.topic.split('.').next().unwrap_or_default();
+
+                match topic {
+                    "channel-subscribe-events-v1" => {
+                        let s: Subscription = serde_json::from_str(&message.data.message)?;
+                        self.global_bus.send(s.into_global()).await;
+                    }
+                    "following" => {
+                        let f: Follow = serde_json::from_str(&message.data.message)?;
+
+                        self.global_bus
+                            .send(bus::Global::TwitchFollow {
+                                user: f.display_name,
+                            })
+                            .await;
+                    }
+                    _ => {
+                        let m: Message = serde_json::from_str(&message.data.message)?;
+                        self.handle_message(m).await?;
+                    }
+                }
             }
             self::transport::Frame::Unknonwn => {
                 bail!("Unsupported payload: {:?}", text);
@@ -274,7 +299,11 @@ impl State {
     }
 }
 
-async fn task(settings: crate::Settings, injector: Injector) -> Result<()> {
+async fn task(
+    settings: crate::Settings,
+    injector: Injector,
+    global_bus: bus::Bus<bus::Global>,
+) -> Result<()> {
     let settings = settings.scoped("pubsub");
 
     let (mut enabled_stream, enabled) = settings.stream::<bool>("enabled").or_default().await?;
@@ -295,6 +324,7 @@ async fn task(settings: crate::Settings, injector: Injector) -> Result<()> {
     let mut state = State {
         enabled,
         ws,
+        global_bus,
         client: Fuse::empty(),
         streamer,
         ping_interval: Fuse::empty(),
@@ -454,6 +484,7 @@ pub(crate) mod transport {
 
 mod model {
     use crate::api::twitch::Data;
+    use crate::bus;
     use chrono::{DateTime, Utc};
     use serde::{Deserialize, Serialize};
 
@@ -546,9 +577,108 @@ mod model {
         pub global_cooldown_seconds: u64,
     }
 
+    /// A message on the `channel-subscribe-events-v1` topic.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Subscription {
+        /// The user who subscribed, or who gifted the subscription.
+        ///
+        /// Missing for anonymous gifts.
+        #[serde(default)]
+        pub display_name: Option<String>,
+        #[serde(default)]
+        pub recipient_display_name: Option<String>,
+        #[serde(default)]
+        pub cumulative_months: Option<u32>,
+        /// The months of a gifted subscription.
+        #[serde(default)]
+        pub months: Option<u32>,
+        #[serde(default)]
+        pub is_gift: bool,
+        #[serde(default)]
+        pub sub_message: Option<SubMessage>,
+    }
+
+    impl Subscription {
+        /// Convert into a notification for the global bus.
+        pub fn into_global(self) -> bus::Global {
+            let message = self
+                .sub_message
+                .map(|m| m.message)
+                .filter(|m| !m.is_empty());
+
+            if self.is_gift {
+                return bus::Global::TwitchSubscription {
+                    user: self.recipient_display_name.unwrap_or_default(),
+                    months: self.months.unwrap_or(1),
+                    gifter: self.display_name,
+                    message,
+                };
+            }
+
+            bus::Global::TwitchSubscription {
+                user: self.display_name.unwrap_or_default(),
+                months: self.cumulative_months.unwrap_or(1),
+                gifter: None,
+                message,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct SubMessage {
+        pub message: String,
+    }
+
+    /// A message on the `following` topic.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Follow {
+        pub display_name: String,
+        pub username: String,
+        pub user_id: String,
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct RewardRedeemed {
         pub timestamp: String,
         pub redemption: Redemption,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Subscription;
+    use crate::bus;
+
+    #[test]
+    fn test_subscription() -> anyhow::Result<()> {
+        let resub = serde_json::from_str::<Subscription>(
+            r#"{"user_name":"tww2","display_name":"TWW2","cumulative_months":9,"streak_months":3,"context":"resub","is_gift":false,"sub_message":{"message":"Hello!","emotes":null}}"#,
+        )?;
+
+        assert!(matches!(
+            resub.into_global(),
+            bus::Global::TwitchSubscription {
+                user,
+                months: 9,
+                gifter: None,
+                message: Some(message),
+            } if user == "TWW2" && message == "Hello!"
+        ));
+
+        let gift = serde_json::from_str::<Subscription>(
+            r#"{"user_name":"tww2","display_name":"TWW2","recipient_display_name":"Forstycup","months":1,"context":"subgift","is_gift":true,"sub_message":{"message":"","emotes":null}}"#,
+        )?;
+
+        assert!(matches!(
+            gift.into_global(),
+            bus::Global::TwitchSubscription {
+                user,
+                months: 1,
+                gifter: Some(gifter),
+                message: None,
+            } if user == "Forstycup" && gifter == "TWW2"
+        ));
+
+        Ok(())
+    }
+}
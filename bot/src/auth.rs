@@ -5,6 +5,7 @@ use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::future::Future;
 use std::iter;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -24,6 +25,27 @@ impl Schema {
     }
 }
 
+/// Keep the test accounts of `auth` up to date with the `auth/test-accounts`
+/// setting.
+pub async fn test_accounts(
+    auth: Auth,
+    settings: &crate::Settings,
+) -> Result<impl Future<Output = Result<(), Error>>, Error> {
+    let (mut accounts_stream, accounts) = settings
+        .stream::<HashSet<String>>("auth/test-accounts")
+        .or_default()
+        .await?;
+
+    auth.set_test_accounts(accounts).await;
+
+    Ok(async move {
+        loop {
+            let accounts = accounts_stream.recv().await;
+            auth.set_test_accounts(accounts).await;
+        }
+    })
+}
+
 /// A role or a user.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RoleOrUser {
@@ -75,6 +97,9 @@ struct Inner {
     grants: RwLock<HashSet<(Scope, Role)>>,
     /// Temporary grants.
     temporary_grants: RwLock<Vec<TemporaryGrant>>,
+    /// Accounts which are allowed every scope which isn't high risk, for
+    /// testing.
+    test_accounts: RwLock<HashSet<String>>,
 }
 
 /// A container for scopes and their grants.
@@ -104,6 +129,7 @@ impl Auth {
                 schema,
                 grants: RwLock::new(grants),
                 temporary_grants: Default::default(),
+                test_accounts: Default::default(),
             }),
         };
 
@@ -150,6 +176,25 @@ impl Auth {
         out
     }
 
+    /// Get the roles which have been granted the given scope.
+    pub async fn roles_for_scope(&self, needle: Scope) -> Vec<Role> {
+        let grants = self.inner.grants.read().await;
+
+        let mut out = grants
+            .iter()
+            .filter(|(scope, _)| *scope == needle)
+            .map(|(_, role)| *role)
+            .collect::<Vec<_>>();
+
+        out.sort();
+        out
+    }
+
+    /// Get the cooldown in effect for the given scope, if any.
+    pub fn scope_cooldown(&self, scope: Scope) -> Option<Duration> {
+        self.inner.schema.scopes.get(&scope)?.cooldown
+    }
+
     /// Construct scope cooldowns.
     pub fn scope_cooldowns(&self) -> HashMap<Scope, Cooldown> {
         let mut cooldowns = HashMap::new();
@@ -207,7 +252,7 @@ impl Auth {
                 .db
                 .asyncify(move |c| {
                     diesel::insert_into(dsl::initialized_grants)
-                        .values((dsl::scope.eq(key), dsl::version.eq(version)))
+                        .values((dsl::scope.eq(key), dsl::version.eq(&version)))
                         .execute(c)?;
                     Ok::<_, Error>(())
                 })
@@ -330,7 +375,25 @@ impl Auth {
                 .retain(|g| !g.is_expired(&now));
         }
 
-        granted
+        granted || self.test_account(scope, user).await
+    }
+
+    /// Set the accounts which are allowed every scope which isn't high risk,
+    /// for testing.
+    pub async fn set_test_accounts(&self, accounts: impl IntoIterator<Item = String>) {
+        let accounts = accounts
+            .into_iter()
+            .map(|a| db::user_id(&a))
+            .collect::<HashSet<_>>();
+
+        if !accounts.is_empty() {
+            log::warn!(
+                "Test accounts are allowed every scope: {}",
+                accounts.iter().cloned().collect::<Vec<_>>().join(", ")
+            );
+        }
+
+        *self.inner.test_accounts.write().await = accounts;
     }
 
     /// Test if the given assignment exists.
@@ -366,7 +429,29 @@ impl Auth {
                 .retain(|g| !g.is_expired(&now));
         }
 
-        granted
+        granted || self.test_account(scope, user).await
+    }
+
+    /// Test if the user is a test account, which is allowed every scope
+    /// except the high risk ones.
+    async fn test_account(&self, scope: Scope, user: &str) -> bool {
+        if !self.inner.test_accounts.read().await.contains(user) {
+            return false;
+        }
+
+        if let Some(data) = self.inner.schema.scopes.get(&scope) {
+            if let Risk::High = data.risk {
+                return false;
+            }
+        }
+
+        log::debug!(
+            "Allowing `{}` for test account `{}`, which it doesn't really have",
+            scope,
+            user
+        );
+
+        true
     }
 
     /// Get a list of scopes and extra information associated with them.
@@ -595,6 +680,7 @@ scopes! {
     (BypassCooldowns, "bypass-cooldowns"),
     (PlayerDetachDetach, "player/attach-detach"),
     (Admin, "admin"),
+    (ConfigShow, "config/show"),
     (Song, "song"),
     (SongYouTube, "song/youtube"),
     (SongSpotify, "song/spotify"),
@@ -604,8 +690,11 @@ scopes! {
     (SongListLimit, "song/list-limit"),
     (SongVolume, "song/volume"),
     (SongPlaybackControl, "song/playback-control"),
+    (SongSkipVote, "song/skip-vote"),
     (SwearJar, "swearjar"),
     (Uptime, "uptime"),
+    (Followers, "followers"),
+    (Shoutout, "shoutout"),
     (Game, "game"),
     (GameEdit, "game/edit"),
     (Title, "title"),
@@ -613,10 +702,14 @@ scopes! {
     (AfterStream, "afterstream"),
     (Clip, "clip"),
     (EightBall, "8ball"),
+    (Roll, "roll"),
     (Command, "command"),
     (CommandEdit, "command/edit"),
+    (Count, "count"),
     (ThemeEdit, "theme/edit"),
     (PromoEdit, "promo/edit"),
+    (TimerEdit, "timer/edit"),
+    (BadWordsEdit, "bad-words/edit"),
     (AliasEdit, "alias/edit"),
     (Countdown, "countdown"),
     (GtavBypassCooldown, "gtav/bypass-cooldown"),
@@ -626,8 +719,16 @@ scopes! {
     (CurrencyBoost, "currency/boost"),
     (CurrencyWindfall, "currency/windfall"),
     (WaterUndo, "water/undo"),
+    (Quote, "quote"),
+    (QuoteEdit, "quote/edit"),
+    (GiveawayEnter, "giveaway/enter"),
+    (GiveawayDraw, "giveaway/draw"),
+    (Gamble, "gamble"),
+    (WatchTime, "watchtime"),
+    (WatchTimeOthers, "watchtime/others"),
     (AuthPermit, "auth/permit"),
     (ChatBypassUrlWhitelist, "chat/bypass-url-whitelist"),
+    (ChatFilters, "chat/filters"),
     (Time, "time"),
     (Poll, "poll"),
     (Weather, "weather"),
@@ -677,3 +778,117 @@ pub struct RoleData {
     /// Documentation for this role.
     pub doc: String,
 }
+
+/// The standing a user needs in chat to use a command.
+///
+/// Unlike roles, permissions are ranked, so a user with a given permission
+/// also has every permission below it.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum Permission {
+    #[serde(rename = "everyone")]
+    Everyone,
+    #[serde(rename = "subscriber")]
+    Subscriber,
+    #[serde(rename = "vip")]
+    Vip,
+    #[serde(rename = "moderator")]
+    Moderator,
+    #[serde(rename = "broadcaster")]
+    Broadcaster,
+}
+
+impl Default for Permission {
+    fn default() -> Self {
+        Permission::Everyone
+    }
+}
+
+impl Permission {
+    /// Describe who has the permission, for use in messages to users.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Permission::Everyone => "anyone",
+            Permission::Subscriber => "a subscriber",
+            Permission::Vip => "a VIP",
+            Permission::Moderator => "a moderator",
+            Permission::Broadcaster => "the broadcaster",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Permission::Everyone => "everyone".fmt(fmt),
+            Permission::Subscriber => "subscriber".fmt(fmt),
+            Permission::Vip => "vip".fmt(fmt),
+            Permission::Moderator => "moderator".fmt(fmt),
+            Permission::Broadcaster => "broadcaster".fmt(fmt),
+        }
+    }
+}
+
+impl std::str::FromStr for Permission {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "everyone" => Ok(Permission::Everyone),
+            "subscriber" | "sub" => Ok(Permission::Subscriber),
+            "vip" => Ok(Permission::Vip),
+            "moderator" | "mod" => Ok(Permission::Moderator),
+            "broadcaster" | "streamer" => Ok(Permission::Broadcaster),
+            _ => Err(anyhow::anyhow!(
+                "expected one of: everyone, subscriber, vip, moderator, or broadcaster"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Auth, Permission, Role, Schema, Scope};
+    use crate::db;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_test_accounts() -> Result<()> {
+        let db = db::Database::memory()?;
+        let auth = Auth::new(db, Schema::load_static()?).await?;
+
+        let roles = || vec![Role::Everyone];
+
+        assert!(!auth.test_any(Scope::SongTheme, "tester", roles()).await);
+
+        auth.set_test_accounts(vec![String::from("@Tester")]).await;
+        assert!(auth.test_any(Scope::SongTheme, "tester", roles()).await);
+        assert!(!auth.test_any(Scope::SongTheme, "viewer", roles()).await);
+        // NB: high risk scopes are never allowed through a test account.
+        assert!(!auth.test_any(Scope::TimerEdit, "tester", roles()).await);
+        assert!(!auth.test_any(Scope::Admin, "tester", roles()).await);
+
+        auth.set_test_accounts(Vec::new()).await;
+        assert!(!auth.test_any(Scope::SongTheme, "tester", roles()).await);
+        Ok(())
+    }
+
+    #[test]
+    fn test_permission() {
+        assert_eq!(
+            str::parse::<Permission>("Mod").ok(),
+            Some(Permission::Moderator)
+        );
+        assert_eq!(
+            str::parse::<Permission>("streamer").ok(),
+            Some(Permission::Broadcaster)
+        );
+        assert!(str::parse::<Permission>("admin").is_err());
+
+        assert!(Permission::Broadcaster > Permission::Moderator);
+        assert!(Permission::Moderator > Permission::Vip);
+        assert!(Permission::Vip > Permission::Subscriber);
+        assert!(Permission::Subscriber > Permission::Everyone);
+    }
+}
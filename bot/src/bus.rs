@@ -157,6 +157,44 @@ pub enum Global {
     },
     #[serde(rename = "song/modified")]
     SongModified,
+    /// The player switched to a new track.
+    ///
+    /// Only sent if `player/events/track-change` is enabled.
+    #[serde(rename = "song/track-change")]
+    SongTrackChange {
+        track_id: TrackId,
+        track: player::Track,
+        user: Option<String>,
+    },
+    /// The player ran out of songs to play.
+    ///
+    /// Only sent if `player/events/queue-empty` is enabled.
+    #[serde(rename = "song/queue-empty")]
+    SongQueueEmpty,
+    /// The player failed to start playing a track.
+    ///
+    /// Only sent if `player/events/playback-error` is enabled.
+    #[serde(rename = "song/playback-error")]
+    SongPlaybackError { track_id: TrackId, message: String },
+    /// Someone followed the channel.
+    #[serde(rename = "twitch/follow")]
+    TwitchFollow { user: String },
+    /// Someone subscribed to the channel, or was gifted a subscription.
+    #[serde(rename = "twitch/subscription")]
+    TwitchSubscription {
+        /// The user who is subscribed.
+        user: String,
+        /// How many months in total the user has been subscribed.
+        months: u32,
+        /// The user who gifted the subscription, if it's a gift which isn't
+        /// anonymous.
+        gifter: Option<String>,
+        /// The message shared with the subscription.
+        message: Option<String>,
+    },
+    /// Someone raided the channel.
+    #[serde(rename = "twitch/raid")]
+    TwitchRaid { user: String, viewers: u32 },
 }
 
 impl Message for Global {
@@ -1,7 +1,8 @@
 //! Traits and shared plumbing for bot commands (e.g. `!uptime`)
 
-use crate::auth::Scope;
+use crate::auth::{Permission, Scope};
 use crate::irc;
+use crate::module;
 use crate::prelude::*;
 use crate::utils;
 use anyhow::Result;
@@ -10,11 +11,15 @@ use std::collections::HashMap;
 use std::fmt;
 use std::num;
 use std::str;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 use thiserror::Error;
 use tokio::sync;
 
+/// The prefix of built-in commands unless another one is configured.
+pub const DEFAULT_COMMAND_PREFIX: &str = "!";
+
 #[derive(Debug, Error)]
 #[error("Command failed with: {0}")]
 pub struct Respond(pub(crate) Cow<'static, str>);
@@ -37,6 +42,25 @@ impl str::FromStr for HookId {
     }
 }
 
+/// A short identifier for a single incoming message, used to follow it and
+/// the command it runs through the logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContextId(u32);
+
+impl ContextId {
+    /// Allocate the next context identifier.
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        ContextId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for ContextId {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{:08x}", self.0)
+    }
+}
+
 #[async_trait]
 /// The handler trait for a given command.
 pub trait Handler
@@ -64,15 +88,36 @@ pub(crate) struct ContextInner {
     pub(crate) sender: irc::Sender,
     /// Active scope cooldowns.
     pub(crate) scope_cooldowns: sync::Mutex<HashMap<Scope, utils::Cooldown>>,
+    /// Named cooldowns shared by groups of commands.
+    pub(crate) cooldowns: utils::Cooldowns,
+    /// The prefix of built-in commands, like the `!` in `!song`.
+    pub(crate) command_prefix: settings::Var<String>,
     /// A hook that can be installed to peek at all incoming messages.
     pub(crate) message_hooks: sync::RwLock<slab::Slab<Box<dyn MessageHook>>>,
+    /// Handlers for specific commands like `!skip`.
+    pub(crate) handlers: module::Handlers,
     /// Shutdown handler.
     pub(crate) restart: utils::Restart,
 }
 
+impl ContextInner {
+    /// Get the prefix of built-in commands, falling back to `!` if it's been
+    /// set to nothing.
+    pub(crate) async fn command_prefix(&self) -> String {
+        let prefix = self.command_prefix.load().await;
+
+        if prefix.is_empty() {
+            return String::from(DEFAULT_COMMAND_PREFIX);
+        }
+
+        prefix
+    }
+}
+
 /// Context for a single command invocation.
 #[derive(Clone)]
 pub struct Context {
+    pub(crate) id: ContextId,
     pub(crate) api_url: Arc<Option<String>>,
     pub(crate) user: irc::User,
     pub(crate) it: utils::Words,
@@ -80,6 +125,11 @@ pub struct Context {
 }
 
 impl Context {
+    /// Identifier of the message this command is being run for.
+    pub fn id(&self) -> ContextId {
+        self.id
+    }
+
     /// Access the last known API url.
     pub fn api_url(&self) -> Option<&str> {
         self.api_url.as_deref()
@@ -90,11 +140,26 @@ impl Context {
         self.inner.sender.channel()
     }
 
+    /// Lookup the handler for the given built-in command.
+    pub fn handler(&self, command: &str) -> Option<Arc<dyn Handler>> {
+        self.inner.handlers.get(command)
+    }
+
     /// Signal that the bot should try to shut down.
     pub async fn restart(&self) -> bool {
         self.inner.restart.restart().await
     }
 
+    /// Get the prefix of built-in commands, like the `!` in `!song`.
+    pub async fn command_prefix(&self) -> String {
+        self.inner.command_prefix().await
+    }
+
+    /// Access the named cooldowns shared by groups of commands.
+    pub fn cooldowns(&self) -> &utils::Cooldowns {
+        &self.inner.cooldowns
+    }
+
     /// Setup the specified hook.
     pub async fn insert_hook<H>(&self, hook: H) -> HookId
     where
@@ -117,6 +182,7 @@ impl Context {
     /// Verify that the current user has the associated scope.
     pub async fn check_scope(&self, scope: Scope) -> Result<()> {
         if !self.user.has_scope(scope).await {
+            log::trace!("[{}] denied: missing scope {}", self.id, scope);
             respond_bail!("Do you think this is a democracy? LUL");
         }
 
@@ -130,6 +196,8 @@ impl Context {
             let now = Instant::now();
 
             if let Some(duration) = cooldown.check(now.clone()) {
+                log::trace!("[{}] denied: cooldown in effect for {}", self.id, scope);
+
                 respond_bail!(
                     "Cooldown in effect for {}",
                     utils::compact_duration(duration),
@@ -142,6 +210,17 @@ impl Context {
         Ok(())
     }
 
+    /// Verify that the current user has at least the given permission, like
+    /// being a moderator or a subscriber.
+    pub fn check_permission(&self, permission: Permission) -> Result<()> {
+        if !self.user.has_permission(permission) {
+            log::trace!("[{}] denied: missing permission {}", self.id, permission);
+            respond_bail!("You need to be {} to do that.", permission.describe());
+        }
+
+        Ok(())
+    }
+
     /// Respond to the user with a message.
     pub async fn respond(&self, m: impl fmt::Display) {
         self.user.respond(m).await;
@@ -156,6 +235,16 @@ impl Context {
         self.user.respond_lines(results, empty).await
     }
 
+    /// Render the given page of an iterable of results, that implements
+    /// display.
+    pub async fn respond_page<I>(&self, results: I, page: usize, empty: &str)
+    where
+        I: IntoIterator,
+        I::Item: fmt::Display,
+    {
+        self.user.respond_page(results, page, empty).await
+    }
+
     /// Send a privmsg to the channel.
     pub async fn privmsg(&self, m: impl fmt::Display) {
         self.inner.sender.privmsg(m).await;
@@ -0,0 +1,148 @@
+//! Formatting of currency amounts.
+
+/// How currency amounts are shown in chat.
+#[derive(Debug, Clone, Default)]
+pub struct Format {
+    /// Group thousands with a separator, like `1,234`.
+    pub separators: bool,
+    /// Symbol to show instead of the currency name, like `$`.
+    pub symbol: Option<String>,
+    /// Place the symbol before the amount, like `$100`, instead of after it,
+    /// like `100 $`.
+    pub symbol_before: bool,
+    /// Abbreviate large balances, like `1.2k`.
+    pub abbreviate: bool,
+}
+
+impl Format {
+    /// Format the given amount of the currency with the given name.
+    pub fn amount(&self, name: &str, amount: i64) -> String {
+        self.with_unit(name, self.number(amount))
+    }
+
+    /// Format the given balance of the currency with the given name, which is
+    /// abbreviated if it's large and abbreviations are enabled.
+    pub fn balance(&self, name: &str, amount: i64) -> String {
+        let number = if self.abbreviate {
+            abbreviate(amount).unwrap_or_else(|| self.number(amount))
+        } else {
+            self.number(amount)
+        };
+
+        self.with_unit(name, number)
+    }
+
+    fn with_unit(&self, name: &str, number: String) -> String {
+        match &self.symbol {
+            Some(symbol) if self.symbol_before => match number.strip_prefix('-') {
+                Some(number) => format!("-{}{}", symbol, number),
+                None => format!("{}{}", symbol, number),
+            },
+            Some(symbol) => format!("{} {}", number, symbol),
+            None => format!("{} {}", number, name),
+        }
+    }
+
+    /// Format the number part of an amount.
+    pub fn number(&self, amount: i64) -> String {
+        if !self.separators {
+            return amount.to_string();
+        }
+
+        let digits = amount.unsigned_abs().to_string();
+        let mut out = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+
+        if amount < 0 {
+            out.push('-');
+        }
+
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                out.push(',');
+            }
+
+            out.push(c);
+        }
+
+        out
+    }
+}
+
+/// Abbreviate the given amount with one decimal, like `1.2k`, or `None` if
+/// it's too small to abbreviate.
+fn abbreviate(amount: i64) -> Option<String> {
+    const UNITS: &[(u64, &str)] = &[(1_000_000_000, "B"), (1_000_000, "M"), (1_000, "k")];
+
+    let abs = amount.unsigned_abs();
+    let (size, unit) = UNITS.iter().copied().find(|(size, _)| abs >= *size)?;
+
+    // NB: truncate instead of round, so that we never show more than what's
+    // actually there.
+    let tenths = abs / (size / 10);
+    let sign = if amount < 0 { "-" } else { "" };
+
+    Some(match tenths % 10 {
+        0 => format!("{}{}{}", sign, tenths / 10, unit),
+        rest => format!("{}{}.{}{}", sign, tenths / 10, rest, unit),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Format;
+
+    #[test]
+    fn test_default() {
+        let format = Format::default();
+        assert_eq!(format.amount("honkos", 1234567), "1234567 honkos");
+        assert_eq!(format.balance("honkos", 1234567), "1234567 honkos");
+    }
+
+    #[test]
+    fn test_separators() {
+        let format = Format {
+            separators: true,
+            ..Format::default()
+        };
+
+        assert_eq!(format.amount("honkos", 0), "0 honkos");
+        assert_eq!(format.amount("honkos", 999), "999 honkos");
+        assert_eq!(format.amount("honkos", 1234), "1,234 honkos");
+        assert_eq!(format.amount("honkos", 123456), "123,456 honkos");
+        assert_eq!(format.amount("honkos", -1234567), "-1,234,567 honkos");
+        assert_eq!(
+            format.amount("honkos", i64::MIN),
+            "-9,223,372,036,854,775,808 honkos"
+        );
+    }
+
+    #[test]
+    fn test_symbol() {
+        let mut format = Format {
+            symbol: Some(String::from("$")),
+            ..Format::default()
+        };
+
+        assert_eq!(format.amount("dollars", 100), "100 $");
+        format.symbol_before = true;
+        assert_eq!(format.amount("dollars", 100), "$100");
+        assert_eq!(format.amount("dollars", -100), "-$100");
+    }
+
+    #[test]
+    fn test_abbreviate() {
+        let format = Format {
+            separators: true,
+            abbreviate: true,
+            ..Format::default()
+        };
+
+        assert_eq!(format.balance("honkos", 999), "999 honkos");
+        assert_eq!(format.balance("honkos", 1000), "1k honkos");
+        assert_eq!(format.balance("honkos", 1299), "1.2k honkos");
+        assert_eq!(format.balance("honkos", 45_678_901), "45.6M honkos");
+        assert_eq!(format.balance("honkos", -2_000_000_000), "-2B honkos");
+        // amounts are never abbreviated.
+        assert_eq!(format.amount("honkos", 1299), "1,299 honkos");
+    }
+}
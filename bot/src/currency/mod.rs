@@ -1,7 +1,7 @@
 //! Stream currency configuration.
 use crate::api;
+use crate::db;
 pub use crate::db::models::Balance;
-use crate::db::Database;
 use crate::injector::Injector;
 use crate::utils::Duration;
 use anyhow::{Error, Result};
@@ -9,7 +9,10 @@ use std::collections::HashSet;
 use std::sync::Arc;
 use thiserror::Error;
 
+pub use self::format::Format;
+
 mod builtin;
+mod format;
 mod mysql;
 
 /// Balance of a single user.
@@ -39,7 +42,11 @@ pub struct CurrencyBuilder {
     pub enabled: bool,
     pub command_enabled: bool,
     pub name: Option<Arc<String>>,
-    pub db: Option<Database>,
+    pub format: Format,
+    /// The database, shared with everything else using it.
+    pub db: Option<Arc<db::Cached<db::Sqlite>>>,
+    /// Cache balances read from the database.
+    pub cache: bool,
     pub mysql_url: Option<String>,
 }
 
@@ -54,7 +61,9 @@ impl CurrencyBuilder {
             enabled: Default::default(),
             command_enabled: Default::default(),
             name: Default::default(),
+            format: Default::default(),
             db: None,
+            cache: true,
             mysql_url: None,
         }
     }
@@ -83,9 +92,9 @@ impl CurrencyBuilder {
 
         let backend = match self.ty {
             BackendType::BuiltIn => {
-                let db = self.db.as_ref()?;
-                let backend = self::builtin::Backend::new(db.clone());
-                Backend::BuiltIn(backend)
+                let db = self.db.clone()?;
+                db.set_cache_balances(self.cache);
+                Backend::BuiltIn(self::builtin::Backend::new(db))
             }
             BackendType::Mysql => {
                 let channel = String::from("");
@@ -130,6 +139,7 @@ impl CurrencyBuilder {
         Some(Currency {
             name,
             command_enabled,
+            format: Arc::new(self.format.clone()),
             inner: Arc::new(Inner { backend, twitch }),
         })
     }
@@ -192,6 +202,16 @@ impl Backend {
         }
     }
 
+    /// Get the highest balances in the given channel.
+    pub async fn top_balances(&self, channel: &str, limit: usize) -> Result<Vec<Balance>> {
+        use self::Backend::*;
+
+        match *self {
+            BuiltIn(ref backend) => backend.top_balances(channel, limit).await,
+            MySql(ref backend) => backend.top_balances(channel, limit).await,
+        }
+    }
+
     /// Import balances for all users.
     pub async fn import_balances(&self, balances: Vec<Balance>) -> Result<()> {
         use self::Backend::*;
@@ -222,6 +242,16 @@ impl Backend {
         }
     }
 
+    /// Subtract from the balance for a single user, if they can afford it.
+    pub async fn balance_take(&self, channel: &str, user: &str, amount: i64) -> Result<bool> {
+        use self::Backend::*;
+
+        match *self {
+            BuiltIn(ref backend) => backend.balance_take(channel, user, amount).await,
+            MySql(ref backend) => backend.balance_take(channel, user, amount).await,
+        }
+    }
+
     /// Add balance to users.
     pub async fn balances_increment<I>(
         &self,
@@ -257,10 +287,40 @@ struct Inner {
 pub struct Currency {
     pub name: Arc<String>,
     pub command_enabled: bool,
+    format: Arc<Format>,
     inner: Arc<Inner>,
 }
 
 impl Currency {
+    /// Construct a built-in currency on top of the given storage backend.
+    #[cfg(test)]
+    pub(crate) fn builtin(
+        name: &str,
+        twitch: api::Twitch,
+        db: Arc<dyn crate::db::Backend>,
+    ) -> Self {
+        Self {
+            name: Arc::new(name.to_string()),
+            command_enabled: true,
+            format: Default::default(),
+            inner: Arc::new(Inner {
+                backend: Backend::BuiltIn(self::builtin::Backend::new(db)),
+                twitch,
+            }),
+        }
+    }
+
+    /// Format the given amount of currency, like `1,234 honkos`.
+    pub fn format(&self, amount: i64) -> String {
+        self.format.amount(&self.name, amount)
+    }
+
+    /// Format the given balance, which unlike [`Currency::format`] is
+    /// abbreviated if configured, like `1.2k honkos`.
+    pub fn format_balance(&self, amount: i64) -> String {
+        self.format.balance(&self.name, amount)
+    }
+
     /// Reward all users.
     pub async fn add_channel_all(
         &self,
@@ -305,6 +365,12 @@ impl Currency {
         self.inner.backend.export_balances().await
     }
 
+    /// Get the highest balances in the given channel, with ties ordered by
+    /// user name.
+    pub async fn top_balances(&self, channel: &str, limit: usize) -> Result<Vec<Balance>> {
+        self.inner.backend.top_balances(channel, limit).await
+    }
+
     /// Import balances for all users.
     pub async fn import_balances(&self, balances: Vec<Balance>) -> Result<()> {
         self.inner.backend.import_balances(balances).await
@@ -320,6 +386,13 @@ impl Currency {
         self.inner.backend.balance_add(channel, user, amount).await
     }
 
+    /// Subtract from the balance for a single user.
+    ///
+    /// Returns `false` without changing anything if they can't afford it.
+    pub async fn balance_take(&self, channel: &str, user: &str, amount: i64) -> Result<bool> {
+        self.inner.backend.balance_take(channel, user, amount).await
+    }
+
     /// Add balance to users.
     pub async fn balances_increment<I>(
         &self,
@@ -58,6 +58,29 @@ impl Queries {
         Ok(results)
     }
 
+    /// Select the highest balances, with ties ordered by user.
+    async fn select_top_balances<Tx>(&self, tx: &mut Tx, limit: usize) -> Result<Vec<(String, i32)>>
+    where
+        Tx: Queryable,
+    {
+        let query = format!(
+            "SELECT `{user_column}`, `{balance_column}` \
+             FROM `{table}` \
+             ORDER BY `{balance_column}` DESC, `{user_column}` ASC \
+             LIMIT {limit}",
+            table = self.schema.table,
+            balance_column = self.schema.balance_column,
+            user_column = self.schema.user_column,
+            limit = limit,
+        );
+
+        log::trace!("select_top_balances: {}", query);
+        let results = tx
+            .exec_map(query.as_str(), (), mysql::from_row::<(String, i32)>)
+            .await?;
+        Ok(results)
+    }
+
     /// Select the given balance.
     async fn select_balance<Tx>(&self, tx: &mut Tx, user: &str) -> Result<Option<i32>>
     where
@@ -241,6 +264,26 @@ impl Backend {
         Ok(output)
     }
 
+    /// Get the highest balances.
+    pub async fn top_balances(&self, _channel: &str, limit: usize) -> Result<Vec<Balance>> {
+        let channel = self.channel.to_string();
+
+        let opts = mysql::TxOpts::new();
+        let mut tx = self.pool.start_transaction(opts).await?;
+
+        let balances = self.queries.select_top_balances(&mut tx, limit).await?;
+
+        Ok(balances
+            .into_iter()
+            .map(|(user, balance)| Balance {
+                channel: channel.clone(),
+                user,
+                amount: balance as i64,
+                watch_time: 0,
+            })
+            .collect())
+    }
+
     /// Import balances for all users.
     pub async fn import_balances(&self, balances: Vec<Balance>) -> Result<()> {
         let opts = mysql::TxOpts::new();
@@ -293,6 +336,25 @@ impl Backend {
         Ok(())
     }
 
+    /// Subtract from the balance for a single user, if they can afford it.
+    pub async fn balance_take(&self, _channel: &str, user: &str, amount: i64) -> Result<bool> {
+        let user = user_id(user);
+        let amount: i32 = amount.try_into()?;
+
+        let opts = mysql::TxOpts::new();
+        let mut tx = self.pool.start_transaction(opts).await?;
+
+        let balance = self.queries.select_balance(&mut tx, &user).await?;
+
+        if balance.unwrap_or_default() < amount {
+            return Ok(false);
+        }
+
+        self.queries.modify_balance(&mut tx, &user, -amount).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
     /// Add balance to users.
     pub async fn balances_increment<I>(&self, _channel: &str, users: I, amount: i64) -> Result<()>
     where
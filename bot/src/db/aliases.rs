@@ -1,6 +1,7 @@
 use crate::db;
 use crate::template;
 use crate::utils;
+use anyhow::{anyhow, Context as _};
 use diesel::prelude::*;
 use std::fmt;
 use std::sync::Arc;
@@ -21,34 +22,37 @@ impl Database {
 
         self.0
             .asyncify(move |c| {
-                let filter =
-                    dsl::aliases.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
-
-                let first = filter.clone().first::<db::models::Alias>(c).optional()?;
-
-                match first {
-                    None => {
-                        let alias = db::models::Alias {
-                            channel: key.channel.to_string(),
-                            pattern: None,
-                            name: key.name.to_string(),
-                            text: text.to_string(),
-                            group: None,
-                            disabled: false,
-                        };
-
-                        diesel::insert_into(dsl::aliases)
-                            .values(&alias)
-                            .execute(c)?;
-                        Ok(alias)
+                c.transaction(|| {
+                    let filter = dsl::aliases
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
+
+                    let first = filter.clone().first::<db::models::Alias>(c).optional()?;
+
+                    match first {
+                        None => {
+                            let alias = db::models::Alias {
+                                channel: key.channel.to_string(),
+                                pattern: None,
+                                name: key.name.to_string(),
+                                text: text.to_string(),
+                                group: None,
+                                disabled: false,
+                                games: None,
+                            };
+
+                            diesel::insert_into(dsl::aliases)
+                                .values(&alias)
+                                .execute(c)?;
+                            Ok(alias)
+                        }
+                        Some(alias) => {
+                            let mut set = db::models::UpdateAlias::default();
+                            set.text = Some(&text);
+                            diesel::update(filter).set(&set).execute(c)?;
+                            Ok(alias)
+                        }
                     }
-                    Some(alias) => {
-                        let mut set = db::models::UpdateAlias::default();
-                        set.text = Some(&text);
-                        diesel::update(filter).set(&set).execute(c)?;
-                        Ok(alias)
-                    }
-                }
+                })
             })
             .await
     }
@@ -57,21 +61,39 @@ impl Database {
     async fn edit_pattern(
         &self,
         key: &db::Key,
-        pattern: Option<&regex::Regex>,
+        pattern: &db::Pattern,
     ) -> Result<(), anyhow::Error> {
         use db::schema::aliases::dsl;
 
         let key = key.clone();
-        let pattern = pattern.cloned();
+        let pattern = pattern.to_db();
 
         self.0
             .asyncify(move |c| {
-                let pattern = pattern.as_ref().map(|p| p.as_str());
+                diesel::update(
+                    dsl::aliases.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::pattern.eq(pattern.clone()))
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
 
+    /// Edit the games an alias is limited to.
+    async fn edit_games(&self, key: &db::Key, games: &db::Games) -> Result<(), anyhow::Error> {
+        use db::schema::aliases::dsl;
+
+        let key = key.clone();
+        let games = games.to_db();
+
+        self.0
+            .asyncify(move |c| {
                 diesel::update(
                     dsl::aliases.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
                 )
-                .set(dsl::pattern.eq(pattern))
+                .set(dsl::games.eq(games.clone()))
                 .execute(c)?;
 
                 Ok(())
@@ -96,7 +118,15 @@ impl Aliases {
         let db = Database(db);
 
         for alias in db.list().await? {
-            let alias = Alias::from_db(&alias)?;
+            // NB: a bad alias shouldn't prevent the rest from loading.
+            let alias = match Alias::from_db(&alias) {
+                Ok(alias) => alias,
+                Err(e) => {
+                    log_error!(e, "failed to load alias `{}`", alias.name);
+                    continue;
+                }
+            };
+
             inner.insert(alias.key.clone(), Arc::new(alias));
         }
 
@@ -106,28 +136,24 @@ impl Aliases {
         })
     }
 
-    /// Resolve the given command.
-    pub async fn resolve(&self, channel: &str, message: Arc<String>) -> Option<(db::Key, String)> {
+    /// Resolve the given command, as available while streaming the given
+    /// game.
+    ///
+    /// Returns the key of the matched alias together with the result of
+    /// rendering it.
+    pub async fn resolve(
+        &self,
+        channel: &str,
+        game: Option<&str>,
+        message: Arc<String>,
+    ) -> Option<(db::Key, Result<String, anyhow::Error>)> {
         let mut it = utils::Words::new(message);
         let first = it.next();
 
-        if let Some((alias, captures)) =
-            self.inner
-                .read()
-                .await
-                .resolve(channel, first.as_deref(), &it)
-        {
-            let key = alias.key.clone();
-
-            match alias.template.render_to_string(&captures) {
-                Ok(s) => return Some((key, s)),
-                Err(e) => {
-                    log::error!("failed to render alias: {}", e);
-                }
-            }
-        }
-
-        None
+        let inner = self.inner.read().await;
+        let (alias, captures) = inner.resolve(channel, game, first.as_deref(), &it)?;
+        let key = alias.key.clone();
+        Some((key, alias.template.render_to_string(&captures)))
     }
 
     /// Insert a word into the bad words list.
@@ -139,6 +165,10 @@ impl Aliases {
     ) -> Result<(), anyhow::Error> {
         let key = db::Key::new(channel, name);
 
+        template
+            .validate(Alias::is_var)
+            .with_context(|| anyhow!("bad template for alias `{}`", key))?;
+
         let alias = self.db.edit(&key, template.source()).await?;
 
         if alias.disabled {
@@ -152,6 +182,7 @@ impl Aliases {
                 template,
                 group: alias.group,
                 disabled: alias.disabled,
+                games: db::Games::from_db(alias.games.as_ref()),
             };
 
             self.inner.write().await.insert(key, Arc::new(alias));
@@ -165,13 +196,28 @@ impl Aliases {
         &self,
         channel: &str,
         name: &str,
-        pattern: Option<regex::Regex>,
+        pattern: db::Pattern,
+    ) -> Result<bool, anyhow::Error> {
+        let key = db::Key::new(channel, name);
+        self.db.edit_pattern(&key, &pattern).await?;
+
+        Ok(self.inner.write().await.modify(key, |alias| {
+            alias.pattern = pattern;
+        }))
+    }
+
+    /// Edit the games the given alias is limited to.
+    pub async fn edit_games(
+        &self,
+        channel: &str,
+        name: &str,
+        games: db::Games,
     ) -> Result<bool, anyhow::Error> {
         let key = db::Key::new(channel, name);
-        self.db.edit_pattern(&key, pattern.as_ref()).await?;
+        self.db.edit_games(&key, &games).await?;
 
         Ok(self.inner.write().await.modify(key, |alias| {
-            alias.pattern = pattern.map(db::Pattern::regex).unwrap_or_default();
+            alias.games = games;
         }))
     }
 }
@@ -183,6 +229,7 @@ pub struct Alias {
     pub template: template::Template,
     pub group: Option<String>,
     pub disabled: bool,
+    pub games: db::Games,
 }
 
 impl db::Matchable for Alias {
@@ -193,23 +240,37 @@ impl db::Matchable for Alias {
     fn pattern(&self) -> &db::Pattern {
         &self.pattern
     }
+
+    fn games(&self) -> &db::Games {
+        &self.games
+    }
 }
 
 impl Alias {
     pub const NAME: &'static str = "alias";
 
+    /// Test if the given variable is available to alias templates.
+    pub fn is_var(var: &str) -> bool {
+        db::Captures::is_var(var)
+    }
+
     /// Convert a database alias into an in-memory alias.
     pub fn from_db(alias: &db::models::Alias) -> Result<Alias, anyhow::Error> {
         let key = db::Key::new(&alias.channel, &alias.name);
         let pattern = db::Pattern::from_db(alias.pattern.as_ref())?;
         let template = template::Template::compile(&alias.text)?;
 
+        template
+            .validate(Self::is_var)
+            .with_context(|| anyhow!("bad template for alias `{}`", key))?;
+
         Ok(Alias {
             key,
             pattern,
             template,
             group: alias.group.clone(),
             disabled: alias.disabled,
+            games: db::Games::from_db(alias.games.as_ref()),
         })
     }
 }
@@ -218,11 +279,12 @@ impl fmt::Display for Alias {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             fmt,
-            "template = \"{template}\", pattern = {pattern}, group = {group}, disabled = {disabled}",
+            "template = \"{template}\", pattern = {pattern}, group = {group}, disabled = {disabled}, games = {games}",
             template = self.template,
             pattern = self.pattern,
             group = self.group.as_deref().unwrap_or("*none*"),
             disabled = self.disabled,
+            games = self.games,
         )
     }
 }
@@ -0,0 +1,2203 @@
+//! Storage backends for the data used by commands, counters, balances, and
+//! quotes.
+//!
+//! [Sqlite] is what the bot uses when running normally, while [Memory] keeps
+//! everything in memory which is useful for tests and ephemeral deployments.
+
+use crate::auth::Permission;
+use crate::db;
+use crate::utils::Lru;
+use anyhow::{bail, Result};
+use chrono::Utc;
+use diesel::prelude::*;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::convert::TryFrom as _;
+use std::time::Instant;
+
+/// Storage operations needed by commands, counters, balances, and quotes.
+///
+/// Names of users and channels are stored as-is, so any normalization needs to
+/// happen in the caller.
+#[async_trait::async_trait]
+pub trait Backend: 'static + Send + Sync {
+    /// List all commands which are not disabled.
+    async fn commands_list(&self) -> Result<Vec<db::models::Command>>;
+
+    /// List all commands in a channel, including disabled ones.
+    async fn commands_list_all(&self, channel: &str) -> Result<Vec<db::models::Command>>;
+
+    /// List all commands in the given group.
+    async fn commands_list_group(
+        &self,
+        channel: &str,
+        group: &str,
+    ) -> Result<Vec<db::models::Command>>;
+
+    /// Set if all commands in the given group are disabled or not.
+    async fn commands_set_group_disabled(
+        &self,
+        channel: &str,
+        group: &str,
+        disabled: bool,
+    ) -> Result<()>;
+
+    /// Fetch a single command.
+    async fn command_fetch(&self, key: &db::Key) -> Result<Option<db::models::Command>>;
+
+    /// Edit the text of a command, creating it if it doesn't exist.
+    ///
+    /// Returns the command as it was before it was edited.
+    async fn command_edit(&self, key: &db::Key, text: &str) -> Result<db::models::Command>;
+
+    /// Edit the pattern of a command.
+    async fn command_edit_pattern(&self, key: &db::Key, pattern: Option<&str>) -> Result<()>;
+
+    /// Edit the description of a command.
+    async fn command_edit_description(
+        &self,
+        key: &db::Key,
+        description: Option<&str>,
+    ) -> Result<()>;
+
+    /// Edit the usage of a command.
+    async fn command_edit_usage(&self, key: &db::Key, usage: Option<&str>) -> Result<()>;
+
+    /// Edit the newline-separated games a command is limited to.
+    async fn command_edit_games(&self, key: &db::Key, games: Option<&str>) -> Result<()>;
+
+    /// Edit the limits on how often a command can be used.
+    async fn command_edit_limits(&self, key: &db::Key, limits: &db::CommandLimits) -> Result<()>;
+
+    /// Edit the named cooldown a command shares with other commands.
+    async fn command_edit_cooldown_group(&self, key: &db::Key, group: Option<&str>) -> Result<()>;
+
+    /// Edit the permission users need to use a command.
+    async fn command_edit_permission(
+        &self,
+        key: &db::Key,
+        permission: Option<Permission>,
+    ) -> Result<()>;
+
+    /// Edit the newline-separated names a command can also be invoked by.
+    async fn command_edit_triggers(&self, key: &db::Key, triggers: Option<&str>) -> Result<()>;
+
+    /// Edit how much currency it costs to use a command.
+    async fn command_edit_cost(&self, key: &db::Key, cost: Option<u32>) -> Result<()>;
+
+    /// Edit the group membership of a command.
+    async fn command_edit_group(&self, key: &db::Key, group: Option<String>) -> Result<()>;
+
+    /// Set the disabled state of a command.
+    async fn command_edit_disabled(&self, key: &db::Key, disabled: bool) -> Result<()>;
+
+    /// Increment the counter of a command, recording who changed it.
+    async fn command_increment(&self, key: &db::Key, by: Option<&str>) -> Result<bool>;
+
+    /// Add to the counter of a command, recording who changed it. The amount
+    /// can be negative.
+    async fn command_add_count(&self, key: &db::Key, amount: i32, by: Option<&str>)
+        -> Result<bool>;
+
+    /// Set the counter of a command, recording who changed it.
+    async fn command_set_count(&self, key: &db::Key, count: i32, by: Option<&str>) -> Result<bool>;
+
+    /// Delete a single command.
+    async fn command_delete(&self, key: &db::Key) -> Result<bool>;
+
+    /// Rename one command to another.
+    async fn command_rename(&self, from: &db::Key, to: &db::Key) -> Result<bool>;
+
+    /// Find the balance of a single user.
+    async fn balance_of(&self, channel: &str, user: &str) -> Result<Option<db::models::Balance>>;
+
+    /// Add (or subtract) from the balance of a single user.
+    async fn balance_add(&self, channel: &str, user: &str, amount: i64) -> Result<()>;
+
+    /// Subtract from the balance of a single user.
+    ///
+    /// Returns `false` without changing anything if the user doesn't have
+    /// enough balance.
+    async fn balance_take(&self, channel: &str, user: &str, amount: i64) -> Result<bool>;
+
+    /// Transfer balance from one user to another.
+    ///
+    /// Returns `false` without changing anything if the giver doesn't have
+    /// enough balance and `override_balance` is not set.
+    async fn balance_transfer(
+        &self,
+        channel: &str,
+        giver: &str,
+        taker: &str,
+        amount: i64,
+        override_balance: bool,
+    ) -> Result<bool>;
+
+    /// Add balance and watch time to all the given users.
+    async fn balances_increment(
+        &self,
+        channel: &str,
+        users: Vec<String>,
+        amount: i64,
+        watch_time: i64,
+    ) -> Result<()>;
+
+    /// Get the highest balances in the given channel.
+    ///
+    /// Ties are ordered by user name.
+    async fn balances_top(&self, channel: &str, limit: usize) -> Result<Vec<db::models::Balance>>;
+
+    /// Get balances for all users.
+    async fn balances_export(&self) -> Result<Vec<db::models::Balance>>;
+
+    /// Import balances, replacing the balances of existing users.
+    async fn balances_import(&self, balances: Vec<db::models::Balance>) -> Result<()>;
+
+    /// Add a quote to a channel, numbering it after the highest numbered quote
+    /// in that channel.
+    async fn quote_add(&self, channel: &str, user: &str, text: &str) -> Result<db::models::Quote>;
+
+    /// Fetch the quote with the given number in a channel.
+    async fn quote_fetch(&self, channel: &str, number: i32) -> Result<Option<db::models::Quote>>;
+
+    /// Fetch a random quote in a channel.
+    async fn quote_random(&self, channel: &str) -> Result<Option<db::models::Quote>>;
+
+    /// Delete the quote with the given number in a channel, returning it.
+    async fn quote_delete(&self, channel: &str, number: i32) -> Result<Option<db::models::Quote>>;
+}
+
+/// Backend which stores everything in the SQLite database.
+#[derive(Clone)]
+pub struct Sqlite(db::Database);
+
+impl Sqlite {
+    /// Construct a new SQLite backend.
+    pub fn new(db: db::Database) -> Self {
+        Self(db)
+    }
+
+    private_database_group_fns!(commands, Command, db::Key);
+}
+
+#[async_trait::async_trait]
+impl Backend for Sqlite {
+    async fn commands_list(&self) -> Result<Vec<db::models::Command>> {
+        self.list().await
+    }
+
+    async fn commands_list_all(&self, channel: &str) -> Result<Vec<db::models::Command>> {
+        self.list_all(channel).await
+    }
+
+    async fn commands_list_group(
+        &self,
+        channel: &str,
+        group: &str,
+    ) -> Result<Vec<db::models::Command>> {
+        self.list_group(channel, group).await
+    }
+
+    async fn commands_set_group_disabled(
+        &self,
+        channel: &str,
+        group: &str,
+        disabled: bool,
+    ) -> Result<()> {
+        self.set_group_disabled(channel, group, disabled).await
+    }
+
+    async fn command_fetch(&self, key: &db::Key) -> Result<Option<db::models::Command>> {
+        self.fetch(key).await
+    }
+
+    async fn command_edit(&self, key: &db::Key, text: &str) -> Result<db::models::Command> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let text = text.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                c.transaction(|| {
+                    let filter = dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
+
+                    match filter.first::<db::models::Command>(c).optional()? {
+                        None => {
+                            let command = db::models::Command {
+                                channel: key.channel.to_string(),
+                                pattern: None,
+                                name: key.name.to_string(),
+                                count: 0,
+                                text: text.to_string(),
+                                group: None,
+                                disabled: false,
+                                description: None,
+                                usage: None,
+                                count_updated_at: None,
+                                count_updated_by: None,
+                                games: None,
+                                cooldown: None,
+                                user_cooldown: None,
+                                stream_limit: None,
+                                moderator_cooldown: None,
+                                cooldown_group: None,
+                                permission: None,
+                                triggers: None,
+                                cost: None,
+                            };
+
+                            diesel::insert_into(dsl::commands)
+                                .values(&command)
+                                .execute(c)?;
+                            Ok(command)
+                        }
+                        Some(command) => {
+                            let set = db::models::UpdateCommand {
+                                text: Some(&text),
+                                ..Default::default()
+                            };
+                            diesel::update(filter).set(&set).execute(c)?;
+                            Ok(command)
+                        }
+                    }
+                })
+            })
+            .await
+    }
+
+    async fn command_edit_pattern(&self, key: &db::Key, pattern: Option<&str>) -> Result<()> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let pattern = pattern.map(String::from);
+
+        self.0
+            .asyncify(move |c| {
+                diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::pattern.eq(pattern.clone()))
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn command_edit_description(
+        &self,
+        key: &db::Key,
+        description: Option<&str>,
+    ) -> Result<()> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let description = description.map(String::from);
+
+        self.0
+            .asyncify(move |c| {
+                diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::description.eq(description.clone()))
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn command_edit_usage(&self, key: &db::Key, usage: Option<&str>) -> Result<()> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let usage = usage.map(String::from);
+
+        self.0
+            .asyncify(move |c| {
+                diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::usage.eq(usage.clone()))
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn command_edit_games(&self, key: &db::Key, games: Option<&str>) -> Result<()> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let games = games.map(String::from);
+
+        self.0
+            .asyncify(move |c| {
+                diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::games.eq(games.clone()))
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn command_edit_limits(&self, key: &db::Key, limits: &db::CommandLimits) -> Result<()> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let set = limits.to_db();
+
+        self.0
+            .asyncify(move |c| {
+                diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(&set)
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn command_edit_cooldown_group(&self, key: &db::Key, group: Option<&str>) -> Result<()> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let group = group.map(String::from);
+
+        self.0
+            .asyncify(move |c| {
+                diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::cooldown_group.eq(group.clone()))
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn command_edit_permission(
+        &self,
+        key: &db::Key,
+        permission: Option<Permission>,
+    ) -> Result<()> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let permission = permission.map(|p| p.to_string());
+
+        self.0
+            .asyncify(move |c| {
+                diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::permission.eq(permission.clone()))
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn command_edit_triggers(&self, key: &db::Key, triggers: Option<&str>) -> Result<()> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let triggers = triggers.map(String::from);
+
+        self.0
+            .asyncify(move |c| {
+                diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::triggers.eq(triggers.clone()))
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn command_edit_cost(&self, key: &db::Key, cost: Option<u32>) -> Result<()> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let cost = cost.map(|cost| i32::try_from(cost).unwrap_or(i32::MAX));
+
+        self.0
+            .asyncify(move |c| {
+                diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::cost.eq(cost))
+                .execute(c)?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn command_edit_group(&self, key: &db::Key, group: Option<String>) -> Result<()> {
+        self.edit_group(key, group).await
+    }
+
+    async fn command_edit_disabled(&self, key: &db::Key, disabled: bool) -> Result<()> {
+        self.edit_disabled(key, disabled).await
+    }
+
+    async fn command_increment(&self, key: &db::Key, by: Option<&str>) -> Result<bool> {
+        self.command_add_count(key, 1, by).await
+    }
+
+    async fn command_add_count(
+        &self,
+        key: &db::Key,
+        amount: i32,
+        by: Option<&str>,
+    ) -> Result<bool> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let by = by.map(String::from);
+        let now = Utc::now().naive_utc();
+
+        self.0
+            .asyncify(move |c| {
+                c.transaction(|| {
+                    let filter = dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
+
+                    let count = match filter.select(dsl::count).first::<i32>(c).optional()? {
+                        Some(count) => count,
+                        None => return Ok(false),
+                    };
+
+                    let count = match count.checked_add(amount) {
+                        Some(count) => count,
+                        None => bail!("count of command `{}` would overflow", key),
+                    };
+
+                    diesel::update(filter)
+                        .set((
+                            dsl::count.eq(count),
+                            dsl::count_updated_at.eq(now),
+                            dsl::count_updated_by.eq(by.clone()),
+                        ))
+                        .execute(c)?;
+
+                    Ok(true)
+                })
+            })
+            .await
+    }
+
+    async fn command_set_count(&self, key: &db::Key, count: i32, by: Option<&str>) -> Result<bool> {
+        use db::schema::commands::dsl;
+
+        let key = key.clone();
+        let by = by.map(String::from);
+        let now = Utc::now().naive_utc();
+
+        self.0
+            .asyncify(move |c| {
+                let count = diesel::update(
+                    dsl::commands
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set((
+                    dsl::count.eq(count),
+                    dsl::count_updated_at.eq(now),
+                    dsl::count_updated_by.eq(by.clone()),
+                ))
+                .execute(c)?;
+                Ok(count == 1)
+            })
+            .await
+    }
+
+    async fn command_delete(&self, key: &db::Key) -> Result<bool> {
+        self.delete(key).await
+    }
+
+    async fn command_rename(&self, from: &db::Key, to: &db::Key) -> Result<bool> {
+        self.rename(from, to).await
+    }
+
+    async fn balance_of(&self, channel: &str, user: &str) -> Result<Option<db::models::Balance>> {
+        use db::schema::balances::dsl;
+
+        let channel = channel.to_string();
+        let user = user.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                let balance = dsl::balances
+                    .filter(dsl::channel.eq(&channel).and(dsl::user.eq(&user)))
+                    .first::<db::models::Balance>(c)
+                    .optional()?;
+
+                Ok(balance)
+            })
+            .await
+    }
+
+    async fn balance_add(&self, channel: &str, user: &str, amount: i64) -> Result<()> {
+        let channel = channel.to_string();
+        let user = user.to_string();
+
+        self.0
+            .asyncify(move |c| c.transaction(|| modify_balance(c, &channel, &user, amount)))
+            .await
+    }
+
+    async fn balance_take(&self, channel: &str, user: &str, amount: i64) -> Result<bool> {
+        use db::schema::balances::dsl;
+
+        let channel = channel.to_string();
+        let user = user.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                c.transaction(|| {
+                    let balance = dsl::balances
+                        .filter(dsl::channel.eq(channel.as_str()).and(dsl::user.eq(&user)))
+                        .select(dsl::amount)
+                        .first::<i64>(c)
+                        .optional()?
+                        .unwrap_or_default();
+
+                    if balance < amount {
+                        return Ok(false);
+                    }
+
+                    modify_balance(c, &channel, &user, -amount)?;
+                    Ok(true)
+                })
+            })
+            .await
+    }
+
+    async fn balance_transfer(
+        &self,
+        channel: &str,
+        giver: &str,
+        taker: &str,
+        amount: i64,
+        override_balance: bool,
+    ) -> Result<bool> {
+        use db::schema::balances::dsl;
+
+        let channel = channel.to_string();
+        let giver = giver.to_string();
+        let taker = taker.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                c.transaction(|| {
+                    let giver_filter = dsl::balances
+                        .filter(dsl::channel.eq(channel.as_str()).and(dsl::user.eq(&giver)));
+
+                    let balance = giver_filter
+                        .select(dsl::amount)
+                        .first::<i64>(c)
+                        .optional()?
+                        .unwrap_or_default();
+
+                    if balance < amount && !override_balance {
+                        return Ok(false);
+                    }
+
+                    modify_balance(c, &channel, &taker, amount)?;
+                    modify_balance(c, &channel, &giver, -amount)?;
+                    Ok(true)
+                })
+            })
+            .await
+    }
+
+    async fn balances_increment(
+        &self,
+        channel: &str,
+        users: Vec<String>,
+        amount: i64,
+        watch_time: i64,
+    ) -> Result<()> {
+        use db::schema::balances::dsl;
+
+        let channel = channel.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                // NB: the task is retried if it fails, so everything has to
+                // be applied at once for users not to be credited twice.
+                c.transaction(|| {
+                    for user in &users {
+                        let filter = dsl::balances
+                            .filter(dsl::channel.eq(channel.as_str()).and(dsl::user.eq(user)));
+
+                        let b = filter.first::<db::models::Balance>(c).optional()?;
+
+                        match b {
+                            None => {
+                                let balance = db::models::Balance {
+                                    channel: channel.to_string(),
+                                    user: user.clone(),
+                                    amount,
+                                    watch_time,
+                                };
+
+                                diesel::insert_into(dsl::balances)
+                                    .values(&balance)
+                                    .execute(c)?;
+                            }
+                            Some(b) => {
+                                let value = b.amount.saturating_add(amount);
+                                let watch_time = b.watch_time.saturating_add(watch_time);
+
+                                diesel::update(filter)
+                                    .set((dsl::amount.eq(value), dsl::watch_time.eq(watch_time)))
+                                    .execute(c)?;
+                            }
+                        }
+                    }
+
+                    Ok(())
+                })
+            })
+            .await
+    }
+
+    async fn balances_top(&self, channel: &str, limit: usize) -> Result<Vec<db::models::Balance>> {
+        use db::schema::balances::dsl;
+
+        let channel = channel.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                let balances = dsl::balances
+                    .filter(dsl::channel.eq(&channel))
+                    .order((dsl::amount.desc(), dsl::user.asc()))
+                    .limit(limit as i64)
+                    .load::<db::models::Balance>(c)?;
+
+                Ok(balances)
+            })
+            .await
+    }
+
+    async fn balances_export(&self) -> Result<Vec<db::models::Balance>> {
+        use db::schema::balances::dsl;
+
+        self.0
+            .asyncify(move |c| Ok(dsl::balances.load::<db::models::Balance>(c)?))
+            .await
+    }
+
+    async fn balances_import(&self, balances: Vec<db::models::Balance>) -> Result<()> {
+        use db::schema::balances::dsl;
+
+        self.0
+            .asyncify(move |c| {
+                c.transaction(|| {
+                    for balance in &balances {
+                        let filter = dsl::balances.filter(
+                            dsl::channel
+                                .eq(balance.channel.as_str())
+                                .and(dsl::user.eq(&balance.user)),
+                        );
+
+                        let b = filter.first::<db::models::Balance>(c).optional()?;
+
+                        match b {
+                            None => {
+                                diesel::insert_into(dsl::balances)
+                                    .values(balance)
+                                    .execute(c)?;
+                            }
+                            Some(_) => {
+                                diesel::update(filter)
+                                    .set((
+                                        dsl::amount.eq(balance.amount),
+                                        dsl::watch_time.eq(balance.watch_time),
+                                    ))
+                                    .execute(c)?;
+                            }
+                        }
+                    }
+
+                    Ok(())
+                })
+            })
+            .await
+    }
+
+    async fn quote_add(&self, channel: &str, user: &str, text: &str) -> Result<db::models::Quote> {
+        use db::schema::quotes::dsl;
+
+        let channel = channel.to_string();
+        let user = user.to_string();
+        let text = text.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                c.transaction(|| {
+                    let filter = dsl::quotes.filter(dsl::channel.eq(&channel));
+
+                    let last = filter
+                        .select(diesel::dsl::max(dsl::number))
+                        .first::<Option<i32>>(c)?
+                        .unwrap_or_default();
+
+                    let number = match last.checked_add(1) {
+                        Some(number) => number,
+                        None => bail!("too many quotes in {}", channel),
+                    };
+
+                    let quote = db::models::InsertQuote {
+                        channel: channel.clone(),
+                        number,
+                        user: user.clone(),
+                        text: text.clone(),
+                    };
+
+                    diesel::insert_into(dsl::quotes).values(&quote).execute(c)?;
+
+                    Ok(filter
+                        .filter(dsl::number.eq(number))
+                        .first::<db::models::Quote>(c)?)
+                })
+            })
+            .await
+    }
+
+    async fn quote_fetch(&self, channel: &str, number: i32) -> Result<Option<db::models::Quote>> {
+        use db::schema::quotes::dsl;
+
+        let channel = channel.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                let quote = dsl::quotes
+                    .filter(dsl::channel.eq(&channel).and(dsl::number.eq(number)))
+                    .first::<db::models::Quote>(c)
+                    .optional()?;
+
+                Ok(quote)
+            })
+            .await
+    }
+
+    async fn quote_random(&self, channel: &str) -> Result<Option<db::models::Quote>> {
+        use db::schema::quotes::dsl;
+        use diesel::dsl::sql;
+        use diesel::sql_types::Integer;
+
+        let channel = channel.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                let quote = dsl::quotes
+                    .filter(dsl::channel.eq(&channel))
+                    .order(sql::<Integer>("RANDOM()"))
+                    .first::<db::models::Quote>(c)
+                    .optional()?;
+
+                Ok(quote)
+            })
+            .await
+    }
+
+    async fn quote_delete(&self, channel: &str, number: i32) -> Result<Option<db::models::Quote>> {
+        use db::schema::quotes::dsl;
+
+        let channel = channel.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                c.transaction(|| {
+                    let filter =
+                        dsl::quotes.filter(dsl::channel.eq(&channel).and(dsl::number.eq(number)));
+
+                    let quote = match filter.first::<db::models::Quote>(c).optional()? {
+                        Some(quote) => quote,
+                        None => return Ok(None),
+                    };
+
+                    diesel::delete(filter).execute(c)?;
+                    Ok(Some(quote))
+                })
+            })
+            .await
+    }
+}
+
+/// Common function to modify the balance for the given user.
+fn modify_balance(c: &SqliteConnection, channel: &str, user: &str, amount: i64) -> Result<()> {
+    use db::schema::balances::dsl;
+
+    let filter = dsl::balances.filter(dsl::channel.eq(channel).and(dsl::user.eq(user)));
+
+    match filter.first::<db::models::Balance>(c).optional()? {
+        None => {
+            let balance = db::models::Balance {
+                channel: channel.to_string(),
+                user: user.to_string(),
+                amount,
+                watch_time: 0,
+            };
+
+            diesel::insert_into(dsl::balances)
+                .values(&balance)
+                .execute(c)?;
+        }
+        Some(b) => {
+            let amount = b.amount.saturating_add(amount);
+
+            diesel::update(filter)
+                .set(dsl::amount.eq(amount))
+                .execute(c)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct MemoryInner {
+    commands: HashMap<db::Key, db::models::Command>,
+    balances: HashMap<(String, String), db::models::Balance>,
+    quotes: Vec<db::models::Quote>,
+    /// The id of the last quote added.
+    quote_id: i32,
+}
+
+/// Backend which keeps everything in memory.
+///
+/// Nothing is persisted, so all state is lost when it's dropped.
+#[derive(Default)]
+pub struct Memory {
+    inner: Mutex<MemoryInner>,
+}
+
+impl Memory {
+    /// Construct a new, empty in-memory backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for Memory {
+    async fn commands_list(&self) -> Result<Vec<db::models::Command>> {
+        let inner = self.inner.lock();
+
+        Ok(inner
+            .commands
+            .values()
+            .filter(|c| !c.disabled)
+            .cloned()
+            .collect())
+    }
+
+    async fn commands_list_all(&self, channel: &str) -> Result<Vec<db::models::Command>> {
+        let inner = self.inner.lock();
+
+        Ok(inner
+            .commands
+            .values()
+            .filter(|c| c.channel == channel)
+            .cloned()
+            .collect())
+    }
+
+    async fn commands_list_group(
+        &self,
+        channel: &str,
+        group: &str,
+    ) -> Result<Vec<db::models::Command>> {
+        let inner = self.inner.lock();
+
+        Ok(inner
+            .commands
+            .values()
+            .filter(|c| c.channel == channel && c.group.as_deref() == Some(group))
+            .cloned()
+            .collect())
+    }
+
+    async fn commands_set_group_disabled(
+        &self,
+        channel: &str,
+        group: &str,
+        disabled: bool,
+    ) -> Result<()> {
+        let mut inner = self.inner.lock();
+
+        for c in inner.commands.values_mut() {
+            if c.channel == channel && c.group.as_deref() == Some(group) {
+                c.disabled = disabled;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn command_fetch(&self, key: &db::Key) -> Result<Option<db::models::Command>> {
+        Ok(self.inner.lock().commands.get(key).cloned())
+    }
+
+    async fn command_edit(&self, key: &db::Key, text: &str) -> Result<db::models::Command> {
+        let mut inner = self.inner.lock();
+
+        if let Some(command) = inner.commands.get_mut(key) {
+            let old = command.clone();
+            command.text = text.to_string();
+            return Ok(old);
+        }
+
+        let command = db::models::Command {
+            channel: key.channel.to_string(),
+            pattern: None,
+            name: key.name.to_string(),
+            count: 0,
+            text: text.to_string(),
+            group: None,
+            disabled: false,
+            description: None,
+            usage: None,
+            count_updated_at: None,
+            count_updated_by: None,
+            games: None,
+            cooldown: None,
+            user_cooldown: None,
+            stream_limit: None,
+            moderator_cooldown: None,
+            cooldown_group: None,
+            permission: None,
+            triggers: None,
+            cost: None,
+        };
+
+        inner.commands.insert(key.clone(), command.clone());
+        Ok(command)
+    }
+
+    async fn command_edit_pattern(&self, key: &db::Key, pattern: Option<&str>) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.pattern = pattern.map(String::from);
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_description(
+        &self,
+        key: &db::Key,
+        description: Option<&str>,
+    ) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.description = description.map(String::from);
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_usage(&self, key: &db::Key, usage: Option<&str>) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.usage = usage.map(String::from);
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_games(&self, key: &db::Key, games: Option<&str>) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.games = games.map(String::from);
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_limits(&self, key: &db::Key, limits: &db::CommandLimits) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            let set = limits.to_db();
+            command.cooldown = set.cooldown;
+            command.user_cooldown = set.user_cooldown;
+            command.moderator_cooldown = set.moderator_cooldown;
+            command.stream_limit = set.stream_limit;
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_cooldown_group(&self, key: &db::Key, group: Option<&str>) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.cooldown_group = group.map(String::from);
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_permission(
+        &self,
+        key: &db::Key,
+        permission: Option<Permission>,
+    ) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.permission = permission.map(|p| p.to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_triggers(&self, key: &db::Key, triggers: Option<&str>) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.triggers = triggers.map(String::from);
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_cost(&self, key: &db::Key, cost: Option<u32>) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.cost = cost.map(|cost| i32::try_from(cost).unwrap_or(i32::MAX));
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_group(&self, key: &db::Key, group: Option<String>) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.group = group;
+        }
+
+        Ok(())
+    }
+
+    async fn command_edit_disabled(&self, key: &db::Key, disabled: bool) -> Result<()> {
+        if let Some(command) = self.inner.lock().commands.get_mut(key) {
+            command.disabled = disabled;
+        }
+
+        Ok(())
+    }
+
+    async fn command_increment(&self, key: &db::Key, by: Option<&str>) -> Result<bool> {
+        self.command_add_count(key, 1, by).await
+    }
+
+    async fn command_add_count(
+        &self,
+        key: &db::Key,
+        amount: i32,
+        by: Option<&str>,
+    ) -> Result<bool> {
+        match self.inner.lock().commands.get_mut(key) {
+            Some(command) => {
+                command.count = match command.count.checked_add(amount) {
+                    Some(count) => count,
+                    None => bail!("count of command `{}` would overflow", key),
+                };
+
+                command.count_updated_at = Some(Utc::now().naive_utc());
+                command.count_updated_by = by.map(String::from);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn command_set_count(&self, key: &db::Key, count: i32, by: Option<&str>) -> Result<bool> {
+        match self.inner.lock().commands.get_mut(key) {
+            Some(command) => {
+                command.count = count;
+                command.count_updated_at = Some(Utc::now().naive_utc());
+                command.count_updated_by = by.map(String::from);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn command_delete(&self, key: &db::Key) -> Result<bool> {
+        Ok(self.inner.lock().commands.remove(key).is_some())
+    }
+
+    async fn command_rename(&self, from: &db::Key, to: &db::Key) -> Result<bool> {
+        let mut inner = self.inner.lock();
+
+        if inner.commands.contains_key(to) {
+            return Ok(false);
+        }
+
+        let mut command = match inner.commands.remove(from) {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+
+        command.channel = to.channel.clone();
+        command.name = to.name.clone();
+        inner.commands.insert(to.clone(), command);
+        Ok(true)
+    }
+
+    async fn balance_of(&self, channel: &str, user: &str) -> Result<Option<db::models::Balance>> {
+        let inner = self.inner.lock();
+        let key = (channel.to_string(), user.to_string());
+        Ok(inner.balances.get(&key).cloned())
+    }
+
+    async fn balance_add(&self, channel: &str, user: &str, amount: i64) -> Result<()> {
+        let mut inner = self.inner.lock();
+        memory_modify_balance(&mut inner.balances, channel, user, amount);
+        Ok(())
+    }
+
+    async fn balance_take(&self, channel: &str, user: &str, amount: i64) -> Result<bool> {
+        let mut inner = self.inner.lock();
+
+        let key = (channel.to_string(), user.to_string());
+        let balance = inner
+            .balances
+            .get(&key)
+            .map(|b| b.amount)
+            .unwrap_or_default();
+
+        if balance < amount {
+            return Ok(false);
+        }
+
+        memory_modify_balance(&mut inner.balances, channel, user, -amount);
+        Ok(true)
+    }
+
+    async fn balance_transfer(
+        &self,
+        channel: &str,
+        giver: &str,
+        taker: &str,
+        amount: i64,
+        override_balance: bool,
+    ) -> Result<bool> {
+        let mut inner = self.inner.lock();
+
+        let key = (channel.to_string(), giver.to_string());
+        let balance = inner
+            .balances
+            .get(&key)
+            .map(|b| b.amount)
+            .unwrap_or_default();
+
+        if balance < amount && !override_balance {
+            return Ok(false);
+        }
+
+        memory_modify_balance(&mut inner.balances, channel, taker, amount);
+        memory_modify_balance(&mut inner.balances, channel, giver, -amount);
+        Ok(true)
+    }
+
+    async fn balances_increment(
+        &self,
+        channel: &str,
+        users: Vec<String>,
+        amount: i64,
+        watch_time: i64,
+    ) -> Result<()> {
+        let mut inner = self.inner.lock();
+
+        for user in users {
+            let balance = inner
+                .balances
+                .entry((channel.to_string(), user.clone()))
+                .or_insert_with(|| db::models::Balance {
+                    channel: channel.to_string(),
+                    user,
+                    amount: 0,
+                    watch_time: 0,
+                });
+
+            balance.amount = balance.amount.saturating_add(amount);
+            balance.watch_time = balance.watch_time.saturating_add(watch_time);
+        }
+
+        Ok(())
+    }
+
+    async fn balances_top(&self, channel: &str, limit: usize) -> Result<Vec<db::models::Balance>> {
+        let mut balances = self
+            .inner
+            .lock()
+            .balances
+            .values()
+            .filter(|b| b.channel == channel)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        balances.sort_by(|a, b| b.amount.cmp(&a.amount).then_with(|| a.user.cmp(&b.user)));
+        balances.truncate(limit);
+        Ok(balances)
+    }
+
+    async fn balances_export(&self) -> Result<Vec<db::models::Balance>> {
+        Ok(self.inner.lock().balances.values().cloned().collect())
+    }
+
+    async fn balances_import(&self, balances: Vec<db::models::Balance>) -> Result<()> {
+        let mut inner = self.inner.lock();
+
+        for balance in balances {
+            let key = (balance.channel.clone(), balance.user.clone());
+            inner.balances.insert(key, balance);
+        }
+
+        Ok(())
+    }
+
+    async fn quote_add(&self, channel: &str, user: &str, text: &str) -> Result<db::models::Quote> {
+        let mut inner = self.inner.lock();
+
+        let last = inner
+            .quotes
+            .iter()
+            .filter(|q| q.channel == channel)
+            .map(|q| q.number)
+            .max()
+            .unwrap_or_default();
+
+        let number = match last.checked_add(1) {
+            Some(number) => number,
+            None => bail!("too many quotes in {}", channel),
+        };
+
+        inner.quote_id += 1;
+
+        let quote = db::models::Quote {
+            id: inner.quote_id,
+            channel: channel.to_string(),
+            number,
+            added_at: Utc::now().naive_utc(),
+            user: user.to_string(),
+            text: text.to_string(),
+        };
+
+        inner.quotes.push(quote.clone());
+        Ok(quote)
+    }
+
+    async fn quote_fetch(&self, channel: &str, number: i32) -> Result<Option<db::models::Quote>> {
+        Ok(self
+            .inner
+            .lock()
+            .quotes
+            .iter()
+            .find(|q| q.channel == channel && q.number == number)
+            .cloned())
+    }
+
+    async fn quote_random(&self, channel: &str) -> Result<Option<db::models::Quote>> {
+        use rand::seq::IteratorRandom as _;
+
+        Ok(self
+            .inner
+            .lock()
+            .quotes
+            .iter()
+            .filter(|q| q.channel == channel)
+            .choose(&mut rand::thread_rng())
+            .cloned())
+    }
+
+    async fn quote_delete(&self, channel: &str, number: i32) -> Result<Option<db::models::Quote>> {
+        let mut inner = self.inner.lock();
+
+        let index = inner
+            .quotes
+            .iter()
+            .position(|q| q.channel == channel && q.number == number);
+
+        Ok(index.map(|index| inner.quotes.remove(index)))
+    }
+}
+
+/// Modify the in-memory balance for the given user.
+fn memory_modify_balance(
+    balances: &mut HashMap<(String, String), db::models::Balance>,
+    channel: &str,
+    user: &str,
+    amount: i64,
+) {
+    let balance = balances
+        .entry((channel.to_string(), user.to_string()))
+        .or_insert_with(|| db::models::Balance {
+            channel: channel.to_string(),
+            user: user.to_string(),
+            amount: 0,
+            watch_time: 0,
+        });
+
+    balance.amount = balance.amount.saturating_add(amount);
+}
+
+type BalanceKey = (String, String);
+
+/// The most balances and commands which are cached at a time.
+const CACHE_CAPACITY: usize = 1000;
+
+struct CachedInner {
+    balances: Lru<BalanceKey, Option<db::models::Balance>>,
+    commands: Lru<db::Key, Option<db::models::Command>>,
+    /// Bumped on every write, so that a read which raced with a write doesn't
+    /// cache what it read.
+    generation: u64,
+}
+
+/// Backend which caches balances and commands read from another backend.
+///
+/// Writes go straight to the wrapped backend, after which anything they might
+/// have changed is dropped from the cache. So a read after a write always sees
+/// the written value, as long as every write goes through the same cache.
+///
+/// A bounded number of balances and commands are cached, after which the
+/// least recently used ones are dropped.
+pub struct Cached<B> {
+    backend: B,
+    inner: Mutex<CachedInner>,
+}
+
+impl<B> Cached<B>
+where
+    B: Backend,
+{
+    /// Construct a new cache around the given backend.
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            inner: Mutex::new(CachedInner {
+                balances: Lru::new(CACHE_CAPACITY, None),
+                commands: Lru::new(CACHE_CAPACITY, None),
+                generation: 0,
+            }),
+        }
+    }
+
+    /// Set if balances should be cached, dropping any cached balances if not.
+    pub fn set_cache_balances(&self, cache_balances: bool) {
+        let capacity = if cache_balances { CACHE_CAPACITY } else { 0 };
+        let mut inner = self.inner.lock();
+        inner.generation += 1;
+        inner.balances.set_capacity(capacity);
+    }
+
+    /// Drop cached balances matching the given keys.
+    fn invalidate_balances<'a>(&self, keys: impl IntoIterator<Item = (&'a str, &'a str)>) {
+        let mut inner = self.inner.lock();
+        inner.generation += 1;
+
+        for (channel, user) in keys {
+            inner
+                .balances
+                .remove(&(channel.to_string(), user.to_string()));
+        }
+    }
+
+    /// Drop the cached commands matching the given keys.
+    fn invalidate_commands<'a>(&self, keys: impl IntoIterator<Item = &'a db::Key>) {
+        let mut inner = self.inner.lock();
+        inner.generation += 1;
+
+        for key in keys {
+            inner.commands.remove(key);
+        }
+    }
+
+    /// Drop everything which is cached.
+    fn invalidate_all(&self) {
+        let mut inner = self.inner.lock();
+        inner.generation += 1;
+        inner.balances.clear();
+        inner.commands.clear();
+    }
+}
+
+#[async_trait::async_trait]
+impl<B> Backend for Cached<B>
+where
+    B: Backend,
+{
+    async fn commands_list(&self) -> Result<Vec<db::models::Command>> {
+        self.backend.commands_list().await
+    }
+
+    async fn commands_list_all(&self, channel: &str) -> Result<Vec<db::models::Command>> {
+        self.backend.commands_list_all(channel).await
+    }
+
+    async fn commands_list_group(
+        &self,
+        channel: &str,
+        group: &str,
+    ) -> Result<Vec<db::models::Command>> {
+        self.backend.commands_list_group(channel, group).await
+    }
+
+    async fn commands_set_group_disabled(
+        &self,
+        channel: &str,
+        group: &str,
+        disabled: bool,
+    ) -> Result<()> {
+        let result = self
+            .backend
+            .commands_set_group_disabled(channel, group, disabled)
+            .await;
+        // NB: we don't know which commands are in the group.
+        self.invalidate_all();
+        result
+    }
+
+    async fn command_fetch(&self, key: &db::Key) -> Result<Option<db::models::Command>> {
+        let generation = {
+            let mut inner = self.inner.lock();
+
+            if let Some(command) = inner.commands.get(key, Instant::now()) {
+                return Ok(command);
+            }
+
+            inner.generation
+        };
+
+        let command = self.backend.command_fetch(key).await?;
+        let mut inner = self.inner.lock();
+
+        if inner.generation == generation {
+            inner
+                .commands
+                .insert(key.clone(), command.clone(), Instant::now());
+        }
+
+        Ok(command)
+    }
+
+    async fn command_edit(&self, key: &db::Key, text: &str) -> Result<db::models::Command> {
+        let result = self.backend.command_edit(key, text).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_pattern(&self, key: &db::Key, pattern: Option<&str>) -> Result<()> {
+        let result = self.backend.command_edit_pattern(key, pattern).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_description(
+        &self,
+        key: &db::Key,
+        description: Option<&str>,
+    ) -> Result<()> {
+        let result = self
+            .backend
+            .command_edit_description(key, description)
+            .await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_usage(&self, key: &db::Key, usage: Option<&str>) -> Result<()> {
+        let result = self.backend.command_edit_usage(key, usage).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_games(&self, key: &db::Key, games: Option<&str>) -> Result<()> {
+        let result = self.backend.command_edit_games(key, games).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_limits(&self, key: &db::Key, limits: &db::CommandLimits) -> Result<()> {
+        let result = self.backend.command_edit_limits(key, limits).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_cooldown_group(&self, key: &db::Key, group: Option<&str>) -> Result<()> {
+        let result = self.backend.command_edit_cooldown_group(key, group).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_permission(
+        &self,
+        key: &db::Key,
+        permission: Option<Permission>,
+    ) -> Result<()> {
+        let result = self.backend.command_edit_permission(key, permission).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_triggers(&self, key: &db::Key, triggers: Option<&str>) -> Result<()> {
+        let result = self.backend.command_edit_triggers(key, triggers).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_cost(&self, key: &db::Key, cost: Option<u32>) -> Result<()> {
+        let result = self.backend.command_edit_cost(key, cost).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_group(&self, key: &db::Key, group: Option<String>) -> Result<()> {
+        let result = self.backend.command_edit_group(key, group).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_edit_disabled(&self, key: &db::Key, disabled: bool) -> Result<()> {
+        let result = self.backend.command_edit_disabled(key, disabled).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_increment(&self, key: &db::Key, by: Option<&str>) -> Result<bool> {
+        let result = self.backend.command_increment(key, by).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_add_count(
+        &self,
+        key: &db::Key,
+        amount: i32,
+        by: Option<&str>,
+    ) -> Result<bool> {
+        let result = self.backend.command_add_count(key, amount, by).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_set_count(&self, key: &db::Key, count: i32, by: Option<&str>) -> Result<bool> {
+        let result = self.backend.command_set_count(key, count, by).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_delete(&self, key: &db::Key) -> Result<bool> {
+        let result = self.backend.command_delete(key).await;
+        self.invalidate_commands(Some(key));
+        result
+    }
+
+    async fn command_rename(&self, from: &db::Key, to: &db::Key) -> Result<bool> {
+        let result = self.backend.command_rename(from, to).await;
+        self.invalidate_commands(vec![from, to]);
+        result
+    }
+
+    async fn balance_of(&self, channel: &str, user: &str) -> Result<Option<db::models::Balance>> {
+        let key = (channel.to_string(), user.to_string());
+
+        let generation = {
+            let mut inner = self.inner.lock();
+
+            if let Some(balance) = inner.balances.get(&key, Instant::now()) {
+                return Ok(balance);
+            }
+
+            inner.generation
+        };
+
+        let balance = self.backend.balance_of(channel, user).await?;
+        let mut inner = self.inner.lock();
+
+        if inner.generation == generation {
+            inner.balances.insert(key, balance.clone(), Instant::now());
+        }
+
+        Ok(balance)
+    }
+
+    async fn balance_add(&self, channel: &str, user: &str, amount: i64) -> Result<()> {
+        let result = self.backend.balance_add(channel, user, amount).await;
+        self.invalidate_balances(Some((channel, user)));
+        result
+    }
+
+    async fn balance_take(&self, channel: &str, user: &str, amount: i64) -> Result<bool> {
+        let result = self.backend.balance_take(channel, user, amount).await;
+        self.invalidate_balances(Some((channel, user)));
+        result
+    }
+
+    async fn balance_transfer(
+        &self,
+        channel: &str,
+        giver: &str,
+        taker: &str,
+        amount: i64,
+        override_balance: bool,
+    ) -> Result<bool> {
+        let result = self
+            .backend
+            .balance_transfer(channel, giver, taker, amount, override_balance)
+            .await;
+        self.invalidate_balances(vec![(channel, giver), (channel, taker)]);
+        result
+    }
+
+    async fn balances_increment(
+        &self,
+        channel: &str,
+        users: Vec<String>,
+        amount: i64,
+        watch_time: i64,
+    ) -> Result<()> {
+        let keys = users
+            .iter()
+            .map(|user| (channel.to_string(), user.to_string()))
+            .collect::<Vec<_>>();
+
+        let result = self
+            .backend
+            .balances_increment(channel, users, amount, watch_time)
+            .await;
+
+        self.invalidate_balances(keys.iter().map(|(c, u)| (c.as_str(), u.as_str())));
+        result
+    }
+
+    async fn balances_top(&self, channel: &str, limit: usize) -> Result<Vec<db::models::Balance>> {
+        self.backend.balances_top(channel, limit).await
+    }
+
+    async fn balances_export(&self) -> Result<Vec<db::models::Balance>> {
+        self.backend.balances_export().await
+    }
+
+    async fn balances_import(&self, balances: Vec<db::models::Balance>) -> Result<()> {
+        let result = self.backend.balances_import(balances).await;
+        self.invalidate_all();
+        result
+    }
+
+    async fn quote_add(&self, channel: &str, user: &str, text: &str) -> Result<db::models::Quote> {
+        self.backend.quote_add(channel, user, text).await
+    }
+
+    async fn quote_fetch(&self, channel: &str, number: i32) -> Result<Option<db::models::Quote>> {
+        self.backend.quote_fetch(channel, number).await
+    }
+
+    async fn quote_random(&self, channel: &str) -> Result<Option<db::models::Quote>> {
+        self.backend.quote_random(channel).await
+    }
+
+    async fn quote_delete(&self, channel: &str, number: i32) -> Result<Option<db::models::Quote>> {
+        self.backend.quote_delete(channel, number).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, Cached, Memory, Sqlite, CACHE_CAPACITY};
+    use crate::auth::Permission;
+    use crate::db;
+    use crate::utils::Duration;
+    use anyhow::Result;
+
+    async fn sqlite() -> Result<Sqlite> {
+        Ok(Sqlite::new(db::Database::memory()?))
+    }
+
+    fn key(name: &str) -> db::Key {
+        db::Key::new("#channel", name)
+    }
+
+    async fn test_commands(backend: &dyn Backend) -> Result<()> {
+        assert!(backend.commands_list().await?.is_empty());
+
+        let created = backend.command_edit(&key("foo"), "hello").await?;
+        assert_eq!("hello", created.text);
+        assert_eq!(0, created.count);
+
+        let old = backend.command_edit(&key("foo"), "world").await?;
+        assert_eq!("hello", old.text);
+
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!("world", command.text);
+
+        assert!(command.count_updated_at.is_none());
+        assert!(backend.command_increment(&key("foo"), None).await?);
+        assert!(backend.command_increment(&key("foo"), Some("bar")).await?);
+        assert!(!backend.command_increment(&key("missing"), None).await?);
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(2, command.count);
+        assert!(command.count_updated_at.is_some());
+        assert_eq!(Some("bar"), command.count_updated_by.as_deref());
+
+        assert!(backend.command_add_count(&key("foo"), 5, None).await?);
+        assert!(backend.command_add_count(&key("foo"), -3, None).await?);
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(4, command.count);
+
+        assert!(
+            backend
+                .command_set_count(&key("foo"), 10, Some("baz"))
+                .await?
+        );
+        assert!(!backend.command_set_count(&key("missing"), 10, None).await?);
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(10, command.count);
+        assert_eq!(Some("baz"), command.count_updated_by.as_deref());
+
+        assert!(
+            backend
+                .command_set_count(&key("foo"), i32::MAX, None)
+                .await?
+        );
+        assert!(backend
+            .command_add_count(&key("foo"), 1, None)
+            .await
+            .is_err());
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(i32::MAX, command.count);
+        assert!(
+            backend
+                .command_set_count(&key("foo"), 2, Some("bar"))
+                .await?
+        );
+
+        backend
+            .command_edit_pattern(&key("foo"), Some("^foo$"))
+            .await?;
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(Some("^foo$"), command.pattern.as_deref());
+
+        backend
+            .command_edit_description(&key("foo"), Some("Says hello"))
+            .await?;
+        backend
+            .command_edit_usage(&key("foo"), Some("!foo"))
+            .await?;
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(Some("Says hello"), command.description.as_deref());
+        assert_eq!(Some("!foo"), command.usage.as_deref());
+
+        backend.command_edit_usage(&key("foo"), None).await?;
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(None, command.usage);
+
+        backend
+            .command_edit_games(&key("foo"), Some("Dark Souls\nElden Ring"))
+            .await?;
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(Some("Dark Souls\nElden Ring"), command.games.as_deref());
+
+        let limits = db::CommandLimits {
+            cooldown: Some(Duration::seconds(30)),
+            moderator_cooldown: Some(Duration::seconds(5)),
+            user_cooldown: None,
+            stream_limit: Some(5),
+        };
+        backend.command_edit_limits(&key("foo"), &limits).await?;
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(Some(30), command.cooldown);
+        assert_eq!(Some(5), command.moderator_cooldown);
+        assert_eq!(None, command.user_cooldown);
+        assert_eq!(Some(5), command.stream_limit);
+
+        backend
+            .command_edit_cooldown_group(&key("foo"), Some("hype"))
+            .await?;
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(Some("hype"), command.cooldown_group.as_deref());
+
+        backend
+            .command_edit_permission(&key("foo"), Some(Permission::Vip))
+            .await?;
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(Some("vip"), command.permission.as_deref());
+
+        backend
+            .command_edit_triggers(&key("foo"), Some("!bar\n!baz"))
+            .await?;
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(Some("!bar\n!baz"), command.triggers.as_deref());
+
+        backend.command_edit_cost(&key("foo"), Some(100)).await?;
+        let command = backend.command_fetch(&key("foo")).await?.expect("command");
+        assert_eq!(Some(100), command.cost);
+
+        backend.command_edit(&key("bar"), "bar").await?;
+        backend
+            .command_edit_group(&key("bar"), Some(String::from("g")))
+            .await?;
+        assert_eq!(1, backend.commands_list_group("#channel", "g").await?.len());
+
+        backend
+            .commands_set_group_disabled("#channel", "g", true)
+            .await?;
+        assert_eq!(1, backend.commands_list().await?.len());
+        assert_eq!(2, backend.commands_list_all("#channel").await?.len());
+        assert!(backend.commands_list_all("#other").await?.is_empty());
+
+        backend.command_edit_disabled(&key("bar"), false).await?;
+        assert_eq!(2, backend.commands_list().await?.len());
+
+        assert!(backend.command_rename(&key("foo"), &key("baz")).await?);
+        assert!(backend.command_fetch(&key("foo")).await?.is_none());
+        let command = backend.command_fetch(&key("baz")).await?.expect("command");
+        assert_eq!("baz", command.name);
+        assert_eq!(2, command.count);
+
+        assert!(backend.command_delete(&key("baz")).await?);
+        assert!(!backend.command_delete(&key("baz")).await?);
+        assert_eq!(1, backend.commands_list_all("#channel").await?.len());
+        Ok(())
+    }
+
+    async fn test_balances(backend: &dyn Backend) -> Result<()> {
+        assert!(backend.balance_of("#channel", "foo").await?.is_none());
+
+        backend.balance_add("#channel", "foo", 10).await?;
+        backend.balance_add("#channel", "foo", -3).await?;
+        let balance = backend
+            .balance_of("#channel", "foo")
+            .await?
+            .expect("balance");
+        assert_eq!(7, balance.amount);
+        assert_eq!(0, balance.watch_time);
+
+        assert!(
+            !backend
+                .balance_transfer("#channel", "foo", "bar", 8, false)
+                .await?
+        );
+        assert!(backend.balance_of("#channel", "bar").await?.is_none());
+
+        assert!(
+            backend
+                .balance_transfer("#channel", "foo", "bar", 5, false)
+                .await?
+        );
+        assert!(
+            backend
+                .balance_transfer("#channel", "foo", "bar", 5, true)
+                .await?
+        );
+        assert_eq!(
+            -3,
+            backend
+                .balance_of("#channel", "foo")
+                .await?
+                .expect("balance")
+                .amount
+        );
+        assert_eq!(
+            10,
+            backend
+                .balance_of("#channel", "bar")
+                .await?
+                .expect("balance")
+                .amount
+        );
+
+        assert!(!backend.balance_take("#channel", "foo", 1).await?);
+        assert!(backend.balance_take("#channel", "bar", 4).await?);
+        assert!(!backend.balance_take("#channel", "bar", 7).await?);
+        assert_eq!(
+            6,
+            backend
+                .balance_of("#channel", "bar")
+                .await?
+                .expect("balance")
+                .amount
+        );
+        backend.balance_add("#channel", "bar", 4).await?;
+
+        backend
+            .balances_increment(
+                "#channel",
+                vec![String::from("bar"), String::from("baz")],
+                2,
+                60,
+            )
+            .await?;
+        let bar = backend
+            .balance_of("#channel", "bar")
+            .await?
+            .expect("balance");
+        assert_eq!((12, 60), (bar.amount, bar.watch_time));
+        let baz = backend
+            .balance_of("#channel", "baz")
+            .await?
+            .expect("balance");
+        assert_eq!((2, 60), (baz.amount, baz.watch_time));
+        assert!(backend.balance_of("#other", "baz").await?.is_none());
+
+        backend
+            .balances_import(vec![
+                db::models::Balance {
+                    channel: String::from("#channel"),
+                    user: String::from("baz"),
+                    amount: 100,
+                    watch_time: 10,
+                },
+                db::models::Balance {
+                    channel: String::from("#other"),
+                    user: String::from("qux"),
+                    amount: 1,
+                    watch_time: 1,
+                },
+            ])
+            .await?;
+        let baz = backend
+            .balance_of("#channel", "baz")
+            .await?
+            .expect("balance");
+        assert_eq!((100, 10), (baz.amount, baz.watch_time));
+
+        let mut exported = backend
+            .balances_export()
+            .await?
+            .into_iter()
+            .map(|b| (b.channel, b.user, b.amount))
+            .collect::<Vec<_>>();
+        exported.sort();
+
+        assert_eq!(
+            vec![
+                (String::from("#channel"), String::from("bar"), 12),
+                (String::from("#channel"), String::from("baz"), 100),
+                (String::from("#channel"), String::from("foo"), -3),
+                (String::from("#other"), String::from("qux"), 1),
+            ],
+            exported
+        );
+
+        backend
+            .balances_import(vec![db::models::Balance {
+                channel: String::from("#channel"),
+                user: String::from("abc"),
+                amount: 12,
+                watch_time: 0,
+            }])
+            .await?;
+
+        let top = backend
+            .balances_top("#channel", 3)
+            .await?
+            .into_iter()
+            .map(|b| (b.user, b.amount))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (String::from("baz"), 100),
+                (String::from("abc"), 12),
+                (String::from("bar"), 12),
+            ],
+            top
+        );
+
+        Ok(())
+    }
+
+    async fn test_quotes(backend: &dyn Backend) -> Result<()> {
+        assert!(backend.quote_random("#channel").await?.is_none());
+
+        let first = backend.quote_add("#channel", "foo", "first").await?;
+        let second = backend.quote_add("#channel", "foo", "second").await?;
+        let other = backend.quote_add("#other", "bar", "other").await?;
+        assert_eq!((1, 2, 1), (first.number, second.number, other.number));
+
+        let quote = backend.quote_delete("#channel", 1).await?.expect("quote");
+        assert_eq!("first", quote.text);
+        assert!(backend.quote_delete("#channel", 1).await?.is_none());
+
+        // NB: numbers don't change when other quotes are deleted.
+        assert!(backend.quote_fetch("#channel", 1).await?.is_none());
+        let quote = backend.quote_fetch("#channel", 2).await?.expect("quote");
+        assert_eq!("second", quote.text);
+        assert_eq!("foo", quote.user);
+
+        let quote = backend.quote_random("#channel").await?.expect("quote");
+        assert_eq!(2, quote.number);
+
+        let third = backend.quote_add("#channel", "foo", "third").await?;
+        assert_eq!(3, third.number);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_commands() -> Result<()> {
+        test_commands(&Memory::new()).await
+    }
+
+    #[tokio::test]
+    async fn test_memory_balances() -> Result<()> {
+        test_balances(&Memory::new()).await
+    }
+
+    #[tokio::test]
+    async fn test_memory_quotes() -> Result<()> {
+        test_quotes(&Memory::new()).await
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_commands() -> Result<()> {
+        test_commands(&sqlite().await?).await
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_balances() -> Result<()> {
+        test_balances(&sqlite().await?).await
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_quotes() -> Result<()> {
+        test_quotes(&sqlite().await?).await
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_increment_retried() -> Result<()> {
+        use diesel::Connection as _;
+
+        let backend = sqlite().await?;
+        backend.0.set_retries(1);
+
+        // NB: crediting `bar` fails like a busy database would, after `foo`
+        // has already been credited.
+        backend
+            .0
+            .asyncify(|c| {
+                c.execute(
+                    "CREATE TRIGGER fail_bar BEFORE INSERT ON balances WHEN NEW.user = 'bar' \
+                     BEGIN SELECT RAISE(ABORT, 'database is locked'); END",
+                )?;
+                Ok::<_, anyhow::Error>(())
+            })
+            .await?;
+
+        let users = vec![String::from("foo"), String::from("bar")];
+
+        assert!(backend
+            .balances_increment("#channel", users.clone(), 10, 60)
+            .await
+            .is_err());
+        assert!(backend.balance_of("#channel", "foo").await?.is_none());
+
+        backend
+            .0
+            .asyncify(|c| {
+                c.execute("DROP TRIGGER fail_bar")?;
+                Ok::<_, anyhow::Error>(())
+            })
+            .await?;
+
+        backend
+            .balances_increment("#channel", users, 10, 60)
+            .await?;
+
+        for user in &["foo", "bar"] {
+            let balance = backend
+                .balance_of("#channel", user)
+                .await?
+                .expect("balance");
+            assert_eq!((10, 60), (balance.amount, balance.watch_time));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cached_commands() -> Result<()> {
+        test_commands(&Cached::new(sqlite().await?)).await
+    }
+
+    #[tokio::test]
+    async fn test_cached_balances() -> Result<()> {
+        test_balances(&Cached::new(sqlite().await?)).await
+    }
+
+    #[tokio::test]
+    async fn test_cached_quotes() -> Result<()> {
+        test_quotes(&Cached::new(sqlite().await?)).await
+    }
+
+    #[tokio::test]
+    async fn test_cached_read_after_write() -> Result<()> {
+        let backend = Cached::new(sqlite().await?);
+
+        // NB: cache that there is no balance or command.
+        assert!(backend.balance_of("#channel", "foo").await?.is_none());
+        assert!(backend.command_fetch(&key("foo")).await?.is_none());
+
+        backend.balance_add("#channel", "foo", 10).await?;
+        let balance = backend.balance_of("#channel", "foo").await?;
+        assert_eq!(Some(10), balance.map(|b| b.amount));
+
+        backend
+            .balances_increment("#channel", vec![String::from("foo")], 5, 60)
+            .await?;
+        let balance = backend.balance_of("#channel", "foo").await?;
+        assert_eq!(Some(15), balance.map(|b| b.amount));
+
+        backend.command_edit(&key("foo"), "hello").await?;
+        let command = backend.command_fetch(&key("foo")).await?;
+        assert_eq!(Some("hello"), command.as_ref().map(|c| c.text.as_str()));
+
+        backend
+            .command_edit_group(&key("foo"), Some(String::from("g")))
+            .await?;
+        let command = backend.command_fetch(&key("foo")).await?;
+        assert_eq!(Some(false), command.map(|c| c.disabled));
+        backend
+            .commands_set_group_disabled("#channel", "g", true)
+            .await?;
+        let command = backend.command_fetch(&key("foo")).await?;
+        assert_eq!(Some(true), command.map(|c| c.disabled));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cached_capacity() -> Result<()> {
+        let backend = Cached::new(Memory::new());
+
+        for n in 0..=CACHE_CAPACITY {
+            backend.balance_of("#channel", &n.to_string()).await?;
+        }
+
+        assert_eq!(CACHE_CAPACITY, backend.inner.lock().balances.len());
+
+        // NB: writes which bypass the cache are seen once balances aren't
+        // cached.
+        backend.set_cache_balances(false);
+        backend.backend.balance_add("#channel", "0", 10).await?;
+        let balance = backend.balance_of("#channel", "0").await?;
+        assert_eq!(Some(10), balance.map(|b| b.amount));
+        assert_eq!(0, backend.inner.lock().balances.len());
+        Ok(())
+    }
+}
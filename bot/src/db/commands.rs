@@ -1,108 +1,168 @@
+use crate::auth::Permission;
 use crate::db;
 use crate::template;
 use crate::utils;
 use anyhow::{anyhow, Context as _, Error};
-use diesel::prelude::*;
+use chrono::{DateTime, Utc};
 use std::collections::HashSet;
+use std::convert::TryFrom as _;
 use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time;
 use tokio::sync::RwLock;
 
 /// Local database wrapper.
 #[derive(Clone)]
-struct Database(db::Database);
+struct Database(Arc<dyn db::Backend>);
 
 impl Database {
-    private_database_group_fns!(commands, Command, db::Key);
+    /// List all members that are not disabled.
+    async fn list(&self) -> Result<Vec<db::models::Command>, Error> {
+        self.0.commands_list().await
+    }
 
-    /// Edit the text for the given key.
-    async fn edit(&self, key: &db::Key, text: &str) -> Result<db::models::Command, Error> {
-        use db::schema::commands::dsl;
+    /// List all members, including disabled ones.
+    async fn list_all(&self, channel: &str) -> Result<Vec<db::models::Command>, Error> {
+        self.0.commands_list_all(channel).await
+    }
 
-        let key = key.clone();
-        let text = text.to_string();
+    /// List all members of the given group.
+    async fn list_group(
+        &self,
+        channel: &str,
+        group: &str,
+    ) -> Result<Vec<db::models::Command>, Error> {
+        self.0.commands_list_group(channel, group).await
+    }
 
+    /// Set if the given group is disabled or not.
+    async fn set_group_disabled(
+        &self,
+        channel: &str,
+        group: &str,
+        disabled: bool,
+    ) -> Result<(), Error> {
         self.0
-            .asyncify(move |c| {
-                let filter = dsl::commands
-                    .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
-
-                match filter.clone().first::<db::models::Command>(c).optional()? {
-                    None => {
-                        let command = db::models::Command {
-                            channel: key.channel.to_string(),
-                            pattern: None,
-                            name: key.name.to_string(),
-                            count: 0,
-                            text: text.to_string(),
-                            group: None,
-                            disabled: false,
-                        };
-
-                        diesel::insert_into(dsl::commands)
-                            .values(&command)
-                            .execute(c)?;
-                        Ok(command)
-                    }
-                    Some(command) => {
-                        let mut set = db::models::UpdateCommand::default();
-                        set.text = Some(&text);
-                        diesel::update(filter).set(&set).execute(c)?;
-                        Ok(command)
-                    }
-                }
-            })
+            .commands_set_group_disabled(channel, group, disabled)
             .await
     }
 
+    /// Edit the group membership of the given thing.
+    async fn edit_group(&self, key: &db::Key, group: Option<String>) -> Result<(), Error> {
+        self.0.command_edit_group(key, group).await
+    }
+
+    /// Set the disabled state of the given command.
+    async fn edit_disabled(&self, key: &db::Key, disabled: bool) -> Result<(), Error> {
+        self.0.command_edit_disabled(key, disabled).await
+    }
+
+    /// Fetch a single entity.
+    async fn fetch(&self, key: &db::Key) -> Result<Option<db::models::Command>, Error> {
+        self.0.command_fetch(key).await
+    }
+
+    /// Delete a single thing.
+    async fn delete(&self, key: &db::Key) -> Result<bool, Error> {
+        self.0.command_delete(key).await
+    }
+
+    /// Rename one thing to another.
+    async fn rename(&self, from: &db::Key, to: &db::Key) -> Result<bool, Error> {
+        self.0.command_rename(from, to).await
+    }
+
+    /// Edit the text for the given key.
+    async fn edit(&self, key: &db::Key, text: &str) -> Result<db::models::Command, Error> {
+        self.0.command_edit(key, text).await
+    }
+
     /// Edit the pattern of a command.
     async fn edit_pattern(
         &self,
         key: &db::Key,
         pattern: Option<&regex::Regex>,
     ) -> Result<(), anyhow::Error> {
-        use db::schema::commands::dsl;
+        self.0
+            .command_edit_pattern(key, pattern.map(|p| p.as_str()))
+            .await
+    }
+
+    /// Edit the description of a command.
+    async fn edit_description(
+        &self,
+        key: &db::Key,
+        description: Option<&str>,
+    ) -> Result<(), Error> {
+        self.0.command_edit_description(key, description).await
+    }
 
-        let key = key.clone();
-        let pattern = pattern.cloned();
+    /// Edit the usage of a command.
+    async fn edit_usage(&self, key: &db::Key, usage: Option<&str>) -> Result<(), Error> {
+        self.0.command_edit_usage(key, usage).await
+    }
 
+    /// Edit the games a command is limited to.
+    async fn edit_games(&self, key: &db::Key, games: &db::Games) -> Result<(), Error> {
         self.0
-            .asyncify(move |c| {
-                let pattern = pattern.as_ref().map(|p| p.as_str());
-
-                diesel::update(
-                    dsl::commands
-                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
-                )
-                .set(dsl::pattern.eq(pattern))
-                .execute(c)?;
-
-                Ok(())
-            })
+            .command_edit_games(key, games.to_db().as_deref())
             .await
     }
 
+    /// Edit the limits of a command.
+    async fn edit_limits(&self, key: &db::Key, limits: &Limits) -> Result<(), Error> {
+        self.0.command_edit_limits(key, limits).await
+    }
+
+    /// Edit the named cooldown a command shares with other commands.
+    async fn edit_cooldown_group(&self, key: &db::Key, group: Option<&str>) -> Result<(), Error> {
+        self.0.command_edit_cooldown_group(key, group).await
+    }
+
+    /// Edit the permission users need to use a command.
+    async fn edit_permission(
+        &self,
+        key: &db::Key,
+        permission: Option<Permission>,
+    ) -> Result<(), Error> {
+        self.0.command_edit_permission(key, permission).await
+    }
+
+    /// Edit the names a command can also be invoked by.
+    async fn edit_triggers(&self, key: &db::Key, triggers: &[String]) -> Result<(), Error> {
+        let triggers = match triggers {
+            [] => None,
+            triggers => Some(triggers.join("\n")),
+        };
+
+        self.0.command_edit_triggers(key, triggers.as_deref()).await
+    }
+
+    /// Edit how much currency it costs to use a command.
+    async fn edit_cost(&self, key: &db::Key, cost: Option<u32>) -> Result<(), Error> {
+        self.0.command_edit_cost(key, cost).await
+    }
+
     /// Increment the given key.
-    async fn increment(&self, key: &db::Key) -> Result<bool, Error> {
-        use db::schema::commands::dsl;
+    async fn increment(&self, key: &db::Key, by: Option<&str>) -> Result<bool, Error> {
+        self.0.command_increment(key, by).await
+    }
 
-        let key = key.clone();
+    async fn add_count(&self, key: &db::Key, amount: i32, by: Option<&str>) -> Result<bool, Error> {
+        self.0.command_add_count(key, amount, by).await
+    }
 
-        self.0
-            .asyncify(move |c| {
-                let count = diesel::update(
-                    dsl::commands
-                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
-                )
-                .set(dsl::count.eq(dsl::count + 1))
-                .execute(c)?;
-                Ok(count == 1)
-            })
-            .await
+    async fn set_count(&self, key: &db::Key, count: i32, by: Option<&str>) -> Result<bool, Error> {
+        self.0.command_set_count(key, count, by).await
     }
 }
 
+/// All commands, kept in memory so that resolving them doesn't hit the
+/// database.
+///
+/// Edits hold the lock on the cache while the database is updated, so that
+/// the two never disagree.
 #[derive(Clone)]
 pub struct Commands {
     inner: Arc<RwLock<db::Matcher<Command>>>,
@@ -112,14 +172,31 @@ pub struct Commands {
 impl Commands {
     database_group_fns!(Command, db::Key);
 
-    /// Construct a new commands store with a db.
+    /// Construct a new commands store with a db, caching what is read from
+    /// it.
+    ///
+    /// Prefer [Commands::load_backend] with a shared [db::Cached] if anything
+    /// else writes to the same database.
     pub async fn load(db: db::Database) -> Result<Commands, Error> {
-        let db = Database(db);
+        Self::load_backend(Arc::new(db::Cached::new(db::Sqlite::new(db)))).await
+    }
+
+    /// Construct a new commands store with the given storage backend.
+    pub async fn load_backend(backend: Arc<dyn db::Backend>) -> Result<Commands, Error> {
+        let db = Database(backend);
 
         let mut matcher = db::Matcher::new();
 
         for command in db.list().await? {
-            let command = Command::from_db(&command)?;
+            // NB: a bad command shouldn't prevent the rest from loading.
+            let command = match Command::from_db(&command) {
+                Ok(command) => command,
+                Err(e) => {
+                    log_error!(e, "failed to load command `{}`", command.name);
+                    continue;
+                }
+            };
+
             matcher.insert(command.key.clone(), Arc::new(command));
         }
 
@@ -138,6 +215,10 @@ impl Commands {
     ) -> Result<(), Error> {
         let key = db::Key::new(channel, name);
 
+        template
+            .validate(Command::is_var)
+            .with_context(|| anyhow!("bad template for command `{}`", key))?;
+
         let mut inner = self.inner.write().await;
         let command = self.db.edit(&key, template.source()).await?;
 
@@ -145,6 +226,8 @@ impl Commands {
             inner.remove(&key);
         } else {
             let vars = template.vars();
+            let required_args = required_args(&template);
+            let limits = Limits::from_db(&command);
 
             let command = Arc::new(Command {
                 key: key.clone(),
@@ -152,8 +235,18 @@ impl Commands {
                 count: Arc::new(AtomicUsize::new(command.count as usize)),
                 template,
                 vars,
+                required_args,
                 group: command.group,
                 disabled: command.disabled,
+                description: command.description,
+                usage: command.usage,
+                games: db::Games::from_db(command.games.as_ref()),
+                limits,
+                cooldown_group: command.cooldown_group,
+                permission: permission_from_db(command.permission.as_deref())?,
+                triggers: triggers_from_db(command.triggers.as_deref()),
+                cost: cost_from_db(command.cost),
+                state: Default::default(),
             });
 
             inner.insert(key, command);
@@ -170,35 +263,309 @@ impl Commands {
         pattern: Option<regex::Regex>,
     ) -> Result<bool, anyhow::Error> {
         let key = db::Key::new(channel, name);
+        let mut inner = self.inner.write().await;
         self.db.edit_pattern(&key, pattern.as_ref()).await?;
 
-        Ok(self.inner.write().await.modify(key, |command| {
+        Ok(inner.modify(key, |command| {
             command.pattern = pattern.map(db::Pattern::regex).unwrap_or_default();
         }))
     }
 
-    /// Increment the specified command.
-    pub async fn increment(&self, command: &Command) -> Result<(), Error> {
-        self.db.increment(&command.key).await?;
+    /// Edit the description for the given command.
+    pub async fn edit_description(
+        &self,
+        channel: &str,
+        name: &str,
+        description: Option<String>,
+    ) -> Result<bool, Error> {
+        let key = db::Key::new(channel, name);
+        let mut inner = self.inner.write().await;
+        self.db
+            .edit_description(&key, description.as_deref())
+            .await?;
+
+        Ok(inner.modify(key, |command| {
+            command.description = description;
+        }))
+    }
+
+    /// Edit the usage for the given command.
+    pub async fn edit_usage(
+        &self,
+        channel: &str,
+        name: &str,
+        usage: Option<String>,
+    ) -> Result<bool, Error> {
+        let key = db::Key::new(channel, name);
+        let mut inner = self.inner.write().await;
+        self.db.edit_usage(&key, usage.as_deref()).await?;
+
+        Ok(inner.modify(key, |command| {
+            command.usage = usage;
+        }))
+    }
+
+    /// Edit the games the given command is limited to.
+    pub async fn edit_games(
+        &self,
+        channel: &str,
+        name: &str,
+        games: db::Games,
+    ) -> Result<bool, Error> {
+        let key = db::Key::new(channel, name);
+        let mut inner = self.inner.write().await;
+        self.db.edit_games(&key, &games).await?;
+
+        Ok(inner.modify(key, |command| {
+            command.games = games;
+        }))
+    }
+
+    /// Edit the limits on how often the given command can be used.
+    pub async fn edit_limits(
+        &self,
+        channel: &str,
+        name: &str,
+        limits: Limits,
+    ) -> Result<bool, Error> {
+        let key = db::Key::new(channel, name);
+        let mut inner = self.inner.write().await;
+        self.db.edit_limits(&key, &limits).await?;
+
+        Ok(inner.modify(key, |command| {
+            command.limits = limits;
+        }))
+    }
+
+    /// Edit the named cooldown the given command shares with other commands.
+    pub async fn edit_cooldown_group(
+        &self,
+        channel: &str,
+        name: &str,
+        group: Option<String>,
+    ) -> Result<bool, Error> {
+        let key = db::Key::new(channel, name);
+        let mut inner = self.inner.write().await;
+        self.db.edit_cooldown_group(&key, group.as_deref()).await?;
+
+        Ok(inner.modify(key, |command| {
+            command.cooldown_group = group;
+        }))
+    }
+
+    /// Edit the permission users need to use the given command, where `None`
+    /// lets everyone use it.
+    pub async fn edit_permission(
+        &self,
+        channel: &str,
+        name: &str,
+        permission: Option<Permission>,
+    ) -> Result<bool, Error> {
+        let key = db::Key::new(channel, name);
+        let mut inner = self.inner.write().await;
+        self.db.edit_permission(&key, permission).await?;
+
+        Ok(inner.modify(key, |command| {
+            command.permission = permission.unwrap_or_default();
+        }))
+    }
+
+    /// Edit the names the given command can also be invoked by, where an
+    /// empty list removes them.
+    pub async fn edit_triggers(
+        &self,
+        channel: &str,
+        name: &str,
+        triggers: Vec<String>,
+    ) -> Result<bool, Error> {
+        let key = db::Key::new(channel, name);
+        let triggers = triggers
+            .into_iter()
+            .map(|trigger| trigger.to_lowercase())
+            .collect::<Vec<_>>();
+
+        let mut inner = self.inner.write().await;
+        self.db.edit_triggers(&key, &triggers).await?;
+
+        Ok(inner.modify(key, |command| {
+            command.triggers = triggers;
+        }))
+    }
+
+    /// Edit how much currency it costs to use the given command, where `None`
+    /// makes it free.
+    pub async fn edit_cost(
+        &self,
+        channel: &str,
+        name: &str,
+        cost: Option<u32>,
+    ) -> Result<bool, Error> {
+        let key = db::Key::new(channel, name);
+        let cost = cost.filter(|cost| *cost > 0);
+
+        let mut inner = self.inner.write().await;
+        self.db.edit_cost(&key, cost).await?;
+
+        Ok(inner.modify(key, |command| {
+            command.cost = cost;
+        }))
+    }
+
+    /// Find the first of the given names which is already used to invoke a
+    /// command other than `name`.
+    pub async fn find_conflict<'a>(
+        &self,
+        channel: &str,
+        name: &str,
+        names: &'a [String],
+    ) -> Option<&'a str> {
+        let key = db::Key::new(channel, name);
+        let inner = self.inner.read().await;
+
+        names.iter().map(String::as_str).find(|other| {
+            matches!(inner.owner(&db::Key::new(channel, other)), Some(owner) if *owner != key)
+        })
+    }
+
+    /// Increment the specified command, recording who incremented it.
+    pub async fn increment(&self, command: &Command, by: Option<&str>) -> Result<(), Error> {
+        self.db.increment(&command.key, by).await?;
         command.count.fetch_add(1, Ordering::SeqCst);
         Ok(())
     }
 
-    /// Resolve the given command.
+    /// Add the given amount to the counter of the specified command, recording
+    /// who changed it.
+    ///
+    /// Returns `false` without changing anything if the counter would
+    /// overflow.
+    pub async fn add_amount(
+        &self,
+        command: &Command,
+        amount: u32,
+        by: Option<&str>,
+    ) -> Result<bool, Error> {
+        let amount = match i32::try_from(amount) {
+            Ok(amount) if command.count().checked_add(amount).is_some() => amount,
+            _ => return Ok(false),
+        };
+
+        self.db.add_count(&command.key, amount, by).await?;
+        command.count.fetch_add(amount as usize, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    /// Subtract the given amount from the counter of the specified command,
+    /// recording who changed it. The counter doesn't go below zero.
+    pub async fn sub_amount(
+        &self,
+        command: &Command,
+        amount: u32,
+        by: Option<&str>,
+    ) -> Result<(), Error> {
+        let amount = (amount as usize).min(command.count.load(Ordering::SeqCst));
+        self.db
+            .add_count(&command.key, -(amount as i32), by)
+            .await?;
+        command.count.fetch_sub(amount, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Set the counter of the specified command, recording who changed it.
+    pub async fn set(&self, command: &Command, count: u32, by: Option<&str>) -> Result<(), Error> {
+        self.db.set_count(&command.key, count as i32, by).await?;
+        command.count.store(count as usize, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Get information on the counter of the given command.
+    pub async fn count_info(&self, channel: &str, name: &str) -> Result<Option<CountInfo>, Error> {
+        let key = db::Key::new(channel, name);
+
+        let command = match self.db.fetch(&key).await? {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+
+        Ok(Some(CountInfo {
+            count: command.count,
+            updated_at: command
+                .count_updated_at
+                .map(|at| DateTime::<Utc>::from_utc(at, Utc)),
+            updated_by: command.count_updated_by,
+        }))
+    }
+
+    /// Resolve the given command, as available while streaming the given
+    /// game.
     pub async fn resolve<'a>(
         &self,
         channel: &'a str,
+        game: Option<&str>,
         first: Option<&'a str>,
         it: &'a utils::Words,
     ) -> Option<(Arc<Command>, db::Captures<'a>)> {
         let inner = self.inner.read().await;
 
         inner
-            .resolve(channel, first, it)
+            .resolve(channel, game, first, it)
             .map(|(command, captures)| (command.clone(), captures))
     }
 }
 
+/// Information on the counter of a command.
+#[derive(Debug, Clone)]
+pub struct CountInfo {
+    /// The current count.
+    pub count: i32,
+    /// When the count was last changed.
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Who last changed the count.
+    pub updated_by: Option<String>,
+}
+
+/// A change to the counter of a command, like `!deaths add 3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountChange {
+    /// Add to the counter.
+    Add(u32),
+    /// Subtract from the counter.
+    Sub(u32),
+    /// Set the counter to an absolute value.
+    Set(u32),
+}
+
+impl CountChange {
+    /// Parse a change from the arguments to a counter.
+    ///
+    /// Returns `None` if the arguments don't change the counter, or an error
+    /// message if they do but the amount is bad.
+    pub fn parse(rest: &str) -> Option<Result<Self, String>> {
+        let mut it = rest.split_whitespace();
+
+        let change: fn(u32) -> Self = match it.next()? {
+            "add" => Self::Add,
+            "sub" => Self::Sub,
+            "set" => Self::Set,
+            _ => return None,
+        };
+
+        let amount = match it.next() {
+            Some(amount) => amount,
+            None => return Some(Err(String::from("Expected <amount>"))),
+        };
+
+        Some(match amount.parse::<i64>() {
+            Ok(n) if n < 0 => Err(String::from(
+                "Amount can't be negative, use `sub` to decrease the counter.",
+            )),
+            Ok(n) if n > i64::from(i32::MAX) => Err(format!("Amount `{}` is too large.", amount)),
+            Ok(n) => Ok(change(n as u32)),
+            Err(..) => Err(format!("Bad amount `{}`, expected a number.", amount)),
+        })
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct Command {
     /// Key for the command.
@@ -210,8 +577,55 @@ pub struct Command {
     count: Arc<AtomicUsize>,
     pub template: template::Template,
     vars: HashSet<String>,
+    required_args: usize,
     pub group: Option<String>,
     pub disabled: bool,
+    /// Description of the command.
+    pub description: Option<String>,
+    /// Usage of the command.
+    pub usage: Option<String>,
+    /// Games the command is limited to.
+    pub games: db::Games,
+    /// Limits on how often the command can be used.
+    pub limits: Limits,
+    /// The named cooldown the command shares with other commands, which is
+    /// used instead of its own.
+    pub cooldown_group: Option<String>,
+    /// The permission users need to use the command.
+    pub permission: Permission,
+    /// Other names the command can be invoked by, like `!afk` for `!lurk`.
+    pub triggers: Vec<String>,
+    /// How much currency it costs to use the command, if anything.
+    pub cost: Option<u32>,
+    /// Tracks uses of the command against its limits.
+    #[serde(skip)]
+    state: Arc<parking_lot::Mutex<LimitsState>>,
+}
+
+/// Parse the newline-separated triggers of a command as stored in the
+/// database.
+fn triggers_from_db(triggers: Option<&str>) -> Vec<String> {
+    triggers
+        .unwrap_or_default()
+        .split('\n')
+        .filter(|trigger| !trigger.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Convert the cost of a command as stored in the database, where anything
+/// which isn't positive is free.
+fn cost_from_db(cost: Option<i32>) -> Option<u32> {
+    cost.and_then(|cost| u32::try_from(cost).ok())
+        .filter(|cost| *cost > 0)
+}
+
+/// Parse the permission of a command as stored in the database.
+fn permission_from_db(permission: Option<&str>) -> Result<Permission, Error> {
+    match permission {
+        Some(permission) => str::parse(permission),
+        None => Ok(Permission::default()),
+    }
 }
 
 /// Serialize the atomic count.
@@ -229,14 +643,33 @@ where
 impl Command {
     pub const NAME: &'static str = "command";
 
+    /// The name of the command as shown in listings, followed by its
+    /// triggers if it has any.
+    pub fn list_name(&self) -> String {
+        match self.triggers.as_slice() {
+            [] => self.key.name.clone(),
+            triggers => format!("{} ({})", self.key.name, triggers.join(", ")),
+        }
+    }
+
+    /// Test if the given variable is available to command templates.
+    pub fn is_var(var: &str) -> bool {
+        matches!(var, "name" | "sender" | "target" | "count") || db::Captures::is_var(var)
+    }
+
     /// Load a command from the database.
     pub fn from_db(command: &db::models::Command) -> Result<Command, Error> {
         let template = template::Template::compile(&command.text)
             .with_context(|| anyhow!("failed to compile command `{:?}` from db", command))?;
 
+        template
+            .validate(Self::is_var)
+            .with_context(|| anyhow!("bad template for command `{:?}` in db", command))?;
+
         let key = db::Key::new(&command.channel, &command.name);
         let count = Arc::new(AtomicUsize::new(command.count as usize));
         let vars = template.vars();
+        let required_args = required_args(&template);
 
         let pattern = db::Pattern::from_db(command.pattern.as_ref())?;
 
@@ -246,11 +679,132 @@ impl Command {
             count,
             template,
             vars,
+            required_args,
             group: command.group.clone(),
             disabled: command.disabled,
+            description: command.description.clone(),
+            usage: command.usage.clone(),
+            games: db::Games::from_db(command.games.as_ref()),
+            limits: Limits::from_db(command),
+            cooldown_group: command.cooldown_group.clone(),
+            permission: permission_from_db(command.permission.as_deref())?,
+            triggers: triggers_from_db(command.triggers.as_deref()),
+            cost: cost_from_db(command.cost),
+            state: Default::default(),
         })
     }
 
+    /// Check the limits of the command for a use by the given user, during
+    /// the stream which started at the given time. The use is counted if it's
+    /// allowed.
+    ///
+    /// The cooldown of the command is checked first, then the cooldown of the
+    /// user, and finally the stream limit. Moderators use the moderator
+    /// cooldown of the command instead if one is set.
+    ///
+    /// If the command is part of a cooldown group, the cooldown with that
+    /// name in `cooldowns` is used instead of the command's own, so that using
+    /// one command in the group puts all of them on cooldown.
+    ///
+    /// Each cooldown is extended by a random amount of up to `jitter` every
+    /// time the command is used.
+    pub fn check_limits(
+        &self,
+        user: Option<&str>,
+        moderator: bool,
+        stream: Option<DateTime<Utc>>,
+        cooldowns: &utils::Cooldowns,
+        jitter: utils::Duration,
+    ) -> Result<(), Rejected> {
+        self.check_limits_at(
+            user,
+            moderator,
+            stream,
+            cooldowns,
+            jitter,
+            time::Instant::now(),
+        )
+    }
+
+    fn check_limits_at(
+        &self,
+        user: Option<&str>,
+        moderator: bool,
+        stream: Option<DateTime<Utc>>,
+        cooldowns: &utils::Cooldowns,
+        jitter: utils::Duration,
+        now: time::Instant,
+    ) -> Result<(), Rejected> {
+        let command_cooldown = match self.limits.moderator_cooldown {
+            Some(cooldown) if moderator => Some(cooldown),
+            _ => self.limits.cooldown,
+        };
+
+        let mut state = self.state.lock();
+
+        let LimitsState {
+            cooldown,
+            user_cooldowns,
+            usage,
+        } = &mut *state;
+
+        let mut shared = self
+            .cooldown_group
+            .as_ref()
+            .map(|group| (group, cooldowns.lock()));
+
+        let cooldown = match &mut shared {
+            Some((group, shared)) => shared.entry(group.to_string()).or_default(),
+            None => cooldown,
+        };
+
+        if let Some(duration) = command_cooldown {
+            cooldown.cooldown = duration;
+
+            if let Some(remaining) = cooldown.check(now) {
+                return Err(Rejected::Cooldown(remaining));
+            }
+        }
+
+        let user_cooldown = match (self.limits.user_cooldown, user) {
+            (Some(duration), Some(user)) => Some((duration, user)),
+            _ => None,
+        };
+
+        if let Some((duration, user)) = user_cooldown {
+            user_cooldowns.cooldown = duration;
+
+            if let Some(remaining) = user_cooldowns.check(user, now) {
+                return Err(Rejected::UserCooldown(remaining));
+            }
+        }
+
+        if let Some(limit) = self.limits.stream_limit {
+            usage.limit = limit;
+
+            if !usage.check(&stream) {
+                return Err(Rejected::StreamLimit(limit));
+            }
+        }
+
+        // NB: only count the use once it's been allowed by every limit.
+        if command_cooldown.is_some() {
+            cooldown.jitter = jitter;
+            cooldown.poke(now);
+        }
+
+        if let Some((_, user)) = user_cooldown {
+            user_cooldowns.jitter = jitter;
+            user_cooldowns.poke(user, now);
+        }
+
+        if self.limits.stream_limit.is_some() {
+            usage.poke(stream);
+        }
+
+        Ok(())
+    }
+
     /// Get the currenct count.
     pub fn count(&self) -> i32 {
         self.count.load(Ordering::SeqCst) as i32
@@ -268,6 +822,38 @@ impl Command {
     pub fn has_var(&self, var: &str) -> bool {
         self.vars.contains(var)
     }
+
+    /// The number of positional arguments the template requires.
+    pub fn required_args(&self) -> usize {
+        self.required_args
+    }
+
+    /// Usage of the command, which is derived from the arguments used in the
+    /// template unless it has been set explicitly.
+    pub fn usage_or_derived(&self) -> String {
+        if let Some(usage) = &self.usage {
+            return usage.clone();
+        }
+
+        let mut usage = self.key.name.clone();
+
+        for n in 1..=self.required_args() {
+            usage.push_str(&format!(" <arg{}>", n));
+        }
+
+        usage
+    }
+}
+
+/// The number of positional arguments the given template always uses, which
+/// is the highest `N` used as `{{argN}}` outside of blocks like `{{#if}}`.
+fn required_args(template: &template::Template) -> usize {
+    template
+        .required_vars()
+        .iter()
+        .filter_map(|var| var.strip_prefix("arg")?.parse::<usize>().ok())
+        .max()
+        .unwrap_or_default()
 }
 
 impl db::Matchable for Command {
@@ -278,17 +864,486 @@ impl db::Matchable for Command {
     fn pattern(&self) -> &db::Pattern {
         &self.pattern
     }
+
+    fn games(&self) -> &db::Games {
+        &self.games
+    }
+
+    fn triggers(&self) -> &[String] {
+        &self.triggers
+    }
 }
 
 impl fmt::Display for Command {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             fmt,
-            "template = \"{template}\", pattern = {pattern}, group = {group}, disabled = {disabled}",
+            "template = \"{template}\", pattern = {pattern}, group = {group}, disabled = {disabled}, games = {games}, limits = {limits}, cooldown group = {cooldown_group}, permission = {permission}, triggers = {triggers}, cost = {cost}",
             template = self.template,
             pattern = self.pattern,
             group = self.group.as_deref().unwrap_or("*none*"),
             disabled = self.disabled,
+            games = self.games,
+            limits = self.limits,
+            cooldown_group = self.cooldown_group.as_deref().unwrap_or("*none*"),
+            permission = self.permission,
+            triggers = match self.triggers.as_slice() {
+                [] => String::from("*none*"),
+                triggers => triggers.join(", "),
+            },
+            cost = match self.cost {
+                Some(cost) => cost.to_string(),
+                None => String::from("*free*"),
+            },
+        )
+    }
+}
+
+/// Limits on how often a command can be used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Limits {
+    /// How long to wait between any uses of the command.
+    pub cooldown: Option<utils::Duration>,
+    /// How long to wait between uses of the command by moderators, instead of
+    /// `cooldown`.
+    #[serde(default)]
+    pub moderator_cooldown: Option<utils::Duration>,
+    /// How long a single user has to wait between uses of the command.
+    #[serde(default)]
+    pub user_cooldown: Option<utils::Duration>,
+    /// How many times the command can be used per stream.
+    #[serde(default)]
+    pub stream_limit: Option<u32>,
+}
+
+impl Limits {
+    /// Load limits from the database.
+    fn from_db(command: &db::models::Command) -> Self {
+        let seconds = |s: i32| utils::Duration::seconds(s.max(0) as u64);
+
+        Self {
+            cooldown: command.cooldown.map(seconds),
+            moderator_cooldown: command.moderator_cooldown.map(seconds),
+            user_cooldown: command.user_cooldown.map(seconds),
+            stream_limit: command.stream_limit.map(|n| n.max(0) as u32),
+        }
+    }
+
+    /// Convert into the columns stored in the database.
+    pub(crate) fn to_db(self) -> db::models::UpdateCommandLimits {
+        let seconds = |d: utils::Duration| d.num_seconds() as i32;
+
+        db::models::UpdateCommandLimits {
+            cooldown: self.cooldown.map(seconds),
+            user_cooldown: self.user_cooldown.map(seconds),
+            moderator_cooldown: self.moderator_cooldown.map(seconds),
+            stream_limit: self.stream_limit.map(|n| n as i32),
+        }
+    }
+}
+
+impl fmt::Display for Limits {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+
+        if let Some(cooldown) = self.cooldown {
+            parts.push(format!("cooldown {}", cooldown));
+        }
+
+        if let Some(moderator_cooldown) = self.moderator_cooldown {
+            parts.push(format!("moderator cooldown {}", moderator_cooldown));
+        }
+
+        if let Some(user_cooldown) = self.user_cooldown {
+            parts.push(format!("user cooldown {}", user_cooldown));
+        }
+
+        if let Some(stream_limit) = self.stream_limit {
+            parts.push(format!("{} per stream", stream_limit));
+        }
+
+        if parts.is_empty() {
+            return "*none*".fmt(fmt);
+        }
+
+        parts.join(", ").fmt(fmt)
+    }
+}
+
+/// Tracks uses of a command against its limits.
+#[derive(Debug)]
+struct LimitsState {
+    cooldown: utils::Cooldown,
+    user_cooldowns: utils::PerUserCooldown,
+    /// Uses per stream, keyed by when the stream started.
+    usage: utils::UsageLimit<Option<DateTime<Utc>>>,
+}
+
+impl Default for LimitsState {
+    fn default() -> Self {
+        Self {
+            cooldown: Default::default(),
+            user_cooldowns: Default::default(),
+            usage: utils::UsageLimit::new(0),
+        }
+    }
+}
+
+/// Why a command wasn't allowed to be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejected {
+    /// The command was used too recently.
+    Cooldown(time::Duration),
+    /// The command was used too recently by the same user.
+    UserCooldown(time::Duration),
+    /// The command has been used as many times as allowed this stream.
+    StreamLimit(u32),
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejected::Cooldown(remaining) => write!(
+                fmt,
+                "Command is on cooldown for another {}.",
+                utils::compact_duration(*remaining)
+            ),
+            Rejected::UserCooldown(remaining) => write!(
+                fmt,
+                "You have to wait {} before using the command again.",
+                utils::compact_duration(*remaining)
+            ),
+            Rejected::StreamLimit(1) => write!(fmt, "Command can only be used once per stream."),
+            Rejected::StreamLimit(limit) => {
+                write!(fmt, "Command can only be used {} times per stream.", limit)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Commands, CountChange, Limits, Rejected};
+    use crate::db::{self, Backend as _};
+    use crate::template::Template;
+    use crate::utils::{self, Duration};
+    use anyhow::Error;
+    use chrono::{DateTime, TimeZone as _, Utc};
+    use std::sync::Arc;
+    use std::time;
+
+    fn command(limits: Limits) -> Command {
+        command_with_text("hello", limits)
+    }
+
+    fn command_with_text(text: &str, limits: Limits) -> Command {
+        let set = limits.to_db();
+
+        let command = db::models::Command {
+            channel: String::from("#channel"),
+            name: String::from("foo"),
+            pattern: None,
+            count: 0,
+            text: String::from(text),
+            group: None,
+            disabled: false,
+            description: None,
+            usage: None,
+            count_updated_at: None,
+            count_updated_by: None,
+            games: None,
+            cooldown: set.cooldown,
+            user_cooldown: set.user_cooldown,
+            stream_limit: set.stream_limit,
+            moderator_cooldown: set.moderator_cooldown,
+            cooldown_group: None,
+            permission: None,
+            triggers: None,
+            cost: None,
+        };
+
+        Command::from_db(&command).expect("command")
+    }
+
+    /// Check the limits of a command outside of any cooldown group and
+    /// without jitter.
+    fn check(
+        command: &Command,
+        user: &str,
+        moderator: bool,
+        stream: Option<DateTime<Utc>>,
+        now: time::Instant,
+    ) -> Result<(), Rejected> {
+        command.check_limits_at(
+            Some(user),
+            moderator,
+            stream,
+            &Default::default(),
+            Duration::default(),
+            now,
         )
     }
+
+    #[test]
+    fn test_count_change() {
+        assert_eq!(CountChange::parse("add 3"), Some(Ok(CountChange::Add(3))));
+        assert_eq!(CountChange::parse(" sub 2 "), Some(Ok(CountChange::Sub(2))));
+        assert_eq!(CountChange::parse("set 10"), Some(Ok(CountChange::Set(10))));
+        assert!(matches!(CountChange::parse("add -3"), Some(Err(..))));
+        assert!(matches!(CountChange::parse("add three"), Some(Err(..))));
+        assert!(matches!(CountChange::parse("set"), Some(Err(..))));
+        assert_eq!(CountChange::parse("everyone"), None);
+        assert_eq!(CountChange::parse(""), None);
+    }
+
+    #[test]
+    fn test_required_args() {
+        let command = command_with_text("{{sender}} hugs {{arg1}} and {{arg2}}", Limits::default());
+        assert_eq!(command.required_args(), 2);
+        assert_eq!(command.usage_or_derived(), "foo <arg1> <arg2>");
+
+        let command = command_with_text("{{sender}} says {{rest}}", Limits::default());
+        assert_eq!(command.required_args(), 0);
+        assert_eq!(command.usage_or_derived(), "foo");
+
+        // NB: arguments which are only used in conditionals are optional.
+        let command = command_with_text(
+            "{{#if arg2}}{{arg1}} hugs {{arg2}}{{else}}hugs {{arg1}}{{/if}} {{arg1}}",
+            Limits::default(),
+        );
+        assert_eq!(command.required_args(), 1);
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let command = command(Limits {
+            cooldown: Some(Duration::seconds(30)),
+            ..Limits::default()
+        });
+
+        let now = time::Instant::now();
+        assert!(check(&command, "a", false, None, now).is_ok());
+        assert_eq!(
+            check(
+                &command,
+                "b",
+                false,
+                None,
+                now + time::Duration::from_secs(10)
+            ),
+            Err(Rejected::Cooldown(time::Duration::from_secs(20)))
+        );
+        assert!(check(
+            &command,
+            "b",
+            false,
+            None,
+            now + time::Duration::from_secs(30)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_moderator_cooldown() {
+        let command = command(Limits {
+            cooldown: Some(Duration::seconds(30)),
+            moderator_cooldown: Some(Duration::seconds(5)),
+            ..Limits::default()
+        });
+
+        let now = time::Instant::now();
+        let later = now + time::Duration::from_secs(10);
+        assert!(check(&command, "a", false, None, now).is_ok());
+        assert_eq!(
+            check(&command, "b", false, None, later),
+            Err(Rejected::Cooldown(time::Duration::from_secs(20)))
+        );
+        assert!(check(&command, "mod", true, None, later).is_ok());
+        assert_eq!(
+            check(&command, "mod", true, None, later),
+            Err(Rejected::Cooldown(time::Duration::from_secs(5)))
+        );
+    }
+
+    #[test]
+    fn test_user_cooldown() {
+        let command = command(Limits {
+            user_cooldown: Some(Duration::seconds(30)),
+            ..Limits::default()
+        });
+
+        let now = time::Instant::now();
+        let later = now + time::Duration::from_secs(10);
+        assert!(check(&command, "a", false, None, now).is_ok());
+        assert!(check(&command, "b", false, None, later).is_ok());
+        assert_eq!(
+            check(&command, "a", false, None, later),
+            Err(Rejected::UserCooldown(time::Duration::from_secs(20)))
+        );
+        assert!(check(
+            &command,
+            "a",
+            false,
+            None,
+            now + time::Duration::from_secs(30)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_stream_limit() {
+        let command = command(Limits {
+            stream_limit: Some(2),
+            ..Limits::default()
+        });
+
+        let now = time::Instant::now();
+        let first = Some(Utc.timestamp(1_600_000_000, 0));
+        let second = Some(Utc.timestamp(1_600_100_000, 0));
+
+        assert!(check(&command, "a", false, first, now).is_ok());
+        assert!(check(&command, "a", false, first, now).is_ok());
+        assert_eq!(
+            check(&command, "b", false, first, now),
+            Err(Rejected::StreamLimit(2))
+        );
+        assert!(check(&command, "b", false, second, now).is_ok());
+    }
+
+    #[test]
+    fn test_rejected_uses_are_not_counted() {
+        let command = command(Limits {
+            user_cooldown: Some(Duration::seconds(30)),
+            stream_limit: Some(2),
+            ..Limits::default()
+        });
+
+        let now = time::Instant::now();
+        assert!(check(&command, "a", false, None, now).is_ok());
+        // rejected by the user cooldown, so it doesn't count towards the
+        // stream limit.
+        assert!(check(&command, "a", false, None, now).is_err());
+        assert!(check(&command, "b", false, None, now).is_ok());
+        assert_eq!(
+            check(&command, "c", false, None, now),
+            Err(Rejected::StreamLimit(2))
+        );
+    }
+
+    #[test]
+    fn test_cooldown_group() {
+        let limits = Limits {
+            cooldown: Some(Duration::seconds(30)),
+            ..Limits::default()
+        };
+
+        let mut a = command_with_text("a", limits);
+        a.cooldown_group = Some(String::from("hype"));
+        let mut b = command_with_text("b", limits);
+        b.cooldown_group = Some(String::from("hype"));
+        let c = command_with_text("c", limits);
+
+        let cooldowns = utils::Cooldowns::default();
+        let now = time::Instant::now();
+
+        assert!(a
+            .check_limits_at(Some("a"), false, None, &cooldowns, Duration::default(), now)
+            .is_ok());
+        assert_eq!(
+            b.check_limits_at(Some("a"), false, None, &cooldowns, Duration::default(), now),
+            Err(Rejected::Cooldown(time::Duration::from_secs(30)))
+        );
+        // NB: not part of the group.
+        assert!(c
+            .check_limits_at(Some("a"), false, None, &cooldowns, Duration::default(), now)
+            .is_ok());
+        assert!(b
+            .check_limits_at(
+                Some("a"),
+                false,
+                None,
+                &cooldowns,
+                Duration::default(),
+                now + time::Duration::from_secs(30)
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_cooldown_jitter() {
+        let command = command(Limits {
+            cooldown: Some(Duration::seconds(30)),
+            ..Limits::default()
+        });
+
+        let jitter = Duration::seconds(10);
+        let now = time::Instant::now();
+
+        assert!(command
+            .check_limits_at(Some("a"), false, None, &Default::default(), jitter, now)
+            .is_ok());
+
+        match command.check_limits_at(
+            Some("b"),
+            false,
+            None,
+            &Default::default(),
+            jitter,
+            now + time::Duration::from_secs(10),
+        ) {
+            Err(Rejected::Cooldown(remaining)) => {
+                assert!(remaining >= time::Duration::from_secs(20));
+                assert!(remaining <= time::Duration::from_secs(30));
+            }
+            other => panic!("expected cooldown, got {:?}", other),
+        }
+
+        assert!(command
+            .check_limits_at(
+                Some("b"),
+                false,
+                None,
+                &Default::default(),
+                jitter,
+                now + time::Duration::from_secs(40)
+            )
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bad_templates() -> Result<(), Error> {
+        let backend = Arc::new(db::Memory::new());
+        let good = db::Key::new("#channel", "good");
+        let bad = db::Key::new("#channel", "bad");
+
+        backend
+            .command_edit(&good, "{{sender}} says {{arg1}} {{count}}")
+            .await?;
+        backend.command_edit(&bad, "{{sendr}} says hi").await?;
+
+        // NB: a bad command is skipped instead of failing the whole load.
+        let commands = Commands::load_backend(backend).await?;
+        assert!(commands.get("#channel", "good").await.is_some());
+        assert!(commands.get("#channel", "bad").await.is_none());
+
+        let template = Template::compile("{{sendr}} says hi")?;
+        assert!(commands.edit("#channel", "bad", template).await.is_err());
+        assert!(commands.get("#channel", "bad").await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_amount_overflow() -> Result<(), Error> {
+        let backend = Arc::new(db::Memory::new());
+        let key = db::Key::new("#channel", "deaths");
+        backend.command_edit(&key, "{{count}} deaths").await?;
+
+        let commands = Commands::load_backend(backend).await?;
+        let command = commands.get("#channel", "deaths").await.unwrap();
+
+        assert!(commands.add_amount(&command, 10, None).await?);
+        assert!(!commands.add_amount(&command, i32::MAX as u32, None).await?);
+        assert_eq!(command.count(), 10);
+        Ok(())
+    }
 }
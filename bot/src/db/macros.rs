@@ -25,6 +25,7 @@ macro_rules! database_group_fns {
         /// Enable the given thing.
         pub async fn enable(&self, channel: &str, name: &str) -> Result<bool, anyhow::Error> {
             let key = <$key>::new(channel, name);
+            let mut inner = self.inner.write().await;
 
             let thing = match self.db.fetch(&key).await? {
                 Some(thing) => <$thing>::from_db(&thing)?,
@@ -32,10 +33,7 @@ macro_rules! database_group_fns {
             };
 
             self.db.edit_disabled(&thing.key, false).await?;
-            self.inner
-                .write()
-                .await
-                .insert(thing.key.clone(), Arc::new(thing));
+            inner.insert(thing.key.clone(), Arc::new(thing));
             Ok(true)
         }
 
@@ -54,9 +52,8 @@ macro_rules! database_group_fns {
 
         /// Enable all things in the given group.
         pub async fn enable_group(&self, channel: &str, group: &str) -> Result<(), anyhow::Error> {
-            self.db.set_group_disabled(channel, group, false).await?;
-
             let mut inner = self.inner.write().await;
+            self.db.set_group_disabled(channel, group, false).await?;
 
             for thing in self.db.list_group(channel, group).await? {
                 let thing = <$thing>::from_db(&thing)?;
@@ -68,9 +65,8 @@ macro_rules! database_group_fns {
 
         /// Disable all things in the given group.
         pub async fn disable_group(&self, channel: &str, group: &str) -> Result<(), anyhow::Error> {
-            self.db.set_group_disabled(channel, group, true).await?;
-
             let mut inner = self.inner.write().await;
+            self.db.set_group_disabled(channel, group, true).await?;
 
             let mut to_delete = Vec::new();
 
@@ -101,12 +97,13 @@ macro_rules! database_group_fns {
         /// Remove thing.
         pub async fn delete(&self, channel: &str, name: &str) -> Result<bool, anyhow::Error> {
             let key = <$key>::new(channel, name);
+            let mut inner = self.inner.write().await;
 
             if !self.db.delete(&key).await? {
                 return Ok(false);
             }
 
-            self.inner.write().await.remove(&key);
+            inner.remove(&key);
             Ok(true)
         }
 
@@ -227,7 +224,7 @@ macro_rules! private_database_group_fns {
             self.0
                 .asyncify(move |c| {
                     Ok(dsl::$module
-                        .filter(dsl::channel.eq(channel))
+                        .filter(dsl::channel.eq(&channel))
                         .load::<db::models::$thing>(c)?)
                 })
                 .await
@@ -246,7 +243,7 @@ macro_rules! private_database_group_fns {
             self.0
                 .asyncify(move |c| {
                     let filter =
-                        dsl::$module.filter(dsl::channel.eq(channel).and(dsl::group.eq(group)));
+                        dsl::$module.filter(dsl::channel.eq(&channel).and(dsl::group.eq(&group)));
                     Ok(filter.load::<db::models::$thing>(c)?)
                 })
                 .await
@@ -266,7 +263,7 @@ macro_rules! private_database_group_fns {
             self.0
                 .asyncify(move |c| {
                     diesel::update(
-                        dsl::$module.filter(dsl::channel.eq(channel).and(dsl::group.eq(group))),
+                        dsl::$module.filter(dsl::channel.eq(&channel).and(dsl::group.eq(&group))),
                     )
                     .set(dsl::disabled.eq(disabled))
                     .execute(c)?;
@@ -287,7 +284,7 @@ macro_rules! private_database_group_fns {
                         dsl::$module
                             .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
                     )
-                    .set(dsl::group.eq(group))
+                    .set(dsl::group.eq(group.clone()))
                     .execute(c)?;
 
                     Ok(())
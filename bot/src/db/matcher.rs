@@ -1,6 +1,6 @@
 use crate::utils;
 use anyhow::Error;
-use std::collections::{hash_map, HashMap, HashSet};
+use std::collections::{hash_map, BTreeSet, HashMap};
 use std::fmt;
 use std::sync::Arc;
 
@@ -11,6 +11,15 @@ pub trait Matchable {
 
     /// Get the pattern for the matchable element.
     fn pattern(&self) -> &Pattern;
+
+    /// Get the games the matchable element is limited to.
+    fn games(&self) -> &Games;
+
+    /// Get the other names the matchable element can be invoked by, if it's
+    /// matched by name.
+    fn triggers(&self) -> &[String] {
+        &[]
+    }
 }
 
 pub struct Matcher<T>
@@ -19,10 +28,12 @@ where
 {
     /// All commands.
     all: HashMap<Key, Arc<T>>,
-    /// Commands indexed by name.
-    by_name: HashSet<Key>,
-    /// Regular expression commands indexed by channel.
-    by_channel_regex: HashMap<String, HashSet<Key>>,
+    /// Commands indexed by name and by each of their triggers, pointing to
+    /// the key of the command.
+    by_name: HashMap<Key, Key>,
+    /// Commands with a pattern indexed by channel, in the order they are
+    /// matched.
+    by_channel_pattern: HashMap<String, BTreeSet<Key>>,
 }
 
 impl<T> Matcher<T>
@@ -33,7 +44,7 @@ where
         Self {
             all: Default::default(),
             by_name: Default::default(),
-            by_channel_regex: Default::default(),
+            by_channel_pattern: Default::default(),
         }
     }
 
@@ -44,40 +55,21 @@ where
 
     /// Insert the given value.
     pub(crate) fn insert(&mut self, key: Key, value: Arc<T>) {
-        match value.pattern() {
-            Pattern::Name => {
-                self.by_name.insert(key.clone());
-            }
-            Pattern::Regex { .. } => {
-                self.by_channel_regex
-                    .entry(key.channel.clone())
-                    .or_default()
-                    .insert(key.clone());
-            }
-        }
-
+        self.index(&key, &*value);
         self.all.insert(key, value);
     }
 
     /// Remove the given value.
     pub(crate) fn remove(&mut self, key: &Key) -> Option<Arc<T>> {
-        if let Some(value) = self.all.remove(key) {
-            match value.pattern() {
-                Pattern::Name => {
-                    self.by_name.remove(key);
-                }
-                Pattern::Regex { .. } => {
-                    self.by_channel_regex
-                        .entry(key.channel.clone())
-                        .or_default()
-                        .remove(&key);
-                }
-            }
-
-            return Some(value);
-        }
+        let value = self.all.remove(key)?;
+        self.unindex(key, &*value);
+        Some(value)
+    }
 
-        None
+    /// Get the key of the element which is invoked by the given name, either
+    /// through its own name or one of its triggers.
+    pub(crate) fn owner(&self, name: &Key) -> Option<&Key> {
+        self.by_name.get(name)
     }
 
     /// Get an iterator over all the values.
@@ -102,76 +94,135 @@ where
         T: Clone,
         F: FnOnce(&mut T),
     {
-        let Self {
-            all,
-            by_channel_regex,
-            by_name,
-        } = self;
-
-        let existing = match all.get_mut(&key) {
-            Some(existing) => existing,
+        let existing = match self.all.get(&key) {
+            Some(existing) => existing.clone(),
             None => return false,
         };
 
-        let mut new = (**existing).clone();
+        let mut new = (*existing).clone();
         m(&mut new);
 
-        // re-index in case pattern has changed.
-        match new.pattern() {
-            Pattern::Regex { .. } => {
-                if let Pattern::Name = existing.pattern() {
-                    by_name.remove(&key);
+        // re-index in case the pattern or triggers have changed.
+        self.unindex(&key, &*existing);
+        self.index(&key, &new);
+        self.all.insert(key, Arc::new(new));
+        true
+    }
 
-                    by_channel_regex
-                        .entry(key.channel.clone())
-                        .or_default()
-                        .insert(key);
-                }
+    /// Index the given value by its name and triggers, or by its pattern.
+    fn index(&mut self, key: &Key, value: &T) {
+        if !value.pattern().is_name() {
+            self.by_channel_pattern
+                .entry(key.channel.clone())
+                .or_default()
+                .insert(key.clone());
+
+            return;
+        }
+
+        self.by_name.insert(key.clone(), key.clone());
+
+        // NB: the name of an element takes precedence over the triggers of
+        // other elements.
+        for trigger in value.triggers() {
+            self.by_name
+                .entry(Key::new(&key.channel, trigger))
+                .or_insert_with(|| key.clone());
+        }
+    }
+
+    /// Remove the given value from the indexes.
+    fn unindex(&mut self, key: &Key, value: &T) {
+        if !value.pattern().is_name() {
+            self.by_channel_pattern
+                .entry(key.channel.clone())
+                .or_default()
+                .remove(key);
+
+            return;
+        }
+
+        let names = std::iter::once(key.clone()).chain(
+            value
+                .triggers()
+                .iter()
+                .map(|trigger| Key::new(&key.channel, trigger)),
+        );
+
+        for name in names {
+            if self.by_name.get(&name) != Some(key) {
+                continue;
             }
-            Pattern::Name => {
-                if let Pattern::Regex { .. } = existing.pattern() {
-                    by_channel_regex
-                        .entry(key.channel.clone())
-                        .or_default()
-                        .remove(&key);
-
-                    by_name.insert(key);
-                }
+
+            self.by_name.remove(&name);
+
+            // NB: the name might have shadowed the trigger of another element,
+            // which now gets to use it.
+            let owner = self
+                .all
+                .iter()
+                .filter(|(other, value)| {
+                    *other != key
+                        && value.pattern().is_name()
+                        && value
+                            .triggers()
+                            .iter()
+                            .any(|trigger| Key::new(&other.channel, trigger) == name)
+                })
+                .map(|(other, _)| other)
+                .min();
+
+            if let Some(owner) = owner {
+                self.by_name.insert(name, owner.clone());
             }
         }
-
-        *existing = Arc::new(new);
-        true
     }
 
-    /// Resolve the given command.
+    /// Resolve the given command, skipping the ones which are not available
+    /// while streaming the given game.
     pub fn resolve<'a>(
         &self,
         channel: &str,
+        game: Option<&str>,
         first: Option<&'a str>,
         it: &'a utils::Words,
     ) -> Option<(&Arc<T>, Captures<'a>)> {
         if let Some(first) = first {
             let key = Key::new(channel, first);
 
-            if self.by_name.contains(&key) {
-                if let Some(command) = self.get(&key) {
-                    let captures = Captures::Prefix { rest: it.rest() };
-                    return Some((command, captures));
+            if let Some(key) = self.by_name.get(&key) {
+                if let Some(command) = self.get(key) {
+                    if command.games().matches(game) {
+                        let captures = Captures::Prefix { rest: it.rest() };
+                        return Some((command, captures));
+                    }
                 }
             }
         }
 
-        if let Some(keys) = self.by_channel_regex.get(channel) {
+        if let Some(keys) = self.by_channel_pattern.get(channel) {
             let full = it.string();
 
             for key in keys {
                 if let Some(command) = self.get(key) {
-                    if let Pattern::Regex { pattern } = command.pattern() {
-                        if let Some(captures) = pattern.captures(full) {
-                            let captures = Captures::Regex { captures };
-                            return Some((command, captures));
+                    if !command.games().matches(game) {
+                        continue;
+                    }
+
+                    match command.pattern() {
+                        Pattern::Regex { pattern } => {
+                            if let Some(captures) = pattern.captures(full) {
+                                let captures = Captures::Regex { captures };
+                                return Some((command, captures));
+                            }
+                        }
+                        Pattern::Contains { text } => {
+                            if full.to_lowercase().contains(text.as_str()) {
+                                let captures = Captures::Prefix { rest: full };
+                                return Some((command, captures));
+                            }
                         }
+                        Pattern::Name => (),
                     }
                 }
             }
@@ -181,7 +232,7 @@ where
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
 pub struct Key {
     pub channel: String,
     pub name: String,
@@ -213,6 +264,9 @@ pub enum Pattern {
         #[serde(serialize_with = "serialize_regex")]
         pattern: regex::Regex,
     },
+    /// Matches if the message contains the lowercase text.
+    #[serde(rename = "contains")]
+    Contains { text: String },
 }
 
 impl Pattern {
@@ -221,15 +275,50 @@ impl Pattern {
         Self::Regex { pattern }
     }
 
+    /// Parse a pattern from user input.
+    ///
+    /// A leading `*` matches messages containing the rest of the input,
+    /// anything else is a regular expression. Since a regular expression
+    /// can't start with `*`, the two can't be confused.
+    pub fn parse(pattern: &str) -> Result<Self, Error> {
+        if let Some(text) = pattern.strip_prefix('*') {
+            let text = text.trim();
+
+            if text.is_empty() {
+                anyhow::bail!("expected text to match after `*`");
+            }
+
+            return Ok(Pattern::Contains {
+                text: text.to_lowercase(),
+            });
+        }
+
+        Ok(Pattern::Regex {
+            pattern: regex::Regex::new(pattern)?,
+        })
+    }
+
+    /// Test if this pattern matches by name.
+    pub fn is_name(&self) -> bool {
+        matches!(self, Pattern::Name)
+    }
+
     /// Convert a database pattern into a matchable pattern here.
     pub fn from_db(pattern: Option<impl AsRef<str>>) -> Result<Self, Error> {
         Ok(match pattern {
-            Some(pattern) => Pattern::Regex {
-                pattern: regex::Regex::new(pattern.as_ref())?,
-            },
+            Some(pattern) => Self::parse(pattern.as_ref())?,
             None => Pattern::Name,
         })
     }
+
+    /// Convert into a pattern stored in the database.
+    pub fn to_db(&self) -> Option<String> {
+        match self {
+            Pattern::Name => None,
+            Pattern::Regex { pattern } => Some(pattern.as_str().to_string()),
+            Pattern::Contains { text } => Some(format!("*{}", text)),
+        }
+    }
 }
 
 impl Default for Pattern {
@@ -243,8 +332,79 @@ impl fmt::Display for Pattern {
         match self {
             Pattern::Name => "*name*".fmt(fmt),
             Pattern::Regex { pattern } => pattern.fmt(fmt),
+            Pattern::Contains { text } => write!(fmt, "*{}", text),
+        }
+    }
+}
+
+/// The games (stream categories) something is limited to.
+///
+/// If no games are specified, it's available regardless of what's being
+/// streamed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(transparent)]
+pub struct Games(Vec<String>);
+
+impl Games {
+    /// Parse games from user input, where they are separated by `|`.
+    pub fn parse(s: &str) -> Self {
+        Self::split(s, '|')
+    }
+
+    /// Convert the newline-separated games stored in the database.
+    pub fn from_db(games: Option<impl AsRef<str>>) -> Self {
+        match games {
+            Some(games) => Self::split(games.as_ref(), '\n'),
+            None => Self::default(),
+        }
+    }
+
+    /// Convert into the representation stored in the database.
+    pub fn to_db(&self) -> Option<String> {
+        if self.0.is_empty() {
+            return None;
+        }
+
+        Some(self.0.join("\n"))
+    }
+
+    /// Test if no games are specified.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Test if the given game matches, where `None` means that no game is
+    /// being streamed.
+    pub fn matches(&self, game: Option<&str>) -> bool {
+        if self.0.is_empty() {
+            return true;
+        }
+
+        match game {
+            Some(game) => self.0.iter().any(|g| g.eq_ignore_ascii_case(game)),
+            None => false,
         }
     }
+
+    fn split(s: &str, sep: char) -> Self {
+        Self(
+            s.split(sep)
+                .map(str::trim)
+                .filter(|g| !g.is_empty())
+                .map(String::from)
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Games {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return "*any*".fmt(fmt);
+        }
+
+        self.0.join(" | ").fmt(fmt)
+    }
 }
 
 /// Serialize a regular expression.
@@ -265,10 +425,42 @@ impl<'a> Captures<'a> {
     /// Get the number of captures.
     fn len(&self) -> usize {
         match self {
-            Self::Prefix { .. } => 1,
+            Self::Prefix { rest } => 2 + rest.split_whitespace().count(),
             Self::Regex { captures, .. } => captures.len(),
         }
     }
+
+    /// Get the rest of the command, or `None` if the command was matched
+    /// through a regular expression.
+    pub fn rest(&self) -> Option<&'a str> {
+        match self {
+            Self::Prefix { rest } => Some(rest),
+            Self::Regex { .. } => None,
+        }
+    }
+
+    /// Test if the given variable might be provided by captures, like
+    /// `{{rest}}`, `{{arg1}}`, `{{args}}`, or `{{1}}`.
+    pub fn is_var(var: &str) -> bool {
+        let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+        match var {
+            "rest" | "args" => true,
+            var => match var.strip_prefix("arg") {
+                Some(n) => is_number(n),
+                None => is_number(var),
+            },
+        }
+    }
+
+    /// Get the number of positional arguments available as `{{argN}}`, or
+    /// `None` if the command was matched through a regular expression.
+    pub fn args(&self) -> Option<usize> {
+        match self {
+            Self::Prefix { rest } => Some(rest.split_whitespace().count()),
+            Self::Regex { .. } => None,
+        }
+    }
 }
 
 impl serde::Serialize for Captures<'_> {
@@ -283,6 +475,14 @@ impl serde::Serialize for Captures<'_> {
         match self {
             Self::Prefix { rest } => {
                 m.serialize_entry("rest", rest)?;
+
+                let args = rest.split_whitespace().collect::<Vec<_>>();
+
+                for (i, arg) in args.iter().enumerate() {
+                    m.serialize_entry(&format!("arg{}", i + 1), arg)?;
+                }
+
+                m.serialize_entry("args", &args)?;
             }
             Self::Regex { captures, .. } => {
                 for (i, g) in captures.iter().enumerate() {
@@ -294,3 +494,263 @@ impl serde::Serialize for Captures<'_> {
         m.end()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Captures, Games, Key, Matchable, Matcher, Pattern};
+    use crate::utils;
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct Entry {
+        key: Key,
+        pattern: Pattern,
+        games: Games,
+        triggers: Vec<String>,
+    }
+
+    impl Matchable for Entry {
+        fn key(&self) -> &Key {
+            &self.key
+        }
+
+        fn pattern(&self) -> &Pattern {
+            &self.pattern
+        }
+
+        fn games(&self) -> &Games {
+            &self.games
+        }
+
+        fn triggers(&self) -> &[String] {
+            &self.triggers
+        }
+    }
+
+    fn matcher() -> Matcher<Entry> {
+        let mut matcher = Matcher::new();
+
+        for (name, pattern, games) in &[
+            ("deaths", Pattern::Name, "Dark Souls | Elden Ring"),
+            ("song", Pattern::Name, ""),
+            (
+                "cheese",
+                Pattern::regex(regex::Regex::new("^cheese").unwrap()),
+                "Dark Souls",
+            ),
+        ] {
+            let key = Key::new("#channel", name);
+
+            matcher.insert(
+                key.clone(),
+                Arc::new(Entry {
+                    key,
+                    pattern: pattern.clone(),
+                    games: Games::parse(games),
+                    triggers: Vec::new(),
+                }),
+            );
+        }
+
+        matcher
+    }
+
+    fn resolve(matcher: &Matcher<Entry>, game: Option<&str>, line: &'static str) -> Option<String> {
+        let mut it = utils::Words::new(line);
+        let first = it.next();
+
+        matcher
+            .resolve("#channel", game, first.as_deref(), &it)
+            .map(|(entry, _)| entry.key.name.clone())
+    }
+
+    #[test]
+    fn test_captures_is_var() {
+        assert!(Captures::is_var("rest"));
+        assert!(Captures::is_var("args"));
+        assert!(Captures::is_var("arg12"));
+        assert!(Captures::is_var("0"));
+        assert!(!Captures::is_var("arg"));
+        assert!(!Captures::is_var("argument"));
+        assert!(!Captures::is_var("sender"));
+    }
+
+    #[test]
+    fn test_resolve_patterns() {
+        let mut matcher = Matcher::new();
+
+        for (name, pattern) in &[
+            ("b-hello", "*Hello"),
+            ("a-greeting", "^(hello|hi) (?P<who>.+)$"),
+            ("c-bye", "*bye"),
+        ] {
+            let key = Key::new("#channel", name);
+            let pattern = Pattern::parse(pattern).expect("pattern");
+            assert_eq!(
+                Pattern::from_db(pattern.to_db()).expect("pattern").to_db(),
+                pattern.to_db()
+            );
+
+            matcher.insert(
+                key.clone(),
+                Arc::new(Entry {
+                    key,
+                    pattern,
+                    games: Games::default(),
+                    triggers: Vec::new(),
+                }),
+            );
+        }
+
+        // Patterns are matched in order, so the first one wins.
+        assert_eq!(
+            resolve(&matcher, None, "hello world"),
+            Some(String::from("a-greeting"))
+        );
+        assert_eq!(
+            resolve(&matcher, None, "well HELLO there"),
+            Some(String::from("b-hello"))
+        );
+        assert_eq!(
+            resolve(&matcher, None, "goodbye"),
+            Some(String::from("c-bye"))
+        );
+        assert_eq!(resolve(&matcher, None, "nothing"), None);
+
+        assert!(Pattern::parse("*").is_err());
+        assert!(Pattern::parse("(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_prefix_captures() {
+        let captures = Captures::Prefix {
+            rest: "  setbac  everyone ",
+        };
+
+        assert_eq!(captures.args(), Some(2));
+        assert_eq!(
+            serde_json::to_value(&captures).expect("serialize"),
+            serde_json::json!({
+                "rest": "  setbac  everyone ",
+                "arg1": "setbac",
+                "arg2": "everyone",
+                "args": ["setbac", "everyone"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_prefix_captures() {
+        let template = crate::template::Template::compile(
+            "!shoutout {{arg1}} {{args.0}}|{{args.1}}|{{arg2}}|{{rest}}",
+        )
+        .expect("template");
+
+        let captures = Captures::Prefix { rest: "setbac" };
+        assert_eq!(
+            template.render_to_string(&captures).expect("render"),
+            "!shoutout setbac setbac|||setbac"
+        );
+    }
+
+    #[test]
+    fn test_games() {
+        let games = Games::parse(" Dark Souls |  | elden ring ");
+        assert_eq!(games.to_string(), "Dark Souls | elden ring");
+        assert_eq!(Games::from_db(games.to_db()), games);
+        assert!(games.matches(Some("ELDEN RING")));
+        assert!(!games.matches(Some("Just Chatting")));
+        assert!(!games.matches(None));
+
+        let any = Games::parse("");
+        assert!(any.is_empty());
+        assert_eq!(any.to_db(), None);
+        assert!(any.matches(None));
+        assert!(any.matches(Some("Just Chatting")));
+    }
+
+    #[test]
+    fn test_resolve_games() {
+        let matcher = matcher();
+
+        assert_eq!(
+            resolve(&matcher, Some("elden ring"), "deaths"),
+            Some(String::from("deaths"))
+        );
+        assert_eq!(resolve(&matcher, Some("Just Chatting"), "deaths"), None);
+        assert_eq!(resolve(&matcher, None, "deaths"), None);
+        assert_eq!(
+            resolve(&matcher, Some("Just Chatting"), "song"),
+            Some(String::from("song"))
+        );
+
+        assert_eq!(
+            resolve(&matcher, Some("Dark Souls"), "cheese it"),
+            Some(String::from("cheese"))
+        );
+        assert_eq!(resolve(&matcher, Some("Elden Ring"), "cheese it"), None);
+    }
+
+    #[test]
+    fn test_resolve_triggers() {
+        let mut matcher = matcher();
+
+        let key = Key::new("#channel", "lurk");
+
+        matcher.insert(
+            key.clone(),
+            Arc::new(Entry {
+                key: key.clone(),
+                pattern: Pattern::Name,
+                games: Games::default(),
+                triggers: vec![String::from("afk"), String::from("song")],
+            }),
+        );
+
+        assert_eq!(resolve(&matcher, None, "lurk"), Some(String::from("lurk")));
+        assert_eq!(resolve(&matcher, None, "AFK"), Some(String::from("lurk")));
+        // NB: the name of another entry takes precedence over a trigger.
+        assert_eq!(resolve(&matcher, None, "song"), Some(String::from("song")));
+
+        assert!(matcher.modify(key.clone(), |entry| {
+            entry.triggers = vec![String::from("brb")];
+        }));
+
+        assert_eq!(resolve(&matcher, None, "afk"), None);
+        assert_eq!(resolve(&matcher, None, "brb"), Some(String::from("lurk")));
+
+        matcher.remove(&key);
+        assert_eq!(resolve(&matcher, None, "brb"), None);
+        assert_eq!(resolve(&matcher, None, "song"), Some(String::from("song")));
+    }
+
+    #[test]
+    fn test_remove_restores_triggers() {
+        let mut matcher = matcher();
+
+        for (name, triggers) in &[("lurk", &["afk", "song"][..]), ("away", &["afk"][..])] {
+            let key = Key::new("#channel", name);
+
+            matcher.insert(
+                key.clone(),
+                Arc::new(Entry {
+                    key,
+                    pattern: Pattern::Name,
+                    games: Games::default(),
+                    triggers: triggers.iter().map(|t| t.to_string()).collect(),
+                }),
+            );
+        }
+
+        assert_eq!(resolve(&matcher, None, "song"), Some(String::from("song")));
+        assert_eq!(resolve(&matcher, None, "afk"), Some(String::from("lurk")));
+
+        // NB: triggers shadowed by a removed entry are picked up again.
+        matcher.remove(&Key::new("#channel", "song"));
+        assert_eq!(resolve(&matcher, None, "song"), Some(String::from("lurk")));
+
+        matcher.remove(&Key::new("#channel", "lurk"));
+        assert_eq!(resolve(&matcher, None, "song"), None);
+        assert_eq!(resolve(&matcher, None, "afk"), Some(String::from("away")));
+    }
+}
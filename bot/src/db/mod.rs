@@ -2,13 +2,17 @@
 mod macros;
 mod after_streams;
 mod aliases;
+mod backend;
 pub(crate) mod commands;
 mod matcher;
 pub(crate) mod models;
 mod promotions;
+mod quotes;
 pub(crate) mod schema;
 mod script_storage;
 mod themes;
+mod timers;
+mod waters;
 mod words;
 
 use crate::task;
@@ -20,28 +24,70 @@ use thiserror::Error;
 
 pub use self::after_streams::{AfterStream, AfterStreams};
 pub use self::aliases::{Alias, Aliases};
-pub use self::commands::{Command, Commands};
+pub use self::backend::{Backend, Cached, Memory, Sqlite};
+pub use self::commands::{
+    Command, Commands, CountChange, Limits as CommandLimits, Rejected as CommandRejected,
+};
 pub use self::matcher::Captures;
 pub use self::promotions::{Promotion, Promotions};
+pub use self::quotes::{Quote, Quotes};
 pub use self::script_storage::ScriptStorage;
 pub use self::themes::{Theme, Themes};
-pub use self::words::{Word, Words};
+pub use self::timers::{Timer, Timers};
+pub use self::waters::{Water, Waters};
+pub use self::words::{
+    Action as BadWordAction, Category as BadWordCategory, Match as BadWordMatch,
+    Penalty as BadWordPenalty, Word, Words,
+};
 
 pub use self::matcher::Key;
-pub(crate) use self::matcher::{Matchable, Matcher, Pattern};
+pub(crate) use self::matcher::{Games, Matchable, Matcher, Pattern};
 
 use anyhow::{anyhow, Context as _, Error};
 use chrono::Utc;
 use diesel::prelude::*;
+use diesel_migrations::MigrationConnection as _;
 use parking_lot::Mutex;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 embed_migrations!("./migrations");
 
+/// The number of times a failed database task is retried by default.
+pub const DEFAULT_RETRIES: u32 = 3;
+/// How long SQLite waits for a lock on the database before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to wait before retrying a failed task. Grows with each attempt.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+/// The version of the latest migration known by this build.
+const LATEST_MIGRATION: &str = include_str!(concat!(env!("OUT_DIR"), "/migration.txt"));
+/// The url of a database which only lives in memory.
+const MEMORY: &str = ":memory:";
+
+/// The health of the database connection.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct Status {
+    /// If the last check or task against the database succeeded.
+    pub healthy: bool,
+    /// The number of times we've had to reconnect to the database.
+    pub reconnects: u32,
+    /// The last error we encountered.
+    pub last_error: Option<String>,
+}
+
+struct Inner {
+    url: String,
+    connection: Mutex<SqliteConnection>,
+    retries: AtomicU32,
+    status: Mutex<Status>,
+}
+
 /// Database abstraction.
 #[derive(Clone)]
 pub struct Database {
-    pool: Arc<Mutex<SqliteConnection>>,
+    inner: Arc<Inner>,
 }
 
 impl Database {
@@ -50,13 +96,26 @@ impl Database {
         let url = path.display().to_string();
 
         log::info!("Using database: {}", url);
+        Self::establish(url)
+    }
+
+    /// Open a database which only lives in memory.
+    ///
+    /// Nothing is persisted, so all state is lost when the database is dropped.
+    pub fn memory() -> Result<Database, Error> {
+        Self::establish(String::from(MEMORY))
+    }
 
-        let pool = SqliteConnection::establish(&url)?;
+    /// Establish a connection to the given url and run all migrations.
+    fn establish(url: String) -> Result<Database, Error> {
+        let connection = connect(&url)?;
+
+        check_schema_version(&connection)?;
 
         let mut output = Vec::new();
 
         // Run all migrations and provide some diagnostics on errors.
-        let result = embedded_migrations::run_with_output(&pool, &mut output);
+        let result = embedded_migrations::run_with_output(&connection, &mut output);
         let output = String::from_utf8_lossy(&output);
         result.with_context(|| anyhow!("error when running migrations: {}", output))?;
 
@@ -65,23 +124,118 @@ impl Database {
         }
 
         Ok(Database {
-            pool: Arc::new(Mutex::new(pool)),
+            inner: Arc::new(Inner {
+                url,
+                connection: Mutex::new(connection),
+                retries: AtomicU32::new(DEFAULT_RETRIES),
+                status: Mutex::new(Status {
+                    healthy: true,
+                    ..Status::default()
+                }),
+            }),
         })
     }
 
+    /// Set the number of times a failed task is retried before giving up.
+    pub fn set_retries(&self, retries: u32) {
+        self.inner.retries.store(retries, Ordering::Relaxed);
+    }
+
+    /// Get the current status of the database connection.
+    pub fn status(&self) -> Status {
+        self.inner.status.lock().clone()
+    }
+
+    /// Check that the database connection is healthy, and try to reconnect
+    /// if it isn't.
+    pub async fn check(&self) -> Status {
+        let inner = self.inner.clone();
+
+        let result = task::asyncify(move || {
+            let mut connection = inner.connection.lock();
+
+            if let Err(e) = ping(&connection) {
+                inner.failed(&e);
+                inner.reconnect(&mut connection)?;
+            }
+
+            inner.succeeded();
+            Ok::<_, Error>(())
+        })
+        .await;
+
+        if let Err(e) = result {
+            log_warn!(e, "database is unavailable");
+        }
+
+        self.status()
+    }
+
     /// Run a blocking task with exlusive access to the database pool.
-    pub async fn asyncify<F, T, E>(&self, task: F) -> Result<T, E>
+    ///
+    /// Tasks which fail because the database is busy or unavailable are
+    /// retried, reconnecting to the database if necessary.
+    /// This is why tasks might run more than once, so tasks which make more
+    /// than one change have to do so in a transaction.
+    pub async fn asyncify<F, T, E>(&self, mut task: F) -> Result<T, E>
     where
-        F: FnOnce(&SqliteConnection) -> Result<T, E> + Send + 'static,
+        F: FnMut(&SqliteConnection) -> Result<T, E> + Send + 'static,
         T: Send + 'static,
-        E: Send + 'static,
-        E: From<tokio::task::JoinError>,
+        E: TaskError + Send + 'static,
     {
-        let pool = self.pool.clone();
+        let inner = self.inner.clone();
 
         task::asyncify(move || {
-            let guard = pool.lock();
-            task(&*guard)
+            let retries = inner.retries.load(Ordering::Relaxed);
+            let mut attempt = 0;
+
+            loop {
+                let mut connection = inner.connection.lock();
+
+                // NB: the connection is checked again after the lock has
+                // been reacquired, since another task might have reconnected
+                // it while we were waiting.
+                if attempt > 0 && ping(&connection).is_err() {
+                    if let Err(e) = inner.reconnect(&mut connection) {
+                        log_warn!(e, "failed to reconnect to database");
+                    }
+                }
+
+                let e = match task(&connection) {
+                    Ok(output) => {
+                        inner.succeeded();
+                        return Ok(output);
+                    }
+                    Err(e) => e,
+                };
+
+                let healthy = ping(&connection).is_ok();
+
+                // NB: errors which aren't caused by the database itself are
+                // returned as-is.
+                if healthy && !e.is_transient() {
+                    return Err(e);
+                }
+
+                inner.failed(&e);
+
+                if attempt >= retries {
+                    return Err(e);
+                }
+
+                // NB: release the connection while backing off, so that other
+                // tasks can use the database in the meantime.
+                drop(connection);
+
+                attempt += 1;
+                log::warn!(
+                    "database task failed, retrying (attempt #{}/{}): {}",
+                    attempt,
+                    retries,
+                    e
+                );
+                std::thread::sleep(RETRY_DELAY * attempt);
+            }
         })
         .await
     }
@@ -117,7 +271,7 @@ impl Database {
         let song = song.clone();
 
         self.asyncify(move |c| {
-            diesel::insert_into(dsl::songs).values(song).execute(c)?;
+            diesel::insert_into(dsl::songs).values(&song).execute(c)?;
             Ok(())
         })
         .await
@@ -150,23 +304,25 @@ impl Database {
         let track_id = track_id.clone();
 
         self.asyncify(move |c| {
-            let ids: Vec<i32> = dsl::songs
-                .select(dsl::id)
-                .filter(
-                    dsl::played
-                        .eq(false)
-                        .and(dsl::deleted.eq(false))
-                        .and(dsl::track_id.eq(&track_id)),
-                )
-                .order(dsl::added_at.desc())
-                .limit(1)
-                .load(c)?;
-
-            let count = diesel::update(dsl::songs.filter(dsl::id.eq_any(ids)))
-                .set((dsl::played.eq(played), dsl::deleted.eq(!played)))
-                .execute(c)?;
-
-            Ok(count == 1)
+            c.transaction(|| {
+                let ids: Vec<i32> = dsl::songs
+                    .select(dsl::id)
+                    .filter(
+                        dsl::played
+                            .eq(false)
+                            .and(dsl::deleted.eq(false))
+                            .and(dsl::track_id.eq(&track_id)),
+                    )
+                    .order(dsl::added_at.desc())
+                    .limit(1)
+                    .load(c)?;
+
+                let count = diesel::update(dsl::songs.filter(dsl::id.eq_any(ids)))
+                    .set((dsl::played.eq(played), dsl::deleted.eq(!played)))
+                    .execute(c)?;
+
+                Ok(count == 1)
+            })
         })
         .await
     }
@@ -183,26 +339,71 @@ impl Database {
         let track_id = track_id.clone();
 
         self.asyncify(move |c| {
-            let ids: Vec<i32> = dsl::songs
-                .select(dsl::id)
-                .filter(
-                    dsl::played
-                        .eq(false)
-                        .and(dsl::deleted.eq(false))
-                        .and(dsl::track_id.eq(&track_id)),
-                )
-                .order(dsl::added_at.desc())
-                .limit(1)
-                .load(c)?;
-
-            let count = diesel::update(dsl::songs.filter(dsl::id.eq_any(ids)))
-                .set((
-                    dsl::promoted_at.eq(Utc::now().naive_utc()),
-                    dsl::promoted_by.eq(user.as_deref()),
-                ))
-                .execute(c)?;
-
-            Ok(count == 1)
+            c.transaction(|| {
+                let ids: Vec<i32> = dsl::songs
+                    .select(dsl::id)
+                    .filter(
+                        dsl::played
+                            .eq(false)
+                            .and(dsl::deleted.eq(false))
+                            .and(dsl::track_id.eq(&track_id)),
+                    )
+                    .order(dsl::added_at.desc())
+                    .limit(1)
+                    .load(c)?;
+
+                let count = diesel::update(dsl::songs.filter(dsl::id.eq_any(ids)))
+                    .set((
+                        dsl::promoted_at.eq(Utc::now().naive_utc()),
+                        dsl::promoted_by.eq(user.as_deref()),
+                    ))
+                    .execute(c)?;
+
+                Ok(count == 1)
+            })
+        })
+        .await
+    }
+
+    /// Count the number of songs requested by the given user which have been
+    /// played.
+    pub async fn player_played_count(&self, user: &str) -> Result<i64, Error> {
+        use self::schema::songs::dsl;
+
+        let user = user.to_lowercase();
+
+        self.asyncify(move |c| {
+            let count = dsl::songs
+                .filter(dsl::user.eq(&user).and(dsl::played.eq(true)))
+                .count()
+                .get_result(c)?;
+
+            Ok(count)
+        })
+        .await
+    }
+
+    /// List the users who have had the most requested songs played, and how
+    /// many.
+    pub async fn player_top_requesters(&self, limit: i64) -> Result<Vec<(String, i64)>, Error> {
+        use self::schema::songs::dsl;
+        use diesel::dsl::sql;
+        use diesel::sql_types::BigInt;
+
+        self.asyncify(move |c| {
+            // NB: diesel can't mix aggregates and grouped columns in a select.
+            let requesters = dsl::songs
+                .filter(dsl::user.is_not_null().and(dsl::played.eq(true)))
+                .group_by(dsl::user)
+                .select((dsl::user, sql::<BigInt>("COUNT(*)")))
+                .order((sql::<BigInt>("COUNT(*)").desc(), dsl::user.asc()))
+                .limit(limit)
+                .load::<(Option<String>, i64)>(c)?;
+
+            Ok(requesters
+                .into_iter()
+                .filter_map(|(user, count)| Some((user?, count)))
+                .collect())
         })
         .await
     }
@@ -246,6 +447,13 @@ pub fn user_id(user: &str) -> String {
     user.trim_start_matches('@').to_lowercase()
 }
 
+/// Convert a channel name into the id balances are stored under.
+///
+/// For legacy reasons, this includes the leading hash.
+pub fn channel_id(channel: &str) -> String {
+    format!("#{}", channel.trim_start_matches('#'))
+}
+
 #[derive(Debug, Error)]
 pub enum RenameError {
     /// Trying to rename something to a conflicting name.
@@ -256,12 +464,280 @@ pub enum RenameError {
     Missing,
 }
 
-#[cfg(tests)]
+/// Keep an eye on the health of the database, reconnecting to it when it
+/// becomes unavailable.
+pub async fn health_check(settings: crate::Settings, db: Database) -> Result<(), Error> {
+    let (mut retries_stream, retries) = settings.stream("retries").or_with(DEFAULT_RETRIES).await?;
+    db.set_retries(retries);
+
+    let (mut interval_stream, interval) = settings
+        .stream("health-check-interval")
+        .or_with_else(|| utils::Duration::seconds(30))
+        .await?;
+
+    // NB: an empty interval disables the health check, since an interval
+    // can't tick every zero seconds.
+    let new_interval = |interval: utils::Duration| {
+        if interval.is_empty() {
+            async_fuse::Fuse::empty()
+        } else {
+            async_fuse::Fuse::new(tokio::time::interval(interval.as_std()))
+        }
+    };
+
+    let mut interval = new_interval(interval);
+
+    loop {
+        tokio::select! {
+            update = retries_stream.recv() => {
+                db.set_retries(update);
+            }
+            update = interval_stream.recv() => {
+                interval = new_interval(update);
+            }
+            _ = interval.as_pin_mut().poll_inner(|mut i, cx| i.poll_tick(cx)) => {
+                let was_healthy = db.status().healthy;
+
+                if db.check().await.healthy && !was_healthy {
+                    log::info!("Database has recovered");
+                }
+            }
+        }
+    }
+}
+
+impl Inner {
+    /// Reconnect to the database, replacing the given connection.
+    fn reconnect(&self, connection: &mut SqliteConnection) -> Result<(), Error> {
+        // NB: reconnecting to an in-memory database would lose everything.
+        if self.url == MEMORY {
+            bail!("cannot reconnect to an in-memory database");
+        }
+
+        *connection = connect(&self.url)?;
+        self.status.lock().reconnects += 1;
+        log::info!("Reconnected to database: {}", self.url);
+        Ok(())
+    }
+
+    /// Mark the connection as healthy.
+    fn succeeded(&self) {
+        self.status.lock().healthy = true;
+    }
+
+    /// Mark the connection as unhealthy because of the given error.
+    fn failed(&self, e: &dyn fmt::Display) {
+        let mut status = self.status.lock();
+        status.healthy = false;
+        status.last_error = Some(e.to_string());
+    }
+}
+
+/// Refuse to touch a database which has been migrated by a newer version of
+/// the bot, since we don't know what its schema looks like.
+fn check_schema_version(connection: &SqliteConnection) -> Result<(), Error> {
+    diesel_migrations::setup_database(connection)?;
+
+    if let Some(version) = connection.latest_run_migration_version()? {
+        if version.as_str() > LATEST_MIGRATION {
+            bail!(
+                "database is at schema version {}, but this version of the bot only supports up to {}, upgrade the bot to use it",
+                version,
+                LATEST_MIGRATION
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Open a connection to the database at the given url.
+fn connect(url: &str) -> Result<SqliteConnection, Error> {
+    let connection = SqliteConnection::establish(url)?;
+
+    // Have SQLite wait for locks held by others instead of failing right away.
+    connection.execute(&format!(
+        "PRAGMA busy_timeout = {}",
+        BUSY_TIMEOUT.as_millis()
+    ))?;
+    Ok(connection)
+}
+
+/// Test that the connection is usable.
+fn ping(connection: &SqliteConnection) -> Result<(), Error> {
+    connection.execute("SELECT 1")?;
+    Ok(())
+}
+
+/// Errors which can be produced by database tasks.
+pub trait TaskError: From<tokio::task::JoinError> + fmt::Display {
+    /// Test if the error is caused by the database being temporarily busy or
+    /// unavailable, in which case the task is worth retrying.
+    fn is_transient(&self) -> bool;
+}
+
+impl TaskError for Error {
+    fn is_transient(&self) -> bool {
+        self.chain().any(|cause| {
+            if cause.is::<diesel::result::ConnectionError>() {
+                return true;
+            }
+
+            match cause.downcast_ref::<diesel::result::Error>() {
+                Some(e) => is_transient(e),
+                None => false,
+            }
+        })
+    }
+}
+
+/// Test if the given error is caused by the database being temporarily busy
+/// or unavailable.
+pub(crate) fn is_transient(e: &diesel::result::Error) -> bool {
+    match e {
+        diesel::result::Error::DatabaseError(_, info) => {
+            let message = info.message();
+            message.contains("database is locked")
+                || message.contains("database table is locked")
+                || message.contains("disk I/O error")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
 mod tests {
-    use super::user_id;
+    use super::{models, user_id, Database};
+    use crate::track_id::TrackId;
+    use anyhow::Result;
+    use chrono::Utc;
 
     #[test]
     fn test_user_id() {
         assert_eq!("oxidizebot", user_id("@OxidizeBot"));
     }
+
+    #[tokio::test]
+    async fn test_top_requesters() -> Result<()> {
+        let db = Database::memory()?;
+
+        let requests = [
+            ("a", Some("setbac")),
+            ("b", Some("setbac")),
+            ("c", Some("viewer")),
+            ("d", Some("other")),
+            ("e", None),
+        ];
+
+        for (id, user) in requests.iter() {
+            let song = models::AddSong {
+                track_id: TrackId::YouTube(id.to_string()),
+                added_at: Utc::now().naive_utc(),
+                user: user.map(String::from),
+                paid_channel: None,
+                paid: None,
+            };
+
+            db.player_push_back(&song).await?;
+        }
+
+        for id in ["a", "b", "c", "e"].iter() {
+            db.player_remove_song(&TrackId::YouTube(id.to_string()), true)
+                .await?;
+        }
+
+        // Removed without being played.
+        db.player_remove_song(&TrackId::YouTube(String::from("d")), false)
+            .await?;
+
+        assert_eq!(db.player_played_count("SetBac").await?, 2);
+        assert_eq!(db.player_played_count("other").await?, 0);
+
+        assert_eq!(
+            db.player_top_requesters(5).await?,
+            vec![(String::from("setbac"), 2), (String::from("viewer"), 1)]
+        );
+        assert_eq!(
+            db.player_top_requesters(1).await?,
+            vec![(String::from("setbac"), 2)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_player_payment() -> Result<()> {
+        let db = Database::memory()?;
+
+        let song = models::AddSong {
+            track_id: TrackId::YouTube(String::from("a")),
+            added_at: Utc::now().naive_utc(),
+            user: Some(String::from("setbac")),
+            paid_channel: Some(String::from("#setbac")),
+            paid: Some(100),
+        };
+
+        db.player_push_back(&song).await?;
+
+        let songs = db.player_list().await?;
+        assert_eq!(songs.len(), 1);
+        assert_eq!(songs[0].paid_channel.as_deref(), Some("#setbac"));
+        assert_eq!(songs[0].paid, Some(100));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry() -> Result<()> {
+        use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+        let db = Database::memory()?;
+        let mut attempts = 0;
+
+        let result = db
+            .asyncify(move |_| {
+                attempts += 1;
+
+                if attempts == 1 {
+                    let info = Box::new(String::from("database is locked"));
+                    return Err(
+                        DieselError::DatabaseError(DatabaseErrorKind::__Unknown, info).into(),
+                    );
+                }
+
+                Ok::<_, anyhow::Error>(attempts)
+            })
+            .await?;
+
+        assert_eq!(result, 2);
+
+        let status = db.check().await;
+        assert!(status.healthy);
+        assert_eq!(status.reconnects, 0);
+        assert!(status.last_error.is_some());
+
+        // NB: errors unrelated to the database aren't retried.
+        let mut attempts = 0;
+
+        let result = db
+            .asyncify(move |_| {
+                attempts += 1;
+                Err::<(), _>(anyhow::anyhow!("failed after {} attempts", attempts))
+            })
+            .await;
+
+        assert_eq!(result.unwrap_err().to_string(), "failed after 1 attempts");
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_version() -> Result<()> {
+        use diesel_migrations::MigrationConnection as _;
+
+        let db = Database::memory()?;
+        let connection = db.inner.connection.lock();
+        super::check_schema_version(&connection)?;
+
+        connection.insert_new_migration("99991231000000")?;
+        assert!(super::check_schema_version(&connection).is_err());
+        Ok(())
+    }
 }
@@ -1,10 +1,13 @@
 use super::schema::{
-    after_streams, aliases, bad_words, balances, commands, promotions, script_keys, songs, themes,
+    after_streams, aliases, bad_word_allowed, bad_word_categories, bad_words, balances, commands,
+    promotions, quotes, script_keys, songs, themes, timers, waters,
 };
 use crate::track_id::TrackId;
 use chrono::NaiveDateTime;
 
-#[derive(serde::Serialize, serde::Deserialize, diesel::Queryable, diesel::Insertable)]
+#[derive(
+    Debug, Clone, serde::Serialize, serde::Deserialize, diesel::Queryable, diesel::Insertable,
+)]
 pub struct Balance {
     pub channel: String,
     pub user: String,
@@ -26,7 +29,7 @@ impl Balance {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
 pub struct Command {
     /// The channel the command belongs to.
     pub channel: String,
@@ -42,6 +45,33 @@ pub struct Command {
     pub group: Option<String>,
     /// If the command is disabled.
     pub disabled: bool,
+    /// Description of the command, as shown by `!help <command>`.
+    pub description: Option<String>,
+    /// Usage of the command, as shown by `!help <command>`.
+    pub usage: Option<String>,
+    /// When the counter of the command was last changed.
+    pub count_updated_at: Option<NaiveDateTime>,
+    /// Who last changed the counter of the command.
+    pub count_updated_by: Option<String>,
+    /// Newline-separated game categories the command is limited to, if any.
+    pub games: Option<String>,
+    /// Seconds to wait between any uses of the command.
+    pub cooldown: Option<i32>,
+    /// Seconds a single user has to wait between uses of the command.
+    pub user_cooldown: Option<i32>,
+    /// How many times the command can be used per stream.
+    pub stream_limit: Option<i32>,
+    /// Seconds to wait between uses of the command by moderators, instead of
+    /// `cooldown`.
+    pub moderator_cooldown: Option<i32>,
+    /// The named cooldown the command shares with other commands, if any.
+    pub cooldown_group: Option<String>,
+    /// The permission users need to use the command, if any.
+    pub permission: Option<String>,
+    /// Newline-separated names the command can also be invoked by, if any.
+    pub triggers: Option<String>,
+    /// How much currency it costs to use the command, if anything.
+    pub cost: Option<i32>,
 }
 
 #[derive(Debug, Clone, Default, diesel::AsChangeset)]
@@ -53,6 +83,16 @@ pub struct UpdateCommand<'a> {
     pub disabled: Option<bool>,
 }
 
+#[derive(Debug, Clone, Default, diesel::AsChangeset)]
+#[table_name = "commands"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct UpdateCommandLimits {
+    pub cooldown: Option<i32>,
+    pub user_cooldown: Option<i32>,
+    pub moderator_cooldown: Option<i32>,
+    pub stream_limit: Option<i32>,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
 #[table_name = "aliases"]
 pub struct Alias {
@@ -68,6 +108,8 @@ pub struct Alias {
     pub group: Option<String>,
     /// If the promotion is disabled.
     pub disabled: bool,
+    /// Newline-separated game categories the alias is limited to, if any.
+    pub games: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Insertable)]
@@ -113,6 +155,34 @@ pub struct InsertAfterStream {
 pub struct BadWord {
     pub word: String,
     pub why: Option<String>,
+    /// The category the word belongs to, if any.
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, diesel::AsChangeset)]
+#[table_name = "bad_words"]
+pub struct UpdateBadWord<'a> {
+    pub why: Option<&'a str>,
+    pub category: Option<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
+#[table_name = "bad_word_categories"]
+pub struct BadWordCategory {
+    /// The name of the category.
+    pub name: String,
+    /// What to do when a word in the category is matched.
+    pub action: String,
+    /// If words in the category are tested for.
+    pub enabled: bool,
+}
+
+/// A bad word which is allowed in a channel.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
+#[table_name = "bad_word_allowed"]
+pub struct BadWordAllowed {
+    pub channel: String,
+    pub word: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, diesel::Queryable)]
@@ -133,6 +203,10 @@ pub struct Song {
     pub promoted_by: Option<String>,
     /// The user that requested the song.
     pub user: Option<String>,
+    /// The channel whose currency was paid to request the song.
+    pub paid_channel: Option<String>,
+    /// How much currency was paid to request the song.
+    pub paid: Option<i32>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, diesel::Insertable)]
@@ -144,6 +218,10 @@ pub struct AddSong {
     pub added_at: NaiveDateTime,
     /// The user that requested the song.
     pub user: Option<String>,
+    /// The channel whose currency was paid to request the song.
+    pub paid_channel: Option<String>,
+    /// How much currency was paid to request the song.
+    pub paid: Option<i32>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
@@ -174,6 +252,28 @@ pub struct UpdatePromotion<'a> {
     pub disabled: Option<bool>,
 }
 
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
+pub struct Timer {
+    /// The channel the timer belongs to.
+    pub channel: String,
+    /// The name of the timer.
+    pub name: String,
+    /// The interval in seconds at which the timer is posted.
+    pub interval: i32,
+    /// The timer template to run.
+    pub text: String,
+    /// If the timer is paused.
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, Default, diesel::AsChangeset)]
+#[table_name = "timers"]
+pub struct UpdateTimer<'a> {
+    pub interval: Option<i32>,
+    pub text: Option<&'a str>,
+    pub paused: Option<bool>,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
 pub struct Theme {
     /// The channel the theme belongs to.
@@ -214,3 +314,60 @@ pub struct ScriptKey {
 pub struct SetScriptKeyValue<'a> {
     pub value: &'a [u8],
 }
+
+#[derive(Debug, Clone, diesel::Queryable)]
+pub struct Water {
+    /// The unique identifier of the water.
+    pub id: i32,
+    /// The channel the water was issued in.
+    pub channel: String,
+    /// When the stream the water was issued during started.
+    pub stream_started_at: NaiveDateTime,
+    /// When the water was rewarded.
+    pub rewarded_at: NaiveDateTime,
+    /// The user that was rewarded.
+    pub user: String,
+    /// The amount the user was rewarded.
+    pub amount: i64,
+    /// If the water has been undone.
+    pub undone: bool,
+}
+
+/// Insert model for waters.
+#[derive(diesel::Insertable)]
+#[table_name = "waters"]
+pub struct InsertWater {
+    pub channel: String,
+    pub stream_started_at: NaiveDateTime,
+    pub rewarded_at: NaiveDateTime,
+    pub user: String,
+    pub amount: i64,
+}
+
+#[derive(Debug, Clone, diesel::Queryable)]
+pub struct Quote {
+    /// The unique identifier of the quote.
+    pub id: i32,
+    /// The channel the quote belongs to.
+    pub channel: String,
+    /// The number of the quote in its channel.
+    ///
+    /// Numbers don't change when other quotes are deleted.
+    pub number: i32,
+    /// When the quote was added.
+    pub added_at: NaiveDateTime,
+    /// The user that added the quote.
+    pub user: String,
+    /// The text of the quote.
+    pub text: String,
+}
+
+/// Insert model for quotes.
+#[derive(diesel::Insertable)]
+#[table_name = "quotes"]
+pub struct InsertQuote {
+    pub channel: String,
+    pub number: i32,
+    pub user: String,
+    pub text: String,
+}
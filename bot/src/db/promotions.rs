@@ -40,47 +40,49 @@ impl Database {
 
         self.0
             .asyncify(move |c| {
-                let filter = dsl::promotions
-                    .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
-                let b = filter
-                    .clone()
-                    .first::<db::models::Promotion>(c)
-                    .optional()?;
-
-                let frequency = frequency.num_seconds() as i32;
-
-                match b {
-                    None => {
-                        let command = db::models::Promotion {
-                            channel: key.channel.to_string(),
-                            name: key.name.to_string(),
-                            frequency,
-                            promoted_at: None,
-                            text: text.to_string(),
-                            group: None,
-                            disabled: false,
-                        };
-
-                        diesel::insert_into(dsl::promotions)
-                            .values(&command)
-                            .execute(c)?;
-
-                        Ok(None)
-                    }
-                    Some(promotion) => {
-                        let mut set = db::models::UpdatePromotion::default();
-                        set.text = Some(&text);
-                        set.frequency = Some(frequency);
+                c.transaction(|| {
+                    let filter = dsl::promotions
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
+                    let b = filter
+                        .clone()
+                        .first::<db::models::Promotion>(c)
+                        .optional()?;
+
+                    let frequency = frequency.num_seconds() as i32;
+
+                    match b {
+                        None => {
+                            let command = db::models::Promotion {
+                                channel: key.channel.to_string(),
+                                name: key.name.to_string(),
+                                frequency,
+                                promoted_at: None,
+                                text: text.to_string(),
+                                group: None,
+                                disabled: false,
+                            };
+
+                            diesel::insert_into(dsl::promotions)
+                                .values(&command)
+                                .execute(c)?;
+
+                            Ok(None)
+                        }
+                        Some(promotion) => {
+                            let mut set = db::models::UpdatePromotion::default();
+                            set.text = Some(&text);
+                            set.frequency = Some(frequency);
 
-                        diesel::update(filter).set(&set).execute(c)?;
+                            diesel::update(filter).set(&set).execute(c)?;
 
-                        if promotion.disabled {
-                            return Ok(None);
-                        }
+                            if promotion.disabled {
+                                return Ok(None);
+                            }
 
-                        Ok(Some(promotion))
+                            Ok(Some(promotion))
+                        }
                     }
-                }
+                })
             })
             .await
     }
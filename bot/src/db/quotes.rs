@@ -0,0 +1,59 @@
+use crate::db;
+use crate::db::models;
+use anyhow::Result;
+use std::convert::TryFrom as _;
+use std::sync::Arc;
+
+pub use self::models::Quote;
+
+/// Storage for the quotes of the !quote command.
+///
+/// Quotes are numbered from `1` in the order they were added to a channel.
+/// A quote keeps its number when other quotes are deleted.
+#[derive(Clone)]
+pub struct Quotes {
+    db: Arc<dyn db::Backend>,
+}
+
+impl Quotes {
+    /// Open the quotes database.
+    pub async fn load(db: db::Database) -> Result<Self> {
+        Self::load_backend(Arc::new(db::Sqlite::new(db))).await
+    }
+
+    /// Open quotes stored in the given backend.
+    pub async fn load_backend(db: Arc<dyn db::Backend>) -> Result<Self> {
+        Ok(Self { db })
+    }
+
+    /// Add a quote to the given channel, returning it as stored.
+    pub async fn push(&self, channel: &str, user: &str, text: &str) -> Result<Quote> {
+        self.db.quote_add(channel, user, text).await
+    }
+
+    /// Get a random quote in the given channel.
+    pub async fn random(&self, channel: &str) -> Result<Option<Quote>> {
+        self.db.quote_random(channel).await
+    }
+
+    /// Get the quote with the given number in the given channel.
+    pub async fn get(&self, channel: &str, number: u32) -> Result<Option<Quote>> {
+        let number = match i32::try_from(number) {
+            Ok(number) => number,
+            Err(..) => return Ok(None),
+        };
+
+        self.db.quote_fetch(channel, number).await
+    }
+
+    /// Delete the quote with the given number in the given channel, returning
+    /// the deleted quote.
+    pub async fn delete(&self, channel: &str, number: u32) -> Result<Option<Quote>> {
+        let number = match i32::try_from(number) {
+            Ok(number) => number,
+            Err(..) => return Ok(None),
+        };
+
+        self.db.quote_delete(channel, number).await
+    }
+}
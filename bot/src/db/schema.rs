@@ -16,6 +16,19 @@ table! {
         text -> Text,
         group -> Nullable<Text>,
         disabled -> Bool,
+        description -> Nullable<Text>,
+        usage -> Nullable<Text>,
+        count_updated_at -> Nullable<Timestamp>,
+        count_updated_by -> Nullable<Text>,
+        games -> Nullable<Text>,
+        cooldown -> Nullable<Integer>,
+        user_cooldown -> Nullable<Integer>,
+        stream_limit -> Nullable<Integer>,
+        moderator_cooldown -> Nullable<Integer>,
+        cooldown_group -> Nullable<Text>,
+        permission -> Nullable<Text>,
+        triggers -> Nullable<Text>,
+        cost -> Nullable<Integer>,
     }
 }
 
@@ -33,6 +46,22 @@ table! {
     bad_words (word) {
         word -> Text,
         why -> Nullable<Text>,
+        category -> Nullable<Text>,
+    }
+}
+
+table! {
+    bad_word_categories (name) {
+        name -> Text,
+        action -> Text,
+        enabled -> Bool,
+    }
+}
+
+table! {
+    bad_word_allowed (channel, word) {
+        channel -> Text,
+        word -> Text,
     }
 }
 
@@ -46,6 +75,8 @@ table! {
         promoted_at -> Nullable<Timestamp>,
         promoted_by -> Nullable<Text>,
         user -> Nullable<Text>,
+        paid_channel -> Nullable<Text>,
+        paid -> Nullable<Integer>,
     }
 }
 
@@ -64,6 +95,7 @@ table! {
         text -> Text,
         group -> Nullable<Text>,
         disabled -> Bool,
+        games -> Nullable<Text>,
     }
 }
 
@@ -79,6 +111,16 @@ table! {
     }
 }
 
+table! {
+    timers (channel, name) {
+        channel -> Text,
+        name -> Text,
+        interval -> Integer,
+        text -> Text,
+        paused -> Bool,
+    }
+}
+
 table! {
     themes (channel, name) {
         channel -> Text,
@@ -114,3 +156,26 @@ table! {
         value -> Binary,
     }
 }
+
+table! {
+    waters (id) {
+        id -> Integer,
+        channel -> Text,
+        stream_started_at -> Timestamp,
+        rewarded_at -> Timestamp,
+        user -> Text,
+        amount -> BigInt,
+        undone -> Bool,
+    }
+}
+
+table! {
+    quotes (id) {
+        id -> Integer,
+        channel -> Text,
+        number -> Integer,
+        added_at -> Timestamp,
+        user -> Text,
+        text -> Text,
+    }
+}
@@ -29,37 +29,39 @@ impl ScriptStorage {
 
         self.db
             .asyncify(move |c| {
-                let key = serde_cbor::to_vec(&key)?;
-                let value = serde_cbor::to_vec(&value)?;
-
-                let filter =
-                    dsl::script_keys.filter(dsl::channel.eq(&channel).and(dsl::key.eq(&key)));
-
-                let first = filter
-                    .clone()
-                    .first::<db::models::ScriptKey>(c)
-                    .optional()?;
-
-                match first {
-                    None => {
-                        let script_key = db::models::ScriptKey {
-                            channel,
-                            key,
-                            value,
-                        };
-
-                        diesel::insert_into(dsl::script_keys)
-                            .values(&script_key)
-                            .execute(c)?;
-
-                        Ok(())
-                    }
-                    Some(..) => {
-                        let set = db::models::SetScriptKeyValue { value: &value };
-                        diesel::update(filter).set(&set).execute(c)?;
-                        Ok(())
+                c.transaction(|| {
+                    let key = serde_cbor::to_vec(&key)?;
+                    let value = serde_cbor::to_vec(&value)?;
+
+                    let filter =
+                        dsl::script_keys.filter(dsl::channel.eq(&channel).and(dsl::key.eq(&key)));
+
+                    let first = filter
+                        .clone()
+                        .first::<db::models::ScriptKey>(c)
+                        .optional()?;
+
+                    match first {
+                        None => {
+                            let script_key = db::models::ScriptKey {
+                                channel: channel.clone(),
+                                key,
+                                value,
+                            };
+
+                            diesel::insert_into(dsl::script_keys)
+                                .values(&script_key)
+                                .execute(c)?;
+
+                            Ok(())
+                        }
+                        Some(..) => {
+                            let set = db::models::SetScriptKeyValue { value: &value };
+                            diesel::update(filter).set(&set).execute(c)?;
+                            Ok(())
+                        }
                     }
-                }
+                })
             })
             .await
     }
@@ -26,38 +26,40 @@ impl Database {
 
         self.0
             .asyncify(move |c| {
-                let filter =
-                    dsl::themes.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
-
-                let first = filter.clone().first::<db::models::Theme>(c).optional()?;
-
-                match first {
-                    None => {
-                        let theme = db::models::Theme {
-                            channel: key.channel.to_string(),
-                            name: key.name.to_string(),
-                            track_id,
-                            start: Default::default(),
-                            end: None,
-                            group: None,
-                            disabled: false,
-                        };
-
-                        diesel::insert_into(dsl::themes).values(&theme).execute(c)?;
-                        Ok(Some(theme))
-                    }
-                    Some(theme) => {
-                        let mut set = db::models::UpdateTheme::default();
-                        set.track_id = Some(&track_id);
-                        diesel::update(filter).set(&set).execute(c)?;
-
-                        if theme.disabled {
-                            return Ok(None);
+                c.transaction(|| {
+                    let filter = dsl::themes
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
+
+                    let first = filter.clone().first::<db::models::Theme>(c).optional()?;
+
+                    match first {
+                        None => {
+                            let theme = db::models::Theme {
+                                channel: key.channel.to_string(),
+                                name: key.name.to_string(),
+                                track_id: track_id.clone(),
+                                start: Default::default(),
+                                end: None,
+                                group: None,
+                                disabled: false,
+                            };
+
+                            diesel::insert_into(dsl::themes).values(&theme).execute(c)?;
+                            Ok(Some(theme))
                         }
+                        Some(theme) => {
+                            let mut set = db::models::UpdateTheme::default();
+                            set.track_id = Some(&track_id);
+                            diesel::update(filter).set(&set).execute(c)?;
 
-                        Ok(Some(theme))
+                            if theme.disabled {
+                                return Ok(None);
+                            }
+
+                            Ok(Some(theme))
+                        }
                     }
-                }
+                })
             })
             .await
     }
@@ -75,7 +77,7 @@ impl Database {
         self.0
             .asyncify(move |c| {
                 let start = start.as_milliseconds() as i32;
-                let end = end.map(|s| s.as_milliseconds() as i32);
+                let end = end.as_ref().map(|s| s.as_milliseconds() as i32);
 
                 diesel::update(
                     dsl::themes.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
@@ -0,0 +1,300 @@
+use crate::db;
+use crate::template;
+use crate::utils;
+use anyhow::{anyhow, Context as _};
+use diesel::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::{Notify, RwLock};
+
+/// Local database wrapper.
+#[derive(Clone)]
+struct Database(db::Database);
+
+impl Database {
+    /// List all timers.
+    async fn list(&self) -> Result<Vec<db::models::Timer>, anyhow::Error> {
+        use db::schema::timers::dsl;
+
+        self.0
+            .asyncify(move |c| Ok(dsl::timers.load::<db::models::Timer>(c)?))
+            .await
+    }
+
+    /// Insert or update the given timer, returning the stored timer.
+    async fn edit(
+        &self,
+        key: &Key,
+        interval: utils::Duration,
+        text: &str,
+    ) -> Result<db::models::Timer, anyhow::Error> {
+        use db::schema::timers::dsl;
+
+        let key = key.clone();
+        let text = text.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                c.transaction(|| {
+                    let filter = dsl::timers
+                        .filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
+                    let b = filter.first::<db::models::Timer>(c).optional()?;
+
+                    let interval = interval.num_seconds() as i32;
+
+                    match b {
+                        None => {
+                            let timer = db::models::Timer {
+                                channel: key.channel.to_string(),
+                                name: key.name.to_string(),
+                                interval,
+                                text: text.clone(),
+                                paused: false,
+                            };
+
+                            diesel::insert_into(dsl::timers).values(&timer).execute(c)?;
+
+                            Ok(timer)
+                        }
+                        Some(mut timer) => {
+                            let set = db::models::UpdateTimer {
+                                interval: Some(interval),
+                                text: Some(&text),
+                                ..Default::default()
+                            };
+
+                            diesel::update(filter).set(&set).execute(c)?;
+
+                            timer.text = text.clone();
+                            timer.interval = interval;
+                            Ok(timer)
+                        }
+                    }
+                })
+            })
+            .await
+    }
+
+    /// Delete the given timer.
+    async fn delete(&self, key: &Key) -> Result<bool, anyhow::Error> {
+        use db::schema::timers::dsl;
+
+        let key = key.clone();
+
+        self.0
+            .asyncify(move |c| {
+                let count = diesel::delete(
+                    dsl::timers.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .execute(c)?;
+
+                Ok(count == 1)
+            })
+            .await
+    }
+
+    /// Set if the given timer is paused.
+    async fn set_paused(&self, key: &Key, paused: bool) -> Result<bool, anyhow::Error> {
+        use db::schema::timers::dsl;
+
+        let key = key.clone();
+
+        self.0
+            .asyncify(move |c| {
+                let count = diesel::update(
+                    dsl::timers.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+                )
+                .set(dsl::paused.eq(paused))
+                .execute(c)?;
+
+                Ok(count == 1)
+            })
+            .await
+    }
+}
+
+#[derive(Clone)]
+pub struct Timers {
+    inner: Arc<RwLock<HashMap<Key, Arc<Timer>>>>,
+    changed: Arc<Notify>,
+    db: Database,
+}
+
+impl Timers {
+    /// Construct a new timers store with a db.
+    pub async fn load(db: db::Database) -> Result<Timers, anyhow::Error> {
+        let db = Database(db);
+
+        let mut inner = HashMap::new();
+
+        for timer in db.list().await? {
+            let timer = Timer::from_db(&timer)?;
+            inner.insert(timer.key.clone(), Arc::new(timer));
+        }
+
+        Ok(Timers {
+            inner: Arc::new(RwLock::new(inner)),
+            changed: Arc::new(Notify::new()),
+            db,
+        })
+    }
+
+    /// Wait until the set of timers has changed.
+    pub async fn changed(&self) {
+        self.changed.notified().await;
+    }
+
+    /// Insert or update the given timer.
+    ///
+    /// Returns `true` if a new timer was added.
+    pub async fn edit(
+        &self,
+        channel: &str,
+        name: &str,
+        interval: utils::Duration,
+        template: template::Template,
+    ) -> Result<bool, anyhow::Error> {
+        let key = Key::new(channel, name);
+
+        let mut inner = self.inner.write().await;
+        let timer = self.db.edit(&key, interval, template.source()).await?;
+
+        let added = inner
+            .insert(
+                key.clone(),
+                Arc::new(Timer {
+                    key,
+                    interval,
+                    template,
+                    paused: timer.paused,
+                }),
+            )
+            .is_none();
+
+        self.changed.notify_one();
+        Ok(added)
+    }
+
+    /// Delete the given timer.
+    pub async fn delete(&self, channel: &str, name: &str) -> Result<bool, anyhow::Error> {
+        let key = Key::new(channel, name);
+
+        let mut inner = self.inner.write().await;
+
+        if !self.db.delete(&key).await? {
+            return Ok(false);
+        }
+
+        inner.remove(&key);
+        self.changed.notify_one();
+        Ok(true)
+    }
+
+    /// Pause or resume the given timer.
+    ///
+    /// Returns `false` if the timer doesn't exist.
+    pub async fn set_paused(
+        &self,
+        channel: &str,
+        name: &str,
+        paused: bool,
+    ) -> Result<bool, anyhow::Error> {
+        let key = Key::new(channel, name);
+
+        let mut inner = self.inner.write().await;
+
+        let timer = match inner.get(&key) {
+            Some(timer) => timer,
+            None => return Ok(false),
+        };
+
+        if !self.db.set_paused(&key, paused).await? {
+            return Ok(false);
+        }
+
+        let mut timer = (**timer).clone();
+        timer.paused = paused;
+        inner.insert(key, Arc::new(timer));
+
+        self.changed.notify_one();
+        Ok(true)
+    }
+
+    /// Get the given timer by name.
+    pub async fn get(&self, channel: &str, name: &str) -> Option<Arc<Timer>> {
+        let key = Key::new(channel, name);
+        self.inner.read().await.get(&key).cloned()
+    }
+
+    /// List all timers in the given channel.
+    pub async fn list(&self, channel: &str) -> Vec<Arc<Timer>> {
+        let inner = self.inner.read().await;
+
+        let mut out = inner
+            .values()
+            .filter(|t| t.key.channel == channel)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        out.sort_by(|a, b| a.key.name.cmp(&b.key.name));
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct Key {
+    pub channel: String,
+    pub name: String,
+}
+
+impl Key {
+    pub fn new(channel: &str, name: &str) -> Self {
+        Self {
+            channel: channel.to_string(),
+            name: name.to_lowercase(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Timer {
+    pub key: Key,
+    pub interval: utils::Duration,
+    pub template: template::Template,
+    pub paused: bool,
+}
+
+impl Timer {
+    pub fn from_db(timer: &db::models::Timer) -> Result<Timer, anyhow::Error> {
+        let template = template::Template::compile(&timer.text)
+            .with_context(|| anyhow!("failed to compile timer `{:?}` from db", timer))?;
+
+        Ok(Timer {
+            key: Key::new(&timer.channel, &timer.name),
+            interval: utils::Duration::seconds(timer.interval as u64),
+            template,
+            paused: timer.paused,
+        })
+    }
+
+    /// Render the given timer.
+    pub fn render<T>(&self, data: &T) -> Result<String, anyhow::Error>
+    where
+        T: serde::Serialize,
+    {
+        self.template.render_to_string(data)
+    }
+}
+
+impl fmt::Display for Timer {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "{name} (every {interval}{paused})",
+            name = self.key.name,
+            interval = self.interval,
+            paused = if self.paused { ", paused" } else { "" },
+        )
+    }
+}
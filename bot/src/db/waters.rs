@@ -0,0 +1,112 @@
+use crate::db;
+use crate::db::models;
+use crate::db::schema;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+
+pub use self::models::Water;
+
+/// Storage for the reward history of the !water command, so that rewards can
+/// be undone after a restart.
+#[derive(Clone)]
+pub struct Waters {
+    db: db::Database,
+}
+
+impl Waters {
+    /// Open the waters database.
+    pub async fn load(db: db::Database) -> Result<Self> {
+        Ok(Self { db })
+    }
+
+    /// Record a water which rewarded the given user, returning its id.
+    pub async fn push(
+        &self,
+        channel: &str,
+        stream_started_at: DateTime<Utc>,
+        rewarded_at: DateTime<Utc>,
+        user: &str,
+        amount: i64,
+    ) -> Result<i32> {
+        use self::schema::waters::dsl;
+
+        let water = models::InsertWater {
+            channel: channel.to_string(),
+            stream_started_at: stream_started_at.naive_utc(),
+            rewarded_at: rewarded_at.naive_utc(),
+            user: user.to_string(),
+            amount,
+        };
+
+        self.db
+            .asyncify(move |c| {
+                c.transaction(|| {
+                    diesel::insert_into(dsl::waters).values(&water).execute(c)?;
+
+                    // NB: the connection is exclusive, so this is the row we
+                    // just inserted.
+                    let id = dsl::waters
+                        .select(dsl::id)
+                        .order(dsl::id.desc())
+                        .first::<i32>(c)?;
+
+                    Ok(id)
+                })
+            })
+            .await
+    }
+
+    /// Mark the water with the given id as undone.
+    ///
+    /// Returns `false` if it doesn't exist or has already been undone, in which
+    /// case the reward must not be refunded again.
+    pub async fn undo(&self, id: i32) -> Result<bool> {
+        use self::schema::waters::dsl;
+
+        self.db
+            .asyncify(move |c| {
+                let count =
+                    diesel::update(dsl::waters.filter(dsl::id.eq(id).and(dsl::undone.eq(false))))
+                        .set(dsl::undone.eq(true))
+                        .execute(c)?;
+
+                Ok(count == 1)
+            })
+            .await
+    }
+
+    /// List the waters which haven't been undone from the most recent stream
+    /// in the given channel, oldest first.
+    pub async fn tail(&self, channel: &str) -> Result<Vec<Water>> {
+        use self::schema::waters::dsl;
+
+        let channel = channel.to_string();
+
+        self.db
+            .asyncify(move |c| {
+                let latest = dsl::waters
+                    .select(dsl::stream_started_at)
+                    .filter(dsl::channel.eq(&channel))
+                    .order(dsl::stream_started_at.desc())
+                    .first::<chrono::NaiveDateTime>(c)
+                    .optional()?;
+
+                let latest = match latest {
+                    Some(latest) => latest,
+                    None => return Ok(Vec::new()),
+                };
+
+                Ok(dsl::waters
+                    .filter(
+                        dsl::channel
+                            .eq(&channel)
+                            .and(dsl::stream_started_at.eq(latest))
+                            .and(dsl::undone.eq(false)),
+                    )
+                    .order(dsl::id.asc())
+                    .load::<Water>(c)?)
+            })
+            .await
+    }
+}
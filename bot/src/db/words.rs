@@ -1,8 +1,12 @@
 use crate::db;
 use crate::template;
+use crate::utils;
 use diesel::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::sync::Arc;
+use std::time;
+use thiserror::Error;
 use tokio::sync::{RwLock, RwLockReadGuard};
 
 /// Tokenize the given word.
@@ -11,18 +15,173 @@ pub fn tokenize(word: &str) -> String {
     inflector::string::singularize::to_singular(&word)
 }
 
+/// What to do with a message containing a bad word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Delete the message.
+    Delete,
+    /// Only respond with the reason the word is bad, if it has one.
+    Warn,
+}
+
+impl Action {
+    /// Get the action as a string.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Delete => "delete",
+            Action::Warn => "warn",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("bad action `{0}`, expected: delete or warn")]
+pub struct BadAction(String);
+
+impl std::str::FromStr for Action {
+    type Err = BadAction;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(Action::Delete),
+            "warn" => Ok(Action::Warn),
+            other => Err(BadAction(other.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(fmt)
+    }
+}
+
+/// A named category of bad words.
+#[derive(Debug, Clone)]
+pub struct Category {
+    pub name: String,
+    pub action: Action,
+    pub enabled: bool,
+}
+
+impl Category {
+    fn from_db(category: &db::models::BadWordCategory) -> Result<Category, anyhow::Error> {
+        Ok(Category {
+            name: category.name.to_string(),
+            action: category.action.parse()?,
+            enabled: category.enabled,
+        })
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "{name} ({action}{disabled})",
+            name = self.name,
+            action = self.action,
+            disabled = if self.enabled { "" } else { ", off" },
+        )
+    }
+}
+
+/// What to do with a user who used a bad word, depending on how many times
+/// they have done so recently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Penalty {
+    /// Warn the user.
+    Warn,
+    /// Time the user out for the given duration.
+    Timeout(utils::Duration),
+}
+
+impl Penalty {
+    /// Pick the penalty for the given number of recent offenses.
+    ///
+    /// The first offense is a warning, and every offense after that picks the
+    /// next timeout in `escalation`, staying at the last one.
+    pub fn for_offenses(offenses: u32, escalation: &[utils::Duration]) -> Penalty {
+        let index = match offenses.checked_sub(2) {
+            Some(index) => index as usize,
+            None => return Penalty::Warn,
+        };
+
+        match escalation.get(index).or_else(|| escalation.last()) {
+            Some(duration) => Penalty::Timeout(*duration),
+            None => Penalty::Warn,
+        }
+    }
+}
+
+/// Recent offenses of a single user.
+#[derive(Debug, Clone, Copy)]
+struct Offense {
+    count: u32,
+    at: time::Instant,
+}
+
+impl Offense {
+    /// How many offenses remain at the given time, where one offense is
+    /// forgiven for each `decay` that has passed since the last one.
+    fn remaining(&self, decay: time::Duration, now: time::Instant) -> u32 {
+        if decay.as_secs_f64() <= 0.0 {
+            return 0;
+        }
+
+        let elapsed = now.saturating_duration_since(self.at);
+        let forgiven = (elapsed.as_secs_f64() / decay.as_secs_f64()) as u32;
+        self.count.saturating_sub(forgiven)
+    }
+}
+
+/// Tracks how many times users have used bad words recently.
+#[derive(Debug, Default)]
+struct Offenses {
+    users: HashMap<String, Offense>,
+}
+
+impl Offenses {
+    /// Record an offense by the given user and return how many recent
+    /// offenses they have, including this one.
+    ///
+    /// One offense is forgiven for each `decay` that has passed since the
+    /// last one. Users whose offenses have all been forgiven are forgotten.
+    fn offend(&mut self, user: &str, decay: time::Duration, now: time::Instant) -> u32 {
+        self.users.retain(|_, o| o.remaining(decay, now) > 0);
+
+        let offense = self
+            .users
+            .entry(user.to_string())
+            .or_insert(Offense { count: 0, at: now });
+
+        offense.count = offense.remaining(decay, now) + 1;
+        offense.at = now;
+        offense.count
+    }
+}
+
 #[derive(Debug, Default)]
 struct Inner {
     hashed: HashMap<eudex::Hash, Arc<Word>>,
     exact: HashMap<String, Arc<Word>>,
+    categories: HashMap<String, Category>,
+    /// Tokenized words which are allowed, by channel.
+    allowed: HashMap<String, HashSet<String>>,
 }
 
 impl Inner {
     /// Insert a bad word.
-    fn insert(&mut self, word: &str, why: Option<&str>) -> Result<(), anyhow::Error> {
+    fn insert(
+        &mut self,
+        word: &str,
+        why: Option<&str>,
+        category: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
         let word = Word {
             word: tokenize(word),
             why: why.map(template::Template::compile).transpose()?,
+            category: category.map(|c| c.to_lowercase()),
         };
 
         let word = Arc::new(word);
@@ -55,37 +214,134 @@ impl Database {
             .await
     }
 
-    /// Insert or update an existing word.
-    async fn edit(&self, word: &str, why: Option<&str>) -> Result<(), anyhow::Error> {
+    /// List all categories in backend.
+    async fn list_categories(&self) -> Result<Vec<db::models::BadWordCategory>, anyhow::Error> {
+        use db::schema::bad_word_categories::dsl;
+
+        self.0
+            .asyncify(move |c| Ok(dsl::bad_word_categories.load::<db::models::BadWordCategory>(c)?))
+            .await
+    }
+
+    /// Insert or update an existing category.
+    async fn edit_category(&self, category: &Category) -> Result<(), anyhow::Error> {
+        use db::schema::bad_word_categories::dsl;
+
+        let category = db::models::BadWordCategory {
+            name: category.name.to_string(),
+            action: category.action.to_string(),
+            enabled: category.enabled,
+        };
+
+        self.0
+            .asyncify(move |c| {
+                diesel::replace_into(dsl::bad_word_categories)
+                    .values(&category)
+                    .execute(c)?;
+                Ok(())
+            })
+            .await
+    }
+
+    /// List all words which are allowed in any channel.
+    async fn list_allowed(&self) -> Result<Vec<db::models::BadWordAllowed>, anyhow::Error> {
+        use db::schema::bad_word_allowed::dsl;
+
+        self.0
+            .asyncify(move |c| Ok(dsl::bad_word_allowed.load::<db::models::BadWordAllowed>(c)?))
+            .await
+    }
+
+    /// Allow a word in the given channel.
+    async fn allow(&self, channel: &str, word: &str) -> Result<(), anyhow::Error> {
+        use db::schema::bad_word_allowed::dsl;
+
+        let allowed = db::models::BadWordAllowed {
+            channel: channel.to_string(),
+            word: word.to_string(),
+        };
+
+        self.0
+            .asyncify(move |c| {
+                diesel::replace_into(dsl::bad_word_allowed)
+                    .values(&allowed)
+                    .execute(c)?;
+                Ok(())
+            })
+            .await
+    }
+
+    /// Stop allowing a word in the given channel.
+    async fn disallow(&self, channel: &str, word: &str) -> Result<bool, anyhow::Error> {
+        use db::schema::bad_word_allowed::dsl;
+
+        let channel = channel.to_string();
+        let word = word.to_string();
+
+        self.0
+            .asyncify(move |c| {
+                let count = diesel::delete(
+                    dsl::bad_word_allowed
+                        .filter(dsl::channel.eq(&channel).and(dsl::word.eq(&word))),
+                )
+                .execute(c)?;
+                Ok(count == 1)
+            })
+            .await
+    }
+
+    /// Insert or update an existing word, returning it as stored.
+    ///
+    /// Only the reason and category which are given are updated for an
+    /// existing word.
+    async fn edit(
+        &self,
+        word: &str,
+        why: Option<&str>,
+        category: Option<&str>,
+    ) -> Result<db::models::BadWord, anyhow::Error> {
         use db::schema::bad_words::dsl;
 
         let word = word.to_string();
         let why = why.map(|w| w.to_string());
+        let category = category.map(|c| c.to_lowercase());
 
         self.0
             .asyncify(move |c| {
-                let filter = dsl::bad_words.filter(dsl::word.eq(&word));
-                let b = filter.clone().first::<db::models::BadWord>(c).optional()?;
-
-                match b {
-                    None => {
-                        let bad_word = db::models::BadWord {
-                            word,
-                            why: why.map(|s| s.to_string()),
-                        };
-
-                        diesel::insert_into(dsl::bad_words)
-                            .values(&bad_word)
-                            .execute(c)?;
-                    }
-                    Some(_) => {
-                        diesel::update(filter)
-                            .set(why.map(|w| dsl::why.eq(w)))
-                            .execute(c)?;
+                c.transaction(|| {
+                    let filter = dsl::bad_words.filter(dsl::word.eq(&word));
+                    let b = filter.clone().first::<db::models::BadWord>(c).optional()?;
+
+                    match b {
+                        None => {
+                            let bad_word = db::models::BadWord {
+                                word: word.clone(),
+                                why: why.clone(),
+                                category: category.clone(),
+                            };
+
+                            diesel::insert_into(dsl::bad_words)
+                                .values(&bad_word)
+                                .execute(c)?;
+
+                            Ok(bad_word)
+                        }
+                        Some(bad_word) => {
+                            // NB: an empty changeset is an error.
+                            if why.is_none() && category.is_none() {
+                                return Ok(bad_word);
+                            }
+
+                            let set = db::models::UpdateBadWord {
+                                why: why.as_deref(),
+                                category: category.as_deref(),
+                            };
+
+                            diesel::update(filter.clone()).set(&set).execute(c)?;
+                            Ok(filter.first::<db::models::BadWord>(c)?)
+                        }
                     }
-                }
-
-                Ok(())
+                })
             })
             .await
     }
@@ -109,6 +365,7 @@ impl Database {
 #[derive(Clone)]
 pub struct Words {
     inner: Arc<RwLock<Inner>>,
+    offenses: Arc<parking_lot::Mutex<Offenses>>,
     db: Database,
 }
 
@@ -119,20 +376,74 @@ impl Words {
         let mut inner = Inner::default();
 
         for word in db.list().await? {
-            inner.insert(&word.word, word.why.as_deref())?;
+            inner.insert(&word.word, word.why.as_deref(), word.category.as_deref())?;
+        }
+
+        for category in db.list_categories().await? {
+            let category = Category::from_db(&category)?;
+            inner.categories.insert(category.name.clone(), category);
+        }
+
+        for allowed in db.list_allowed().await? {
+            inner
+                .allowed
+                .entry(allowed.channel)
+                .or_default()
+                .insert(allowed.word);
         }
 
         Ok(Words {
             inner: Arc::new(RwLock::new(inner)),
+            offenses: Default::default(),
             db,
         })
     }
 
-    /// Insert a word into the bad words list.
-    pub async fn edit(&self, word: &str, why: Option<&str>) -> Result<(), anyhow::Error> {
-        self.db.edit(word, why).await?;
+    /// Insert a word into the bad words list, optionally as part of a
+    /// category.
+    pub async fn edit(
+        &self,
+        word: &str,
+        why: Option<&str>,
+        category: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let word = self.db.edit(word, why, category).await?;
         let mut inner = self.inner.write().await;
-        inner.insert(word, why)?;
+        inner.insert(&word.word, word.why.as_deref(), word.category.as_deref())?;
+        Ok(())
+    }
+
+    /// List all categories which have been configured.
+    pub async fn categories(&self) -> Vec<Category> {
+        let inner = self.inner.read().await;
+        let mut out = inner.categories.values().cloned().collect::<Vec<_>>();
+        out.sort_by(|a, b| a.name.cmp(&b.name));
+        out
+    }
+
+    /// Get the given category.
+    ///
+    /// Categories which haven't been configured are enabled and delete
+    /// messages.
+    pub async fn category(&self, name: &str) -> Category {
+        let name = name.to_lowercase();
+        let inner = self.inner.read().await;
+
+        match inner.categories.get(&name) {
+            Some(category) => category.clone(),
+            None => Category {
+                name,
+                action: Action::Delete,
+                enabled: true,
+            },
+        }
+    }
+
+    /// Store the settings of the given category.
+    pub async fn edit_category(&self, category: Category) -> Result<(), anyhow::Error> {
+        self.db.edit_category(&category).await?;
+        let mut inner = self.inner.write().await;
+        inner.categories.insert(category.name.clone(), category);
         Ok(())
     }
 
@@ -147,6 +458,74 @@ impl Words {
         Ok(true)
     }
 
+    /// Allow a word in the given channel, even if it's a bad word.
+    pub async fn allow(&self, channel: &str, word: &str) -> Result<(), anyhow::Error> {
+        let word = tokenize(word);
+        self.db.allow(channel, &word).await?;
+        let mut inner = self.inner.write().await;
+        inner
+            .allowed
+            .entry(channel.to_string())
+            .or_default()
+            .insert(word);
+        Ok(())
+    }
+
+    /// Stop allowing a word in the given channel.
+    pub async fn disallow(&self, channel: &str, word: &str) -> Result<bool, anyhow::Error> {
+        let word = tokenize(word);
+
+        if !self.db.disallow(channel, &word).await? {
+            return Ok(false);
+        }
+
+        let mut inner = self.inner.write().await;
+
+        if let Some(allowed) = inner.allowed.get_mut(channel) {
+            allowed.remove(&word);
+        }
+
+        Ok(true)
+    }
+
+    /// List the words which are allowed in the given channel.
+    pub async fn allowed(&self, channel: &str) -> Vec<String> {
+        let inner = self.inner.read().await;
+
+        let mut out = inner
+            .allowed
+            .get(channel)
+            .map(|allowed| allowed.iter().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        out.sort();
+        out
+    }
+
+    /// Test if the given word is bad in the given channel, taking the words
+    /// allowed in the channel into account.
+    pub async fn is_bad_word(&self, channel: &str, word: &str) -> bool {
+        self.tester().await.test_in(channel, word).is_some()
+    }
+
+    /// Record that the given user used a bad word, returning the penalty
+    /// for it.
+    ///
+    /// One offense is forgiven for each `decay` that has passed since their
+    /// last one.
+    pub fn offend(
+        &self,
+        user: &str,
+        decay: utils::Duration,
+        escalation: &[utils::Duration],
+    ) -> Penalty {
+        let offenses = self
+            .offenses
+            .lock()
+            .offend(user, decay.as_std(), time::Instant::now());
+        Penalty::for_offenses(offenses, escalation)
+    }
+
     /// Build a tester.
     pub async fn tester(&self) -> Tester<'_> {
         let inner = self.inner.read().await;
@@ -161,24 +540,138 @@ pub struct Tester<'a> {
 }
 
 impl Tester<'_> {
-    /// Test the given word.
-    pub fn test(&self, word: &str) -> Option<Arc<Word>> {
+    /// Test the given word, ignoring words in categories which are disabled.
+    pub fn test(&self, word: &str) -> Option<Match> {
         let word = tokenize(word);
 
-        if let Some(w) = self.inner.hashed.get(&eudex::Hash::new(&word)) {
-            return Some(Arc::clone(w));
+        if let Some(m) = self.matched(self.inner.hashed.get(&eudex::Hash::new(&word))) {
+            return Some(m);
         }
 
-        if let Some(w) = self.inner.exact.get(&word) {
-            return Some(Arc::clone(w));
+        if let Some(m) = self.matched(self.inner.exact.get(&word)) {
+            return Some(m);
         }
 
         None
     }
+
+    /// Test the given word in the given channel, ignoring words which are
+    /// allowed in the channel.
+    pub fn test_in(&self, channel: &str, word: &str) -> Option<Match> {
+        let m = self.test(word)?;
+
+        if let Some(allowed) = self.inner.allowed.get(channel) {
+            // NB: the word might have matched a similar bad word, so both
+            // are checked.
+            if allowed.contains(&tokenize(word)) || allowed.contains(&m.word.word) {
+                return None;
+            }
+        }
+
+        Some(m)
+    }
+
+    /// Build a match for the given word, unless its category is disabled.
+    fn matched(&self, word: Option<&Arc<Word>>) -> Option<Match> {
+        let word = word?;
+
+        let category = word
+            .category
+            .as_ref()
+            .and_then(|c| self.inner.categories.get(c));
+
+        let action = match category {
+            Some(category) if !category.enabled => return None,
+            Some(category) => category.action,
+            None => Action::Delete,
+        };
+
+        Some(Match {
+            word: Arc::clone(word),
+            action,
+        })
+    }
+}
+
+/// A bad word that was matched.
+#[derive(Debug)]
+pub struct Match {
+    pub word: Arc<Word>,
+    /// The action of the category the word belongs to.
+    pub action: Action,
 }
 
 #[derive(Debug)]
 pub struct Word {
     pub word: String,
     pub why: Option<template::Template>,
+    /// The category the word belongs to, if any.
+    pub category: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Database, Offenses, Penalty};
+    use crate::db;
+    use crate::utils::Duration;
+    use anyhow::Result;
+    use std::time;
+
+    #[tokio::test]
+    async fn test_edit_keeps_missing_fields() -> Result<()> {
+        let db = Database(db::Database::memory()?);
+
+        db.edit("word", Some("not nice"), None).await?;
+        let word = db.edit("word", None, Some("Rude")).await?;
+        assert_eq!(word.why.as_deref(), Some("not nice"));
+        assert_eq!(word.category.as_deref(), Some("rude"));
+
+        let word = db.edit("word", Some("very rude"), None).await?;
+        assert_eq!(word.why.as_deref(), Some("very rude"));
+        assert_eq!(word.category.as_deref(), Some("rude"));
+
+        assert_eq!(db.list().await?, vec![word]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_penalty() {
+        let escalation = [Duration::seconds(60), Duration::seconds(600)];
+
+        assert_eq!(Penalty::for_offenses(1, &escalation), Penalty::Warn);
+        assert_eq!(
+            Penalty::for_offenses(2, &escalation),
+            Penalty::Timeout(Duration::seconds(60))
+        );
+        assert_eq!(
+            Penalty::for_offenses(3, &escalation),
+            Penalty::Timeout(Duration::seconds(600))
+        );
+        assert_eq!(
+            Penalty::for_offenses(10, &escalation),
+            Penalty::Timeout(Duration::seconds(600))
+        );
+        assert_eq!(Penalty::for_offenses(3, &[]), Penalty::Warn);
+    }
+
+    #[test]
+    fn test_offenses_decay() {
+        let mut offenses = Offenses::default();
+        let decay = time::Duration::from_secs(3600);
+        let now = time::Instant::now();
+
+        assert_eq!(offenses.offend("a", decay, now), 1);
+        assert_eq!(offenses.offend("a", decay, now), 2);
+        assert_eq!(offenses.offend("b", decay, now), 1);
+
+        // one offense is forgiven after an hour.
+        let later = now + time::Duration::from_secs(3700);
+        assert_eq!(offenses.offend("a", decay, later), 2);
+
+        // and all of them after a long time, at which point users who haven't
+        // offended again are forgotten.
+        let much_later = later + time::Duration::from_secs(3600 * 10);
+        assert_eq!(offenses.offend("a", decay, much_later), 1);
+        assert!(!offenses.users.contains_key("b"));
+    }
 }
@@ -0,0 +1,90 @@
+//! Shared state for the automatic chat filters, like bad words and links.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Pause state for the automatic chat filters.
+///
+/// Each filter is expected to check [Filters::is_paused] before acting on a
+/// message.
+#[derive(Clone, Default)]
+pub struct Filters {
+    /// The instant at which the filters are enabled again, if paused.
+    paused_until: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Filters {
+    /// Pause all filters for the given duration.
+    ///
+    /// Returns the instant at which the filters will be enabled again.
+    pub fn pause(&self, duration: Duration) -> Instant {
+        let deadline = Instant::now() + duration;
+        *self.paused_until.lock() = Some(deadline);
+        deadline
+    }
+
+    /// Resume all filters, returning `true` if they were paused.
+    pub fn resume(&self) -> bool {
+        self.paused_until.lock().take().is_some()
+    }
+
+    /// Resume all filters, but only if they are still paused until the given
+    /// deadline.
+    ///
+    /// This makes sure that a pause which has been replaced or cancelled in
+    /// the meantime is left alone.
+    pub fn resume_at(&self, deadline: Instant) -> bool {
+        let mut paused_until = self.paused_until.lock();
+
+        if *paused_until != Some(deadline) {
+            return false;
+        }
+
+        *paused_until = None;
+        true
+    }
+
+    /// Get how much time remains until filters are enabled again.
+    pub fn remaining(&self) -> Option<Duration> {
+        let paused_until = (*self.paused_until.lock())?;
+        let now = Instant::now();
+
+        if paused_until <= now {
+            return None;
+        }
+
+        Some(paused_until - now)
+    }
+
+    /// Test if filters are currently paused.
+    pub fn is_paused(&self) -> bool {
+        self.remaining().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filters;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pause_resume() {
+        let filters = Filters::default();
+        assert!(!filters.is_paused());
+
+        let first = filters.pause(Duration::from_secs(60));
+        assert!(filters.is_paused());
+
+        let second = filters.pause(Duration::from_secs(120));
+        assert!(!filters.resume_at(first));
+        assert!(filters.is_paused());
+        assert!(filters.resume_at(second));
+        assert!(!filters.is_paused());
+
+        filters.pause(Duration::from_secs(0));
+        assert!(!filters.is_paused());
+        assert!(filters.resume());
+        assert!(!filters.resume());
+    }
+}
@@ -0,0 +1,117 @@
+//! Track users who have recently chatted, so that they can be rewarded for
+//! being active.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// When a chatter was last seen.
+#[derive(Debug, Clone, Copy)]
+struct Seen {
+    at: Instant,
+    subscriber: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    seen: HashMap<String, Seen>,
+    /// The longest window asked about, anyone who chatted before it can be
+    /// forgotten.
+    longest: Duration,
+}
+
+/// Users who have recently chatted.
+#[derive(Clone, Default)]
+pub(crate) struct ActiveChatters {
+    inner: Arc<parking_lot::Mutex<Inner>>,
+}
+
+impl ActiveChatters {
+    /// Indicate that the given user sent a message at `now`.
+    pub(crate) fn seen(&self, user: &str, subscriber: bool, now: Instant) {
+        let seen = Seen {
+            at: now,
+            subscriber,
+        };
+
+        self.inner.lock().seen.insert(user.to_lowercase(), seen);
+    }
+
+    /// Get all users who have chatted within `window` of `now`, and whether
+    /// they are subscribers.
+    ///
+    /// Users who haven't chatted within the longest window asked about so
+    /// far are forgotten.
+    pub(crate) fn active(&self, window: Duration, now: Instant) -> Vec<(String, bool)> {
+        let mut inner = self.inner.lock();
+        inner.longest = Duration::max(inner.longest, window);
+
+        let longest = inner.longest;
+        inner
+            .seen
+            .retain(|_, seen| now.saturating_duration_since(seen.at) <= longest);
+
+        let mut active = inner
+            .seen
+            .iter()
+            .filter(|(_, seen)| now.saturating_duration_since(seen.at) <= window)
+            .map(|(user, seen)| (user.clone(), seen.subscriber))
+            .collect::<Vec<_>>();
+
+        active.sort();
+        active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ActiveChatters;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_active() {
+        let chatters = ActiveChatters::default();
+        let start = Instant::now();
+
+        chatters.seen("Foo", false, start);
+        chatters.seen("bar", true, start + Duration::from_secs(60));
+        chatters.seen("baz", false, start + Duration::from_secs(90));
+
+        let now = start + Duration::from_secs(120);
+
+        assert_eq!(
+            chatters.active(Duration::from_secs(300), now),
+            vec![
+                (String::from("bar"), true),
+                (String::from("baz"), false),
+                (String::from("foo"), false),
+            ]
+        );
+
+        assert_eq!(
+            chatters.active(Duration::from_secs(60), now),
+            vec![(String::from("bar"), true), (String::from("baz"), false)]
+        );
+
+        // NB: a shorter window doesn't forget users for longer ones.
+        assert_eq!(
+            chatters.active(Duration::from_secs(300), now),
+            vec![
+                (String::from("bar"), true),
+                (String::from("baz"), false),
+                (String::from("foo"), false),
+            ]
+        );
+
+        // NB: foo is outside the longest window, so it's forgotten.
+        let later = start + Duration::from_secs(330);
+        assert_eq!(
+            chatters.active(Duration::from_secs(300), later),
+            vec![(String::from("bar"), true), (String::from("baz"), false)]
+        );
+        assert_eq!(
+            chatters.active(Duration::from_secs(600), later),
+            vec![(String::from("bar"), true), (String::from("baz"), false)]
+        );
+    }
+}
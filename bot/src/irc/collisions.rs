@@ -0,0 +1,133 @@
+//! Detect names which are claimed by more than one kind of command.
+//!
+//! Commands are resolved in a fixed order: aliases are expanded first, then
+//! custom commands are matched, and only if no custom command matched are
+//! built-in commands run. So an alias named `!song` hides both a custom
+//! command and the built-in command by the same name.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The kind of a command, in the order they take precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Kind {
+    Alias,
+    Command,
+    BuiltIn,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Alias => "alias".fmt(fmt),
+            Kind::Command => "custom command".fmt(fmt),
+            Kind::BuiltIn => "built-in command".fmt(fmt),
+        }
+    }
+}
+
+/// A name which is claimed by more than one kind of command.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Collision {
+    pub(crate) name: String,
+    /// The kind of command which is used.
+    pub(crate) used: Kind,
+    /// The kinds of command which are hidden by the one used.
+    pub(crate) hidden: Vec<Kind>,
+}
+
+impl fmt::Display for Collision {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kinds = std::iter::once(&self.used)
+            .chain(&self.hidden)
+            .map(|k| k.to_string())
+            .collect::<Vec<_>>();
+
+        write!(
+            fmt,
+            "`{}` is defined as {}, only the {} will be used",
+            self.name,
+            kinds.join(", "),
+            self.used,
+        )
+    }
+}
+
+/// Find all names which collide, given names of `(kind, name)`.
+///
+/// Names are compared case-insensitively.
+pub(crate) fn find(names: impl IntoIterator<Item = (Kind, String)>) -> Vec<Collision> {
+    let mut by_name = BTreeMap::<_, Vec<Kind>>::new();
+
+    for (kind, name) in names {
+        let kinds = by_name.entry(name.to_lowercase()).or_default();
+
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+
+    let mut out = Vec::new();
+
+    for (name, mut kinds) in by_name {
+        if kinds.len() < 2 {
+            continue;
+        }
+
+        kinds.sort();
+        let used = kinds.remove(0);
+
+        out.push(Collision {
+            name,
+            used,
+            hidden: kinds,
+        });
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, Collision, Kind};
+
+    #[test]
+    fn test_find() {
+        let names = vec![
+            (Kind::BuiltIn, String::from("!song")),
+            (Kind::Command, String::from("!song")),
+            (Kind::Alias, String::from("!Song")),
+            (Kind::BuiltIn, String::from("!water")),
+            (Kind::Command, String::from("!water")),
+            (Kind::Command, String::from("!discord")),
+            (Kind::Alias, String::from("!sr")),
+        ];
+
+        let collisions = find(names);
+
+        assert_eq!(
+            collisions,
+            vec![
+                Collision {
+                    name: String::from("!song"),
+                    used: Kind::Alias,
+                    hidden: vec![Kind::Command, Kind::BuiltIn],
+                },
+                Collision {
+                    name: String::from("!water"),
+                    used: Kind::Command,
+                    hidden: vec![Kind::BuiltIn],
+                },
+            ]
+        );
+
+        assert_eq!(
+            collisions[0].to_string(),
+            "`!song` is defined as alias, custom command, built-in command, only the alias will be used"
+        );
+        assert_eq!(
+            collisions[1].to_string(),
+            "`!water` is defined as custom command, built-in command, only the custom command will be used"
+        );
+    }
+}
@@ -3,13 +3,19 @@ use crate::command;
 use crate::currency::{BalanceTransferError, Currency};
 use crate::db;
 use crate::prelude::*;
-use crate::utils;
+use crate::utils::{self, Cooldown, Duration};
 use anyhow::Error;
 use std::sync::Arc;
 
 /// Handler for the !admin command.
 pub struct Handler {
     pub currency: injector::Ref<Currency>,
+    /// How many users to show in `!<currency> top`.
+    pub top_limit: settings::Var<u32>,
+    /// Cooldown between each `!<currency> top`.
+    pub top_cooldown: settings::Var<Cooldown>,
+    /// The smallest amount which can be given with `!<currency> give`.
+    pub min_transfer: settings::Var<i64>,
 }
 
 impl Handler {
@@ -50,15 +56,14 @@ impl command::Handler for Handler {
 
                         respond!(
                             user,
-                            "You have {balance} {name} [{watch_time}].",
-                            balance = balance.balance,
-                            name = currency.name,
+                            "You have {balance} [{watch_time}].",
+                            balance = currency.format_balance(balance.balance),
                             watch_time = watch_time,
                         );
                     }
                     Err(e) => {
                         respond!(user, "Could not get balance, sorry :(");
-                        log_error!(e, "failed to get balance");
+                        log_error!(e, "[{}] failed to get balance", ctx.id());
                     }
                 }
             }
@@ -73,19 +78,59 @@ impl command::Handler for Handler {
 
                         respond!(
                             ctx,
-                            "{user} has {balance} {name} [{watch_time}].",
+                            "{user} has {balance} [{watch_time}].",
                             user = to_show,
-                            balance = balance.balance,
-                            name = currency.name,
+                            balance = currency.format_balance(balance.balance),
                             watch_time = watch_time,
                         );
                     }
                     Err(e) => {
                         respond!(ctx, "Count not get balance, sorry :(");
-                        log_error!(e, "failed to get balance");
+                        log_error!(e, "[{}] failed to get balance", ctx.id());
                     }
                 }
             }
+            Some("top") => {
+                if !self.top_cooldown.write().await.is_open() {
+                    respond!(
+                        ctx,
+                        "The top list was recently shown, please wait a bit longer!"
+                    );
+                    return Ok(());
+                }
+
+                let limit = self.top_limit.load().await as usize;
+                let top = currency.top_balances(ctx.channel(), limit).await?;
+
+                if top.is_empty() {
+                    respond!(
+                        ctx,
+                        "No one has any {currency} yet.",
+                        currency = currency.name
+                    );
+                    return Ok(());
+                }
+
+                let top = top
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| {
+                        format!(
+                            "#{} {} ({})",
+                            i + 1,
+                            b.user,
+                            currency.format_balance(b.amount)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                respond!(
+                    ctx,
+                    "Top {currency}: {top}.",
+                    currency = currency.name,
+                    top = top.join(", ")
+                );
+            }
             Some("give") => {
                 let taker = db::user_id(&ctx.next_str("<user> <amount>")?);
                 let amount: i64 = ctx.next_parse("<user> <amount>")?;
@@ -112,6 +157,17 @@ impl command::Handler for Handler {
                     return Ok(());
                 }
 
+                let min_transfer = self.min_transfer.load().await;
+
+                if amount < min_transfer {
+                    respond!(
+                        ctx,
+                        "You have to give at least {amount}",
+                        amount = currency.format(min_transfer),
+                    );
+                    return Ok(());
+                }
+
                 let result = currency
                     .balance_transfer(
                         user.channel(),
@@ -124,12 +180,17 @@ impl command::Handler for Handler {
 
                 match result {
                     Ok(()) => {
+                        let balance = currency
+                            .balance_of(user.channel(), user.name())
+                            .await?
+                            .unwrap_or_default();
+
                         respond!(
                             user,
-                            "Gave {user} {amount} {currency}!",
+                            "Gave {user} {amount}! You now have {balance}.",
                             user = taker,
-                            amount = amount,
-                            currency = currency.name
+                            amount = currency.format(amount),
+                            balance = currency.format_balance(balance.balance),
                         );
                     }
                     Err(BalanceTransferError::NoBalance) => {
@@ -137,7 +198,7 @@ impl command::Handler for Handler {
                             user,
                             "Not enough {currency} to transfer {amount}",
                             currency = currency.name,
-                            amount = amount,
+                            amount = currency.format(amount),
                         );
                     }
                     Err(BalanceTransferError::Other(e)) => {
@@ -146,7 +207,7 @@ impl command::Handler for Handler {
                             "Failed to give {currency}, sorry :(",
                             currency = currency.name
                         );
-                        log_error!(e, "failed to modify currency");
+                        log_error!(e, "[{}] failed to modify currency", ctx.id());
                     }
                 }
             }
@@ -171,18 +232,16 @@ impl command::Handler for Handler {
                 if amount >= 0 {
                     respond!(
                         ctx,
-                        "Gave {user} {amount} {currency}!",
+                        "Gave {user} {amount}!",
                         user = boosted_user,
-                        amount = amount,
-                        currency = currency.name
+                        amount = currency.format(amount),
                     );
                 } else {
                     respond!(
                         ctx,
-                        "Took away {amount} {currency} from {user}!",
+                        "Took away {amount} from {user}!",
                         user = boosted_user,
-                        amount = -amount,
-                        currency = currency.name
+                        amount = currency.format(-amount),
                     );
                 }
             }
@@ -197,16 +256,14 @@ impl command::Handler for Handler {
 
                 if amount >= 0 {
                     ctx.privmsg(format!(
-                        "/me gave {amount} {currency} to EVERYONE!",
-                        amount = amount,
-                        currency = currency.name
+                        "/me gave {amount} to EVERYONE!",
+                        amount = currency.format(amount),
                     ))
                     .await;
                 } else {
                     ctx.privmsg(format!(
-                        "/me took away {amount} {currency} from EVERYONE!",
-                        amount = amount,
-                        currency = currency.name
+                        "/me took away {amount} from EVERYONE!",
+                        amount = currency.format(amount),
                     ))
                     .await;
                 }
@@ -215,6 +272,7 @@ impl command::Handler for Handler {
                 let mut alts = Vec::new();
 
                 alts.push("give");
+                alts.push("top");
 
                 if ctx.user.has_scope(Scope::CurrencyBoost).await {
                     alts.push("boost");
@@ -242,8 +300,25 @@ impl command::Handler for Handler {
     }
 }
 
-pub async fn setup(injector: &Injector) -> Result<Arc<Handler>, Error> {
+pub async fn setup(injector: &Injector, settings: &crate::Settings) -> Result<Arc<Handler>, Error> {
     let currency = injector.var::<Currency>().await;
-    let handler = Handler { currency };
+    let top_limit = settings.var("currency/top/limit", 5).await?;
+
+    let top_cooldown = settings
+        .var(
+            "currency/top/cooldown",
+            Cooldown::from_duration(Duration::seconds(30)),
+        )
+        .await?;
+
+    let min_transfer = settings.var("currency/min-transfer", 1).await?;
+
+    let handler = Handler {
+        currency,
+        top_limit,
+        top_cooldown,
+        min_transfer,
+    };
+
     Ok(Arc::new(handler))
 }
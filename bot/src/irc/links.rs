@@ -0,0 +1,149 @@
+//! Detect links in chat messages.
+//!
+//! Besides URLs with a scheme this finds bare links like `example.com/foo`,
+//! and optionally obfuscated ones like `example dot com`.
+
+/// Top-level domains which links without a scheme are recognized for.
+///
+/// Anything with a dot in it could be a link, so without a scheme we only
+/// look for the most common ones to avoid catching regular sentences.
+const TLDS: &[&str] = &[
+    "app", "be", "biz", "ca", "cc", "co", "com", "de", "dev", "eu", "fr", "gg", "info", "io", "ly",
+    "me", "net", "nl", "org", "ru", "se", "shop", "site", "store", "to", "tv", "uk", "us", "xyz",
+];
+
+/// Ways of writing a dot to get around link filters.
+const OBFUSCATED_DOTS: &[&str] = &["(dot)", "[dot]", "{dot}", " dot ", " . "];
+
+/// A link found in a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Link {
+    /// The host linked to, in lowercase.
+    pub(crate) host: String,
+    /// The path linked to, always starting with `/`.
+    pub(crate) path: String,
+}
+
+impl Link {
+    /// Test if the link is to the given domain, or one of its subdomains.
+    pub(crate) fn is_domain(&self, domain: &str) -> bool {
+        let domain = domain.trim_start_matches("www.");
+
+        match self.host.strip_suffix(domain) {
+            Some(rest) => rest.is_empty() || rest.ends_with('.'),
+            None => false,
+        }
+    }
+}
+
+/// Find all links in the given message.
+pub(crate) fn find(message: &str, obfuscated: bool) -> Vec<Link> {
+    let mut message = message.to_lowercase();
+
+    if obfuscated {
+        for dot in OBFUSCATED_DOTS {
+            message = message.replace(dot, ".");
+        }
+    }
+
+    message
+        .split_whitespace()
+        .filter_map(|word| parse(word.trim_matches(is_surrounding)))
+        .collect()
+}
+
+/// Parse a single word as a link.
+fn parse(word: &str) -> Option<Link> {
+    if word.starts_with("http://") || word.starts_with("https://") {
+        let url = str::parse::<url::Url>(word).ok()?;
+
+        return Some(Link {
+            host: url.host_str()?.to_string(),
+            path: url.path().to_string(),
+        });
+    }
+
+    let (host, path) = match word.find('/') {
+        Some(index) => word.split_at(index),
+        None => (word, "/"),
+    };
+
+    let mut labels = host.split('.');
+    let tld = labels.next_back()?;
+
+    if !TLDS.contains(&tld) {
+        return None;
+    }
+
+    let mut any = false;
+
+    for label in labels {
+        if label.is_empty() || !label.chars().all(|c| c.is_alphanumeric() || c == '-') {
+            return None;
+        }
+
+        any = true;
+    }
+
+    if !any {
+        return None;
+    }
+
+    Some(Link {
+        host: host.to_string(),
+        path: path.to_string(),
+    })
+}
+
+/// Characters which commonly surround a link in a sentence.
+fn is_surrounding(c: char) -> bool {
+    matches!(
+        c,
+        '(' | ')' | '[' | ']' | '<' | '>' | ',' | '.' | '!' | '?' | '"' | '\'' | ':' | ';'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, Link};
+
+    fn link(host: &str, path: &str) -> Link {
+        Link {
+            host: host.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(
+            find(
+                "check https://Example.com/foo and (example.org/url).",
+                false
+            ),
+            vec![link("example.com", "/foo"), link("example.org", "/url")]
+        );
+
+        assert_eq!(find("go to example dot com now", false), vec![]);
+        assert_eq!(
+            find("go to example dot com now", true),
+            vec![link("example.com", "/")]
+        );
+        assert_eq!(
+            find("or example(dot)net/hello", true),
+            vec![link("example.net", "/hello")]
+        );
+
+        // NB: regular sentences aren't links.
+        assert_eq!(find("well...that was fun. e.g. this", true), vec![]);
+    }
+
+    #[test]
+    fn test_is_domain() {
+        let l = link("www.example.com", "/");
+        assert!(l.is_domain("example.com"));
+        assert!(l.is_domain("www.example.com"));
+        assert!(!l.is_domain("ample.com"));
+        assert!(!link("example.com", "/").is_domain("foo.example.com"));
+    }
+}
@@ -0,0 +1,225 @@
+//! An in-memory stand-in for an IRC connection, used to test modules.
+//!
+//! Commands are run against a [command::Context] built by [Mock], and
+//! everything they send to chat is captured instead of being sent over the
+//! network.
+
+use super::{Principal, Sender, Tags, User, UserInner};
+use crate::api::{self, twitch};
+use crate::auth::{self, Auth};
+use crate::command;
+use crate::currency::Currency;
+use crate::db;
+use crate::injector::Injector;
+use crate::module;
+use crate::oauth2;
+use crate::settings;
+use crate::stream_info;
+use crate::utils;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use irc::proto::command::Command;
+use irc::proto::message::Message;
+use leaky_bucket::LeakyBuckets;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::{self, mpsc, oneshot};
+
+/// The channel commands are run against.
+pub(crate) const CHANNEL: &str = "#streamer";
+/// The name of the streamer.
+pub(crate) const STREAMER: &str = "streamer";
+
+/// A mock connection to chat, backed by an in-memory database.
+pub(crate) struct Mock {
+    /// Injector that handlers can be constructed from.
+    pub(crate) injector: Injector,
+    /// Information about the stream.
+    pub(crate) stream_info: stream_info::StreamInfo,
+    moderators: Arc<RwLock<HashSet<String>>>,
+    vips: Arc<RwLock<HashSet<String>>>,
+    auth: Auth,
+    sender: Sender,
+    sent: Arc<Mutex<Vec<Message>>>,
+    context_inner: Arc<command::ContextInner>,
+    _buckets: LeakyBuckets,
+    _restart: oneshot::Receiver<()>,
+}
+
+impl Mock {
+    /// Construct a new mock connection with empty state.
+    pub(crate) async fn new() -> Result<Self> {
+        let db = db::Database::memory()?;
+        let injector = Injector::new();
+        injector.update(db.clone()).await;
+
+        let auth = db.auth(auth::Schema::load_static()?).await?;
+        injector.update(auth.clone()).await;
+
+        let mut buckets = LeakyBuckets::new();
+        let coordinate = buckets.coordinate()?;
+
+        tokio::spawn(async move {
+            if let Err(e) = coordinate.await {
+                log_error!(e, "leaky bucket coordinator failed");
+            }
+        });
+
+        let (sender, sent) = Sender::capture(
+            settings::Var::new(Default::default()),
+            String::from(CHANNEL),
+            injector.var().await,
+            &buckets,
+        )?;
+
+        let streamer = Arc::new(twitch::v5::User {
+            id: String::from("1"),
+            name: String::from(STREAMER),
+            display_name: String::from("Streamer"),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            bio: None,
+            email: String::from("streamer@example.com"),
+            email_verified: true,
+            logo: None,
+            notifications: HashMap::new(),
+            partnered: false,
+            twitter_connected: false,
+            ty: String::from("user"),
+        });
+
+        let stream_info = stream_info::StreamInfo {
+            user: streamer,
+            data: Default::default(),
+        };
+
+        let (restart, restart_rx) = utils::Restart::new();
+
+        let context_inner = Arc::new(command::ContextInner {
+            sender: sender.clone(),
+            scope_cooldowns: sync::Mutex::new(auth.scope_cooldowns()),
+            cooldowns: Default::default(),
+            command_prefix: settings::Var::new(String::from(command::DEFAULT_COMMAND_PREFIX)),
+            message_hooks: sync::RwLock::new(Default::default()),
+            handlers: module::Handlers::default(),
+            restart,
+        });
+
+        Ok(Self {
+            injector,
+            stream_info,
+            moderators: Default::default(),
+            vips: Default::default(),
+            auth,
+            sender,
+            sent,
+            context_inner,
+            _buckets: buckets,
+            _restart: restart_rx,
+        })
+    }
+
+    /// Mark the stream as live since the given point in time.
+    pub(crate) fn go_live(&self, started_at: DateTime<Utc>) {
+        self.stream_info.data.write().stream = Some(twitch::new::Stream {
+            id: String::from("1"),
+            user_id: self.stream_info.user.id.clone(),
+            user_name: self.stream_info.user.name.clone(),
+            game_id: None,
+            community_ids: Vec::new(),
+            ty: String::from("live"),
+            title: String::from("Test Stream"),
+            viewer_count: 0,
+            started_at,
+            language: String::from("en"),
+            thumbnail_url: String::new(),
+        });
+    }
+
+    /// Set up an in-memory currency named `points` and make it available
+    /// through the injector.
+    pub(crate) async fn currency(&self) -> Result<Currency> {
+        let (force_refresh, _) = mpsc::unbounded_channel();
+        let twitch = api::Twitch::new(oauth2::SyncToken::new("test", force_refresh))?;
+        let currency = Currency::builtin("points", twitch, Arc::new(db::Memory::new()));
+        self.injector.update(currency.clone()).await;
+        Ok(currency)
+    }
+
+    /// Make the given user a moderator.
+    pub(crate) fn moderator(&self, name: &str) {
+        self.moderators.write().insert(name.to_string());
+    }
+
+    /// Make the given user a VIP.
+    pub(crate) fn vip(&self, name: &str) {
+        self.vips.write().insert(name.to_string());
+    }
+
+    /// Build a context for the given user running a command, where `line`
+    /// are the arguments following the command name.
+    pub(crate) fn context(&self, name: &str, line: &str) -> command::Context {
+        self.build_context(
+            Principal::User {
+                name: name.to_string(),
+            },
+            line,
+        )
+    }
+
+    /// Build a context for an injected command, which has no real user.
+    pub(crate) fn injected(&self, line: &str) -> command::Context {
+        self.build_context(Principal::Injected, line)
+    }
+
+    /// Run the given handler as the specified user.
+    pub(crate) async fn run(
+        &self,
+        handler: &dyn command::Handler,
+        name: &str,
+        line: &str,
+    ) -> Result<()> {
+        let mut ctx = self.context(name, line);
+
+        if let Some(scope) = handler.scope() {
+            ctx.check_scope(scope).await?;
+        }
+
+        handler.handle(&mut ctx).await
+    }
+
+    /// Take all messages that have been sent to chat so far.
+    pub(crate) fn take_sent(&self) -> Vec<String> {
+        std::mem::take(&mut *self.sent.lock())
+            .into_iter()
+            .filter_map(|m| match m.command {
+                Command::PRIVMSG(_, m) => Some(m),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn build_context(&self, principal: Principal, line: &str) -> command::Context {
+        let user = User {
+            inner: Arc::new(UserInner {
+                tags: Tags::default(),
+                sender: self.sender.clone(),
+                principal,
+                streamer: self.stream_info.user.clone(),
+                moderators: self.moderators.clone(),
+                vips: self.vips.clone(),
+                stream_info: self.stream_info.clone(),
+                auth: self.auth.clone(),
+            }),
+        };
+
+        command::Context {
+            id: command::ContextId::next(),
+            api_url: Arc::new(None),
+            user,
+            it: utils::Words::new(Arc::new(line.to_string())),
+            inner: self.context_inner.clone(),
+        }
+    }
+}
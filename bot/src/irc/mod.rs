@@ -1,9 +1,10 @@
 use crate::api::{self, twitch};
-use crate::auth::{Auth, Role, Scope};
+use crate::auth::{Auth, Permission, Role, Scope};
 use crate::bus;
 use crate::command;
-use crate::currency::CurrencyBuilder;
+use crate::currency::{self, CurrencyBuilder};
 use crate::db;
+use crate::filters;
 use crate::idle;
 use crate::message_log::MessageLog;
 use crate::module;
@@ -11,6 +12,7 @@ use crate::oauth2;
 use crate::prelude::*;
 use crate::script;
 use crate::stream_info;
+use crate::sys;
 use crate::tags;
 use crate::task;
 use crate::utils::{self, Cooldown, Duration};
@@ -35,12 +37,23 @@ use tracing_futures::Instrument as _;
 pub use self::sender::Sender;
 
 mod chat_log;
+pub(crate) mod chatters;
+mod collisions;
 mod currency_admin;
+mod links;
+#[cfg(test)]
+pub(crate) mod mock;
 mod sender;
+mod spam;
+mod suggestions;
+mod watch_time;
 
 const SERVER: &str = "irc.chat.twitch.tv";
 const TWITCH_TAGS_CAP: &str = "twitch.tv/tags";
 const TWITCH_COMMANDS_CAP: &str = "twitch.tv/commands";
+/// How long a connection has to stay up before earlier failures are forgotten
+/// when backing off.
+const STABLE_CONNECTION: time::Duration = time::Duration::from_secs(60);
 
 /// Helper struct to construct IRC integration.
 pub struct Irc {
@@ -48,6 +61,8 @@ pub struct Irc {
     pub modules: Vec<Box<dyn module::Module>>,
     pub script_dirs: Vec<PathBuf>,
     pub stream_state_tx: mpsc::Sender<stream_info::StreamState>,
+    /// Leaves chat when triggered.
+    pub shutdown: utils::Shutdown,
 }
 
 impl Irc {
@@ -57,32 +72,99 @@ impl Irc {
         let mut provider = Setup::provider(&self.injector).await?;
 
         let mut error_backoff = backoff::ExponentialBackoff::default();
-        error_backoff.current_interval = time::Duration::from_secs(5);
-        error_backoff.initial_interval = time::Duration::from_secs(5);
         error_backoff.max_elapsed_time = None;
+        // Number of reconnects since the last stable connection.
+        let mut attempts = 0u32;
+
+        // NB: kept across reconnects to debounce the join message.
+        let mut last_join_message = None;
 
         loop {
             while let Some(setup) = provider.build() {
+                let initial_delay = setup
+                    .settings
+                    .get::<Duration>("irc/reconnect/initial-delay")
+                    .await?
+                    .unwrap_or_else(|| Duration::seconds(5));
+
+                let max_delay = setup
+                    .settings
+                    .get::<Duration>("irc/reconnect/max-delay")
+                    .await?
+                    .unwrap_or_else(|| Duration::seconds(5 * 60));
+
+                error_backoff.initial_interval = initial_delay.as_std();
+                error_backoff.max_interval = max_delay.as_std();
+
+                if attempts == 0 {
+                    error_backoff.reset();
+                }
+
+                let started = time::Instant::now();
+
                 let irc_loop = IrcLoop {
                     setup,
                     provider: &mut provider,
                     irc: &self,
+                    last_join_message: &mut last_join_message,
                 };
 
-                match irc_loop.run().await {
+                let result = irc_loop.run().await;
+
+                if self.shutdown.is_triggered() {
+                    return Ok(());
+                }
+
+                match result {
                     Ok(()) => {
-                        error_backoff.reset();
+                        attempts = 0;
                     }
                     Err(e) => {
-                        let backoff = error_backoff.next_backoff().unwrap_or_default();
-                        log_error!(e, "chat component crashed, restarting in {:?}", backoff);
-                        tokio::time::sleep(backoff).await;
-                        continue;
+                        if started.elapsed() >= STABLE_CONNECTION {
+                            attempts = 0;
+                            error_backoff.reset();
+                        }
+
+                        attempts += 1;
+
+                        let backoff = error_backoff
+                            .next_backoff()
+                            .unwrap_or_default()
+                            .min(error_backoff.max_interval);
+
+                        log_error!(
+                            e,
+                            "chat disconnected, reconnecting in {:?} (attempt #{})",
+                            backoff,
+                            attempts
+                        );
+
+                        // NB: only notify once for a run of failed reconnects.
+                        if attempts == 1 {
+                            if let Some(notifier) = self.injector.get::<sys::Notifier>().await {
+                                notifier
+                                    .notify(
+                                        sys::Category::IrcDisconnected,
+                                        "Chat disconnected",
+                                        "Lost connection to chat, reconnecting...",
+                                        sys::NotificationIcon::Warning,
+                                    )
+                                    .await;
+                            }
+                        }
+
+                        tokio::select! {
+                            _ = tokio::time::sleep(backoff) => continue,
+                            _ = self.shutdown.wait() => return Ok(()),
+                        }
                     }
                 }
             }
 
-            provider.wait().await;
+            tokio::select! {
+                _ = provider.wait() => (),
+                _ = self.shutdown.wait() => return Ok(()),
+            }
         }
     }
 }
@@ -115,6 +197,8 @@ struct IrcLoop<'a> {
     setup: Setup,
     provider: &'a mut SetupProvider,
     irc: &'a Irc,
+    /// When the join message was last sent.
+    last_join_message: &'a mut Option<time::Instant>,
 }
 
 impl IrcLoop<'_> {
@@ -123,6 +207,7 @@ impl IrcLoop<'_> {
             setup,
             provider,
             irc,
+            last_join_message,
         } = self;
 
         let Setup {
@@ -179,20 +264,81 @@ impl IrcLoop<'_> {
 
         let chat_settings = settings.scoped("chat");
 
-        let url_whitelist_enabled = chat_settings.var("url-whitelist/enabled", true).await?;
+        let url_whitelist = UrlWhitelist {
+            enabled: chat_settings.var("url-whitelist/enabled", true).await?,
+            obfuscated: chat_settings.var("url-whitelist/obfuscated", false).await?,
+            action: chat_settings
+                .var("url-whitelist/action", FilterAction::Delete)
+                .await?,
+            timeout: chat_settings
+                .var("url-whitelist/timeout", utils::Duration::seconds(60))
+                .await?,
+        };
         let bad_words_enabled = chat_settings.var("bad-words/enabled", false).await?;
+        let bad_words_escalation = BadWordsEscalation {
+            enabled: chat_settings
+                .var("bad-words/escalation/enabled", false)
+                .await?,
+            timeouts: chat_settings
+                .var(
+                    "bad-words/escalation/timeouts",
+                    vec![utils::Duration::seconds(60), utils::Duration::seconds(600)],
+                )
+                .await?,
+            decay: chat_settings
+                .var("bad-words/escalation/decay", utils::Duration::seconds(3600))
+                .await?,
+        };
+        let spam_filter = SpamFilter {
+            enabled: chat_settings.var("spam-filter/enabled", false).await?,
+            min_length: chat_settings.var("spam-filter/min-length", 10).await?,
+            caps_ratio: chat_settings.var("spam-filter/caps%", 70).await?,
+            symbols_ratio: chat_settings.var("spam-filter/symbols%", 50).await?,
+            action: chat_settings
+                .var("spam-filter/action", FilterAction::Delete)
+                .await?,
+            timeout: chat_settings
+                .var("spam-filter/timeout", utils::Duration::seconds(60))
+                .await?,
+        };
+        let template_errors = chat_settings.var("template-errors", true).await?;
+        let cost_moderator_exempt = settings.var("command/cost-moderator-exempt", false).await?;
+        let cooldown_jitter = chat_settings
+            .var("jitter", utils::Duration::default())
+            .await?;
+        let command_prefix = chat_settings
+            .var(
+                "command-prefix",
+                String::from(command::DEFAULT_COMMAND_PREFIX),
+            )
+            .await?;
         let sender_ty = chat_settings.var("sender-type", sender::Type::Chat).await?;
         let threshold = chat_settings.var("idle-detection/threshold", 5).await?;
         let idle = idle::Idle::new(threshold);
+        let active_chatters = chatters::ActiveChatters::default();
+        injector.update(active_chatters.clone()).await;
+        let filters = filters::Filters::default();
 
         let nightbot = injector.var::<api::NightBot>().await;
 
         let mut buckets = LeakyBuckets::new();
 
+        let response_delay = sender::ResponseDelay {
+            min: chat_settings.var("response-delay/min", 0).await?,
+            max: chat_settings.var("response-delay/max", 0).await?,
+        };
+
+        let rate_limit = sender::RateLimit {
+            messages: chat_settings.var("rate-limit/messages", 20).await?,
+            capacity: chat_settings.var("rate-limit/queue-capacity", 50).await?,
+        };
+
         let sender = Sender::new(
             sender_ty,
             chat_channel.clone(),
             client.sender(),
+            response_delay,
+            rate_limit,
             nightbot,
             &buckets,
         )?;
@@ -212,7 +358,8 @@ impl IrcLoop<'_> {
 
         let stream_info = {
             let (stream_info, mut stream_state_rx, future) =
-                stream_info::setup(streamer.user.clone(), streamer.client.clone());
+                stream_info::setup(streamer.user.clone(), streamer.client.clone(), &settings)
+                    .await?;
 
             let stream_state_tx = stream_state_tx.clone();
 
@@ -276,6 +423,7 @@ impl IrcLoop<'_> {
                     futures: &mut futures,
                     stream_info: &stream_info,
                     idle: &idle,
+                    filters: &filters,
                     twitch: &bot.client,
                     streamer_twitch: &streamer.client,
                     sender: &sender,
@@ -287,15 +435,15 @@ impl IrcLoop<'_> {
             result.with_context(|| anyhow!("failed to initialize module: {}", module.ty()))?;
         }
 
-        let currency_handler = currency_admin::setup(injector).await?;
+        let currency_handler = currency_admin::setup(injector, &settings).await?;
 
         let future = currency_loop(
             streamer.clone(),
-            streamer_channel.clone(),
             sender.clone(),
             idle.clone(),
+            active_chatters.clone(),
             injector.clone(),
-            chat_settings.clone(),
+            stream_info.clone(),
             settings.clone(),
         )
         .await?;
@@ -318,6 +466,33 @@ impl IrcLoop<'_> {
 
         let join_message = chat_settings.get::<String>("join-message").await?;
 
+        let join_message_cooldown = chat_settings
+            .get::<Duration>("join-message-cooldown")
+            .await?
+            .unwrap_or_else(|| Duration::seconds(5 * 60));
+
+        // Only announce that we've joined at most once per cooldown, to avoid
+        // spamming chat when we're reconnecting a lot.
+        let join_message = match join_message {
+            Some(join_message) => {
+                let now = time::Instant::now();
+
+                let suppress = match *last_join_message {
+                    Some(last) => now.duration_since(last) < join_message_cooldown.as_std(),
+                    None => false,
+                };
+
+                if suppress {
+                    log::info!("Suppressing join message, since one was recently sent");
+                    None
+                } else {
+                    *last_join_message = Some(now);
+                    Some(join_message)
+                }
+            }
+            None => None,
+        };
+
         let leave_message = chat_settings
             .get::<String>("leave-message")
             .await?
@@ -349,26 +524,41 @@ impl IrcLoop<'_> {
             aliases,
             api_url: Arc::new(api_url),
             moderator_cooldown,
-            handlers,
             scripts,
             idle: &idle,
+            active_chatters,
+            filters,
             pong_timeout: &mut pong_timeout,
             token: &bot.client.token,
             handler_shutdown: false,
             stream_info: &stream_info,
             auth: &auth,
             currency_handler,
-            url_whitelist_enabled,
+            url_whitelist,
             bad_words_enabled,
+            bad_words_escalation,
+            spam_filter,
+            template_errors,
+            cost_moderator_exempt,
+            cooldown_jitter,
             chat_log: chat_log_builder.build()?,
             context_inner: Arc::new(command::ContextInner {
                 sender: sender.clone(),
                 scope_cooldowns: sync::Mutex::new(auth.scope_cooldowns()),
+                cooldowns: Default::default(),
+                command_prefix,
                 message_hooks: sync::RwLock::new(Default::default()),
+                handlers,
                 restart,
             }),
+            suggestions: suggestions::Suggestions::new(
+                &chat_settings.scoped("command-suggestions"),
+            )
+            .await?,
         };
 
+        handler.warn_collisions().await;
+
         let mut outgoing = client
             .outgoing()
             .ok_or_else(|| anyhow!("missing outgoing future for irc client"))?;
@@ -438,6 +628,12 @@ impl IrcLoop<'_> {
                     // If configuration state changes, force a reconnect.
                     leave.set(Fuse::new(tokio::time::sleep(time::Duration::from_secs(1))));
                 }
+                _ = irc.shutdown.wait() => {
+                    // Stop handling messages, and give the leave message a
+                    // chance to be sent before disconnecting.
+                    log::info!("Leaving chat since the bot is shutting down");
+                    leave.set(Fuse::new(tokio::time::sleep(time::Duration::from_secs(1))));
+                }
                 commands = commands_stream.recv() => {
                     handler.commands = commands;
                 }
@@ -463,10 +659,13 @@ impl IrcLoop<'_> {
                     handler.whitelisted_hosts = update;
                 },
                 message = client_stream.next() => {
-                    if let Some(m) = message.transpose()? {
-                        if let Err(e) = handler.handle(m).await {
-                            log_error!(e, "Failed to handle message");
-                        }
+                    let m = match message.transpose()? {
+                        Some(m) => m,
+                        None => bail!("connection closed by server"),
+                    };
+
+                    if let Err(e) = handler.handle(m).await {
+                        log_error!(e, "Failed to handle message");
                     }
 
                     if handler.handler_shutdown {
@@ -503,15 +702,17 @@ impl IrcLoop<'_> {
 /// Set up a reward loop.
 async fn currency_loop(
     streamer: api::TwitchAndUser,
-    streamer_channel: Arc<twitch::v5::Channel>,
     sender: Sender,
     idle: idle::Idle,
+    active_chatters: chatters::ActiveChatters,
     injector: Injector,
-    chat_settings: crate::Settings,
+    stream_info: stream_info::StreamInfo,
     settings: crate::Settings,
 ) -> Result<impl Future<Output = Result<()>>> {
     log::trace!("Setting up currency loop");
 
+    let chat_settings = settings.scoped("chat");
+
     let reward = 10;
     let default_interval = Duration::seconds(60 * 10);
 
@@ -521,10 +722,37 @@ async fn currency_loop(
         .await?;
 
     let reward_percentage = chat_settings.var("viewer-reward%", 100).await?;
-    let (mut viewer_reward_stream, viewer_reward) = chat_settings
+    let allowed_categories = chat_settings
+        .var("viewer-reward/allowed-categories", HashSet::<String>::new())
+        .await?;
+    let blocked_categories = chat_settings
+        .var("viewer-reward/blocked-categories", HashSet::<String>::new())
+        .await?;
+    let (mut viewer_reward_stream, mut viewer_reward) = chat_settings
         .stream("viewer-reward/enabled")
         .or_with(false)
         .await?;
+    let (mut active_enabled_stream, mut active_enabled) = chat_settings
+        .stream("active-reward/enabled")
+        .or_with(false)
+        .await?;
+    let (mut active_interval_stream, mut active_interval) = chat_settings
+        .stream("active-reward/interval")
+        .or_with(Duration::seconds(60 * 5))
+        .await?;
+    let active_amount = chat_settings.var("active-reward/amount", 5).await?;
+    let active_window = chat_settings
+        .var("active-reward/window", Duration::seconds(60 * 10))
+        .await?;
+    let active_subscriber_multiplier = chat_settings
+        .var("active-reward/subscriber-multiplier%", 100)
+        .await?;
+    let jitter = chat_settings.var("jitter", Duration::default()).await?;
+    let watch_time_enabled = settings.var("watchtime/enabled", false).await?;
+    let (mut watch_time_interval_stream, mut watch_time_interval) = settings
+        .stream("watchtime/interval")
+        .or_with(Duration::seconds(5 * 60))
+        .await?;
     let (mut notify_rewards_stream, mut notify_rewards) = settings
         .stream("currency/notify-rewards")
         .or_with(true)
@@ -537,6 +765,20 @@ async fn currency_loop(
         .stream("currency/command-enabled")
         .or_with(true)
         .await?;
+    let (mut separators_stream, separators) = settings
+        .stream("currency/format/separators")
+        .or_default()
+        .await?;
+    let (mut symbol_stream, symbol) = settings.stream("currency/format/symbol").optional().await?;
+    let (mut symbol_before_stream, symbol_before) = settings
+        .stream("currency/format/symbol-before")
+        .or_default()
+        .await?;
+    let (mut abbreviate_stream, abbreviate) = settings
+        .stream("currency/format/abbreviate")
+        .or_default()
+        .await?;
+    let (mut cache_stream, cache) = settings.stream("currency/cache").or_with(true).await?;
     let (mut mysql_url_stream, mysql_url) =
         settings.stream("currency/mysql/url").optional().await?;
     let (mut mysql_schema_stream, mysql_schema) = settings
@@ -544,7 +786,7 @@ async fn currency_loop(
         .or_default()
         .await?;
 
-    let (mut db_stream, db) = injector.stream::<db::Database>().await;
+    let (mut db_stream, db) = injector.stream::<Arc<db::Cached<db::Sqlite>>>().await;
 
     let mut builder = CurrencyBuilder::new(streamer.client.clone(), mysql_schema, injector.clone());
 
@@ -553,26 +795,41 @@ async fn currency_loop(
     builder.enabled = enabled;
     builder.command_enabled = command_enabled;
     builder.name = name.map(Arc::new);
+    builder.format = currency::Format {
+        separators,
+        symbol,
+        symbol_before,
+        abbreviate,
+    };
     builder.mysql_url = mysql_url;
+    builder.cache = cache;
 
     let mut currency = builder.build_and_inject().await;
 
     Ok(async move {
-        let new_timer = |interval: &Duration, viewer_reward: bool| {
-            if viewer_reward && !interval.is_empty() {
-                Fuse::new(tokio::time::interval(interval.as_std()))
+        let new_timer = |interval: &Duration, jitter: Duration, enabled: bool| {
+            if enabled && !interval.is_empty() {
+                let mut cooldown = Cooldown::with_jitter(*interval, jitter);
+                Fuse::new(Box::pin(cooldown.sleep()))
             } else {
                 Fuse::empty()
             }
         };
 
-        let mut timer = new_timer(&reward_interval, viewer_reward);
+        let mut timer = new_timer(&reward_interval, jitter.load().await, viewer_reward);
+        let mut active_timer = new_timer(&active_interval, jitter.load().await, active_enabled);
+        let mut watch_time_timer = new_timer(&watch_time_interval, jitter.load().await, true);
+        // If rewards accrued in the current category the last time we checked.
+        let mut accruing = None;
+        // NB: watch time is sampled separately from viewer rewards, so that
+        // it's tracked regardless of the currency in use.
+        let mut sampler = watch_time::Sampler::default();
 
         loop {
             tokio::select! {
                 update = interval_stream.recv() => {
                     reward_interval = update;
-                    timer = new_timer(&reward_interval, viewer_reward);
+                    timer = new_timer(&reward_interval, jitter.load().await, viewer_reward);
                 }
                 update = notify_rewards_stream.recv() => {
                     notify_rewards = update;
@@ -601,32 +858,180 @@ async fn currency_loop(
                     builder.mysql_schema = update;
                     currency = builder.build_and_inject().await;
                 }
+                cache = cache_stream.recv() => {
+                    builder.cache = cache;
+                    currency = builder.build_and_inject().await;
+                }
                 command_enabled = command_enabled_stream.recv() => {
                     builder.command_enabled = command_enabled;
                     currency = builder.build_and_inject().await;
                 }
-                viewer_reward = viewer_reward_stream.recv() => {
-                    timer = new_timer(&reward_interval, viewer_reward);
+                separators = separators_stream.recv() => {
+                    builder.format.separators = separators;
+                    currency = builder.build_and_inject().await;
+                }
+                symbol = symbol_stream.recv() => {
+                    builder.format.symbol = symbol;
+                    currency = builder.build_and_inject().await;
+                }
+                symbol_before = symbol_before_stream.recv() => {
+                    builder.format.symbol_before = symbol_before;
+                    currency = builder.build_and_inject().await;
+                }
+                abbreviate = abbreviate_stream.recv() => {
+                    builder.format.abbreviate = abbreviate;
+                    currency = builder.build_and_inject().await;
+                }
+                update = viewer_reward_stream.recv() => {
+                    viewer_reward = update;
+                    timer = new_timer(&reward_interval, jitter.load().await, viewer_reward);
+                }
+                update = active_interval_stream.recv() => {
+                    active_interval = update;
+                    active_timer = new_timer(&active_interval, jitter.load().await, active_enabled);
+                }
+                update = active_enabled_stream.recv() => {
+                    active_enabled = update;
+                    active_timer = new_timer(&active_interval, jitter.load().await, active_enabled);
+                }
+                update = watch_time_interval_stream.recv() => {
+                    watch_time_interval = update;
+                    watch_time_timer = new_timer(&watch_time_interval, jitter.load().await, true);
+                }
+                _ = &mut watch_time_timer => {
+                    let jitter = jitter.load().await;
+                    watch_time_timer = new_timer(&watch_time_interval, jitter, true);
+
+                    let db = match builder.db.as_ref() {
+                        Some(db) => db,
+                        None => continue,
+                    };
+
+                    let is_live = stream_info.data.read().stream.is_some();
+
+                    if !watch_time_enabled.load().await || !is_live {
+                        sampler.reset();
+                        continue;
+                    }
+
+                    let max = watch_time_interval.as_std() + jitter.as_std();
+
+                    if let Err(e) = watch_time::credit(
+                        &mut sampler,
+                        &streamer.client,
+                        db.as_ref(),
+                        sender.channel(),
+                        max,
+                    ).await {
+                        log_error!(e, "failed to track watch time");
+                    }
                 }
-                _ = timer.as_pin_mut().poll_inner(|mut i, cx| i.poll_tick(cx)) => {
+                _ = &mut active_timer => {
+                    active_timer = new_timer(&active_interval, jitter.load().await, active_enabled);
+
                     let currency = match currency.as_ref() {
                         Some(currency) => currency,
                         None => continue,
                     };
 
-                    let seconds = reward_interval.num_seconds() as i64;
+                    let (is_live, game) = {
+                        let data = stream_info.data.read();
+                        (data.stream.is_some(), data.game.clone())
+                    };
+
+                    // NB: active chatters are only rewarded while live, and
+                    // in the same categories as viewer rewards accrue in.
+                    let accrue = category_accrues(
+                        game.as_deref(),
+                        &*allowed_categories.read().await,
+                        &*blocked_categories.read().await,
+                    );
+
+                    if !is_live || !accrue {
+                        continue;
+                    }
+
+                    let window = active_window.load().await.as_std();
+                    let active = active_chatters.active(window, time::Instant::now());
+
+                    if active.is_empty() {
+                        continue;
+                    }
+
+                    log::trace!("rewarding {} active chatters", active.len());
+
+                    let amount = active_amount.load().await;
+                    let subscriber_amount =
+                        (amount * active_subscriber_multiplier.load().await as i64) / 100i64;
+
+                    let (subscribers, others): (Vec<_>, Vec<_>) =
+                        active.into_iter().partition(|(_, subscriber)| *subscriber);
+
+                    let channel = &stream_info.user.name;
+
+                    for (users, amount) in vec![(subscribers, subscriber_amount), (others, amount)] {
+                        if users.is_empty() {
+                            continue;
+                        }
+
+                        let users = users.into_iter().map(|(user, _)| user).collect::<Vec<_>>();
+
+                        if let Err(e) = currency.balances_increment(channel, users, amount, 0).await {
+                            log_error!(e, "failed to reward active chatters");
+                        }
+                    }
+                }
+                _ = &mut timer => {
+                    timer = new_timer(&reward_interval, jitter.load().await, viewer_reward);
+
+                    let currency = match currency.as_ref() {
+                        Some(currency) => currency,
+                        None => continue,
+                    };
+
+                    let game = stream_info.data.read().game.clone();
+
+                    let accrue = category_accrues(
+                        game.as_deref(),
+                        &*allowed_categories.read().await,
+                        &*blocked_categories.read().await,
+                    );
+
+                    if accruing != Some(accrue) {
+                        let game = game.as_deref().unwrap_or("no category");
+
+                        if accrue {
+                            log::info!("Viewer rewards accruing in category: {}", game);
+                        } else {
+                            log::info!("Viewer rewards paused in category: {}", game);
+                        }
+
+                        accruing = Some(accrue);
+                    }
+
+                    if !accrue {
+                        continue;
+                    }
+
+                    // NB: watch time is credited by sampling chat instead when
+                    // it's enabled, so it's not counted twice.
+                    let seconds = if watch_time_enabled.load().await {
+                        0
+                    } else {
+                        reward_interval.num_seconds() as i64
+                    };
 
                     log::trace!("running reward loop");
 
                     let reward = (reward * reward_percentage.load().await as i64) / 100i64;
                     let count = currency
-                        .add_channel_all(&streamer_channel.name, reward, seconds)
+                        .add_channel_all(&stream_info.user.name, reward, seconds)
                         .await?;
 
                     if notify_rewards && count > 0 && !idle.is_idle().await {
                         sender.privmsg(format!(
-                            "/me has given {} {} to all viewers!",
-                            reward, currency.name
+                            "/me has given {} to all viewers!",
+                            currency.format(reward)
                         )).await;
                     }
                 }
@@ -635,6 +1040,26 @@ async fn currency_loop(
     })
 }
 
+/// Test if viewer rewards should accrue while streaming in the given
+/// category.
+///
+/// Blocked categories never accrue. If any categories are allowed, only those
+/// accrue.
+fn category_accrues(
+    game: Option<&str>,
+    allowed: &HashSet<String>,
+    blocked: &HashSet<String>,
+) -> bool {
+    let contains =
+        |set: &HashSet<String>, game: &str| set.iter().any(|c| c.eq_ignore_ascii_case(game));
+
+    match game {
+        Some(game) if contains(blocked, game) => false,
+        Some(game) => allowed.is_empty() || contains(allowed, game),
+        None => allowed.is_empty(),
+    }
+}
+
 /// Handler for incoming messages.
 struct Handler<'a> {
     /// Current Streamer.
@@ -661,12 +1086,14 @@ struct Handler<'a> {
     api_url: Arc<Option<String>>,
     /// Active moderator cooldown.
     moderator_cooldown: Option<Cooldown>,
-    /// Handlers for specific commands like `!skip`.
-    handlers: module::Handlers,
     /// Dynamic handlers.
     scripts: script::Scripts,
     /// Build idle detection.
     idle: &'a idle::Idle,
+    /// Users who have recently chatted.
+    active_chatters: chatters::ActiveChatters,
+    /// Pause state of the automatic chat filters.
+    filters: filters::Filters,
     /// Pong timeout currently running.
     pong_timeout: &'a mut Fuse<Pin<Box<tokio::time::Sleep>>>,
     /// OAuth 2.0 Token used to authenticate with IRC.
@@ -680,11 +1107,24 @@ struct Handler<'a> {
     /// Handler for currencies.
     currency_handler: Arc<currency_admin::Handler>,
     bad_words_enabled: settings::Var<bool>,
-    url_whitelist_enabled: settings::Var<bool>,
+    /// How bad words by repeat offenders are penalized.
+    bad_words_escalation: BadWordsEscalation,
+    /// Filter for messages with excessive caps or symbols.
+    spam_filter: SpamFilter,
+    /// Filter for links to hosts which aren't whitelisted.
+    url_whitelist: UrlWhitelist,
+    /// Tell moderators about templates which fail to render.
+    template_errors: settings::Var<bool>,
+    /// If moderators can use custom commands without paying their cost.
+    cost_moderator_exempt: settings::Var<bool>,
+    /// Random extra time added to the cooldowns of custom commands.
+    cooldown_jitter: settings::Var<utils::Duration>,
     /// Handler for chat logs.
     chat_log: Option<chat_log::ChatLog>,
     /// Shared context paramters.
     context_inner: Arc<command::ContextInner>,
+    /// Suggestions for mistyped commands.
+    suggestions: suggestions::Suggestions,
 }
 
 impl Handler<'_> {
@@ -738,7 +1178,7 @@ impl Handler<'_> {
     }
 }
 
-/// Handle a command.
+/// Handle a command, returning `true` if there was a handler for it.
 async fn process_command(
     command: &str,
     mut ctx: command::Context,
@@ -746,14 +1186,15 @@ async fn process_command(
     currency_handler: &Arc<currency_admin::Handler>,
     handlers: &module::Handlers,
     scripts: &script::Scripts,
-) -> Result<()> {
+) -> Result<bool> {
     match command {
         "ping" => {
             respond!(ctx, "What do you want?");
             global_bus.send(bus::Global::Ping).await;
+            Ok(true)
         }
         other => {
-            log::trace!("Testing command: {}", other);
+            log::trace!("[{}] testing command: {}", ctx.id(), other);
 
             // TODO: store currency name locally to match against.
             let currency_command = currency_handler.command_name().await;
@@ -769,52 +1210,377 @@ async fn process_command(
                 let scope = handler.scope();
 
                 if log::log_enabled!(log::Level::Trace) {
-                    log::trace!("Auth: {:?} against {:?}", scope, ctx.user.roles());
+                    log::trace!(
+                        "[{}] auth: {:?} against {:?}",
+                        ctx.id(),
+                        scope,
+                        ctx.user.roles()
+                    );
                 }
 
                 // Test if user has the required scope to run the given
                 // command.
                 if let Some(scope) = scope {
                     if !ctx.user.has_scope(scope).await {
+                        log::trace!("[{}] denied: missing scope {}", ctx.id(), scope);
+
                         if ctx.user.is_moderator() {
                             respond!(ctx, "You are not allowed to run that command");
                         } else {
                             respond!(ctx, "Do you think this is a democracy? LUL");
                         }
 
-                        return Ok(());
+                        return Ok(true);
                     }
                 }
 
                 task::spawn(async move {
+                    log::trace!("[{}] running handler", ctx.id());
+
                     if let Err(e) = handler.handle(&mut ctx).await {
                         if let Some(command::Respond(respond)) = e.downcast_ref() {
+                            log::trace!("[{}] responded with: {}", ctx.id(), respond);
                             respond!(ctx, respond);
                         } else {
                             respond!(ctx, "Sorry, something went wrong :(");
-                            log_error!(e, "Error when processing command");
+                            log_error!(e, "[{}] Error when processing command", ctx.id());
                         }
                     }
                 });
 
-                return Ok(());
+                return Ok(true);
             }
 
             if let Some(handler) = scripts.get(other) {
                 if let Err(e) = handler.call(ctx.clone()).await {
                     ctx.respond("Sorry, something went wrong :(").await;
-                    log_error!(e, "Error when processing command");
+                    log_error!(e, "[{}] Error when processing command", ctx.id());
                 }
 
-                return Ok(());
+                return Ok(true);
             }
+
+            Ok(false)
         }
     }
-
-    Ok(())
 }
 
 impl<'a> Handler<'a> {
+    /// Check the limits of the given custom command, responding to the user
+    /// if they aren't allowed to use it right now.
+    async fn check_command_limits(&self, user: &User, command: &db::Command) -> bool {
+        if user.has_scope(Scope::BypassCooldowns).await {
+            return true;
+        }
+
+        let started_at = self
+            .stream_info
+            .data
+            .read()
+            .stream
+            .as_ref()
+            .map(|s| s.started_at);
+
+        let moderator = user.is_moderator() || user.is_streamer();
+
+        match command.check_limits(
+            user.name(),
+            moderator,
+            started_at,
+            &self.context_inner.cooldowns,
+            self.cooldown_jitter.load().await,
+        ) {
+            Ok(()) => true,
+            Err(rejected) => {
+                log::trace!("command `{}` denied: {:?}", command.key, rejected);
+                respond!(user, "{}", rejected);
+                false
+            }
+        }
+    }
+
+    /// Check that the user can afford the cost of the given custom command,
+    /// responding to them if they can't.
+    ///
+    /// Returns the currency to charge if the command isn't free for them.
+    async fn check_command_cost(&self, user: &User, command: &db::Command) -> Result<CommandCost> {
+        let cost = match command.cost {
+            Some(cost) => i64::from(cost),
+            None => return Ok(CommandCost::Free),
+        };
+
+        let name = match user.real() {
+            Some(user) => user.name(),
+            None => return Ok(CommandCost::Free),
+        };
+
+        if (user.is_moderator() || user.is_streamer()) && self.cost_moderator_exempt.load().await {
+            return Ok(CommandCost::Free);
+        }
+
+        let currency = match self.currency_handler.currency.load().await {
+            Some(currency) => currency,
+            None => {
+                respond!(user, "No currency configured for stream, sorry :(");
+                return Ok(CommandCost::Denied);
+            }
+        };
+
+        let balance = currency
+            .balance_of(user.channel(), name)
+            .await?
+            .map(|b| b.balance)
+            .unwrap_or_default();
+
+        if balance < cost {
+            log::trace!("command `{}` denied: not enough currency", command.key);
+            self.cannot_afford(user, &currency, name, cost).await?;
+            return Ok(CommandCost::Denied);
+        }
+
+        Ok(CommandCost::Charge(currency, cost))
+    }
+
+    /// Check the limits and the cost of the given custom command, charging
+    /// the user for it. The charge is refunded if the limits don't allow the
+    /// command to be used.
+    ///
+    /// Returns `true` if the command can be used.
+    async fn check_command_use(&self, user: &User, command: &db::Command) -> Result<bool> {
+        let charge = match self.check_command_cost(user, command).await? {
+            CommandCost::Denied => return Ok(false),
+            CommandCost::Free => None,
+            CommandCost::Charge(currency, cost) => user.name().map(|name| (currency, name, cost)),
+        };
+
+        // NB: the user is charged before the limits are checked, since
+        // checking them counts the use. Otherwise someone who can't afford
+        // the command would still put it on cooldown.
+        if let Some((currency, name, cost)) = &charge {
+            // NB: the balance might have changed since it was checked, so the
+            // charge only goes through if the user can still afford it.
+            if !currency.balance_take(user.channel(), name, *cost).await? {
+                log::trace!("command `{}` denied: not enough currency", command.key);
+                self.cannot_afford(user, currency, name, *cost).await?;
+                return Ok(false);
+            }
+        }
+
+        if !self.check_command_limits(user, command).await {
+            if let Some((currency, name, cost)) = charge {
+                currency.balance_add(user.channel(), name, cost).await?;
+            }
+
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Tell the user that they can't afford to use a command.
+    async fn cannot_afford(
+        &self,
+        user: &User,
+        currency: &currency::Currency,
+        name: &str,
+        cost: i64,
+    ) -> Result<()> {
+        let balance = currency
+            .balance_of(user.channel(), name)
+            .await?
+            .map(|b| b.balance)
+            .unwrap_or_default();
+
+        respond!(
+            user,
+            "You need {cost} to use that command, you currently have {balance}.",
+            cost = currency.format(cost),
+            balance = currency.format_balance(balance),
+        );
+
+        Ok(())
+    }
+
+    /// Check that the user can make the given change to the counter of a
+    /// custom command, like `!deaths add 3`. Setting the counter requires a
+    /// moderator.
+    async fn check_count_change(
+        &self,
+        user: &User,
+        change: Result<db::CountChange, String>,
+    ) -> Option<db::CountChange> {
+        let change = match change {
+            Ok(change) => change,
+            Err(m) => {
+                respond!(user, m);
+                return None;
+            }
+        };
+
+        if let db::CountChange::Set(..) = change {
+            if !(user.is_moderator() || user.is_streamer()) {
+                respond!(user, "Only moderators can set counters.");
+                return None;
+            }
+        }
+
+        Some(change)
+    }
+
+    /// Apply a change to the counter of a custom command.
+    async fn change_count(
+        &self,
+        user: &User,
+        commands: &db::Commands,
+        command: &db::Command,
+        change: db::CountChange,
+    ) -> Result<()> {
+        match change {
+            db::CountChange::Add(amount) => {
+                if !commands.add_amount(command, amount, user.name()).await? {
+                    respond!(user, "Counter can't go above {}.", i32::MAX);
+                }
+            }
+            db::CountChange::Sub(amount) => {
+                commands.sub_amount(command, amount, user.name()).await?;
+            }
+            db::CountChange::Set(count) => {
+                commands.set(command, count, user.name()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Render a custom command and send the response to the channel.
+    async fn render_command(&self, user: &User, command: &db::Command, captures: db::Captures<'_>) {
+        let vars = CommandVars {
+            name: user.display_name(),
+            sender: user.display_name(),
+            target: user.channel(),
+            count: command.count(),
+            captures,
+        };
+
+        match command.render(&vars) {
+            Ok(response) => {
+                self.sender.privmsg(response).await;
+            }
+            Err(e) => {
+                self.template_error(user, "command", &command.key, e).await;
+            }
+        }
+    }
+
+    /// Report a command or alias template which failed to render.
+    ///
+    /// Regular users only cause the error to be logged, while moderators are
+    /// told about it so that they can fix the template.
+    async fn template_error(&self, user: &User, what: &str, key: &db::Key, e: anyhow::Error) {
+        let message = e.to_string();
+        log_error!(e, "failed to render {} `{}`", what, key);
+
+        if (user.is_moderator() || user.is_streamer()) && self.template_errors.load().await {
+            respond!(
+                user,
+                "Failed to render {} `{}`: {}",
+                what,
+                key.name,
+                message
+            );
+        }
+    }
+
+    /// Suggest a command if the given unknown command is a likely typo.
+    async fn suggest_command(&mut self, user: &User, command: &str, game: Option<&str>) {
+        let name = match user.name() {
+            Some(name) => name,
+            None => return,
+        };
+
+        let mut candidates = self.builtin_names().await;
+
+        // NB: only commands which are invoked by name can be mistyped.
+        if let Some(commands) = self.commands.as_ref() {
+            for command in commands.list(user.channel()).await {
+                if let db::Pattern::Name = command.pattern {
+                    if command.games.matches(game) {
+                        candidates.push(command.key.name.clone());
+                    }
+                }
+            }
+        }
+
+        if let Some(aliases) = self.aliases.as_ref() {
+            for alias in aliases.list(user.channel()).await {
+                if let db::Pattern::Name = alias.pattern {
+                    if alias.games.matches(game) {
+                        candidates.push(alias.key.name.clone());
+                    }
+                }
+            }
+        }
+
+        let command = format!("{}{}", self.context_inner.command_prefix().await, command);
+
+        if let Some(suggestion) = self.suggestions.suggest(name, &command, candidates).await {
+            respond!(user, "Did you mean {}?", suggestion);
+        }
+    }
+
+    /// Names of all built-in commands, including scripts.
+    async fn builtin_names(&self) -> Vec<String> {
+        let prefix = self.context_inner.command_prefix().await;
+
+        let mut names = self
+            .context_inner
+            .handlers
+            .names()
+            .chain(self.scripts.names())
+            .chain(std::iter::once("ping"))
+            .map(|name| format!("{}{}", prefix, name))
+            .collect::<Vec<_>>();
+
+        if let Some(name) = self.currency_handler.command_name().await {
+            names.push(format!("{}{}", prefix, name));
+        }
+
+        names
+    }
+
+    /// Warn about aliases, custom commands, and built-in commands which share
+    /// a name, since only one of them will ever be used.
+    async fn warn_collisions(&self) {
+        let channel = self.streamer_channel.name.as_str();
+
+        let mut names = self
+            .builtin_names()
+            .await
+            .into_iter()
+            .map(|name| (collisions::Kind::BuiltIn, name))
+            .collect::<Vec<_>>();
+
+        if let Some(commands) = self.commands.as_ref() {
+            for command in commands.list(channel).await {
+                if let db::Pattern::Name = command.pattern {
+                    names.push((collisions::Kind::Command, command.key.name.clone()));
+                }
+            }
+        }
+
+        if let Some(aliases) = self.aliases.as_ref() {
+            for alias in aliases.list(channel).await {
+                if let db::Pattern::Name = alias.pattern {
+                    names.push((collisions::Kind::Alias, alias.key.name.clone()));
+                }
+            }
+        }
+
+        for collision in collisions::find(names) {
+            log::warn!("{}", collision);
+        }
+    }
+
     /// Delete the given message.
     fn delete_message(&self, user: &User) -> Result<()> {
         let id = match &user.inner.tags.id {
@@ -834,8 +1600,22 @@ impl<'a> Handler<'a> {
             return false;
         }
 
+        // Filters have been temporarily paused by a moderator.
+        if self.filters.is_paused() {
+            return false;
+        }
+
         if self.bad_words_enabled.load().await {
-            if let Some(word) = self.test_bad_words(message).await {
+            if let Some(m) = self.test_bad_words(user.channel(), message).await {
+                let word = &m.word;
+
+                log::trace!(
+                    "bad word `{}` in category {:?}: {}",
+                    word.word,
+                    word.category,
+                    m.action
+                );
+
                 if let Some(why) = word.why.as_ref() {
                     let why = why.render_to_string(&BadWordsVars {
                         name: user.display_name(),
@@ -852,16 +1632,42 @@ impl<'a> Handler<'a> {
                     }
                 }
 
-                return true;
+                let reason = "bad language";
+                let mut delete = false;
+
+                if let db::BadWordAction::Delete = m.action {
+                    delete |= self
+                        .punish(user, FilterAction::Delete, Default::default(), reason)
+                        .await;
+                }
+
+                if let Some((action, timeout)) = self.bad_word_penalty(user).await {
+                    delete |= self.punish(user, action, timeout, reason).await;
+                }
+
+                if delete {
+                    return true;
+                }
             }
         }
 
-        #[allow(clippy::collapsible_if)]
+        if self.test_spam(user, message).await {
+            return true;
+        }
+
+        if self.url_whitelist.enabled.load().await
+            && !user.has_scope(Scope::ChatBypassUrlWhitelist).await
         {
-            if !user.has_scope(Scope::ChatBypassUrlWhitelist).await
-                && self.url_whitelist_enabled.load().await
-            {
-                if self.has_bad_link(message) {
+            let obfuscated = self.url_whitelist.obfuscated.load().await;
+
+            if let Some(link) = self.find_bad_link(message, obfuscated) {
+                log::trace!("link to host which isn't whitelisted: {}", link.host);
+
+                let action = self.url_whitelist.action.load().await;
+                let timeout = self.url_whitelist.timeout.load().await;
+                let reason = "posting links which aren't allowed";
+
+                if self.punish(user, action, timeout, reason).await {
                     return true;
                 }
             }
@@ -870,12 +1676,92 @@ impl<'a> Handler<'a> {
         false
     }
 
+    /// Apply the action of a chat filter to the given user, timing them out
+    /// for `timeout` if that is the action.
+    ///
+    /// Returns `true` if the message should be deleted.
+    async fn punish(
+        &self,
+        user: &User,
+        action: FilterAction,
+        timeout: utils::Duration,
+        reason: impl fmt::Display,
+    ) -> bool {
+        match action {
+            FilterAction::Delete => true,
+            FilterAction::Timeout => {
+                if let Some(name) = user.name() {
+                    log::info!("Timing out {} for {} for {}", name, timeout, reason);
+                    self.sender.timeout(name, timeout, &reason.to_string());
+                }
+
+                false
+            }
+            FilterAction::Warn => {
+                respond!(user, "Please avoid {} in chat.", reason);
+                false
+            }
+        }
+    }
+
+    /// Test the message for excessive caps or symbols and apply the
+    /// configured action.
+    ///
+    /// Returns `true` if the message should be deleted.
+    async fn test_spam(&self, user: &User, message: &str) -> bool {
+        if !self.spam_filter.enabled.load().await {
+            return false;
+        }
+
+        // Subscribers are trusted to get excited sometimes.
+        if user.real().map(|u| u.is_subscriber()).unwrap_or(true) {
+            return false;
+        }
+
+        let reason = match spam::test(
+            message,
+            self.spam_filter.min_length.load().await,
+            self.spam_filter.caps_ratio.load().await,
+            self.spam_filter.symbols_ratio.load().await,
+        ) {
+            Some(reason) => reason,
+            None => return false,
+        };
+
+        log::trace!("message caught by spam filter: {}", reason);
+
+        let action = self.spam_filter.action.load().await;
+        let timeout = self.spam_filter.timeout.load().await;
+        self.punish(user, action, timeout, reason).await
+    }
+
+    /// Record that a user used a bad word, and decide how to penalize them
+    /// depending on how many times they have done so recently.
+    ///
+    /// Returns the action to apply and how long to time them out for, if
+    /// that is the action.
+    async fn bad_word_penalty(&self, user: &User) -> Option<(FilterAction, utils::Duration)> {
+        if !self.bad_words_escalation.enabled.load().await {
+            return None;
+        }
+
+        let name = user.name()?;
+
+        let decay = self.bad_words_escalation.decay.load().await;
+        let timeouts = self.bad_words_escalation.timeouts.load().await;
+
+        match self.bad_words.offend(name, decay, &timeouts) {
+            db::BadWordPenalty::Warn => Some((FilterAction::Warn, Default::default())),
+            db::BadWordPenalty::Timeout(duration) => Some((FilterAction::Timeout, duration)),
+        }
+    }
+
     /// Test the message for bad words.
-    async fn test_bad_words(&self, message: &str) -> Option<Arc<db::Word>> {
+    async fn test_bad_words(&self, channel: &str, message: &str) -> Option<db::BadWordMatch> {
         let tester = self.bad_words.tester().await;
 
         for word in utils::TrimmedWords::new(message) {
-            if let Some(word) = tester.test(word) {
+            if let Some(word) = tester.test_in(channel, word) {
                 return Some(word);
             }
         }
@@ -883,17 +1769,28 @@ impl<'a> Handler<'a> {
         None
     }
 
-    /// Check if the given iterator has URLs that need to be
-    fn has_bad_link(&self, message: &str) -> bool {
-        for url in utils::Urls::new(message) {
-            if let Some(host) = url.host_str() {
-                if !self.whitelisted_hosts.contains(host) {
-                    return true;
-                }
+    /// Find the first link in the message to a host which isn't whitelisted.
+    ///
+    /// Clips and the streamer's own channel are always allowed.
+    fn find_bad_link(&self, message: &str, obfuscated: bool) -> Option<links::Link> {
+        let channel = format!("/{}", self.streamer.user.name.to_lowercase());
+
+        links::find(message, obfuscated).into_iter().find(|link| {
+            if link.is_domain("clips.twitch.tv") {
+                return false;
             }
-        }
 
-        false
+            if link.is_domain("twitch.tv")
+                && (link.path == channel || link.path.starts_with(&format!("{}/", channel)))
+            {
+                return false;
+            }
+
+            !self
+                .whitelisted_hosts
+                .iter()
+                .any(|host| link.is_domain(&host.to_lowercase()))
+        })
     }
 
     /// Send a ping to the remote server.
@@ -908,6 +1805,14 @@ impl<'a> Handler<'a> {
 
     /// Process the given command.
     pub async fn process_message(&mut self, user: &User, mut message: Arc<String>) -> Result<()> {
+        let id = command::ContextId::next();
+        log::trace!(
+            "[{}] processing message from {:?} (id: {:?})",
+            id,
+            user.name(),
+            user.tags().user_id
+        );
+
         // Run message hooks.
         let _ = task::spawn({
             let user = user.clone();
@@ -919,7 +1824,7 @@ impl<'a> Handler<'a> {
 
                 for (key, hook) in &*message_hooks {
                     if let Err(e) = hook.peek(&user, &*message).await {
-                        log_error!(e, "Hook `{}` failed", key);
+                        log_error!(e, "[{}] Hook `{}` failed", id, key);
                     }
                 }
             }
@@ -928,14 +1833,34 @@ impl<'a> Handler<'a> {
         // only non-moderators and non-streamer bumps the idle counter.
         if !user.is_streamer() {
             self.idle.seen();
+
+            if let Some(real) = user.real() {
+                self.active_chatters
+                    .seen(real.name(), real.is_subscriber(), time::Instant::now());
+            }
         }
 
         // NB: declared here to be in scope.
         let mut seen = HashSet::new();
         let mut path = Vec::new();
 
+        // The game being streamed, which limits which commands and aliases
+        // are available.
+        let game = self.stream_info.data.read().game.clone();
+
         if let Some(aliases) = self.aliases.as_ref() {
-            while let Some((key, next)) = aliases.resolve(user.channel(), message.clone()).await {
+            while let Some((key, next)) = aliases
+                .resolve(user.channel(), game.as_deref(), message.clone())
+                .await
+            {
+                let next = match next {
+                    Ok(next) => next,
+                    Err(e) => {
+                        self.template_error(user, "alias", &key, e).await;
+                        return Ok(());
+                    }
+                };
+
                 path.push(key.to_string());
 
                 if !seen.insert(key.clone()) {
@@ -954,32 +1879,69 @@ impl<'a> Handler<'a> {
         let mut it = utils::Words::new(message.clone());
         let first = it.next();
 
+        // If the message matched a custom command.
+        let mut custom = false;
+
         if let Some(commands) = self.commands.as_ref() {
             if let Some((command, captures)) = commands
-                .resolve(user.channel(), first.as_deref(), &it)
+                .resolve(user.channel(), game.as_deref(), first.as_deref(), &it)
                 .await
             {
-                if command.has_var("count") {
-                    commands.increment(&*command).await?;
-                }
+                custom = true;
 
-                let vars = CommandVars {
-                    name: user.display_name(),
-                    target: user.channel(),
-                    count: command.count(),
-                    captures,
+                let change = if command.has_var("count") {
+                    captures.rest().and_then(db::CountChange::parse)
+                } else {
+                    None
                 };
 
-                let response = command.render(&vars)?;
-                self.sender.privmsg(response).await;
+                let missing_args = matches!(
+                    captures.args(),
+                    Some(args) if args < command.required_args()
+                );
+
+                if !user.has_permission(command.permission) {
+                    log::trace!(
+                        "command `{}` denied: missing permission {}",
+                        command.key,
+                        command.permission
+                    );
+
+                    respond!(
+                        user,
+                        "You need to be {} to use that command.",
+                        command.permission.describe()
+                    );
+                } else if let Some(change) = change {
+                    if let Some(change) = self.check_count_change(user, change).await {
+                        if self.check_command_use(user, &command).await? {
+                            self.change_count(user, commands, &command, change).await?;
+                            let captures = db::Captures::Prefix { rest: "" };
+                            self.render_command(user, &command, captures).await;
+                        }
+                    }
+                } else if missing_args {
+                    respond!(user, "Usage: {}", command.usage_or_derived());
+                } else if self.check_command_use(user, &command).await? {
+                    if command.has_var("count") {
+                        commands.increment(&command, user.name()).await?;
+                    }
+
+                    self.render_command(user, &command, captures).await;
+                }
             }
         }
 
-        if let Some(command) = first {
-            if command.starts_with('!') {
-                let command = &command[1..];
+        // NB: custom commands hide built-in commands by the same name, see
+        // the `collisions` module.
+        let builtin = if custom { None } else { first };
+
+        let prefix = self.context_inner.command_prefix().await;
 
+        if let Some(command) = builtin {
+            if let Some(command) = command.strip_prefix(prefix.as_str()) {
                 let ctx = command::Context {
+                    id,
                     api_url: self.api_url.clone(),
                     user: user.clone(),
                     it,
@@ -991,17 +1953,24 @@ impl<'a> Handler<'a> {
                     ctx,
                     self.global_bus,
                     &self.currency_handler,
-                    &self.handlers,
+                    &self.context_inner.handlers,
                     &self.scripts,
                 );
 
-                if let Err(e) = result.await {
-                    log_error!(e, "failed to process command");
+                match result.await {
+                    Ok(false) if path.is_empty() => {
+                        self.suggest_command(user, command, game.as_deref()).await;
+                    }
+                    Ok(_) => (),
+                    Err(e) => {
+                        log_error!(e, "[{}] failed to process command", id);
+                    }
                 }
             }
         }
 
         if self.should_be_deleted(&user, &*message).await {
+            log::trace!("[{}] message matched a chat filter", id);
             self.delete_message(&user)?;
         }
 
@@ -1138,6 +2107,16 @@ impl<'a> Handler<'a> {
                         }
                     }
                 }
+                "USERNOTICE" => {
+                    if let Some(tags) = RaidTags::from_tags(m.tags) {
+                        self.global_bus
+                            .send(bus::Global::TwitchRaid {
+                                user: tags.display_name,
+                                viewers: tags.viewer_count,
+                            })
+                            .await;
+                    }
+                }
                 "CLEARCHAT" => {
                     if let Some(chat_log) = self.chat_log.as_ref() {
                         match tail.first() {
@@ -1178,6 +2157,16 @@ pub struct RealUser<'a> {
 }
 
 impl<'a> RealUser<'a> {
+    /// Get tags associated with the message.
+    pub fn tags(&self) -> &'a Tags {
+        self.tags
+    }
+
+    /// Get the Twitch ID of the user, if it was sent with the message.
+    pub fn user_id(&self) -> Option<&'a str> {
+        self.tags.user_id.as_deref()
+    }
+
     /// Get the channel the user is associated with.
     pub fn channel(&self) -> &str {
         self.sender.channel()
@@ -1209,18 +2198,18 @@ impl<'a> RealUser<'a> {
     }
 
     /// Test if streamer.
-    fn is_streamer(&self) -> bool {
+    pub fn is_streamer(&self) -> bool {
         self.name == self.streamer.name
     }
 
     /// Test if moderator.
-    fn is_moderator(&self) -> bool {
+    pub fn is_moderator(&self) -> bool {
         self.moderators.read().contains(self.name)
     }
 
     /// Test if user is a subscriber.
-    fn is_subscriber(&self) -> bool {
-        self.is_streamer() || self.stream_info.is_subscriber(self.name)
+    pub fn is_subscriber(&self) -> bool {
+        self.is_streamer() || self.tags.is_subscriber() || self.stream_info.is_subscriber(self.name)
     }
 
     /// Test if vip.
@@ -1228,6 +2217,22 @@ impl<'a> RealUser<'a> {
         self.vips.read().contains(self.name)
     }
 
+    /// Get the highest permission the user has, going by their standing in
+    /// the channel and the badges Twitch sent along with their message.
+    pub fn permission(&self) -> Permission {
+        if self.is_streamer() || self.tags.has_badge("broadcaster") {
+            Permission::Broadcaster
+        } else if self.is_moderator() || self.tags.has_badge("moderator") {
+            Permission::Moderator
+        } else if self.is_vip() || self.tags.has_badge("vip") {
+            Permission::Vip
+        } else if self.is_subscriber() {
+            Permission::Subscriber
+        } else {
+            Permission::Everyone
+        }
+    }
+
     /// Get a list of all roles the current requester belongs to.
     pub fn roles(&self) -> smallvec::SmallVec<[Role; 4]> {
         let mut roles = smallvec::SmallVec::new();
@@ -1388,6 +2393,39 @@ impl User {
         }
     }
 
+    /// Respond with the given page of results, where each page is a line
+    /// which fits in a single message. Pages are numbered from 1.
+    pub async fn respond_page<I>(&self, results: I, page: usize, empty: &str)
+    where
+        I: IntoIterator,
+        I::Item: fmt::Display,
+    {
+        let pages = partition_response(results, 360, " | ").collect::<Vec<_>>();
+
+        if pages.is_empty() {
+            self.respond(empty).await;
+            return;
+        }
+
+        match page.checked_sub(1).and_then(|index| pages.get(index)) {
+            Some(line) if pages.len() > 1 => {
+                self.respond(format!("{} (page {}/{})", line, page, pages.len()))
+                    .await;
+            }
+            Some(line) => {
+                self.respond(line).await;
+            }
+            None => {
+                self.respond(format!(
+                    "There's no page {}, there are {} page(s).",
+                    page,
+                    pages.len()
+                ))
+                .await;
+            }
+        }
+    }
+
     /// Get a list of all roles the current requester belongs to.
     pub fn roles(&self) -> smallvec::SmallVec<[Role; 4]> {
         match self.real().map(|u| u.roles()) {
@@ -1403,6 +2441,19 @@ impl User {
         }
     }
 
+    /// Get the highest permission the user has. Injected commands have every
+    /// permission.
+    pub fn permission(&self) -> Permission {
+        self.real()
+            .map(|u| u.permission())
+            .unwrap_or(Permission::Broadcaster)
+    }
+
+    /// Test if the user has at least the given permission.
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permission() >= permission
+    }
+
     /// Test if the current user has the given scope.
     pub async fn has_scope(&self, scope: Scope) -> bool {
         let user = match self.real() {
@@ -1514,10 +2565,39 @@ pub struct Tags {
     /// Emotes part of the message.
     pub emotes: Option<String>,
     /// Badges part of the message.
-    pub badges: Option<String>,
+    pub badges: Vec<Badge>,
+    /// Extra information on badges, like the exact number of months the user
+    /// has been subscribed for.
+    pub badge_info: Vec<Badge>,
+    /// If the user is subscribed to the channel.
+    pub subscriber: bool,
 }
 
 impl Tags {
+    /// Get the badge with the given name, like `moderator`.
+    pub fn badge(&self, name: &str) -> Option<&Badge> {
+        self.badges.iter().find(|badge| badge.name == name)
+    }
+
+    /// Test if the message carried the badge with the given name.
+    pub fn has_badge(&self, name: &str) -> bool {
+        self.badge(name).is_some()
+    }
+
+    /// Test if the user is subscribed to the channel.
+    pub fn is_subscriber(&self) -> bool {
+        self.subscriber || self.has_badge("subscriber") || self.has_badge("founder")
+    }
+
+    /// Get the number of months the user has been subscribed for, if they
+    /// are subscribed.
+    pub fn subscriber_months(&self) -> Option<u32> {
+        self.badge_info
+            .iter()
+            .find(|badge| badge.name == "subscriber" || badge.name == "founder")
+            .and_then(|badge| badge.version.parse().ok())
+    }
+
     /// Extract tags from message.
     #[allow(clippy::single_match)]
     fn from_tags(tags: Option<Vec<Tag>>) -> Tags {
@@ -1527,7 +2607,9 @@ impl Tags {
         let mut user_id = None;
         let mut color = None;
         let mut emotes = None;
-        let mut badges = None;
+        let mut badges = Vec::new();
+        let mut badge_info = Vec::new();
+        let mut subscriber = false;
 
         if let Some(tags) = tags {
             for t in tags {
@@ -1539,7 +2621,9 @@ impl Tags {
                         "user-id" => user_id = Some(value),
                         "color" => color = Some(value),
                         "emotes" => emotes = Some(value),
-                        "badges" => badges = Some(value),
+                        "badges" => badges = Badge::parse_list(&value),
+                        "badge-info" => badge_info = Badge::parse_list(&value),
+                        "subscriber" => subscriber = value == "1",
                         _ => (),
                     },
                     _ => (),
@@ -1555,10 +2639,38 @@ impl Tags {
             color,
             emotes,
             badges,
+            badge_info,
+            subscriber,
         }
     }
 }
 
+/// A single chat badge, like `subscriber/12`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Badge {
+    /// Name of the badge, like `subscriber`.
+    pub name: String,
+    /// Version of the badge, like `12`.
+    pub version: String,
+}
+
+impl Badge {
+    /// Parse a comma-separated list of badges, like `moderator/1,subscriber/12`.
+    fn parse_list(s: &str) -> Vec<Badge> {
+        s.split(',')
+            .filter(|badge| !badge.is_empty())
+            .map(|badge| {
+                let (name, version) = badge.split_once('/').unwrap_or((badge, ""));
+
+                Badge {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
 /// Tags associated with a CLEARMSG.
 struct ClearMsgTags {
     target_msg_id: String,
@@ -1588,6 +2700,41 @@ impl ClearMsgTags {
     }
 }
 
+/// Tags associated with a USERNOTICE for a raid.
+struct RaidTags {
+    display_name: String,
+    viewer_count: u32,
+}
+
+impl RaidTags {
+    /// Extract tags from message, if it's a raid.
+    fn from_tags(tags: Option<Vec<Tag>>) -> Option<RaidTags> {
+        let mut msg_id = None;
+        let mut display_name = None;
+        let mut viewer_count = None;
+
+        for t in tags? {
+            if let Tag(name, Some(value)) = t {
+                match name.as_str() {
+                    "msg-id" => msg_id = Some(value),
+                    "msg-param-displayName" => display_name = Some(value),
+                    "msg-param-viewerCount" => viewer_count = str::parse(&value).ok(),
+                    _ => (),
+                }
+            }
+        }
+
+        if msg_id.as_deref() != Some("raid") {
+            return None;
+        }
+
+        Some(RaidTags {
+            display_name: display_name?,
+            viewer_count: viewer_count.unwrap_or_default(),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum SenderThreadItem {
     Exit,
@@ -1600,9 +2747,67 @@ pub struct BadWordsVars<'a> {
     target: &'a str,
 }
 
+/// What to do with a message caught by a chat filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum FilterAction {
+    /// Delete the message.
+    #[serde(rename = "delete")]
+    Delete,
+    /// Time out the user who sent it.
+    #[serde(rename = "timeout")]
+    Timeout,
+    /// Only ask the user to stop.
+    #[serde(rename = "warn")]
+    Warn,
+}
+
+/// Settings for the filter for links to hosts which aren't whitelisted.
+struct UrlWhitelist {
+    enabled: settings::Var<bool>,
+    /// Also look for obfuscated links, like `example dot com`.
+    obfuscated: settings::Var<bool>,
+    action: settings::Var<FilterAction>,
+    /// How long to time out users for, if that is the action.
+    timeout: settings::Var<utils::Duration>,
+}
+
+/// The outcome of checking what using a custom command costs the user.
+enum CommandCost {
+    /// The command doesn't cost the user anything.
+    Free,
+    /// The user can't afford the command.
+    Denied,
+    /// The user has to pay the given amount of currency.
+    Charge(currency::Currency, i64),
+}
+
+/// Settings for the filter for excessive caps and symbols.
+struct SpamFilter {
+    enabled: settings::Var<bool>,
+    /// Messages shorter than this are never filtered.
+    min_length: settings::Var<u32>,
+    /// Percentage of letters which have to be uppercase.
+    caps_ratio: settings::Var<u32>,
+    /// Percentage of characters which have to be symbols.
+    symbols_ratio: settings::Var<u32>,
+    action: settings::Var<FilterAction>,
+    /// How long to time out users for, if that is the action.
+    timeout: settings::Var<utils::Duration>,
+}
+
+/// Settings for escalating penalties for repeated bad words.
+struct BadWordsEscalation {
+    enabled: settings::Var<bool>,
+    /// Timeouts to use after the first offense, which is only a warning.
+    timeouts: settings::Var<Vec<utils::Duration>>,
+    /// How long it takes for a single offense to be forgiven.
+    decay: settings::Var<utils::Duration>,
+}
+
 #[derive(serde::Serialize)]
 pub struct CommandVars<'a> {
     name: Option<&'a str>,
+    sender: Option<&'a str>,
     target: &'a str,
     count: i32,
     #[serde(flatten)]
@@ -1643,9 +2848,65 @@ fn parse_room_members(message: &str) -> HashSet<String> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_room_members;
+    use super::{category_accrues, parse_room_members, Badge, Tag, Tags};
+    use crate::auth::Permission;
+    use crate::irc::mock::Mock;
+    use anyhow::Result;
     use std::collections::HashSet;
 
+    #[test]
+    fn test_tags() {
+        let tag = |name: &str, value: &str| Tag(name.to_string(), Some(value.to_string()));
+
+        let tags = Tags::from_tags(Some(vec![
+            tag("user-id", "1234"),
+            tag("color", "#FF0000"),
+            tag("badges", "moderator/1,subscriber/12"),
+            tag("badge-info", "subscriber/14"),
+            tag("subscriber", "1"),
+        ]));
+
+        assert_eq!(tags.user_id.as_deref(), Some("1234"));
+        assert_eq!(tags.color.as_deref(), Some("#FF0000"));
+        assert_eq!(
+            tags.badge("subscriber"),
+            Some(&Badge {
+                name: String::from("subscriber"),
+                version: String::from("12"),
+            })
+        );
+        assert!(tags.has_badge("moderator"));
+        assert!(!tags.has_badge("vip"));
+        assert!(tags.is_subscriber());
+        assert_eq!(tags.subscriber_months(), Some(14));
+
+        let tags = Tags::from_tags(Some(vec![tag("badges", ""), tag("subscriber", "0")]));
+        assert!(tags.badges.is_empty());
+        assert!(!tags.is_subscriber());
+        assert_eq!(tags.subscriber_months(), None);
+    }
+
+    #[tokio::test]
+    async fn test_check_permission() -> Result<()> {
+        let mock = Mock::new().await?;
+        mock.moderator("moderator");
+        mock.vip("vip");
+
+        let check = |name: &str, permission| mock.context(name, "").check_permission(permission);
+
+        assert!(check("viewer", Permission::Everyone).is_ok());
+        assert!(check("viewer", Permission::Subscriber).is_err());
+        assert!(check("vip", Permission::Vip).is_ok());
+        assert!(check("vip", Permission::Moderator).is_err());
+        assert!(check("moderator", Permission::Vip).is_ok());
+        assert!(check("moderator", Permission::Broadcaster).is_err());
+        assert!(mock
+            .injected("")
+            .check_permission(Permission::Broadcaster)
+            .is_ok());
+        Ok(())
+    }
+
     #[test]
     fn test_parse_room_mods() {
         assert_eq!(
@@ -1667,4 +2928,33 @@ mod tests {
             parse_room_members("The moderators of this channel are:")
         );
     }
+
+    #[test]
+    fn test_category_accrues() {
+        let set = |c: &[&str]| c.iter().map(|c| c.to_string()).collect::<HashSet<String>>();
+
+        assert!(category_accrues(
+            Some("Just Chatting"),
+            &set(&[]),
+            &set(&[])
+        ));
+        assert!(category_accrues(None, &set(&[]), &set(&[])));
+
+        let blocked = set(&["Just Chatting"]);
+        assert!(!category_accrues(
+            Some("just chatting"),
+            &set(&[]),
+            &blocked
+        ));
+        assert!(category_accrues(Some("Minecraft"), &set(&[]), &blocked));
+
+        let allowed = set(&["Minecraft"]);
+        assert!(category_accrues(Some("Minecraft"), &allowed, &set(&[])));
+        assert!(!category_accrues(
+            Some("Just Chatting"),
+            &allowed,
+            &set(&[])
+        ));
+        assert!(!category_accrues(None, &allowed, &set(&[])));
+    }
 }
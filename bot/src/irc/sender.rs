@@ -1,14 +1,22 @@
 use crate::api;
 use crate::injector;
 use crate::settings;
+use crate::utils;
 use anyhow::Result;
 use irc::client;
 use irc::proto::command::{CapSubCommand, Command};
 use irc::proto::message::Message;
 use leaky_bucket::{LeakyBucket, LeakyBuckets};
+use rand::Rng as _;
+use std::collections::VecDeque;
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time;
+use tokio::sync::Mutex;
+
+/// The window Twitch counts sent messages over.
+const RATE_LIMIT_WINDOW: time::Duration = time::Duration::from_secs(30);
 
 #[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
 pub enum Type {
@@ -24,10 +32,181 @@ impl Default for Type {
     }
 }
 
+/// A randomized delay applied before sending each response to chat.
+#[derive(Clone)]
+pub struct ResponseDelay {
+    /// The shortest delay in milliseconds.
+    pub min: settings::Var<u32>,
+    /// The longest delay in milliseconds.
+    pub max: settings::Var<u32>,
+}
+
+impl ResponseDelay {
+    /// A response delay which never delays.
+    pub fn none() -> Self {
+        Self {
+            min: settings::Var::new(0),
+            max: settings::Var::new(0),
+        }
+    }
+
+    /// Pick how long to wait before sending the next response.
+    async fn pick(&self) -> Option<time::Duration> {
+        let min = self.min.load().await;
+        let max = self.max.load().await;
+        let (min, max) = if min <= max { (min, max) } else { (max, min) };
+
+        if max == 0 {
+            return None;
+        }
+
+        let ms = rand::thread_rng().gen_range(min..=max);
+        Some(time::Duration::from_millis(u64::from(ms)))
+    }
+}
+
+/// Limits on how fast messages are sent to chat.
+#[derive(Clone)]
+pub struct RateLimit {
+    /// The most messages to send every 30 seconds.
+    pub messages: settings::Var<u32>,
+    /// The most messages waiting to be sent before new ones are dropped.
+    pub capacity: settings::Var<u32>,
+}
+
+impl RateLimit {
+    /// The rate limit Twitch applies to regular accounts.
+    pub fn standard() -> Self {
+        Self {
+            messages: settings::Var::new(20),
+            capacity: settings::Var::new(50),
+        }
+    }
+}
+
+/// Rate limiter for messages sent to chat, shared by everything sending
+/// through the same connection.
+struct Limiter {
+    rate_limit: RateLimit,
+    /// When the messages in the current window were sent.
+    ///
+    /// Held while waiting for a slot, so that messages are sent in the order
+    /// they were queued.
+    sent: Mutex<VecDeque<time::Instant>>,
+    /// Messages waiting for a slot.
+    queued: AtomicUsize,
+}
+
+impl Limiter {
+    fn new(rate_limit: RateLimit) -> Self {
+        Self {
+            rate_limit,
+            sent: Mutex::new(VecDeque::new()),
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reserve a place in the queue of messages waiting to be sent.
+    ///
+    /// Returns `None` if the queue is full, in which case the message should
+    /// be dropped.
+    async fn reserve(&self) -> Option<Reservation<'_>> {
+        let capacity = self.rate_limit.capacity.load().await as usize;
+
+        if self.queued.fetch_add(1, Ordering::SeqCst) >= capacity {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+
+        Some(Reservation { limiter: self })
+    }
+}
+
+/// A place in the queue of messages waiting to be sent.
+///
+/// The place is given up once the reservation has been acquired or dropped.
+struct Reservation<'a> {
+    limiter: &'a Limiter,
+}
+
+impl Reservation<'_> {
+    /// Wait until another message can be sent.
+    async fn acquire(self) {
+        let mut sent = self.limiter.sent.lock().await;
+
+        loop {
+            let messages = self.limiter.rate_limit.messages.load().await as usize;
+
+            match next_slot(&mut sent, messages, time::Instant::now()) {
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => return,
+            }
+        }
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.limiter.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Try to take a slot to send a message at `now`, given when messages in the
+/// current window were sent.
+///
+/// Returns how long to wait before trying again if all slots are taken.
+fn next_slot(
+    sent: &mut VecDeque<time::Instant>,
+    messages: usize,
+    now: time::Instant,
+) -> Option<time::Duration> {
+    while let Some(at) = sent.front() {
+        if now.saturating_duration_since(*at) < RATE_LIMIT_WINDOW {
+            break;
+        }
+
+        sent.pop_front();
+    }
+
+    if sent.len() < usize::max(messages, 1) {
+        sent.push_back(now);
+        return None;
+    }
+
+    let oldest = *sent.front()?;
+    Some((oldest + RATE_LIMIT_WINDOW).saturating_duration_since(now))
+}
+
+/// The transport used by a sender.
+enum Transport {
+    /// Send messages over an IRC client.
+    Client(client::Sender),
+    /// Capture sent messages, used when testing.
+    #[cfg(test)]
+    Capture(Arc<parking_lot::Mutex<Vec<Message>>>),
+}
+
+impl Transport {
+    /// Send the given message over the transport.
+    fn send(&self, m: impl Into<Message>) -> Result<()> {
+        match self {
+            Self::Client(sender) => sender.send(m)?,
+            #[cfg(test)]
+            Self::Capture(sent) => sent.lock().push(m.into()),
+        }
+
+        Ok(())
+    }
+}
+
 struct Inner {
     target: String,
-    sender: client::Sender,
-    limiter: LeakyBucket,
+    sender: Transport,
+    response_delay: ResponseDelay,
+    /// Held while a response is being delayed, so that delayed responses are
+    /// sent in the order they were issued.
+    ordering: Mutex<()>,
+    limiter: Limiter,
     nightbot_limiter: LeakyBucket,
     nightbot: injector::Ref<api::NightBot>,
 }
@@ -44,17 +223,54 @@ impl Sender {
         ty: settings::Var<Type>,
         target: String,
         sender: client::Sender,
+        response_delay: ResponseDelay,
+        rate_limit: RateLimit,
         nightbot: injector::Ref<api::NightBot>,
         buckets: &LeakyBuckets,
     ) -> Result<Sender> {
-        // limiter to use for IRC chat messages.
-        let limiter = buckets
-            .rate_limiter()
-            .refill_amount(10)
-            .refill_interval(time::Duration::from_secs(1))
-            .max(95)
-            .build()?;
+        Self::with_transport(
+            ty,
+            target,
+            Transport::Client(sender),
+            response_delay,
+            rate_limit,
+            nightbot,
+            buckets,
+        )
+    }
 
+    /// Create a new sender which captures all sent messages instead of
+    /// sending them.
+    #[cfg(test)]
+    pub(crate) fn capture(
+        ty: settings::Var<Type>,
+        target: String,
+        nightbot: injector::Ref<api::NightBot>,
+        buckets: &LeakyBuckets,
+    ) -> Result<(Sender, Arc<parking_lot::Mutex<Vec<Message>>>)> {
+        let sent = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let transport = Transport::Capture(sent.clone());
+        let sender = Self::with_transport(
+            ty,
+            target,
+            transport,
+            ResponseDelay::none(),
+            RateLimit::standard(),
+            nightbot,
+            buckets,
+        )?;
+        Ok((sender, sent))
+    }
+
+    fn with_transport(
+        ty: settings::Var<Type>,
+        target: String,
+        sender: Transport,
+        response_delay: ResponseDelay,
+        rate_limit: RateLimit,
+        nightbot: injector::Ref<api::NightBot>,
+        buckets: &LeakyBuckets,
+    ) -> Result<Sender> {
         let nightbot_limiter = buckets
             .rate_limiter()
             .max(1)
@@ -66,7 +282,9 @@ impl Sender {
             inner: Arc::new(Inner {
                 target,
                 sender,
-                limiter,
+                response_delay,
+                ordering: Mutex::new(()),
+                limiter: Limiter::new(rate_limit),
                 nightbot_limiter,
                 nightbot,
             }),
@@ -83,6 +301,16 @@ impl Sender {
         self.privmsg_immediate(format!("/delete {}", id));
     }
 
+    /// Time out the given user.
+    pub fn timeout(&self, user: &str, duration: utils::Duration, reason: &str) {
+        self.privmsg_immediate(format!(
+            "/timeout {} {} {}",
+            user,
+            duration.num_seconds(),
+            reason
+        ));
+    }
+
     /// Get list of mods.
     pub fn mods(&self) {
         self.privmsg_immediate("/mods");
@@ -97,9 +325,12 @@ impl Sender {
     pub async fn send(&self, m: impl Into<Message>) {
         let m = m.into();
 
-        if let Err(e) = self.inner.limiter.acquire(1).await {
-            log_error!(e, "error in limiter");
-            return;
+        match self.inner.limiter.reserve().await {
+            Some(reservation) => reservation.acquire().await,
+            None => {
+                dropped(&m);
+                return;
+            }
         }
 
         if let Err(e) = self.inner.sender.send(m) {
@@ -116,13 +347,34 @@ impl Sender {
 
     /// Send a PRIVMSG.
     pub async fn privmsg(&self, f: impl fmt::Display) {
-        match self.ty.load().await {
-            Type::NightBot => {
-                self.send_nightbot(&*self.inner, f.to_string()).await;
+        let m = f.to_string();
+
+        // NB: the place in the queue is reserved before waiting for earlier
+        // responses, so that the queue capacity covers them as well.
+        let reservation = match self.ty.load().await {
+            Type::NightBot => None,
+            Type::Chat => match self.inner.limiter.reserve().await {
+                Some(reservation) => Some(reservation),
+                None => {
+                    dropped(&m);
+                    return;
+                }
+            },
+        };
+
+        let _ordering = self.inner.ordering.lock().await;
+
+        if let Some(delay) = self.inner.response_delay.pick().await {
+            tokio::time::sleep(delay).await;
+        }
+
+        match reservation {
+            None => {
+                self.send_nightbot(&self.inner, m).await;
             }
-            Type::Chat => {
-                self.send(Command::PRIVMSG(self.inner.target.clone(), f.to_string()))
-                    .await;
+            Some(reservation) => {
+                reservation.acquire().await;
+                self.send_immediate(Command::PRIVMSG(self.inner.target.clone(), m));
             }
         }
     }
@@ -180,3 +432,137 @@ impl Sender {
         }
     }
 }
+
+/// Warn about a message dropped because too many are waiting to be sent.
+fn dropped(m: impl fmt::Display) {
+    log::warn!(
+        "Too many messages waiting to be sent to chat, dropping: {}",
+        m
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_slot, RateLimit, ResponseDelay, Sender, Transport, RATE_LIMIT_WINDOW};
+    use crate::injector::Injector;
+    use crate::settings;
+    use anyhow::Result;
+    use irc::proto::command::Command;
+    use leaky_bucket::LeakyBuckets;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_response_delay_keeps_order() -> Result<()> {
+        let mut buckets = LeakyBuckets::new();
+        let coordinate = buckets.coordinate()?;
+        tokio::spawn(coordinate);
+
+        let sent = Arc::new(parking_lot::Mutex::new(Vec::new()));
+
+        let sender = Sender::with_transport(
+            settings::Var::new(Default::default()),
+            String::from("#channel"),
+            Transport::Capture(sent.clone()),
+            ResponseDelay {
+                min: settings::Var::new(0),
+                max: settings::Var::new(20),
+            },
+            RateLimit::standard(),
+            Injector::new().var().await,
+            &buckets,
+        )?;
+
+        futures_util::future::join_all((0..10).map(|n| sender.privmsg(n))).await;
+
+        let sent = sent
+            .lock()
+            .iter()
+            .filter_map(|m| match &m.command {
+                Command::PRIVMSG(_, m) => Some(m.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let expected = (0..10).map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(sent, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_privmsg_drops_when_full() -> Result<()> {
+        let mut buckets = LeakyBuckets::new();
+        let coordinate = buckets.coordinate()?;
+        tokio::spawn(coordinate);
+
+        let sent = Arc::new(parking_lot::Mutex::new(Vec::new()));
+
+        let sender = Sender::with_transport(
+            settings::Var::new(Default::default()),
+            String::from("#channel"),
+            Transport::Capture(sent.clone()),
+            ResponseDelay::none(),
+            RateLimit {
+                messages: settings::Var::new(1),
+                capacity: settings::Var::new(2),
+            },
+            Injector::new().var().await,
+            &buckets,
+        )?;
+
+        let done = AtomicUsize::new(0);
+
+        let all = futures_util::future::join_all((0..5).map(|n| {
+            let sender = &sender;
+            let done = &done;
+
+            async move {
+                sender.privmsg(n).await;
+                done.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+
+        // NB: waiting messages need the window to free up, which takes longer
+        // than the test.
+        assert!(tokio::time::timeout(Duration::from_millis(200), all)
+            .await
+            .is_err());
+
+        let sent = sent
+            .lock()
+            .iter()
+            .filter_map(|m| match &m.command {
+                Command::PRIVMSG(_, m) => Some(m.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(sent, vec![String::from("0")]);
+        // One message sent, two waiting for a slot and two dropped.
+        assert_eq!(done.load(Ordering::SeqCst), 3);
+        assert_eq!(sender.inner.limiter.queued.load(Ordering::SeqCst), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_slot() {
+        let start = Instant::now();
+        let mut sent = VecDeque::new();
+
+        assert_eq!(next_slot(&mut sent, 2, start), None);
+        assert_eq!(
+            next_slot(&mut sent, 2, start + Duration::from_secs(10)),
+            None
+        );
+        assert_eq!(
+            next_slot(&mut sent, 2, start + Duration::from_secs(20)),
+            Some(Duration::from_secs(10))
+        );
+
+        // NB: the first message has left the window.
+        assert_eq!(next_slot(&mut sent, 2, start + RATE_LIMIT_WINDOW), None);
+        assert_eq!(sent.len(), 2);
+    }
+}
@@ -0,0 +1,82 @@
+//! Detect messages which are mostly uppercase letters or mostly symbols.
+
+use std::fmt;
+
+/// Why a message was caught by the spam filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reason {
+    /// Too many uppercase letters.
+    Caps,
+    /// Too many symbols.
+    Symbols,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Caps => "excessive caps".fmt(fmt),
+            Reason::Symbols => "excessive symbols".fmt(fmt),
+        }
+    }
+}
+
+/// Test if the given message is spam.
+///
+/// Messages with fewer than `min_length` non-whitespace characters are never
+/// spam. The ratios are percentages, where `0` disables the check.
+pub(crate) fn test(
+    message: &str,
+    min_length: u32,
+    caps_ratio: u32,
+    symbols_ratio: u32,
+) -> Option<Reason> {
+    let mut length = 0u32;
+    let mut letters = 0u32;
+    let mut upper = 0u32;
+    let mut symbols = 0u32;
+
+    for c in message.chars().filter(|c| !c.is_whitespace()) {
+        length += 1;
+
+        if c.is_alphabetic() {
+            letters += 1;
+
+            if c.is_uppercase() {
+                upper += 1;
+            }
+        } else if !c.is_alphanumeric() {
+            symbols += 1;
+        }
+    }
+
+    if length == 0 || length < min_length {
+        return None;
+    }
+
+    if caps_ratio > 0 && letters > 0 && upper * 100 >= caps_ratio * letters {
+        return Some(Reason::Caps);
+    }
+
+    if symbols_ratio > 0 && symbols * 100 >= symbols_ratio * length {
+        return Some(Reason::Symbols);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test, Reason};
+
+    #[test]
+    fn test_spam() {
+        assert_eq!(test("THIS IS SO COOL", 10, 70, 50), Some(Reason::Caps));
+        assert_eq!(test("This is so cool", 10, 70, 50), None);
+        // NB: too short to be punished.
+        assert_eq!(test("LOL", 10, 70, 50), None);
+        assert_eq!(test("!!!!!??????###", 10, 70, 50), Some(Reason::Symbols));
+        assert_eq!(test("Wow!!! That was great", 10, 70, 50), None);
+        assert_eq!(test("THIS IS SO COOL", 10, 0, 50), None);
+        assert_eq!(test("", 0, 70, 50), None);
+    }
+}
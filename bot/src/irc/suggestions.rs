@@ -0,0 +1,109 @@
+//! Suggest the closest matching command when someone makes a typo, like
+//! `!sogn` instead of `!song`.
+
+use crate::settings;
+use crate::utils::{self, Duration};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time;
+
+pub(crate) struct Suggestions {
+    enabled: settings::Var<bool>,
+    cooldown: settings::Var<Duration>,
+    /// When each user was last given a suggestion.
+    suggested_at: HashMap<String, time::Instant>,
+}
+
+impl Suggestions {
+    pub(crate) async fn new(settings: &crate::Settings) -> Result<Self> {
+        Ok(Self {
+            enabled: settings.var("enabled", false).await?,
+            cooldown: settings.var("cooldown", Duration::seconds(5 * 60)).await?,
+            suggested_at: HashMap::new(),
+        })
+    }
+
+    /// Find a command to suggest instead of the one which was typed, unless
+    /// suggestions are disabled or the user was recently given one.
+    pub(crate) async fn suggest(
+        &mut self,
+        user: &str,
+        command: &str,
+        candidates: impl IntoIterator<Item = String>,
+    ) -> Option<String> {
+        if !self.enabled.load().await {
+            return None;
+        }
+
+        let cooldown = self.cooldown.load().await.as_std();
+        let now = time::Instant::now();
+
+        self.suggested_at
+            .retain(|_, at| now.duration_since(*at) < cooldown);
+
+        if self.suggested_at.contains_key(user) {
+            return None;
+        }
+
+        let suggestion = closest(command, candidates)?;
+        self.suggested_at.insert(user.to_string(), now);
+        Some(suggestion)
+    }
+}
+
+/// Find the candidate closest to the given command, as long as it's within a
+/// small edit distance.
+fn closest(command: &str, candidates: impl IntoIterator<Item = String>) -> Option<String> {
+    let command = command.to_lowercase();
+
+    // NB: short commands are only a couple of edits away from most other
+    // short commands.
+    let max = if command.chars().count() <= 4 { 1 } else { 2 };
+
+    let mut best = None::<(usize, String)>;
+
+    for candidate in candidates {
+        let distance = utils::edit_distance(&command, &candidate.to_lowercase());
+
+        if distance == 0 || distance > max {
+            continue;
+        }
+
+        let better = match &best {
+            Some((d, c)) => distance < *d || (distance == *d && candidate < *c),
+            None => true,
+        };
+
+        if better {
+            best = Some((distance, candidate));
+        }
+    }
+
+    best.map(|(_, c)| c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::closest;
+
+    fn candidates() -> Vec<String> {
+        vec!["!song", "!swearjar", "!sr", "!uptime", "!water"]
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_closest() {
+        assert_eq!(closest("!sogn", candidates()), Some(String::from("!song")));
+        assert_eq!(closest("!SONGS", candidates()), Some(String::from("!song")));
+        assert_eq!(
+            closest("!uptiem", candidates()),
+            Some(String::from("!uptime"))
+        );
+        assert_eq!(closest("!st", candidates()), Some(String::from("!sr")));
+        assert_eq!(closest("!song", candidates()), None);
+        assert_eq!(closest("!hello", candidates()), None);
+        assert_eq!(closest("!xy", candidates()), None);
+    }
+}
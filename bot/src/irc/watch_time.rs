@@ -0,0 +1,116 @@
+//! Sampling of who is watching the stream, to credit them with watch time.
+//!
+//! Watch time is kept with the balances of the built-in currency, so it's the
+//! same watch time that is shown by `!balance`.
+
+use crate::api;
+use crate::db;
+use anyhow::Result;
+use std::collections::HashSet;
+use std::convert::TryFrom as _;
+use std::time;
+
+/// Keeps track of how much time to credit viewers with each time chat is
+/// sampled.
+///
+/// Viewers are credited with the time since the last sample, so no stretch
+/// of time is counted twice even if samples are taken irregularly.
+#[derive(Debug, Default)]
+pub(crate) struct Sampler {
+    last: Option<time::Instant>,
+    /// Time which has been sampled but not credited since it was less than a
+    /// second.
+    carry: time::Duration,
+}
+
+impl Sampler {
+    /// Take a sample at `now`, returning the number of seconds to credit.
+    ///
+    /// At most `max` is credited since the last sample, which avoids
+    /// crediting time while the bot couldn't check who was watching.
+    pub(crate) fn sample(&mut self, now: time::Instant, max: time::Duration) -> u64 {
+        let last = match self.last.replace(now) {
+            Some(last) => last,
+            None => return 0,
+        };
+
+        let total = self.carry + now.saturating_duration_since(last).min(max);
+        let seconds = total.as_secs();
+        self.carry = total - time::Duration::from_secs(seconds);
+        seconds
+    }
+
+    /// Reset the sampler, like when the stream goes offline.
+    pub(crate) fn reset(&mut self) {
+        self.last = None;
+        self.carry = time::Duration::default();
+    }
+}
+
+/// Credit everyone in the chat of the given channel with watch time since
+/// the last sample.
+pub(crate) async fn credit(
+    sampler: &mut Sampler,
+    twitch: &api::Twitch,
+    db: &dyn db::Backend,
+    channel: &str,
+    max: time::Duration,
+) -> Result<()> {
+    let seconds = sampler.sample(time::Instant::now(), max);
+
+    if seconds == 0 {
+        return Ok(());
+    }
+
+    let chatters = twitch.chatters(channel).await?;
+
+    let mut users = HashSet::new();
+    users.extend(chatters.broadcaster);
+    users.extend(chatters.vips);
+    users.extend(chatters.moderators);
+    users.extend(chatters.viewers);
+
+    log::trace!(
+        "crediting {} users with {}s watch time",
+        users.len(),
+        seconds
+    );
+
+    let users = users.iter().map(|user| db::user_id(user)).collect();
+    let seconds = i64::try_from(seconds).unwrap_or(i64::MAX);
+
+    db.balances_increment(&db::channel_id(channel), users, 0, seconds)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sampler;
+    use std::time;
+
+    #[test]
+    fn test_sampler() {
+        let now = time::Instant::now();
+        let max = time::Duration::from_secs(5 * 60);
+        let mut sampler = Sampler::default();
+
+        assert_eq!(sampler.sample(now, max), 0);
+        assert_eq!(
+            sampler.sample(now + time::Duration::from_millis(1500), max),
+            1
+        );
+        // NB: the half second left over from the last sample is carried.
+        assert_eq!(sampler.sample(now + time::Duration::from_secs(90), max), 89);
+        // NB: long gaps are capped.
+        assert_eq!(
+            sampler.sample(now + time::Duration::from_secs(3600), max),
+            300
+        );
+
+        sampler.reset();
+        assert_eq!(
+            sampler.sample(now + time::Duration::from_secs(7200), max),
+            0
+        );
+    }
+}
@@ -36,6 +36,7 @@ mod command;
 pub mod currency;
 pub mod db;
 pub mod emotes;
+pub mod filters;
 mod idle;
 pub mod irc;
 pub mod message_log;
@@ -38,6 +38,8 @@ const FILE: &str = "file";
 #[cfg(not(feature = "windows"))]
 const STDOUT: &str = "stdout";
 const PACKAGE: &str = env!("CARGO_PKG_NAME");
+/// How long to wait for everything to wind down when shutting down.
+const SHUTDOWN_TIMEOUT: time::Duration = time::Duration::from_secs(10);
 
 #[cfg(feature = "windows")]
 mod internal {
@@ -451,6 +453,11 @@ async fn try_main(
 
     injector.update(db.clone()).await;
 
+    // NB: shared by commands and the currency, so that writes through either
+    // are seen by both.
+    let backend = Arc::new(db::Cached::new(db::Sqlite::new(db.clone())));
+    injector.update(backend.clone()).await;
+
     let scopes_schema = auth::Schema::load_static()?;
     let auth = db.auth(scopes_schema).await?;
     injector.update(auth.clone()).await;
@@ -472,18 +479,31 @@ async fn try_main(
 
     injector.update(settings.clone()).await;
 
+    futures.push(Box::pin(
+        auth::test_accounts(auth.clone(), &settings)
+            .await?
+            .instrument(trace_span!(target: "futures", "auth-test-accounts",)),
+    ));
+
     let bad_words = db::Words::load(db.clone()).await?;
     injector.update(bad_words).await;
 
     injector
         .update(db::AfterStreams::load(db.clone()).await?)
         .await;
-    injector.update(db::Commands::load(db.clone()).await?).await;
+    injector
+        .update(db::Commands::load_backend(backend.clone()).await?)
+        .await;
     injector.update(db::Aliases::load(db.clone()).await?).await;
     injector
         .update(db::Promotions::load(db.clone()).await?)
         .await;
     injector.update(db::Themes::load(db.clone()).await?).await;
+    injector.update(db::Timers::load(db.clone()).await?).await;
+    injector.update(db::Waters::load(db.clone()).await?).await;
+    injector
+        .update(db::Quotes::load_backend(backend.clone()).await?)
+        .await;
 
     let message_bus = bus::Bus::new();
     injector.update(message_bus.clone()).await;
@@ -499,6 +519,20 @@ async fn try_main(
             .instrument(trace_span!(target: "futures", "system-loop",)),
     ));
 
+    let notifier =
+        sys::Notifier::new(&settings.scoped("system/notifications"), system.clone()).await?;
+    injector.update(notifier.clone()).await;
+
+    futures.push(Box::pin(
+        notify_subscriptions(global_bus.clone(), notifier)
+            .instrument(trace_span!(target: "futures", "notify-subscriptions",)),
+    ));
+
+    futures.push(Box::pin(
+        db::health_check(settings.scoped("database"), db.clone())
+            .instrument(trace_span!(target: "futures", "database-health-check",)),
+    ));
+
     injector.update(storage.cache()?).await;
 
     let (latest, future) = updater::run(&injector);
@@ -512,6 +546,15 @@ async fn try_main(
         .build();
     injector.update(message_log.clone()).await;
 
+    let web_host = settings
+        .get::<String>("web/host")
+        .await?
+        .unwrap_or_else(|| String::from(web::DEFAULT_HOST));
+    let web_port = settings
+        .get::<u16>("web/port")
+        .await?
+        .unwrap_or(web::DEFAULT_PORT);
+
     let (web, future) = web::setup(
         &injector,
         message_log.clone(),
@@ -521,9 +564,14 @@ async fn try_main(
         command_bus.clone(),
         auth.clone(),
         latest.clone(),
+        &web_host,
+        web_port,
     )
     .await?;
 
+    system.set_url(web.url().to_string());
+    injector.update(web.clone()).await;
+
     futures.push(Box::pin(
         async {
             future.await;
@@ -533,16 +581,16 @@ async fn try_main(
     ));
 
     if settings.get::<bool>("first-run").await?.unwrap_or(true) {
-        log::info!("Opening {} for the first time", web::URL);
+        log::info!("Opening {} for the first time", web.url());
 
-        if let Err(e) = webbrowser::open(web::URL) {
+        if let Err(e) = webbrowser::open(web.url()) {
             log::error!("failed to open browser: {}", e);
         }
 
         settings.set("first-run", false).await?;
     }
 
-    log::info!("Listening on: {}", web::URL);
+    log::info!("Listening on: {}", web.url());
 
     let token_settings = settings.scoped("secrets/oauth2");
 
@@ -620,8 +668,8 @@ async fn try_main(
         (spotify_token, spotify_future),
         (youtube_token, youtube_future),
         (_, nightbot_future),
-        (_, streamer_future),
-        (_, bot_future),
+        (streamer_token, streamer_future),
+        (bot_token, bot_future),
     ) = tokio::try_join!(
         spotify_setup,
         youtube_setup,
@@ -630,7 +678,11 @@ async fn try_main(
         bot_setup
     )?;
 
-    futures.push(Box::pin(api::twitch::pubsub::connect(&settings, &injector)));
+    futures.push(Box::pin(api::twitch::pubsub::connect(
+        &settings,
+        &injector,
+        &global_bus,
+    )));
     futures.push(Box::pin(api::twitch_clients_task(injector.clone())));
 
     futures.push(Box::pin(
@@ -653,6 +705,12 @@ async fn try_main(
         bot_future.instrument(trace_span!(target: "futures", "bot-token",)),
     ));
 
+    futures.push(Box::pin(
+        oauth2::auth_timeout(&settings, vec![streamer_token, bot_token], web.url())
+            .await?
+            .instrument(trace_span!(target: "futures", "auth-timeout",)),
+    ));
+
     futures.push(Box::pin(
         api::open_weather_map::setup(settings.clone(), injector.clone())
             .await?
@@ -663,6 +721,18 @@ async fn try_main(
     injector.update(restart).await;
 
     let spotify = Arc::new(api::Spotify::new(spotify_token.clone())?);
+    injector.update(spotify.clone()).await;
+
+    futures.push(Box::pin(
+        api::spotify::limit_requests(spotify.clone(), &settings)
+            .await?
+            .instrument(trace_span!(target: "futures", "spotify-limiter",)),
+    ));
+    futures.push(Box::pin(
+        api::spotify::cache_tracks(spotify.clone(), &settings)
+            .await?
+            .instrument(trace_span!(target: "futures", "spotify-track-cache",)),
+    ));
     let youtube = Arc::new(api::YouTube::new(youtube_token.clone())?);
     injector.update(youtube.clone()).await;
 
@@ -698,17 +768,31 @@ async fn try_main(
     modules.push(Box::new(module::song::Module));
     modules.push(Box::new(module::command_admin::Module));
     modules.push(Box::new(module::admin::Module));
+    modules.push(Box::new(module::config::Module));
     modules.push(Box::new(module::alias_admin::Module));
     modules.push(Box::new(module::theme_admin::Module));
     modules.push(Box::new(module::promotions::Module));
+    modules.push(Box::new(module::announcements::Module));
+    modules.push(Box::new(module::timer::Module));
     modules.push(Box::new(module::swearjar::Module));
+    modules.push(Box::new(module::balance::Module));
     modules.push(Box::new(module::countdown::Module));
     modules.push(Box::new(module::gtav::Module));
     modules.push(Box::new(module::water::Module));
     modules.push(Box::new(module::misc::Module));
+    modules.push(Box::new(module::shoutout::Module));
+    modules.push(Box::new(module::uptime::Module));
     modules.push(Box::new(module::after_stream::Module));
     modules.push(Box::new(module::clip::Module));
-    modules.push(Box::new(module::eight_ball::Module));
+    modules.push(Box::new(module::fun::Module));
+    modules.push(Box::new(module::quote::Module));
+    modules.push(Box::new(module::giveaway::Module));
+    modules.push(Box::new(module::gamble::Module));
+    modules.push(Box::new(module::events::Module));
+    modules.push(Box::new(module::watch_time::Module));
+    modules.push(Box::new(module::count::Module));
+    modules.push(Box::new(module::filters::Module));
+    modules.push(Box::new(module::bad_words::Module));
     modules.push(Box::new(module::speedrun::Module));
     modules.push(Box::new(module::auth::Module));
     modules.push(Box::new(module::poll::Module));
@@ -717,43 +801,96 @@ async fn try_main(
 
     let (stream_state_tx, stream_state_rx) = mpsc::channel(64);
 
-    let notify_after_streams = notify_after_streams(&injector, stream_state_rx, system.clone());
+    let notify_after_streams = notify_after_streams(
+        &injector,
+        stream_state_rx,
+        system.clone(),
+        web.url().to_string(),
+    );
     futures.push(Box::pin(
         notify_after_streams.instrument(trace_span!(target: "futures", "notify-after-streams",)),
     ));
 
+    let shutdown = utils::Shutdown::new();
+
     let irc = irc::Irc {
         modules,
         injector: injector.clone(),
         stream_state_tx,
         script_dirs: script_dirs.clone(),
+        shutdown: shutdown.clone(),
     };
 
-    futures.push(Box::pin(
-        irc.run().instrument(trace_span!(target: "futures", "irc",)),
-    ));
+    let irc = irc.run().instrument(trace_span!(target: "futures", "irc",));
+    tokio::pin!(irc);
 
     tokio::select! {
+        result = &mut irc => {
+            return result.map(|_| Intent::Shutdown);
+        }
         Some(result) = futures.next() => {
-            result.map(|_| Intent::Shutdown)
+            return result.map(|_| Intent::Shutdown);
         }
         _ = system.wait_for_shutdown() => {
             log::info!("shutdown triggered by system");
-            Ok(Intent::Shutdown)
         },
         _ = system.wait_for_restart() => {
             log::info!("restart triggered by system");
-            Ok(Intent::Restart)
+            return Ok(Intent::Restart);
         },
         _ = restart_rx => {
             log::info!("restart triggered by bot");
-            Ok(Intent::Restart)
+            return Ok(Intent::Restart);
         },
         _ = tokio::signal::ctrl_c() => {
             log::info!("shutdown triggered by signal");
-            Ok(Intent::Shutdown)
         },
     }
+
+    log::info!("Shutting down, press Ctrl+C again to exit immediately...");
+    shutdown.trigger();
+
+    // Keep driving everything else while we wind down, so that pending
+    // database writes and the player get to finish what they're doing.
+    let background = async {
+        while let Some(result) = futures.next().await {
+            if let Err(e) = result {
+                oxidize::log_error!(e, "Error while shutting down");
+            }
+        }
+
+        std::future::pending::<()>().await
+    };
+
+    let graceful = async {
+        if let Some(player::State::Playing) = injector.get::<player::State>().await {
+            if let Some(player) = injector.get::<player::Player>().await {
+                if let Err(e) = player.pause().await {
+                    oxidize::log_error!(e, "Failed to pause player");
+                }
+            }
+        }
+
+        // NB: completes once the leave message has been sent.
+        irc.await
+    };
+
+    tokio::select! {
+        result = graceful => {
+            if let Err(e) = result {
+                oxidize::log_error!(e, "Failed to leave chat");
+            }
+        }
+        _ = background => (),
+        _ = tokio::time::sleep(SHUTDOWN_TIMEOUT) => {
+            log::warn!("Timed out waiting for a graceful shutdown");
+        }
+        _ = tokio::signal::ctrl_c() => {
+            log::warn!("Shutdown forced by signal");
+        }
+    }
+
+    Ok(Intent::Shutdown)
 }
 
 /// Notify if there are any after streams.
@@ -763,6 +900,7 @@ async fn notify_after_streams(
     injector: &Injector,
     mut rx: mpsc::Receiver<stream_info::StreamState>,
     system: sys::System,
+    url: String,
 ) -> Result<()> {
     let (mut after_streams_stream, mut after_streams) = injector.stream::<db::AfterStreams>().await;
 
@@ -790,8 +928,10 @@ async fn notify_after_streams(
                                 list.len()
                             ));
 
-                            let reminder = reminder.on_click(|| {
-                                webbrowser::open(&format!("{}/after-streams", web::URL))?;
+                            let url = format!("{}/after-streams", url);
+
+                            let reminder = reminder.on_click(move || {
+                                webbrowser::open(&url)?;
                                 Ok(())
                             });
 
@@ -804,6 +944,38 @@ async fn notify_after_streams(
     }
 }
 
+/// Show a desktop notification when someone subscribes.
+async fn notify_subscriptions(
+    global_bus: bus::Bus<bus::Global>,
+    notifier: sys::Notifier,
+) -> Result<()> {
+    use tokio::sync::broadcast::error::RecvError;
+
+    let mut rx = global_bus.subscribe();
+
+    loop {
+        let (user, months) = match rx.recv().await {
+            Ok(bus::Global::TwitchSubscription { user, months, .. }) => (user, months),
+            Ok(..) | Err(RecvError::Lagged(..)) => continue,
+            Err(RecvError::Closed) => return Ok(()),
+        };
+
+        let body = match months {
+            0 | 1 => format!("{} subscribed!", user),
+            months => format!("{} subscribed for {} months!", user, months),
+        };
+
+        notifier
+            .notify(
+                sys::Category::Subscription,
+                "New subscriber",
+                body,
+                sys::NotificationIcon::Info,
+            )
+            .await;
+    }
+}
+
 /// Run the loop that handles installing this as a service.
 async fn system_loop(settings: oxidize::Settings, system: sys::System) -> Result<()> {
     settings
@@ -3,6 +3,7 @@ use crate::command;
 use crate::db;
 use crate::module;
 use crate::prelude::*;
+use crate::template;
 use anyhow::Result;
 
 /// Handler for the !alias command.
@@ -25,7 +26,12 @@ impl command::Handler for Handler {
                 ctx.check_scope(auth::Scope::AliasEdit).await?;
 
                 let name = ctx.next_str("<name>")?;
-                let template = ctx.rest_parse("<name> <template>")?;
+                let template = ctx.rest_parse::<template::Template, _>("<name> <template>")?;
+
+                if let Err(e) = template.validate(db::Alias::is_var) {
+                    respond_bail!("Bad template: {}", e);
+                }
+
                 aliases.edit(ctx.channel(), &name, template).await?;
 
                 respond!(ctx, "Edited alias");
@@ -36,9 +42,9 @@ impl command::Handler for Handler {
                 let name = ctx.next_str("<name> [pattern]")?;
 
                 let pattern = match ctx.rest() {
-                    pattern if pattern.trim().is_empty() => None,
-                    pattern => match regex::Regex::new(pattern) {
-                        Ok(pattern) => Some(pattern),
+                    pattern if pattern.trim().is_empty() => db::Pattern::Name,
+                    pattern => match db::Pattern::parse(pattern) {
+                        Ok(pattern) => pattern,
                         Err(e) => {
                             ctx.user
                                 .respond(format!("Bad pattern provided: {}", e))
@@ -55,10 +61,23 @@ impl command::Handler for Handler {
 
                 respond!(ctx, "Edited pattern for alias.");
             }
+            Some("games") => {
+                ctx.check_scope(auth::Scope::AliasEdit).await?;
+
+                let name = ctx.next_str("<name> [game | ...]")?;
+                let games = db::Games::parse(ctx.rest());
+
+                if !aliases.edit_games(ctx.channel(), &name, games).await? {
+                    respond!(ctx, format!("No such alias: `{}`", name));
+                    return Ok(());
+                }
+
+                respond!(ctx, "Edited games for alias.");
+            }
             None | Some(..) => {
                 respond!(
                     ctx,
-                    "Expected: show, list, edit, delete, enable, disable, or group."
+                    "Expected: show, list, edit, pattern, games, delete, enable, disable, or group."
                 );
             }
         }
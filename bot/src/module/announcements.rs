@@ -0,0 +1,143 @@
+use crate::irc;
+use crate::module;
+use crate::prelude::*;
+use crate::stream_info;
+use crate::template::Template;
+use crate::utils;
+use anyhow::Result;
+use std::time;
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "announcements"
+    }
+
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            futures,
+            sender,
+            settings,
+            stream_info,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        let jitter = settings
+            .var("chat/jitter", utils::Duration::default())
+            .await?;
+        let settings = settings.scoped("announcements");
+        let enabled = settings.var("enabled", false).await?;
+        let messages = settings.var("messages", Vec::<Template>::new()).await?;
+        let min_chatters = settings.var("min-chatters", 1).await?;
+
+        let (mut interval_stream, interval) = settings
+            .stream("interval")
+            .or_with_else(|| utils::Duration::seconds(15 * 60))
+            .await?;
+
+        let announcer = Announcer {
+            messages,
+            min_chatters,
+            stream_info: stream_info.clone(),
+            active_chatters: injector.var().await,
+            sender: sender.clone(),
+            window: interval.as_std(),
+            next: 0,
+        };
+
+        // NB: the first announcement is only posted after a full interval,
+        // so that reconnecting doesn't post one.
+        let mut cooldown = utils::Cooldown::with_jitter(interval, jitter.load().await);
+        let mut timer = Box::pin(cooldown.sleep());
+
+        let future = async move {
+            let mut announcer = announcer;
+
+            loop {
+                tokio::select! {
+                    update = interval_stream.recv() => {
+                        announcer.window = update.as_std();
+                        cooldown.cooldown = update;
+                        cooldown.jitter = jitter.load().await;
+                        timer = Box::pin(cooldown.sleep());
+                    }
+                    _ = &mut timer => {
+                        cooldown.jitter = jitter.load().await;
+                        timer = Box::pin(cooldown.sleep());
+
+                        if !enabled.load().await {
+                            continue;
+                        }
+
+                        if let Err(e) = announcer.announce().await {
+                            log_error!(e, "failed to post announcement");
+                        }
+                    }
+                }
+            }
+        };
+
+        futures.push(Box::pin(future));
+        Ok(())
+    }
+}
+
+/// Posts announcements in order.
+struct Announcer {
+    messages: settings::Var<Vec<Template>>,
+    min_chatters: settings::Var<u32>,
+    stream_info: stream_info::StreamInfo,
+    active_chatters: injector::Ref<irc::chatters::ActiveChatters>,
+    sender: irc::Sender,
+    /// How far back to look for chat activity.
+    window: time::Duration,
+    /// The index of the next message to post.
+    next: usize,
+}
+
+impl Announcer {
+    /// Post the next announcement, if the stream is live and chat is active
+    /// enough.
+    async fn announce(&mut self) -> Result<()> {
+        if self.stream_info.data.read().stream.is_none() {
+            return Ok(());
+        }
+
+        let chatters = match self.active_chatters.load().await {
+            Some(active_chatters) => active_chatters
+                .active(self.window, time::Instant::now())
+                .len(),
+            None => 0,
+        };
+
+        if chatters < self.min_chatters.load().await as usize {
+            log::trace!("chat is too quiet to post an announcement");
+            return Ok(());
+        }
+
+        let messages = self.messages.load().await;
+
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let template = &messages[self.next % messages.len()];
+        self.next = (self.next + 1) % messages.len();
+
+        let text = template.render_to_string(Vars {
+            channel: self.sender.channel(),
+        })?;
+
+        self.sender.privmsg(text).await;
+        return Ok(());
+
+        #[derive(serde::Serialize)]
+        struct Vars<'a> {
+            channel: &'a str,
+        }
+    }
+}
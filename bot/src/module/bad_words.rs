@@ -0,0 +1,278 @@
+//! Module for editing the bad words which are filtered from chat.
+
+use crate::auth;
+use crate::command;
+use crate::db;
+use crate::module;
+use crate::prelude::*;
+use crate::template;
+use anyhow::Result;
+
+/// Handler for the `!badwords` command.
+pub struct Handler {
+    words: injector::Ref<db::Words>,
+}
+
+impl Handler {
+    /// Add the next word with an optional reason to the given category.
+    async fn add(
+        &self,
+        ctx: &mut command::Context,
+        words: &db::Words,
+        category: Option<&str>,
+    ) -> Result<()> {
+        let word = ctx.next_str("<word> [why...]")?;
+
+        let why = match ctx.rest().trim() {
+            "" => None,
+            why => {
+                if let Err(e) = template::Template::compile(why) {
+                    respond!(ctx, "Bad reason: {}", e);
+                    return Ok(());
+                }
+
+                Some(why)
+            }
+        };
+
+        words.edit(&word, why, category).await?;
+
+        match category {
+            Some(category) => respond!(ctx, "Added bad word to category `{}`.", category),
+            None => respond!(ctx, "Added bad word."),
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::BadWordsEdit)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        let words = match self.words.load().await {
+            Some(words) => words,
+            None => return Ok(()),
+        };
+
+        match ctx.next().as_deref() {
+            Some("add") => {
+                self.add(ctx, &words, None).await?;
+            }
+            Some("remove") => {
+                let word = ctx.next_str("<word>")?;
+
+                if words.delete(&word).await? {
+                    respond!(ctx, "Removed bad word.");
+                } else {
+                    respond!(ctx, "No such bad word.");
+                }
+            }
+            Some("allow") => {
+                let word = ctx.next_str("<word>")?;
+                words.allow(ctx.channel(), &word).await?;
+                respond!(ctx, "Allowed `{}` in this channel.", word);
+            }
+            Some("disallow") => {
+                let word = ctx.next_str("<word>")?;
+
+                if words.disallow(ctx.channel(), &word).await? {
+                    respond!(ctx, "`{}` is no longer allowed in this channel.", word);
+                } else {
+                    respond!(ctx, "`{}` isn't allowed in this channel.", word);
+                }
+            }
+            Some("allowed") => {
+                let allowed = words.allowed(ctx.channel()).await;
+                ctx.respond_lines(allowed, "No words allowed in this channel.")
+                    .await;
+            }
+            Some("categories") => {
+                let categories = words.categories().await;
+                ctx.respond_lines(categories, "No categories configured.")
+                    .await;
+            }
+            Some("category") => {
+                let name = ctx.next_str("<name> <on | off | action | add>")?;
+                let mut category = words.category(&name).await;
+
+                match ctx.next().as_deref() {
+                    Some("on") => {
+                        category.enabled = true;
+                        words.edit_category(category).await?;
+                        respond!(ctx, "Enabled bad words category `{}`.", name);
+                    }
+                    Some("off") => {
+                        category.enabled = false;
+                        words.edit_category(category).await?;
+                        respond!(ctx, "Disabled bad words category `{}`.", name);
+                    }
+                    Some("action") => {
+                        category.action = ctx.next_parse("<delete | warn>")?;
+                        let action = category.action;
+                        words.edit_category(category).await?;
+                        respond!(ctx, "Bad words in `{}` will now {}.", name, action);
+                    }
+                    Some("add") => {
+                        self.add(ctx, &words, Some(&category.name)).await?;
+                    }
+                    None => {
+                        respond!(ctx, "{}", category);
+                    }
+                    _ => {
+                        respond!(ctx, "Expected: on, off, action, or add.");
+                    }
+                }
+            }
+            _ => {
+                respond!(
+                    ctx,
+                    "Expected: add, remove, allow, disallow, allowed, categories, or category."
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "bad-words"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers, injector, ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        handlers.insert(
+            "badwords",
+            Handler {
+                words: injector.var().await,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Handler;
+    use crate::db;
+    use crate::irc::mock::{Mock, CHANNEL};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_bad_words_categories() -> Result<()> {
+        let mock = Mock::new().await?;
+        mock.moderator("moderator");
+
+        let db = mock.injector.get::<db::Database>().await.expect("database");
+        let words = db::Words::load(db.clone()).await?;
+        mock.injector.update(words.clone()).await;
+
+        let handler = Handler {
+            words: mock.injector.var().await,
+        };
+
+        mock.run(&handler, "moderator", "add heck").await?;
+        mock.run(
+            &handler,
+            "moderator",
+            "category spoilers add rosebud It's a sled!",
+        )
+        .await?;
+        mock.run(&handler, "moderator", "category spoilers action warn")
+            .await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("moderator -> Added bad word."),
+                String::from("moderator -> Added bad word to category `spoilers`."),
+                String::from("moderator -> Bad words in `spoilers` will now warn."),
+            ]
+        );
+
+        {
+            let tester = words.tester().await;
+            let m = tester.test("rosebud").expect("match");
+            assert_eq!(m.word.category.as_deref(), Some("spoilers"));
+            assert_eq!(m.action, db::BadWordAction::Warn);
+            let m = tester.test("heck").expect("match");
+            assert_eq!(m.word.category, None);
+            assert_eq!(m.action, db::BadWordAction::Delete);
+        }
+
+        mock.run(&handler, "moderator", "category spoilers off")
+            .await?;
+        assert!(words.tester().await.test("rosebud").is_none());
+
+        // categories survive a reload from the database.
+        let words = db::Words::load(db).await?;
+        assert!(words.tester().await.test("rosebud").is_none());
+        assert!(words.tester().await.test("heck").is_some());
+
+        mock.run(&handler, "moderator", "categories").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("moderator -> Disabled bad words category `spoilers`."),
+                String::from("moderator -> spoilers (warn, off)"),
+            ]
+        );
+
+        assert!(mock.run(&handler, "viewer", "add heck").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bad_words_allowed() -> Result<()> {
+        let mock = Mock::new().await?;
+        mock.moderator("moderator");
+
+        let db = mock.injector.get::<db::Database>().await.expect("database");
+        let words = db::Words::load(db.clone()).await?;
+        mock.injector.update(words.clone()).await;
+
+        let handler = Handler {
+            words: mock.injector.var().await,
+        };
+
+        mock.run(&handler, "moderator", "add heck").await?;
+        mock.run(&handler, "moderator", "allow HECK").await?;
+        mock.take_sent();
+
+        assert!(!words.is_bad_word(CHANNEL, "Heck").await);
+        assert!(words.is_bad_word("#other", "heck").await);
+
+        // allowed words survive a reload from the database.
+        let words = db::Words::load(db).await?;
+        assert!(!words.is_bad_word(CHANNEL, "heck").await);
+
+        mock.run(&handler, "moderator", "allowed").await?;
+        mock.run(&handler, "moderator", "disallow heck").await?;
+        mock.run(&handler, "moderator", "disallow heck").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("moderator -> heck"),
+                String::from("moderator -> `heck` is no longer allowed in this channel."),
+                String::from("moderator -> `heck` isn't allowed in this channel."),
+            ]
+        );
+
+        let words = mock.injector.get::<db::Words>().await.expect("words");
+        assert!(words.is_bad_word(CHANNEL, "heck").await);
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use crate::auth::Scope;
+use crate::command;
+use crate::currency::Currency;
+use crate::db;
+use crate::module;
+use crate::prelude::*;
+use crate::utils::{self, Cooldown, Duration};
+use anyhow::Result;
+use tokio::sync::Mutex;
+
+/// Handler for the `!balance` command.
+pub struct Handler {
+    enabled: settings::Var<bool>,
+    cooldown: settings::Var<Cooldown>,
+    /// Cooldowns for each user.
+    cooldowns: Mutex<utils::PerUserCooldown>,
+    currency: injector::Ref<Currency>,
+}
+
+impl Handler {
+    /// Test if the given user is past their cooldown, and start a new one if
+    /// they are.
+    async fn is_open(&self, user: &str) -> bool {
+        let cooldown = self.cooldown.load().await;
+        let mut cooldowns = self.cooldowns.lock().await;
+        cooldowns.cooldown = cooldown.cooldown;
+        cooldowns.is_open(user)
+    }
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let currency = match self.currency.load().await {
+            Some(currency) => currency,
+            None => {
+                respond!(ctx, "No currency configured for stream, sorry :(");
+                return Ok(());
+            }
+        };
+
+        let name = match ctx.user.real() {
+            Some(user) => user.name().to_string(),
+            None => {
+                respond!(ctx, "Only real users can check balances");
+                return Ok(());
+            }
+        };
+
+        // NB: don't respond while on cooldown, since that would defeat the
+        // purpose of it.
+        if !self.is_open(&name).await {
+            return Ok(());
+        }
+
+        let (user, own) = match ctx.next() {
+            Some(other) => {
+                ctx.check_scope(Scope::CurrencyShow).await?;
+                (db::user_id(&other), false)
+            }
+            None => (name, true),
+        };
+
+        let balance = currency
+            .balance_of(ctx.channel(), &user)
+            .await?
+            .unwrap_or_default();
+
+        let watch_time = utils::compact_duration(balance.watch_time().as_std());
+
+        if own {
+            respond!(
+                ctx,
+                "You have {balance} [{watch_time}].",
+                balance = currency.format_balance(balance.balance),
+                watch_time = watch_time,
+            );
+        } else {
+            respond!(
+                ctx,
+                "{user} has {balance} [{watch_time}].",
+                user = user,
+                balance = currency.format_balance(balance.balance),
+                watch_time = watch_time,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "balance"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            injector,
+            settings,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        let enabled = settings.var("balance/enabled", false).await?;
+
+        let cooldown = settings
+            .var(
+                "balance/cooldown",
+                Cooldown::from_duration(Duration::seconds(30)),
+            )
+            .await?;
+
+        handlers.insert(
+            "balance",
+            Handler {
+                enabled,
+                cooldown,
+                cooldowns: Mutex::new(Default::default()),
+                currency: injector.var().await,
+            },
+        );
+
+        Ok(())
+    }
+}
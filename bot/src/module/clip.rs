@@ -6,6 +6,12 @@ use crate::prelude::*;
 use crate::stream_info;
 use crate::utils::{Cooldown, Duration};
 use anyhow::Result;
+use std::time;
+
+/// How many times to check if a new clip is ready.
+const READY_ATTEMPTS: usize = 5;
+/// How long to wait between each check if a new clip is ready.
+const READY_INTERVAL: time::Duration = time::Duration::from_secs(2);
 
 /// Handler for the `!clip` command.
 pub struct Clip {
@@ -42,12 +48,19 @@ impl command::Handler for Clip {
 
         match twitch.new_create_clip(&stream_user.id).await? {
             Some(clip) => {
-                respond!(
-                    ctx,
-                    "Created clip at {}/{}",
-                    api::twitch::CLIPS_URL,
-                    clip.id
-                );
+                match wait_until_ready(&twitch, &clip.id).await? {
+                    Some(info) => {
+                        respond!(ctx, "Created clip at {}", info.url);
+                    }
+                    None => {
+                        respond!(
+                            ctx,
+                            "Clip is still being processed, it will show up at {}/{}",
+                            api::twitch::CLIPS_URL,
+                            clip.id
+                        );
+                    }
+                }
 
                 if let Some(_title) = title {
                     log::warn!("Title was requested, but it can't be set (right now)")
@@ -63,6 +76,22 @@ impl command::Handler for Clip {
     }
 }
 
+/// Poll briefly until the clip with the given id has been processed.
+async fn wait_until_ready(
+    twitch: &api::Twitch,
+    id: &str,
+) -> Result<Option<api::twitch::new::ClipInfo>> {
+    for _ in 0..READY_ATTEMPTS {
+        tokio::time::sleep(READY_INTERVAL).await;
+
+        if let Some(info) = twitch.new_clip_by_id(id).await? {
+            return Ok(Some(info));
+        }
+    }
+
+    Ok(None)
+}
+
 pub struct Module;
 
 #[async_trait]
@@ -78,7 +107,7 @@ impl super::Module for Module {
             handlers,
             settings,
             stream_info,
-            twitch,
+            streamer_twitch,
             ..
         }: module::HookContext<'_>,
     ) -> Result<()> {
@@ -92,7 +121,7 @@ impl super::Module for Module {
                 clip_cooldown: settings
                     .var("cooldown", Cooldown::from_duration(Duration::seconds(30)))
                     .await?,
-                twitch: twitch.clone(),
+                twitch: streamer_twitch.clone(),
             },
         );
 
@@ -3,6 +3,8 @@ use crate::command;
 use crate::db;
 use crate::module;
 use crate::prelude::*;
+use crate::template;
+use crate::utils;
 
 pub struct Handler {
     pub enabled: settings::Var<bool>,
@@ -25,14 +27,19 @@ impl command::Handler for Handler {
             None => return Ok(()),
         };
 
-        let next = command_base!(ctx, commands, "command", CommandEdit);
+        let next = command_base!(ctx, commands, "command", CommandEdit, |c| c.list_name());
 
         match next.as_deref() {
             Some("edit") => {
                 ctx.check_scope(auth::Scope::CommandEdit).await?;
 
                 let name = ctx.next_str("<name>")?;
-                let template = ctx.rest_parse("<name> <template>")?;
+                let template = ctx.rest_parse::<template::Template, _>("<name> <template>")?;
+
+                if let Err(e) = template.validate(db::Command::is_var) {
+                    respond_bail!("Bad template: {}", e);
+                }
+
                 commands.edit(ctx.channel(), &name, template).await?;
 
                 respond!(ctx, "Edited command.");
@@ -62,10 +69,207 @@ impl command::Handler for Handler {
 
                 respond!(ctx, "Edited pattern for command.");
             }
+            Some("describe") => {
+                ctx.check_scope(auth::Scope::CommandEdit).await?;
+
+                let name = ctx.next_str("<name> [description]")?;
+
+                let description = match ctx.rest().trim() {
+                    "" => None,
+                    description => Some(description.to_string()),
+                };
+
+                if !commands
+                    .edit_description(ctx.channel(), &name, description)
+                    .await?
+                {
+                    respond!(ctx, format!("No such command: `{}`", name));
+                    return Ok(());
+                }
+
+                respond!(ctx, "Edited description for command.");
+            }
+            Some("usage") => {
+                ctx.check_scope(auth::Scope::CommandEdit).await?;
+
+                let name = ctx.next_str("<name> [usage]")?;
+
+                let usage = match ctx.rest().trim() {
+                    "" => None,
+                    usage => Some(usage.to_string()),
+                };
+
+                if !commands.edit_usage(ctx.channel(), &name, usage).await? {
+                    respond!(ctx, format!("No such command: `{}`", name));
+                    return Ok(());
+                }
+
+                respond!(ctx, "Edited usage for command.");
+            }
+            Some("games") => {
+                ctx.check_scope(auth::Scope::CommandEdit).await?;
+
+                let name = ctx.next_str("<name> [game | ...]")?;
+                let games = db::Games::parse(ctx.rest());
+
+                if !commands.edit_games(ctx.channel(), &name, games).await? {
+                    respond!(ctx, format!("No such command: `{}`", name));
+                    return Ok(());
+                }
+
+                respond!(ctx, "Edited games for command.");
+            }
+            Some(what @ "cooldown")
+            | Some(what @ "moderator-cooldown")
+            | Some(what @ "user-cooldown") => {
+                ctx.check_scope(auth::Scope::CommandEdit).await?;
+
+                let name = ctx.next_str("<name> [duration]")?;
+                let duration = ctx.next_parse_optional::<utils::Duration>()?;
+
+                let mut limits = match commands.get(ctx.channel(), &name).await {
+                    Some(command) => command.limits,
+                    None => {
+                        respond!(ctx, format!("No such command: `{}`", name));
+                        return Ok(());
+                    }
+                };
+
+                match what {
+                    "cooldown" => limits.cooldown = duration,
+                    "moderator-cooldown" => limits.moderator_cooldown = duration,
+                    _ => limits.user_cooldown = duration,
+                }
+
+                commands.edit_limits(ctx.channel(), &name, limits).await?;
+
+                match duration {
+                    Some(duration) => respond!(ctx, "Set {} for command to {}.", what, duration),
+                    None => respond!(ctx, "Removed {} for command.", what),
+                }
+            }
+            Some("cooldown-group") => {
+                ctx.check_scope(auth::Scope::CommandEdit).await?;
+
+                let name = ctx.next_str("<name> [group]")?;
+                let group = ctx.next();
+
+                if !commands
+                    .edit_cooldown_group(ctx.channel(), &name, group.clone())
+                    .await?
+                {
+                    respond!(ctx, format!("No such command: `{}`", name));
+                    return Ok(());
+                }
+
+                match group {
+                    Some(group) => respond!(ctx, "Command now shares the `{}` cooldown.", group),
+                    None => respond!(ctx, "Command no longer shares a cooldown."),
+                }
+            }
+            Some("permission") => {
+                ctx.check_scope(auth::Scope::CommandEdit).await?;
+
+                let name = ctx.next_str("<name> [permission]")?;
+                let permission = ctx.next_parse_optional::<auth::Permission>()?;
+
+                if !commands
+                    .edit_permission(ctx.channel(), &name, permission)
+                    .await?
+                {
+                    respond!(ctx, format!("No such command: `{}`", name));
+                    return Ok(());
+                }
+
+                match permission {
+                    Some(permission) if permission != auth::Permission::Everyone => respond!(
+                        ctx,
+                        "Command can now only be used by {} or above.",
+                        permission
+                    ),
+                    _ => respond!(ctx, "Command can now be used by everyone."),
+                }
+            }
+            Some("triggers") => {
+                ctx.check_scope(auth::Scope::CommandEdit).await?;
+
+                let name = ctx.next_str("<name> [trigger...]")?;
+                let triggers = ctx
+                    .rest()
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect::<Vec<_>>();
+
+                if let Some(conflict) = commands
+                    .find_conflict(ctx.channel(), &name, &triggers)
+                    .await
+                {
+                    respond!(ctx, "`{}` is already used by another command.", conflict);
+                    return Ok(());
+                }
+
+                if !commands
+                    .edit_triggers(ctx.channel(), &name, triggers.clone())
+                    .await?
+                {
+                    respond!(ctx, format!("No such command: `{}`", name));
+                    return Ok(());
+                }
+
+                if triggers.is_empty() {
+                    respond!(ctx, "Removed the triggers for command.");
+                } else {
+                    respond!(
+                        ctx,
+                        "Command can now also be used with: {}.",
+                        triggers.join(", ")
+                    );
+                }
+            }
+            Some("cost") => {
+                ctx.check_scope(auth::Scope::CommandEdit).await?;
+
+                let name = ctx.next_str("<name> [amount]")?;
+                let cost = ctx.next_parse_optional::<u32>()?.filter(|cost| *cost > 0);
+
+                if !commands.edit_cost(ctx.channel(), &name, cost).await? {
+                    respond!(ctx, format!("No such command: `{}`", name));
+                    return Ok(());
+                }
+
+                match cost {
+                    Some(cost) => respond!(ctx, "Using the command now costs {}.", cost),
+                    None => respond!(ctx, "Command is now free to use."),
+                }
+            }
+            Some("limit") => {
+                ctx.check_scope(auth::Scope::CommandEdit).await?;
+
+                let name = ctx.next_str("<name> [uses per stream]")?;
+                let limit = ctx.next_parse_optional::<u32>()?;
+
+                let mut limits = match commands.get(ctx.channel(), &name).await {
+                    Some(command) => command.limits,
+                    None => {
+                        respond!(ctx, format!("No such command: `{}`", name));
+                        return Ok(());
+                    }
+                };
+
+                limits.stream_limit = limit;
+                commands.edit_limits(ctx.channel(), &name, limits).await?;
+
+                match limit {
+                    Some(limit) => {
+                        respond!(ctx, "Command can now be used {} times per stream.", limit)
+                    }
+                    None => respond!(ctx, "Removed the per stream limit for command."),
+                }
+            }
             None | Some(..) => {
                 respond!(
                     ctx,
-                    "Expected: show, list, edit, delete, enable, disable, or group."
+                    "Expected: show, list, edit, pattern, describe, usage, games, cooldown, moderator-cooldown, user-cooldown, cooldown-group, permission, triggers, cost, limit, delete, enable, disable, or group."
                 );
             }
         }
@@ -74,6 +278,52 @@ impl command::Handler for Handler {
     }
 }
 
+/// Handler for the !commands command, which lists custom commands.
+pub struct List {
+    pub enabled: settings::Var<bool>,
+    pub commands: injector::Ref<db::Commands>,
+}
+
+#[async_trait]
+impl command::Handler for List {
+    async fn handle(&self, ctx: &mut command::Context) -> Result<(), anyhow::Error> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let commands = match self.commands.load().await {
+            Some(commands) => commands,
+            None => return Ok(()),
+        };
+
+        let page = ctx.next_parse_optional::<usize>()?.unwrap_or(1);
+
+        // NB: disabled commands can't be used, so they are only shown to
+        // moderators.
+        let roles = ctx.user.roles();
+        let moderator =
+            roles.contains(&auth::Role::Moderator) || roles.contains(&auth::Role::Streamer);
+
+        let mut names = commands
+            .list_all(ctx.channel())
+            .await?
+            .into_iter()
+            .filter(|c| moderator || !c.disabled)
+            .map(|c| {
+                if c.disabled {
+                    format!("{} (disabled)", c.key.name)
+                } else {
+                    c.key.name
+                }
+            })
+            .collect::<Vec<_>>();
+
+        names.sort();
+        ctx.respond_page(names, page, "No custom commands.").await;
+        Ok(())
+    }
+}
+
 pub struct Module;
 
 #[async_trait]
@@ -93,7 +343,14 @@ impl super::Module for Module {
     ) -> Result<(), anyhow::Error> {
         let enabled = settings.var("command/enabled", true).await?;
         let commands = injector.var().await;
-        handlers.insert("command", Handler { enabled, commands });
+        handlers.insert(
+            "command",
+            Handler {
+                enabled: enabled.clone(),
+                commands: commands.clone(),
+            },
+        );
+        handlers.insert("commands", List { enabled, commands });
         Ok(())
     }
 }
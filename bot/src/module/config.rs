@@ -0,0 +1,135 @@
+use crate::auth;
+use crate::command;
+use crate::module;
+use crate::prelude::*;
+use anyhow::Result;
+
+/// Handler for the `!config` command.
+pub struct Handler {
+    settings: crate::Settings,
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::ConfigShow)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        match ctx.next().as_deref() {
+            Some("show") => {
+                let key = ctx.next_str("<key>")?;
+                let key = key.trim_start_matches('/');
+
+                let setting = match self.settings.inspect(key).await? {
+                    Some(setting) => setting,
+                    None => {
+                        respond!(ctx, "No setting `{}` which can be shown.", key);
+                        return Ok(());
+                    }
+                };
+
+                if setting.value.is_null() {
+                    respond!(ctx, "{} is not set.", setting.key);
+                    return Ok(());
+                }
+
+                respond!(
+                    ctx,
+                    "{} = {}",
+                    setting.key,
+                    serde_json::to_string(&setting.value)?
+                );
+            }
+            _ => {
+                respond!(ctx, "Expected: show.");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "config"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers, settings, ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        handlers.insert(
+            "config",
+            Handler {
+                settings: settings.clone(),
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Handler;
+    use crate::db;
+    use crate::irc::mock::{Mock, STREAMER};
+    use crate::utils;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_config_show() -> Result<()> {
+        let mock = Mock::new().await?;
+        let db = mock.injector.get::<db::Database>().await.expect("database");
+        let settings = db.settings(crate::load_schema()?)?;
+
+        settings
+            .set("water/cooldown", utils::Duration::seconds(5 * 60))
+            .await?;
+        settings.set("remote/secret-key", "hunter2").await?;
+        settings.set("player/spotify/volume", 50u32).await?;
+
+        let handler = Handler { settings };
+
+        mock.run(&handler, STREAMER, "show water/cooldown").await?;
+        mock.run(&handler, STREAMER, "show clip/cooldown").await?;
+        mock.run(&handler, STREAMER, "show remote/secret-key")
+            .await?;
+        mock.run(&handler, STREAMER, "show secrets/oauth2/spotify/connection")
+            .await?;
+        mock.run(&handler, STREAMER, "show player/spotify/volume")
+            .await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                format!("{} -> water/cooldown = \"5m\"", STREAMER),
+                format!("{} -> clip/cooldown is not set.", STREAMER),
+                format!(
+                    "{} -> No setting `remote/secret-key` which can be shown.",
+                    STREAMER
+                ),
+                format!(
+                    "{} -> No setting `secrets/oauth2/spotify/connection` which can be shown.",
+                    STREAMER
+                ),
+                format!(
+                    "{} -> No setting `player/spotify/volume` which can be shown.",
+                    STREAMER
+                ),
+            ]
+        );
+
+        assert!(mock
+            .run(&handler, "viewer", "show water/cooldown")
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use crate::auth;
+use crate::command;
+use crate::db;
+use crate::module;
+use crate::prelude::*;
+use anyhow::Result;
+use chrono::Utc;
+
+/// Handler for the `!count` command.
+pub struct Handler {
+    enabled: settings::Var<bool>,
+    commands: injector::Ref<db::Commands>,
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Count)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let commands = match self.commands.load().await {
+            Some(commands) => commands,
+            None => return Ok(()),
+        };
+
+        let name = ctx.next_str("<name> info")?;
+
+        match ctx.next().as_deref() {
+            Some("info") => {
+                // NB: counters are named by their full trigger, like
+                // `!deaths`, but can also be asked about in the other form.
+                let info = match commands.count_info(ctx.channel(), &name).await? {
+                    Some(info) => info,
+                    None => {
+                        let prefix = ctx.command_prefix().await;
+
+                        let other = match name.strip_prefix(prefix.as_str()) {
+                            Some(other) => other.to_string(),
+                            None => format!("{}{}", prefix, name),
+                        };
+
+                        match commands.count_info(ctx.channel(), &other).await? {
+                            Some(info) => info,
+                            None => {
+                                respond!(ctx, "No counter named `{}`.", name);
+                                return Ok(());
+                            }
+                        }
+                    }
+                };
+
+                let updated_at = match info.updated_at {
+                    Some(updated_at) => updated_at,
+                    None => {
+                        respond!(ctx, "{} is at {}, never changed.", name, info.count);
+                        return Ok(());
+                    }
+                };
+
+                let ago = (Utc::now() - updated_at).to_std().unwrap_or_default();
+                let ago = utils::compact_duration(ago);
+
+                match info.updated_by {
+                    Some(by) => {
+                        respond!(
+                            ctx,
+                            "{} is at {}, last changed {} ago by {}.",
+                            name,
+                            info.count,
+                            ago,
+                            by
+                        );
+                    }
+                    None => {
+                        respond!(
+                            ctx,
+                            "{} is at {}, last changed {} ago.",
+                            name,
+                            info.count,
+                            ago
+                        );
+                    }
+                }
+            }
+            _ => {
+                respond!(ctx, "Expected: !count <name> info");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "count"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            settings,
+            injector,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        handlers.insert(
+            "count",
+            Handler {
+                enabled: settings.var("count/enabled", true).await?,
+                commands: injector.var().await,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Handler;
+    use crate::db;
+    use crate::irc::mock::{Mock, CHANNEL};
+    use crate::prelude::*;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_count_info() -> Result<()> {
+        let mock = Mock::new().await?;
+
+        let commands = db::Commands::load_backend(Arc::new(db::Memory::new())).await?;
+        commands
+            .edit(
+                CHANNEL,
+                "!deaths",
+                crate::template::Template::compile("Deaths: {{count}}")?,
+            )
+            .await?;
+        mock.injector.update(commands.clone()).await;
+
+        let handler = Handler {
+            enabled: settings::Var::new(true),
+            commands: mock.injector.var().await,
+        };
+
+        mock.run(&handler, "viewer", "!deaths info").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from("viewer -> !deaths is at 0, never changed.")]
+        );
+
+        let command = commands.get(CHANNEL, "!deaths").await.expect("command");
+        commands.increment(&command, Some("moderator")).await?;
+
+        mock.run(&handler, "viewer", "deaths info").await?;
+        let sent = mock.take_sent();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].starts_with("viewer -> deaths is at 1, last changed "));
+        assert!(sent[0].ends_with(" ago by moderator."));
+
+        mock.run(&handler, "viewer", "missing info").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from("viewer -> No counter named `missing`.")]
+        );
+        Ok(())
+    }
+}
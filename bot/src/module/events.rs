@@ -0,0 +1,260 @@
+use crate::bus;
+use crate::irc;
+use crate::module;
+use crate::prelude::*;
+use crate::template::Template;
+use crate::utils;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time;
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "events"
+    }
+
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            futures,
+            sender,
+            settings,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        let settings = settings.scoped("events");
+
+        let global_bus = match injector.get::<bus::Bus<bus::Global>>().await {
+            Some(global_bus) => global_bus,
+            None => return Ok(()),
+        };
+
+        let notifier = Notifier {
+            follow: Event::new(
+                &settings.scoped("follow"),
+                "Thanks for following, {{user}}!",
+            )
+            .await?,
+            subscription: Event::new(
+                &settings.scoped("subscription"),
+                "Thanks for subscribing, {{user}}!",
+            )
+            .await?,
+            raid: Event::new(
+                &settings.scoped("raid"),
+                "{{user}} is raiding with {{viewers}} viewers!",
+            )
+            .await?,
+            debounce: settings
+                .var("debounce", utils::Duration::seconds(60))
+                .await?,
+            sender: sender.clone(),
+            seen: Debounce::default(),
+        };
+
+        let mut rx = global_bus.subscribe();
+
+        let future = async move {
+            use tokio::sync::broadcast::error::RecvError;
+
+            let mut notifier = notifier;
+
+            loop {
+                let m = match rx.recv().await {
+                    Ok(m) => m,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(..)) => continue,
+                };
+
+                if let Err(e) = notifier.notify(m).await {
+                    log_error!(e, "failed to notify about event");
+                }
+            }
+
+            Ok(())
+        };
+
+        futures.push(Box::pin(future));
+        Ok(())
+    }
+}
+
+/// Settings for a single kind of event.
+struct Event {
+    enabled: settings::Var<bool>,
+    template: settings::Var<Template>,
+}
+
+impl Event {
+    async fn new(settings: &crate::Settings, template: &str) -> Result<Self> {
+        Ok(Self {
+            enabled: settings.var("enabled", false).await?,
+            template: settings
+                .var("template", Template::compile(template)?)
+                .await?,
+        })
+    }
+
+    /// Render the template of the event if it's enabled.
+    async fn render<T>(&self, vars: T) -> Result<Option<String>>
+    where
+        T: serde::Serialize,
+    {
+        if !self.enabled.load().await {
+            return Ok(None);
+        }
+
+        Ok(Some(self.template.load().await.render_to_string(vars)?))
+    }
+}
+
+/// Posts messages in chat for Twitch events.
+struct Notifier {
+    follow: Event,
+    subscription: Event,
+    raid: Event,
+    debounce: settings::Var<utils::Duration>,
+    sender: irc::Sender,
+    seen: Debounce,
+}
+
+impl Notifier {
+    /// Post a message for the given notification, if it's an enabled event
+    /// which hasn't been seen recently.
+    async fn notify(&mut self, m: bus::Global) -> Result<()> {
+        let (kind, user, message) = match m {
+            bus::Global::TwitchFollow { user } => {
+                let message = self.follow.render(Follow { user: &user }).await?;
+                (Kind::Follow, user, message)
+            }
+            bus::Global::TwitchSubscription {
+                user,
+                months,
+                gifter,
+                message,
+            } => {
+                let message = self
+                    .subscription
+                    .render(Subscription {
+                        user: &user,
+                        months,
+                        gifter: gifter.as_deref(),
+                        message: message.as_deref(),
+                    })
+                    .await?;
+
+                (Kind::Subscription, user, message)
+            }
+            bus::Global::TwitchRaid { user, viewers } => {
+                let message = self
+                    .raid
+                    .render(Raid {
+                        user: &user,
+                        viewers,
+                    })
+                    .await?;
+
+                (Kind::Raid, user, message)
+            }
+            _ => return Ok(()),
+        };
+
+        let message = match message {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+
+        let window = self.debounce.load().await.as_std();
+
+        if !self.seen.check(kind, &user, time::Instant::now(), window) {
+            log::trace!("ignoring duplicate {:?} event for {}", kind, user);
+            return Ok(());
+        }
+
+        self.sender.privmsg(message).await;
+        return Ok(());
+
+        #[derive(serde::Serialize)]
+        struct Follow<'a> {
+            user: &'a str,
+        }
+
+        #[derive(serde::Serialize)]
+        struct Subscription<'a> {
+            user: &'a str,
+            months: u32,
+            gifter: Option<&'a str>,
+            message: Option<&'a str>,
+        }
+
+        #[derive(serde::Serialize)]
+        struct Raid<'a> {
+            user: &'a str,
+            viewers: u32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Follow,
+    Subscription,
+    Raid,
+}
+
+/// Keeps track of recent events to ignore duplicates.
+///
+/// Twitch might deliver the same event more than once, and users can follow
+/// and unfollow repeatedly to spam chat.
+#[derive(Default)]
+struct Debounce {
+    seen: HashMap<(Kind, String), time::Instant>,
+}
+
+impl Debounce {
+    /// Test if the event should be posted, which it shouldn't if the same kind
+    /// of event for the same user was seen within `window`.
+    fn check(
+        &mut self,
+        kind: Kind,
+        user: &str,
+        now: time::Instant,
+        window: time::Duration,
+    ) -> bool {
+        self.seen.retain(|_, at| now.duration_since(*at) < window);
+
+        let key = (kind, user.to_lowercase());
+
+        if self.seen.contains_key(&key) {
+            return false;
+        }
+
+        self.seen.insert(key, now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Debounce, Kind};
+    use std::time;
+
+    #[test]
+    fn test_debounce() {
+        let now = time::Instant::now();
+        let window = time::Duration::from_secs(60);
+        let mut debounce = Debounce::default();
+
+        assert!(debounce.check(Kind::Follow, "setbac", now, window));
+        assert!(!debounce.check(Kind::Follow, "SetBac", now, window));
+        // NB: different kinds of events are debounced separately.
+        assert!(debounce.check(Kind::Raid, "setbac", now, window));
+
+        let later = now + window;
+        assert!(debounce.check(Kind::Follow, "setbac", later, window));
+    }
+}
@@ -0,0 +1,117 @@
+//! Module for temporarily pausing the automatic chat filters.
+
+use crate::auth;
+use crate::command;
+use crate::filters;
+use crate::irc;
+use crate::module;
+use crate::prelude::*;
+use crate::task;
+use crate::utils;
+use anyhow::Result;
+
+/// Handler for the `!filters` command.
+pub struct Handler {
+    enabled: settings::Var<bool>,
+    filters: filters::Filters,
+    sender: irc::Sender,
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::ChatFilters)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        match ctx.next().as_deref() {
+            None => match self.filters.remaining() {
+                Some(remaining) => {
+                    respond!(
+                        ctx,
+                        "Chat filters are paused for another {}.",
+                        utils::compact_duration(remaining)
+                    );
+                }
+                None => {
+                    respond!(ctx, "Chat filters are enabled.");
+                }
+            },
+            Some("off") => {
+                let duration = ctx.next_parse::<utils::Duration, _>("<duration>")?;
+
+                if duration.is_empty() {
+                    respond!(ctx, "Expected a non-empty <duration>, like `5m`.");
+                    return Ok(());
+                }
+
+                let deadline = self.filters.pause(duration.as_std());
+
+                let filters = self.filters.clone();
+                let sender = self.sender.clone();
+
+                task::spawn(async move {
+                    tokio::time::sleep_until(deadline.into()).await;
+
+                    if filters.resume_at(deadline) {
+                        sender.privmsg("Chat filters are enabled again.").await;
+                    }
+                });
+
+                respond!(
+                    ctx,
+                    "Chat filters paused for {}.",
+                    utils::compact_duration(duration.as_std())
+                );
+            }
+            Some("on") => {
+                if self.filters.resume() {
+                    respond!(ctx, "Chat filters are enabled again.");
+                } else {
+                    respond!(ctx, "Chat filters are not paused.");
+                }
+            }
+            _ => {
+                respond!(ctx, "Expected: !filters off <duration>, or !filters on.");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "filters"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            filters,
+            sender,
+            settings,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        handlers.insert(
+            "filters",
+            Handler {
+                enabled: settings.var("filters/enabled", true).await?,
+                filters: filters.clone(),
+                sender: sender.clone(),
+            },
+        );
+
+        Ok(())
+    }
+}
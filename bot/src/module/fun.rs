@@ -0,0 +1,263 @@
+use crate::auth;
+use crate::command;
+use crate::module;
+use crate::prelude::*;
+use crate::template::Template;
+use std::fmt;
+use std::str;
+
+static MAGIC_8BALL_ANSWER: &[&str] = &[
+    "It is certain.",
+    "It is decidedly so.",
+    "Without a doubt.",
+    "Yes - definitely.",
+    "You may rely on it.",
+    "As I see it, yes.",
+    "Most likely.",
+    "Outlook good.",
+    "Yes.",
+    "Signs point to yes.",
+    "Reply hazy, try again.",
+    "Ask again later.",
+    "Better not tell you now.",
+    "Cannot predict now.",
+    "Concentrate and ask again.",
+    "Don't count on it.",
+    "My reply is no.",
+    "My sources say no.",
+    "Outlook not so good.",
+    "Very doubtful.",
+];
+
+/// The most dice which can be rolled at once.
+const MAX_DICE: u32 = 100;
+/// The most sides a single die can have.
+const MAX_SIDES: u32 = 1000;
+
+/// Handler for the !8ball command.
+pub struct EightBall {
+    enabled: settings::Var<bool>,
+    answers: settings::Var<Vec<Template>>,
+}
+
+#[async_trait]
+impl command::Handler for EightBall {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::EightBall)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<(), anyhow::Error> {
+        use rand::seq::SliceRandom as _;
+
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let question = ctx.rest().trim().to_string();
+
+        if question.is_empty() {
+            respond!(ctx, "Ask a question.");
+            return Ok(());
+        }
+
+        let answers = self.answers.load().await;
+
+        let answer = answers.choose(&mut rand::thread_rng()).cloned();
+
+        let answer = match answer {
+            Some(answer) => answer,
+            None => {
+                respond!(ctx, "The 8 ball has no answers, sorry :(");
+                return Ok(());
+            }
+        };
+
+        let answer = answer.render_to_string(Vars {
+            name: ctx.user.display_name(),
+            question: &question,
+        })?;
+
+        respond!(ctx, answer);
+        return Ok(());
+
+        #[derive(serde::Serialize)]
+        struct Vars<'a> {
+            name: Option<&'a str>,
+            question: &'a str,
+        }
+    }
+}
+
+/// Handler for the !roll command.
+pub struct Roll {
+    enabled: settings::Var<bool>,
+}
+
+#[async_trait]
+impl command::Handler for Roll {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Roll)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<(), anyhow::Error> {
+        use rand::Rng as _;
+
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let dice = ctx.next_parse_optional::<Dice>()?.unwrap_or_default();
+
+        let rolls = {
+            let mut rng = rand::thread_rng();
+
+            (0..dice.count)
+                .map(|_| rng.gen_range(1..=dice.sides))
+                .collect::<Vec<_>>()
+        };
+
+        let sum = rolls.iter().sum::<u32>();
+
+        if rolls.len() == 1 {
+            respond!(ctx, "You rolled {}.", sum);
+        } else {
+            let rolls = rolls.iter().map(u32::to_string).collect::<Vec<_>>();
+            respond!(ctx, "You rolled {} ({}).", sum, rolls.join(", "));
+        }
+
+        Ok(())
+    }
+}
+
+/// Dice in dice notation, like `2d6` for two six-sided dice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Dice {
+    count: u32,
+    sides: u32,
+}
+
+impl Default for Dice {
+    fn default() -> Self {
+        Self { count: 1, sides: 6 }
+    }
+}
+
+impl str::FromStr for Dice {
+    type Err = DiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+
+        let (count, sides) = s.split_once('d').ok_or(DiceError::Notation)?;
+
+        // NB: `d20` is the same as `1d20`.
+        let count = match count {
+            "" => 1,
+            count => str::parse::<u32>(count).map_err(|_| DiceError::Notation)?,
+        };
+
+        let sides = str::parse::<u32>(sides).map_err(|_| DiceError::Notation)?;
+
+        if !(1..=MAX_DICE).contains(&count) {
+            return Err(DiceError::Count);
+        }
+
+        if !(2..=MAX_SIDES).contains(&sides) {
+            return Err(DiceError::Sides);
+        }
+
+        Ok(Self { count, sides })
+    }
+}
+
+/// Why dice notation couldn't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiceError {
+    Notation,
+    Count,
+    Sides,
+}
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiceError::Notation => write!(fmt, "expected dice like `2d6`"),
+            DiceError::Count => write!(fmt, "you can roll between 1 and {} dice", MAX_DICE),
+            DiceError::Sides => write!(fmt, "dice can have between 2 and {} sides", MAX_SIDES),
+        }
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "fun"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers, settings, ..
+        }: module::HookContext<'_>,
+    ) -> Result<(), anyhow::Error> {
+        let answers = MAGIC_8BALL_ANSWER
+            .iter()
+            .map(|answer| Template::compile(answer))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        handlers.insert(
+            "8ball",
+            EightBall {
+                enabled: settings.var("8ball/enabled", true).await?,
+                answers: settings.var("8ball/answers", answers).await?,
+            },
+        );
+
+        handlers.insert(
+            "roll",
+            Roll {
+                enabled: settings.var("roll/enabled", true).await?,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dice, DiceError, MAX_DICE, MAX_SIDES};
+
+    #[test]
+    fn test_dice() {
+        assert_eq!(str::parse::<Dice>("2d6"), Ok(Dice { count: 2, sides: 6 }));
+        assert_eq!(
+            str::parse::<Dice>("D20"),
+            Ok(Dice {
+                count: 1,
+                sides: 20
+            })
+        );
+        assert_eq!(str::parse::<Dice>("6"), Err(DiceError::Notation));
+        assert_eq!(str::parse::<Dice>("2d"), Err(DiceError::Notation));
+        assert_eq!(str::parse::<Dice>("-1d6"), Err(DiceError::Notation));
+        assert_eq!(str::parse::<Dice>("0d6"), Err(DiceError::Count));
+        assert_eq!(str::parse::<Dice>("2d1"), Err(DiceError::Sides));
+        assert_eq!(
+            str::parse::<Dice>(&format!("{}d6", MAX_DICE + 1)),
+            Err(DiceError::Count)
+        );
+        assert_eq!(
+            str::parse::<Dice>(&format!("2d{}", MAX_SIDES + 1)),
+            Err(DiceError::Sides)
+        );
+        // NB: numbers too large to parse are bad notation.
+        assert_eq!(
+            str::parse::<Dice>("99999999999d99999"),
+            Err(DiceError::Notation)
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use crate::auth;
+use crate::command;
+use crate::currency::Currency;
+use crate::module;
+use crate::prelude::*;
+use crate::utils;
+use anyhow::Result;
+use std::time;
+use tokio::sync::Mutex;
+
+/// Handler for the `!gamble` command.
+pub struct Handler {
+    enabled: settings::Var<bool>,
+    cooldown: settings::Var<utils::Duration>,
+    win_chance: settings::Var<u32>,
+    payout: settings::Var<u32>,
+    house_edge: settings::Var<u32>,
+    max_bet: settings::Var<Option<u32>>,
+    currency: injector::Ref<Currency>,
+    /// When each user last gambled.
+    cooldowns: Mutex<utils::PerUserCooldown>,
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Gamble)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        use rand::Rng as _;
+
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let currency = match self.currency.load().await {
+            Some(currency) => currency,
+            None => {
+                respond!(ctx, "No currency configured for stream, sorry :(");
+                return Ok(());
+            }
+        };
+
+        let bet = ctx.next_parse::<u32, _>("<amount>")?;
+
+        let user = match ctx.user.real() {
+            Some(user) => user,
+            None => {
+                respond!(ctx, "Only real users can gamble");
+                return Ok(());
+            }
+        };
+
+        if bet == 0 {
+            respond_bail!("You have to bet something!");
+        }
+
+        if let Some(max_bet) = self.max_bet.load().await {
+            if bet > max_bet {
+                respond_bail!(
+                    "You can bet at most {}.",
+                    currency.format(i64::from(max_bet))
+                );
+            }
+        }
+
+        {
+            let mut cooldowns = self.cooldowns.lock().await;
+            let now = time::Instant::now();
+            cooldowns.cooldown = self.cooldown.load().await;
+
+            if let Some(remaining) = cooldowns.check(user.name(), now) {
+                respond_bail!(
+                    "You can gamble again in {}.",
+                    utils::compact_duration(remaining)
+                );
+            }
+
+            // NB: the cooldown starts before the bet is settled, so that no
+            // one can get around it by gambling twice at once.
+            cooldowns.poke(user.name(), now);
+        }
+
+        let bet = i64::from(bet);
+
+        // NB: the stake is taken before rolling, so that it can't be spent
+        // on anything else while the bet is settled.
+        if !currency
+            .balance_take(ctx.channel(), user.name(), bet)
+            .await?
+        {
+            let balance = currency
+                .balance_of(ctx.channel(), user.name())
+                .await?
+                .map(|b| b.balance)
+                .unwrap_or_default();
+
+            // NB: nothing was bet, so the cooldown doesn't apply.
+            self.cooldowns.lock().await.clear(user.name());
+            respond_bail!("You only have {}.", currency.format_balance(balance));
+        }
+
+        let roll = rand::thread_rng().gen_range(1..=100);
+        let win_chance = self.win_chance.load().await.min(100);
+
+        let change = outcome(
+            bet,
+            roll,
+            win_chance,
+            self.payout.load().await,
+            self.house_edge.load().await,
+        );
+
+        if change >= 0 {
+            currency
+                .balance_add(ctx.channel(), user.name(), bet + change)
+                .await?;
+        }
+
+        let balance = currency
+            .balance_of(ctx.channel(), user.name())
+            .await?
+            .map(|b| b.balance)
+            .unwrap_or_default();
+
+        let balance = currency.format_balance(balance);
+
+        if roll > win_chance {
+            respond!(
+                ctx,
+                "You rolled {} and lost {}. You now have {}.",
+                roll,
+                currency.format(bet),
+                balance
+            );
+        } else if change > 0 {
+            respond!(
+                ctx,
+                "You rolled {} and won {}! You now have {}.",
+                roll,
+                currency.format(change),
+                balance
+            );
+        } else {
+            respond!(
+                ctx,
+                "You rolled {} and won, but the winnings round down to nothing so your bet of {} was returned. You now have {}.",
+                roll,
+                currency.format(bet),
+                balance
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Calculate how much a `bet` changes the balance of the user, given a `roll`
+/// in `1..=100`.
+///
+/// The bet is won if the roll is at most `win_chance`. A won bet pays out
+/// `payout` percent of the bet including the stake, where `house_edge` percent
+/// of the winnings are kept by the house. A lost bet loses the stake.
+///
+/// A won bet never loses the user anything, so a `payout` at or below `100`
+/// percent, or winnings which round down to nothing, only return the stake.
+fn outcome(bet: i64, roll: u32, win_chance: u32, payout: u32, house_edge: u32) -> i64 {
+    if roll > win_chance {
+        return -bet;
+    }
+
+    let winnings = bet * i64::from(payout.saturating_sub(100)) / 100;
+    winnings * i64::from(100u32.saturating_sub(house_edge)) / 100
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "gamble"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            injector,
+            settings,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        let settings = settings.scoped("gamble");
+
+        handlers.insert(
+            "gamble",
+            Handler {
+                enabled: settings.var("enabled", false).await?,
+                cooldown: settings
+                    .var("cooldown", utils::Duration::seconds(60))
+                    .await?,
+                win_chance: settings.var("win-chance%", 50).await?,
+                payout: settings.var("payout%", 200).await?,
+                house_edge: settings.var("house-edge%", 5).await?,
+                max_bet: settings.optional("max-bet").await?,
+                currency: injector.var().await,
+                cooldowns: Mutex::new(Default::default()),
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{outcome, Handler};
+    use crate::irc::mock::{Mock, CHANNEL};
+    use crate::prelude::*;
+    use crate::utils;
+    use anyhow::Result;
+    use std::time;
+    use tokio::sync::Mutex;
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(outcome(100, 50, 50, 200, 0), 100);
+        assert_eq!(outcome(100, 51, 50, 200, 0), -100);
+        assert_eq!(outcome(100, 1, 50, 200, 5), 95);
+        assert_eq!(outcome(100, 1, 50, 300, 10), 180);
+        // NB: a payout below the stake can't lose the user anything on a win.
+        assert_eq!(outcome(100, 1, 50, 50, 0), 0);
+    }
+
+    #[tokio::test]
+    async fn test_gamble() -> Result<()> {
+        let mock = Mock::new().await?;
+
+        let currency = mock.currency().await?;
+        currency.balance_add(CHANNEL, "viewer", 100).await?;
+        currency.balance_add(CHANNEL, "small", 1).await?;
+
+        let handler = Handler {
+            enabled: settings::Var::new(true),
+            cooldown: settings::Var::new(utils::Duration::seconds(60)),
+            win_chance: settings::Var::new(100),
+            payout: settings::Var::new(200),
+            house_edge: settings::Var::new(10),
+            max_bet: settings::Var::new(Some(50)),
+            currency: mock.injector.var().await,
+            cooldowns: Mutex::new(Default::default()),
+        };
+
+        assert!(mock.run(&handler, "viewer", "100").await.is_err());
+        assert!(mock.run(&handler, "broke", "10").await.is_err());
+        assert!(handler
+            .cooldowns
+            .lock()
+            .await
+            .check("broke", time::Instant::now())
+            .is_none());
+        mock.run(&handler, "viewer", "50").await?;
+        assert!(mock.run(&handler, "viewer", "10").await.is_err());
+        // NB: 10% of the single point won is kept by the house.
+        mock.run(&handler, "small", "1").await?;
+
+        let sent = mock.take_sent();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].starts_with("viewer -> You rolled "));
+        assert!(sent[0].ends_with(" and won 45 points! You now have 145 points."));
+        assert!(sent[1].starts_with("small -> You rolled "));
+        assert!(sent[1].ends_with(
+            " and won, but the winnings round down to nothing so your bet of 1 points was returned. You now have 1 points."
+        ));
+
+        let balance = currency.balance_of(CHANNEL, "viewer").await?.unwrap();
+        assert_eq!(balance.balance, 145);
+        let balance = currency.balance_of(CHANNEL, "small").await?.unwrap();
+        assert_eq!(balance.balance, 1);
+        Ok(())
+    }
+}
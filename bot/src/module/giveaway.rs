@@ -0,0 +1,425 @@
+use crate::auth;
+use crate::command;
+use crate::currency::Currency;
+use crate::module;
+use crate::prelude::*;
+use crate::template::Template;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::Mutex;
+
+/// A single entry in the giveaway.
+struct Entry {
+    name: String,
+    display_name: String,
+    /// How many tickets the entry has in the draw.
+    weight: u32,
+    /// What the entry cost, which is refunded if the giveaway is reset.
+    paid: u32,
+}
+
+/// The entries and winners of the running giveaway.
+#[derive(Default)]
+struct Giveaway {
+    entries: Vec<Entry>,
+    /// Users who have already won, and are excluded from rerolls.
+    winners: HashSet<String>,
+}
+
+/// The running giveaway of each channel.
+type Giveaways = Arc<Mutex<HashMap<String, Giveaway>>>;
+
+impl Giveaway {
+    /// Test if the given user has entered the giveaway.
+    fn has_entered(&self, name: &str) -> bool {
+        self.entries.iter().any(|e| e.name == name)
+    }
+
+    /// Pick a winner among the entries which haven't won yet, where `pick`
+    /// is given the total weight of the eligible entries and returns a number
+    /// in `0..total`.
+    fn draw(&mut self, pick: impl FnOnce(u32) -> u32) -> Option<&Entry> {
+        let winners = &self.winners;
+
+        let eligible = self
+            .entries
+            .iter()
+            .filter(|e| !winners.contains(&e.name))
+            .collect::<Vec<_>>();
+
+        let total = eligible.iter().map(|e| e.weight).sum::<u32>();
+
+        if total == 0 {
+            return None;
+        }
+
+        let mut n = pick(total);
+        let mut winner = None;
+
+        for entry in eligible {
+            if n < entry.weight {
+                winner = Some(entry);
+                break;
+            }
+
+            n -= entry.weight;
+        }
+
+        let winner = winner?;
+        self.winners.insert(winner.name.clone());
+        Some(winner)
+    }
+}
+
+/// Handler for the `!enter` command.
+pub struct Enter {
+    enabled: settings::Var<bool>,
+    cost: settings::Var<u32>,
+    subscriber_weight: settings::Var<u32>,
+    currency: injector::Ref<Currency>,
+    giveaways: Giveaways,
+}
+
+#[async_trait]
+impl command::Handler for Enter {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::GiveawayEnter)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let user = match ctx.user.real() {
+            Some(user) => user,
+            None => {
+                respond!(ctx, "Only real users can enter the giveaway");
+                return Ok(());
+            }
+        };
+
+        let mut giveaways = self.giveaways.lock().await;
+        let giveaway = giveaways.entry(ctx.channel().to_string()).or_default();
+
+        if giveaway.has_entered(user.name()) {
+            respond!(ctx, "You have already entered the giveaway.");
+            return Ok(());
+        }
+
+        let cost = self.cost.load().await;
+
+        if cost > 0 {
+            let currency = match self.currency.load().await {
+                Some(currency) => currency,
+                None => {
+                    respond!(ctx, "No currency configured for stream, sorry :(");
+                    return Ok(());
+                }
+            };
+
+            if !currency
+                .balance_take(ctx.channel(), user.name(), i64::from(cost))
+                .await?
+            {
+                let balance = currency
+                    .balance_of(ctx.channel(), user.name())
+                    .await?
+                    .map(|b| b.balance)
+                    .unwrap_or_default();
+
+                respond!(
+                    ctx,
+                    "You need {cost} to enter the giveaway, you currently have {balance}.",
+                    cost = currency.format(i64::from(cost)),
+                    balance = currency.format_balance(balance),
+                );
+                return Ok(());
+            }
+        }
+
+        let weight = if user.is_subscriber() {
+            self.subscriber_weight.load().await.max(1)
+        } else {
+            1
+        };
+
+        giveaway.entries.push(Entry {
+            name: user.name().to_string(),
+            display_name: user.display_name().to_string(),
+            weight,
+            paid: cost,
+        });
+
+        respond!(ctx, "You have entered the giveaway!");
+        Ok(())
+    }
+}
+
+/// Handler for the `!draw` command.
+pub struct Draw {
+    enabled: settings::Var<bool>,
+    template: settings::Var<Template>,
+    currency: injector::Ref<Currency>,
+    giveaways: Giveaways,
+}
+
+#[async_trait]
+impl command::Handler for Draw {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::GiveawayDraw)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let mut giveaways = self.giveaways.lock().await;
+        let giveaway = giveaways.entry(ctx.channel().to_string()).or_default();
+
+        match ctx.next().as_deref() {
+            Some("reset") => {
+                let currency = self.currency.load().await;
+                let mut refunded = 0;
+
+                // NB: entries are removed as they are refunded, so the ones
+                // which haven't been are kept if refunding fails.
+                while let Some(entry) = giveaway.entries.last() {
+                    if entry.paid > 0 {
+                        let currency = match currency.as_ref() {
+                            Some(currency) => currency,
+                            None => {
+                                respond!(
+                                    ctx,
+                                    "No currency configured for stream, so entries can't be refunded."
+                                );
+                                return Ok(());
+                            }
+                        };
+
+                        currency
+                            .balance_add(ctx.channel(), &entry.name, i64::from(entry.paid))
+                            .await?;
+                        refunded += 1;
+                    }
+
+                    giveaway.entries.pop();
+                }
+
+                giveaways.remove(ctx.channel());
+
+                if refunded > 0 {
+                    respond!(
+                        ctx,
+                        "Refunded {} entries and cleared them, the giveaway is open again.",
+                        refunded
+                    );
+                } else {
+                    respond!(ctx, "Cleared all entries, the giveaway is open again.");
+                }
+            }
+            None => {
+                use rand::Rng as _;
+
+                let entries = giveaway.entries.len();
+
+                let winner = match giveaway.draw(|total| rand::thread_rng().gen_range(0..total)) {
+                    Some(winner) => winner,
+                    None if entries == 0 => {
+                        respond!(ctx, "No one has entered the giveaway yet.");
+                        return Ok(());
+                    }
+                    None => {
+                        respond!(ctx, "Everyone who entered the giveaway has already won.");
+                        return Ok(());
+                    }
+                };
+
+                let response = self.template.load().await.render_to_string(Vars {
+                    winner: &winner.display_name,
+                    entries,
+                })?;
+
+                ctx.privmsg(response).await;
+            }
+            Some(_) => {
+                respond!(ctx, "Expected: !draw, or !draw reset.");
+            }
+        }
+
+        return Ok(());
+
+        #[derive(serde::Serialize)]
+        struct Vars<'a> {
+            winner: &'a str,
+            entries: usize,
+        }
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "giveaway"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            injector,
+            settings,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        let settings = settings.scoped("giveaway");
+        let enabled = settings.var("enabled", false).await?;
+        let default_template = Template::compile(
+            "Congratulations {{winner}}, you won the giveaway out of {{entries}} entries!",
+        )?;
+
+        let giveaways = Giveaways::default();
+
+        handlers.insert(
+            "enter",
+            Enter {
+                enabled: enabled.clone(),
+                cost: settings.var("cost", 0).await?,
+                subscriber_weight: settings.var("subscriber-weight", 1).await?,
+                currency: injector.var().await,
+                giveaways: giveaways.clone(),
+            },
+        );
+
+        handlers.insert(
+            "draw",
+            Draw {
+                enabled,
+                template: settings.var("template", default_template).await?,
+                currency: injector.var().await,
+                giveaways,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Draw, Enter, Entry, Giveaway, Giveaways};
+    use crate::irc::mock::{Mock, CHANNEL};
+    use crate::prelude::*;
+    use crate::template::Template;
+    use anyhow::Result;
+
+    fn entry(name: &str, weight: u32) -> Entry {
+        Entry {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            weight,
+            paid: 0,
+        }
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut giveaway = Giveaway::default();
+        giveaway.entries.push(entry("a", 1));
+        giveaway.entries.push(entry("b", 3));
+        giveaway.entries.push(entry("c", 1));
+
+        assert_eq!(giveaway.draw(|total| total - 2).unwrap().name, "b");
+        // NB: b is excluded from the reroll.
+        assert_eq!(giveaway.draw(|total| total - 1).unwrap().name, "c");
+        assert_eq!(giveaway.draw(|total| total - 1).unwrap().name, "a");
+        assert!(giveaway.draw(|_| 0).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_giveaway() -> Result<()> {
+        let mock = Mock::new().await?;
+        mock.moderator("moderator");
+
+        let currency = mock.currency().await?;
+        currency.balance_add(CHANNEL, "viewer", 15).await?;
+
+        let giveaways = Giveaways::default();
+
+        let enter = Enter {
+            enabled: settings::Var::new(true),
+            cost: settings::Var::new(10),
+            subscriber_weight: settings::Var::new(1),
+            currency: mock.injector.var().await,
+            giveaways: giveaways.clone(),
+        };
+
+        let draw = Draw {
+            enabled: settings::Var::new(true),
+            template: settings::Var::new(Template::compile("{{winner}} won!")?),
+            currency: mock.injector.var().await,
+            giveaways: giveaways.clone(),
+        };
+
+        mock.run(&draw, "moderator", "").await?;
+        mock.run(&enter, "viewer", "").await?;
+        mock.run(&enter, "viewer", "").await?;
+        mock.run(&enter, "broke", "").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("moderator -> No one has entered the giveaway yet."),
+                String::from("viewer -> You have entered the giveaway!"),
+                String::from("viewer -> You have already entered the giveaway."),
+                String::from(
+                    "broke -> You need 10 points to enter the giveaway, you currently have 0 points."
+                ),
+            ]
+        );
+
+        let balance = currency.balance_of(CHANNEL, "viewer").await?.unwrap();
+        assert_eq!(balance.balance, 5);
+
+        mock.run(&draw, "moderator", "").await?;
+        mock.run(&draw, "moderator", "").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("viewer won!"),
+                String::from("moderator -> Everyone who entered the giveaway has already won."),
+            ]
+        );
+
+        assert!(mock.run(&draw, "viewer", "reset").await.is_err());
+
+        // NB: giveaways are kept apart per channel.
+        assert!(giveaways.lock().await.contains_key(CHANNEL));
+
+        mock.run(&draw, "moderator", "reset").await?;
+        mock.run(&draw, "moderator", "").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from(
+                    "moderator -> Refunded 1 entries and cleared them, the giveaway is open again."
+                ),
+                String::from("moderator -> No one has entered the giveaway yet."),
+            ]
+        );
+
+        let balance = currency.balance_of(CHANNEL, "viewer").await?.unwrap();
+        assert_eq!(balance.balance, 15);
+        assert!(giveaways
+            .lock()
+            .await
+            .get(CHANNEL)
+            .unwrap()
+            .entries
+            .is_empty());
+        Ok(())
+    }
+}
@@ -999,13 +999,12 @@ impl command::Handler for Handler {
                 respond!(
                     ctx,
                     "{prefix}\
-                        You need at least {limit} {currency} to reward the streamer, \
-                        you currently have {balance} {currency}. \
+                        You need at least {limit} to reward the streamer, \
+                        you currently have {balance}. \
                         Keep watching to earn more!",
                     prefix = prefix,
-                    limit = cost,
-                    currency = currency.name,
-                    balance = balance,
+                    limit = currency.format(cost as i64),
+                    balance = currency.format_balance(balance as i64),
                 );
 
                 return Ok(());
@@ -1024,13 +1023,12 @@ impl command::Handler for Handler {
 
             sender
                 .privmsg(format!(
-                    "{prefix}{user} {what} the streamer for {cost} {currency} by {command}",
+                    "{prefix}{user} {what} the streamer for {cost} by {command}",
                     prefix = prefix,
                     user = who,
                     what = command.what(),
                     command = command,
-                    cost = cost,
-                    currency = currency.name,
+                    cost = currency.format(cost as i64),
                 ))
                 .await;
         }
@@ -1,6 +1,10 @@
+use crate::auth;
 use crate::command;
+use crate::currency::Currency;
+use crate::db;
 use crate::module;
 use crate::prelude::*;
+use crate::utils;
 use anyhow::Result;
 use url::Url;
 
@@ -10,6 +14,114 @@ const DEFAULT_URL: &str = "https://setbac.tv/help";
 pub struct Help {
     enabled: settings::Var<bool>,
     url: settings::Var<Url>,
+    auth: injector::Ref<auth::Auth>,
+    commands: injector::Ref<db::Commands>,
+    currency: injector::Ref<Currency>,
+}
+
+impl Help {
+    /// Describe a custom command.
+    ///
+    /// Shows the metadata which has been configured for it, followed by who
+    /// can use it, its cooldown and what it costs.
+    async fn describe_custom(&self, command: &db::Command) -> String {
+        let mut parts = Vec::new();
+
+        match command.description.as_deref() {
+            Some(description) => parts.push(description.to_string()),
+            None => parts.push(format!("Responds with: {}", command.template)),
+        }
+
+        if let Some(usage) = command.usage.as_deref() {
+            parts.push(format!("Usage: {}", usage));
+        }
+
+        let mut limits = vec![format!("Can be used by {}", command.permission.describe())];
+
+        match (command.cooldown_group.as_deref(), command.limits.cooldown) {
+            (Some(group), _) => limits.push(format!("shares the `{}` cooldown", group)),
+            (None, Some(cooldown)) => limits.push(format!(
+                "has a cooldown of {}",
+                utils::compact_duration(cooldown.as_std())
+            )),
+            (None, None) => limits.push(String::from("has no cooldown")),
+        }
+
+        if let Some(cost) = command.cost {
+            let cost = match self.currency.load().await {
+                Some(currency) => currency.format(i64::from(cost)),
+                None => cost.to_string(),
+            };
+
+            limits.push(format!("costs {}", cost));
+        }
+
+        let last = limits.pop().unwrap_or_default();
+
+        if limits.is_empty() {
+            parts.push(last);
+        } else {
+            parts.push(format!("{} and {}", limits.join(", "), last));
+        }
+
+        format!("{}: {}", command.key.name, parts.join(". "))
+    }
+
+    /// Describe a built-in command.
+    async fn describe_builtin(
+        &self,
+        prefix: &str,
+        name: &str,
+        handler: &dyn command::Handler,
+        url: &Url,
+    ) -> String {
+        let scope = match handler.scope() {
+            Some(scope) => scope,
+            None => {
+                return format!(
+                    "{}{} can be used by everyone. For more help, go to {}",
+                    prefix, name, url
+                );
+            }
+        };
+
+        let mut parts = Vec::new();
+
+        let auth = self.auth.load().await;
+
+        let roles = match auth.as_ref() {
+            Some(auth) => auth.roles_for_scope(scope).await,
+            None => Vec::new(),
+        };
+
+        if roles.is_empty() {
+            parts.push(format!("requires the `{}` permission", scope));
+        } else {
+            let roles = roles.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+
+            parts.push(format!(
+                "requires the `{}` permission (granted to {})",
+                scope,
+                roles.join(", ")
+            ));
+        }
+
+        match auth.as_ref().and_then(|auth| auth.scope_cooldown(scope)) {
+            Some(cooldown) => parts.push(format!(
+                "has a cooldown of {}",
+                utils::compact_duration(cooldown.as_std())
+            )),
+            None => parts.push(String::from("has no cooldown")),
+        }
+
+        format!(
+            "{}{} {}. For more help, go to {}",
+            prefix,
+            name,
+            parts.join(" and "),
+            url
+        )
+    }
 }
 
 #[async_trait]
@@ -21,21 +133,75 @@ impl command::Handler for Help {
 
         let next = ctx.next();
         let mut url = self.url.load().await;
+        let prefix = ctx.command_prefix().await;
 
-        match next.as_deref() {
+        let command = match next.as_deref() {
             None => {
-                respond!(
-                    ctx,
-                    "You can find documentation for each command at {}",
-                    url
-                );
+                let mut names = match self.commands.load().await {
+                    Some(commands) => commands
+                        .list(ctx.channel())
+                        .await
+                        .into_iter()
+                        .filter(|c| c.description.is_some() || c.usage.is_some())
+                        .map(|c| c.key.name.clone())
+                        .collect::<Vec<_>>(),
+                    None => Vec::new(),
+                };
+
+                if names.is_empty() {
+                    respond!(
+                        ctx,
+                        "You can find documentation for each command at {}",
+                        url
+                    );
+                } else {
+                    names.sort();
+
+                    respond!(
+                        ctx,
+                        "Commands with help: {}. You can find documentation for each command at {}",
+                        names.join(", "),
+                        url
+                    );
+                }
+
+                return Ok(());
             }
-            Some(command) => {
-                url.query_pairs_mut().append_pair("q", command);
-                respond!(ctx, format!("For help on that, go to {}", url));
+            Some(command) => command,
+        };
+
+        // NB: custom commands are named by their full trigger, like `!lurk`,
+        // but can also be asked about without the prefix.
+        if let Some(commands) = self.commands.load().await {
+            let custom = match commands.get(ctx.channel(), command).await {
+                Some(custom) => Some(custom),
+                None => {
+                    commands
+                        .get(ctx.channel(), &format!("{}{}", prefix, command))
+                        .await
+                }
+            };
+
+            if let Some(custom) = custom {
+                let m = self.describe_custom(&custom).await;
+                respond!(ctx, m);
+                return Ok(());
             }
         }
 
+        let command = command.trim_start_matches(prefix.as_str());
+
+        url.query_pairs_mut().append_pair("q", command);
+
+        if let Some(handler) = ctx.handler(command) {
+            let m = self
+                .describe_builtin(&prefix, command, &*handler, &url)
+                .await;
+            respond!(ctx, m);
+            return Ok(());
+        }
+
+        respond!(ctx, format!("For help on that, go to {}", url));
         Ok(())
     }
 }
@@ -51,7 +217,10 @@ impl super::Module for Module {
     async fn hook(
         &self,
         module::HookContext {
-            handlers, settings, ..
+            injector,
+            handlers,
+            settings,
+            ..
         }: module::HookContext<'_>,
     ) -> Result<()> {
         let default_url = Url::parse(DEFAULT_URL)?;
@@ -61,9 +230,83 @@ impl super::Module for Module {
             Help {
                 enabled: settings.var("help/enabled", true).await?,
                 url: settings.var("help/url", default_url).await?,
+                auth: injector.var().await,
+                commands: injector.var().await,
+                currency: injector.var().await,
             },
         );
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Help, DEFAULT_URL};
+    use crate::auth;
+    use crate::db;
+    use crate::irc::mock::{Mock, CHANNEL};
+    use crate::prelude::*;
+    use crate::template::Template;
+    use anyhow::Result;
+    use url::Url;
+
+    #[tokio::test]
+    async fn test_help() -> Result<()> {
+        let mock = Mock::new().await?;
+
+        let db = mock.injector.get::<db::Database>().await.expect("database");
+        let commands = db::Commands::load(db).await?;
+        mock.injector.update(commands.clone()).await;
+
+        commands
+            .edit(CHANNEL, "!lurk", Template::compile("{{name}} is lurking")?)
+            .await?;
+        commands
+            .edit(
+                CHANNEL,
+                "!hug",
+                Template::compile("{{name}} hugs {{arg1}}")?,
+            )
+            .await?;
+        commands
+            .edit_description(CHANNEL, "!lurk", Some(String::from("Lurk in chat")))
+            .await?;
+        commands
+            .edit_permission(CHANNEL, "!hug", Some(auth::Permission::Subscriber))
+            .await?;
+        commands.edit_cost(CHANNEL, "!hug", Some(100)).await?;
+        commands
+            .edit_limits(
+                CHANNEL,
+                "!lurk",
+                db::CommandLimits {
+                    cooldown: Some(utils::Duration::seconds(90)),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let handler = Help {
+            enabled: settings::Var::new(true),
+            url: settings::Var::new(Url::parse(DEFAULT_URL)?),
+            auth: mock.injector.var().await,
+            commands: mock.injector.var().await,
+            currency: mock.injector.var().await,
+        };
+
+        mock.run(&handler, "viewer", "").await?;
+        mock.run(&handler, "viewer", "!lurk").await?;
+        mock.run(&handler, "viewer", "hug").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("viewer -> Commands with help: !lurk. You can find documentation for each command at https://setbac.tv/help"),
+                String::from("viewer -> !lurk: Lurk in chat. Can be used by anyone and has a cooldown of 1m 30s"),
+                String::from("viewer -> !hug: Responds with: {{name}} hugs {{arg1}}. Can be used by a subscriber, has no cooldown and costs 100"),
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -126,11 +126,14 @@ macro_rules! command_group {
 
 macro_rules! command_list {
     ($ctx:expr, $db:expr, $what:expr) => {{
+        command_list!($ctx, $db, $what, |c| c.key.name.to_string())
+    }};
+    ($ctx:expr, $db:expr, $what:expr, $name:expr) => {{
         let mut names = $db
             .list($ctx.channel())
             .await
             .into_iter()
-            .map(|c| c.key.name.to_string())
+            .map($name)
             .collect::<Vec<_>>();
 
         if names.is_empty() {
@@ -213,6 +216,10 @@ macro_rules! command_show {
 
 macro_rules! command_base {
     ($ctx:expr, $db:expr, $what:expr, $edit_scope:ident) => {{
+        command_base!($ctx, $db, $what, $edit_scope, |c| c.key.name.to_string())
+    }};
+    // NB: `$list_name` is how each element is named by `list`.
+    ($ctx:expr, $db:expr, $what:expr, $edit_scope:ident, $list_name:expr) => {{
         let arg = $ctx.next();
 
         match arg.as_deref() {
@@ -233,7 +240,7 @@ macro_rules! command_base {
                 return Ok(());
             }
             Some("list") => {
-                command_list!($ctx, $db, $what);
+                command_list!($ctx, $db, $what, $list_name);
                 return Ok(());
             }
             Some("delete") => {
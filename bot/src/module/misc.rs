@@ -7,20 +7,18 @@ use crate::irc;
 use crate::module;
 use crate::prelude::*;
 use crate::stream_info;
-use crate::utils;
 use anyhow::Result;
-use chrono::Utc;
 
-/// Handler for the `!uptime` command.
-pub struct Uptime {
+/// Handler for the `!followers` command.
+pub struct Followers {
     pub enabled: settings::Var<bool>,
     pub stream_info: stream_info::StreamInfo,
 }
 
 #[async_trait]
-impl command::Handler for Uptime {
+impl command::Handler for Followers {
     fn scope(&self) -> Option<auth::Scope> {
-        Some(auth::Scope::Uptime)
+        Some(auth::Scope::Followers)
     }
 
     async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
@@ -28,29 +26,29 @@ impl command::Handler for Uptime {
             return Ok(());
         }
 
-        let started_at = self
-            .stream_info
-            .data
-            .read()
-            .stream
-            .as_ref()
-            .map(|s| s.started_at);
-
-        let now = Utc::now();
-
-        match started_at {
-            // NB: very important to check that _now_ is after started at.
-            Some(ref started_at) if now > *started_at => {
-                let uptime =
-                    utils::compact_duration((now - *started_at).to_std().unwrap_or_default());
+        let (followers, subscribers) = {
+            let data = self.stream_info.data.read();
+            (data.followers, data.subscribers)
+        };
 
-                respond!(ctx, "Stream has been live for {uptime}.", uptime = uptime);
+        match (followers, subscribers) {
+            (Some(followers), Some(subscribers)) => {
+                respond!(
+                    ctx,
+                    "Stream has {followers} followers and {subscribers} subscribers.",
+                    followers = followers,
+                    subscribers = subscribers,
+                );
             }
-            Some(_) => {
-                respond!(ctx, "Stream is live, but start time is weird!");
+            (Some(followers), None) => {
+                respond!(
+                    ctx,
+                    "Stream has {followers} followers.",
+                    followers = followers
+                );
             }
-            None => {
-                respond!(ctx, "Stream is not live right now, try again later!");
+            (None, _) => {
+                respond!(ctx, "Followers aren't known yet, try again later!");
             }
         }
 
@@ -101,17 +99,30 @@ impl command::Handler for Title {
             ctx.check_scope(auth::Scope::TitleEdit).await?;
 
             let user = ctx.user.clone();
+            let title = rest.to_string();
 
             let mut request = api::twitch::v5::UpdateChannelRequest::default();
-            request.channel.status = Some(rest.to_string());
-            self.twitch
+            request.channel.status = Some(title.clone());
+
+            if let Err(e) = self
+                .twitch
                 .v5_update_channel(&user.streamer().id, request)
-                .await?;
-            self.stream_info
+                .await
+            {
+                respond!(ctx, "Failed to update the title, sorry :(");
+                log_error!(e, "Failed to update title");
+                return Ok(());
+            }
+
+            if let Err(e) = self
+                .stream_info
                 .refresh_channel(&self.twitch, user.streamer())
-                .await?;
+                .await
+            {
+                log_error!(e, "Failed to refresh channel after updating title");
+            }
 
-            respond!(ctx, "Title updated!");
+            respond!(ctx, "Title updated to: {}", title);
         }
 
         Ok(())
@@ -167,15 +178,25 @@ impl command::Handler for Game {
         let stream_info = self.stream_info.clone();
 
         let mut request = api::twitch::v5::UpdateChannelRequest::default();
-        request.channel.game = Some(game);
-        twitch
+        request.channel.game = Some(game.clone());
+
+        if let Err(e) = twitch
             .v5_update_channel(&ctx.user.streamer().id, request)
-            .await?;
-        stream_info
+            .await
+        {
+            respond!(ctx, "Failed to update the game, sorry :(");
+            log_error!(e, "Failed to update game");
+            return Ok(());
+        }
+
+        if let Err(e) = stream_info
             .refresh_channel(&twitch, ctx.user.streamer())
-            .await?;
+            .await
+        {
+            log_error!(e, "Failed to refresh channel after updating game");
+        }
 
-        respond!(ctx, "Game updated!");
+        respond!(ctx, "Game updated to: {}", game);
         Ok(())
     }
 }
@@ -218,9 +239,9 @@ impl super::Module for Module {
         );
 
         handlers.insert(
-            "uptime",
-            Uptime {
-                enabled: settings.var("uptime/enabled", true).await?,
+            "followers",
+            Followers {
+                enabled: settings.var("followers/enabled", true).await?,
                 stream_info: stream_info.clone(),
             },
         );
@@ -13,21 +13,35 @@ mod macros;
 pub mod admin;
 pub mod after_stream;
 pub mod alias_admin;
+pub mod announcements;
 pub mod auth;
+pub mod bad_words;
+pub mod balance;
 pub mod clip;
 pub mod command_admin;
+pub mod config;
+pub mod count;
 pub mod countdown;
-pub mod eight_ball;
+pub mod events;
+pub mod filters;
+pub mod fun;
+pub mod gamble;
+pub mod giveaway;
 pub mod gtav;
 pub mod help;
 pub mod misc;
 pub mod poll;
 pub mod promotions;
+pub mod quote;
+pub mod shoutout;
 pub mod song;
 pub mod speedrun;
 pub mod swearjar;
 pub mod theme_admin;
 pub mod time;
+pub mod timer;
+pub mod uptime;
+pub mod watch_time;
 pub mod water;
 pub mod weather;
 
@@ -47,6 +61,11 @@ impl Handlers {
     pub fn get(&self, command: &str) -> Option<Arc<dyn command::Handler>> {
         self.handlers.get(command).cloned()
     }
+
+    /// Iterate over the names of all registered commands.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
 }
 
 /// Context for a hook.
@@ -56,6 +75,7 @@ pub struct HookContext<'a> {
     pub futures: &'a mut utils::Futures<'static>,
     pub stream_info: &'a stream_info::StreamInfo,
     pub idle: &'a idle::Idle,
+    pub filters: &'a crate::filters::Filters,
     pub twitch: &'a api::Twitch,
     pub streamer_twitch: &'a api::Twitch,
     pub sender: &'a irc::Sender,
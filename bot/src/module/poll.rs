@@ -6,13 +6,82 @@ use crate::prelude::*;
 use crate::utils;
 use anyhow::Error;
 use chrono::{DateTime, Utc};
-use std::collections::{HashMap, HashSet};
-use tokio::sync::Mutex;
+use std::collections::HashMap;
+use std::fmt;
+use tokio::sync::{Mutex, Notify};
 
 /// Handler for the !poll command.
 pub struct Poll {
     enabled: settings::Var<bool>,
-    polls: Mutex<HashMap<command::HookId, ActivePoll>>,
+    duration: settings::Var<utils::Duration>,
+    polls: Polls,
+    /// The hook counting votes, once it's been installed.
+    hook: Mutex<Option<command::HookId>>,
+}
+
+impl Poll {
+    /// Start a poll with the given question, where the remaining arguments are
+    /// the options.
+    async fn run(&self, ctx: &mut command::Context, question: String) -> Result<(), Error> {
+        let mut options = Vec::new();
+
+        while let Some(option) = ctx.next() {
+            let (key, description) = match option.find('=') {
+                Some(i) => {
+                    let (keyword, description) = option.split_at(i);
+                    (keyword.to_string(), Some(description[1..].to_string()))
+                }
+                None => (option, None),
+            };
+
+            options.push(PollOption {
+                key: key.to_lowercase(),
+                description,
+            });
+        }
+
+        if options.is_empty() {
+            respond_bail!("Expected: <question> <options...>");
+        }
+
+        {
+            let mut hook = self.hook.lock().await;
+
+            if hook.is_none() {
+                *hook = Some(ctx.insert_hook(self.polls.clone()).await);
+            }
+        }
+
+        let duration = self.duration.load().await;
+        let started_at = Utc::now();
+
+        let ends_at = if duration.is_empty() {
+            None
+        } else {
+            Some(started_at + duration.as_chrono())
+        };
+
+        let previous = self.polls.start(ActivePoll {
+            id: 0,
+            question: question.clone(),
+            options,
+            started_at,
+            ends_at,
+            votes: HashMap::new(),
+        });
+
+        if let Some(previous) = previous {
+            ctx.privmsg(format!("Poll closed: {}", previous)).await;
+        }
+
+        if duration.is_empty() {
+            respond!(ctx, "Started poll `{}`", question);
+        } else {
+            respond!(ctx, "Started poll `{}` for {}", question, duration);
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -26,145 +95,253 @@ impl command::Handler for Poll {
             return Ok(());
         }
 
-        match ctx.next().as_deref() {
-            Some("run") => {
+        match ctx.next() {
+            Some(command) if command == "close" => {
+                let results = self
+                    .polls
+                    .close()
+                    .ok_or_else(|| respond_err!("No running polls"))?;
+
+                respond!(ctx, "{}", results);
+            }
+            Some(command) if command == "run" => {
                 let question = ctx.next_str("<question> <options...>")?;
+                self.run(ctx, question).await?;
+            }
+            Some(question) => {
+                self.run(ctx, question).await?;
+            }
+            None => {
+                ctx.respond("Expected: <question> <options...>, run, or close.")
+                    .await;
+            }
+        }
 
-                let mut options = HashMap::new();
+        Ok(())
+    }
+}
 
-                while let Some(option) = ctx.next() {
-                    let (key, description) = match option.find('=') {
-                        Some(i) => {
-                            let (keyword, description) = option.split_at(i);
-                            (keyword.to_string(), Some(description[1..].to_string()))
-                        }
-                        None => (option, None),
-                    };
+/// The running poll, shared between the `!poll` command, the hook which
+/// counts votes, and anything else which wants to read the current state of
+/// the poll, like overlays.
+#[derive(Clone, Default)]
+pub struct Polls {
+    inner: Arc<parking_lot::Mutex<PollsInner>>,
+    /// Notified when a poll is started or closed.
+    changed: Arc<Notify>,
+}
 
-                    options.insert(key.to_lowercase(), description);
-                }
+#[derive(Default)]
+struct PollsInner {
+    current: Option<ActivePoll>,
+    /// The id to give to the next poll.
+    next_id: u64,
+}
 
-                let poll = ActivePoll {
-                    question: question.clone(),
-                    created_at: Utc::now(),
-                    options,
-                    inner: settings::Var::new(Inner {
-                        voted: Default::default(),
-                        votes: Default::default(),
-                    }),
-                };
-
-                let hook_id = ctx.insert_hook(poll.clone()).await;
-                self.polls.lock().await.insert(hook_id, poll);
-                ctx.respond(format!("Started poll `{}` (id: {})", question, hook_id))
-                    .await;
-            }
-            Some("close") => {
-                let mut polls = self.polls.lock().await;
-
-                let id = match ctx.next() {
-                    Some(id) => str::parse::<command::HookId>(&id)
-                        .map_err(|_| respond_err!("Bad id `{}`", id))?,
-                    None => {
-                        *polls
-                            .iter()
-                            .max_by_key(|e| e.1.created_at)
-                            .ok_or_else(|| respond_err!("No running polls"))?
-                            .0
-                    }
-                };
+impl Polls {
+    /// Get the current state of the running poll, if there is one.
+    pub fn current(&self) -> Option<Results> {
+        self.inner.lock().current.as_ref().map(ActivePoll::results)
+    }
 
-                let poll = polls
-                    .remove(&id)
-                    .ok_or_else(|| respond_err!("No poll with id `{}`!", id))?;
+    /// Start the given poll, closing and returning the results of the one
+    /// which was running.
+    fn start(&self, mut poll: ActivePoll) -> Option<Results> {
+        let mut inner = self.inner.lock();
+        poll.id = inner.next_id;
+        inner.next_id += 1;
+        let previous = inner.current.replace(poll);
+        self.changed.notify_one();
+        previous.as_ref().map(ActivePoll::results)
+    }
 
-                ctx.remove_hook(id).await;
-                let results = poll.close().await;
+    /// Close the running poll, returning its results.
+    fn close(&self) -> Option<Results> {
+        let poll = self.inner.lock().current.take()?;
+        self.changed.notify_one();
+        Some(poll.results())
+    }
 
-                let total = results.iter().map(|(_, c)| c).sum::<u32>();
+    /// Close the poll with the given id if it's still running.
+    fn close_id(&self, id: u64) -> Option<Results> {
+        let mut inner = self.inner.lock();
 
-                let mut formatted = Vec::new();
+        match &inner.current {
+            Some(poll) if poll.id == id => (),
+            _ => return None,
+        }
 
-                for (key, votes) in results {
-                    let p = utils::percentage(votes, total);
+        inner.current.take().map(|poll| poll.results())
+    }
 
-                    let votes = match votes {
-                        0 => "no votes".to_string(),
-                        1 => "one vote".to_string(),
-                        n => format!("{} votes", n),
-                    };
+    /// The id of the running poll and when it should be closed, if it should
+    /// be closed automatically.
+    fn deadline(&self) -> Option<(u64, DateTime<Utc>)> {
+        let inner = self.inner.lock();
+        let poll = inner.current.as_ref()?;
+        Some((poll.id, poll.ends_at?))
+    }
 
-                    formatted.push(format!("{} = {} ({})", key, votes, p));
-                }
+    /// Count a vote in the given message.
+    ///
+    /// Only the last vote of each user is counted.
+    fn vote(&self, user: &str, message: &str) {
+        let mut inner = self.inner.lock();
 
-                respond!(ctx, "{} -> {}.", poll.question, formatted.join(", "));
+        let poll = match &mut inner.current {
+            Some(poll) => poll,
+            None => return,
+        };
+
+        for word in utils::TrimmedWords::new(message) {
+            if let Some(index) = poll.option(word) {
+                poll.votes.insert(user.to_string(), index);
+                break;
             }
-            _ => {
-                ctx.respond("Expected: run, close.").await;
+        }
+    }
+
+    /// Close polls when their time runs out, and announce the results.
+    async fn run(self, sender: irc::Sender) -> Result<(), Error> {
+        loop {
+            let (id, ends_at) = match self.deadline() {
+                Some(deadline) => deadline,
+                None => {
+                    self.changed.notified().await;
+                    continue;
+                }
+            };
+
+            let remaining = (ends_at - Utc::now()).to_std().unwrap_or_default();
+
+            tokio::select! {
+                _ = tokio::time::sleep(remaining) => {
+                    if let Some(results) = self.close_id(id) {
+                        sender.privmsg(format!("Poll closed: {}", results)).await;
+                    }
+                }
+                _ = self.changed.notified() => (),
             }
         }
+    }
+}
+
+#[async_trait]
+impl command::MessageHook for Polls {
+    async fn peek(&self, user: &irc::User, m: &str) -> Result<(), Error> {
+        if let Some(user) = user.real() {
+            self.vote(user.name(), m);
+        }
 
         Ok(())
     }
 }
 
-struct Inner {
-    voted: HashSet<String>,
-    votes: HashMap<String, u32>,
+struct PollOption {
+    /// The keyword to vote for the option, in lowercase.
+    key: String,
+    description: Option<String>,
 }
 
-#[derive(Clone)]
 struct ActivePoll {
+    id: u64,
     question: String,
-    created_at: DateTime<Utc>,
-    options: HashMap<String, Option<String>>,
-    inner: settings::Var<Inner>,
+    options: Vec<PollOption>,
+    started_at: DateTime<Utc>,
+    ends_at: Option<DateTime<Utc>>,
+    /// The index of the option each user voted for.
+    votes: HashMap<String, usize>,
 }
 
 impl ActivePoll {
-    /// Close the poll.
-    pub async fn close(&self) -> Vec<(String, u32)> {
-        let inner = self.inner.read().await;
+    /// Find the index of the option the given word votes for, which is either
+    /// its keyword or its number.
+    fn option(&self, word: &str) -> Option<usize> {
+        let word = word.to_lowercase();
+
+        if let Some(index) = self.options.iter().position(|o| o.key == word) {
+            return Some(index);
+        }
 
-        let mut results = Vec::new();
+        let number = str::parse::<usize>(&word).ok()?;
 
-        for (o, description) in &self.options {
-            results.push((
-                description.clone().unwrap_or_else(|| o.to_string()),
-                inner.votes.get(o).cloned().unwrap_or_default(),
-            ));
+        if (1..=self.options.len()).contains(&number) {
+            return Some(number - 1);
         }
 
-        results.sort_by(|a, b| b.1.cmp(&a.1));
-        results
+        None
     }
-}
 
-#[async_trait]
-impl command::MessageHook for ActivePoll {
-    async fn peek(&self, user: &irc::User, m: &str) -> Result<(), Error> {
-        let mut inner = self.inner.write().await;
+    /// Tally up the votes of the poll.
+    fn results(&self) -> Results {
+        let mut counts = vec![0u32; self.options.len()];
 
-        let user = match user.real() {
-            Some(user) => user,
-            None => return Ok(()),
-        };
+        for index in self.votes.values() {
+            counts[*index] += 1;
+        }
 
-        if inner.voted.contains(user.name()) {
-            return Ok(());
+        let options = self
+            .options
+            .iter()
+            .zip(counts)
+            .map(|(o, votes)| OptionResult {
+                key: o.key.clone(),
+                description: o.description.clone(),
+                votes,
+            })
+            .collect();
+
+        Results {
+            question: self.question.clone(),
+            started_at: self.started_at,
+            ends_at: self.ends_at,
+            options,
         }
+    }
+}
 
-        for word in utils::TrimmedWords::new(m) {
-            if self.options.get(&word.to_lowercase()).is_none() {
-                continue;
-            }
+/// The state of a poll.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Results {
+    pub question: String,
+    pub started_at: DateTime<Utc>,
+    /// When the poll will be closed, if it's closed automatically.
+    pub ends_at: Option<DateTime<Utc>>,
+    /// The options of the poll and their votes, in the order they were given.
+    pub options: Vec<OptionResult>,
+}
+
+/// The votes for a single option of a poll.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OptionResult {
+    pub key: String,
+    pub description: Option<String>,
+    pub votes: u32,
+}
+
+impl fmt::Display for Results {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.options.iter().map(|o| o.votes).sum::<u32>();
+
+        let mut options = self.options.iter().collect::<Vec<_>>();
+        options.sort_by(|a, b| b.votes.cmp(&a.votes));
+
+        let mut formatted = Vec::new();
 
-            *inner.votes.entry(word.to_string()).or_default() += 1;
-            inner.voted.insert(user.name().to_string());
-            break;
+        for o in options {
+            let p = utils::percentage(o.votes, total);
+
+            let votes = match o.votes {
+                0 => "no votes".to_string(),
+                1 => "one vote".to_string(),
+                n => format!("{} votes", n),
+            };
+
+            let name = o.description.as_deref().unwrap_or(&o.key);
+            formatted.push(format!("{} = {} ({})", name, votes, p));
         }
 
-        Ok(())
+        write!(fmt, "{} -> {}.", self.question, formatted.join(", "))
     }
 }
 
@@ -180,17 +357,105 @@ impl super::Module for Module {
     async fn hook(
         &self,
         module::HookContext {
-            handlers, settings, ..
+            handlers,
+            futures,
+            injector,
+            sender,
+            settings,
+            ..
         }: module::HookContext<'_>,
     ) -> Result<(), anyhow::Error> {
+        let polls = Polls::default();
+        injector.update(polls.clone()).await;
+        futures.push(Box::pin(polls.clone().run(sender.clone())));
+
         handlers.insert(
             "poll",
             Poll {
-                polls: Mutex::new(Default::default()),
                 enabled: settings.var("poll/enabled", false).await?,
+                duration: settings
+                    .var("poll/duration", utils::Duration::seconds(60))
+                    .await?,
+                polls,
+                hook: Mutex::new(None),
             },
         );
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ActivePoll, PollOption, Polls};
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    fn poll(question: &str, options: &[(&str, Option<&str>)]) -> ActivePoll {
+        ActivePoll {
+            id: 0,
+            question: question.to_string(),
+            options: options
+                .iter()
+                .map(|(key, description)| PollOption {
+                    key: key.to_string(),
+                    description: description.map(String::from),
+                })
+                .collect(),
+            started_at: Utc::now(),
+            ends_at: None,
+            votes: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_votes() {
+        let polls = Polls::default();
+        assert!(polls
+            .start(poll(
+                "Which game?",
+                &[("gta", Some("GTA 5")), ("doom", None)]
+            ))
+            .is_none());
+
+        polls.vote("a", "GTA!");
+        polls.vote("b", "2");
+        polls.vote("c", "I vote doom");
+        // NB: only the last vote of a user counts.
+        polls.vote("d", "gta");
+        polls.vote("d", "doom");
+        // out of range.
+        polls.vote("e", "3");
+
+        let results = polls.current().expect("running poll");
+        assert_eq!(results.options[0].votes, 1);
+        assert_eq!(results.options[1].votes, 3);
+        assert_eq!(
+            results.to_string(),
+            "Which game? -> doom = 3 votes (75%), GTA 5 = one vote (25%)."
+        );
+    }
+
+    #[test]
+    fn test_start_closes_previous() {
+        let polls = Polls::default();
+        polls.start(poll("First?", &[("yes", None), ("no", None)]));
+        polls.vote("a", "yes");
+
+        let id = polls.inner.lock().current.as_ref().unwrap().id;
+
+        let previous = polls
+            .start(poll("Second?", &[("yes", None), ("no", None)]))
+            .expect("previous poll");
+        assert_eq!(
+            previous.to_string(),
+            "First? -> yes = one vote (100%), no = no votes (0%)."
+        );
+
+        // NB: the timer of the first poll must not close the second one.
+        assert!(polls.close_id(id).is_none());
+        assert_eq!(polls.current().unwrap().question, "Second?");
+        assert!(polls.close().is_some());
+        assert!(polls.current().is_none());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::auth;
+use crate::command;
+use crate::db;
+use crate::module;
+use crate::prelude::*;
+use crate::template::Template;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+/// Handler for the `!quote` command.
+pub struct Handler {
+    enabled: settings::Var<bool>,
+    template: settings::Var<Template>,
+    quotes: injector::Ref<db::Quotes>,
+}
+
+impl Handler {
+    /// Show the given quote.
+    async fn show(&self, ctx: &mut command::Context, quote: db::Quote) -> Result<()> {
+        let date = DateTime::<Utc>::from_utc(quote.added_at, Utc)
+            .format("%Y-%m-%d")
+            .to_string();
+
+        let response = self.template.load().await.render_to_string(Vars {
+            id: quote.number,
+            text: &quote.text,
+            author: &quote.user,
+            date: &date,
+        })?;
+
+        ctx.privmsg(response).await;
+        return Ok(());
+
+        #[derive(serde::Serialize)]
+        struct Vars<'a> {
+            id: i32,
+            text: &'a str,
+            author: &'a str,
+            date: &'a str,
+        }
+    }
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Quote)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let quotes = match self.quotes.load().await {
+            Some(quotes) => quotes,
+            None => return Ok(()),
+        };
+
+        match ctx.next().as_deref() {
+            Some("add") => {
+                ctx.check_scope(auth::Scope::QuoteEdit).await?;
+
+                let user = match ctx.user.real() {
+                    Some(user) => user,
+                    None => {
+                        respond!(ctx, "Only real users can add quotes");
+                        return Ok(());
+                    }
+                };
+
+                let text = ctx.rest().trim();
+
+                if text.is_empty() {
+                    respond!(ctx, "Expected: !quote add <text>");
+                    return Ok(());
+                }
+
+                let quote = quotes
+                    .push(ctx.channel(), user.display_name(), text)
+                    .await?;
+                respond!(ctx, "Added quote #{}.", quote.number);
+            }
+            Some("delete") => {
+                ctx.check_scope(auth::Scope::QuoteEdit).await?;
+
+                let number = ctx.next_parse("<number>")?;
+
+                match quotes.delete(ctx.channel(), number).await? {
+                    Some(..) => respond!(ctx, "Deleted quote #{}.", number),
+                    None => respond!(ctx, "There is no quote #{}.", number),
+                }
+            }
+            Some(number) => {
+                let number = match str::parse::<u32>(number) {
+                    Ok(number) => number,
+                    Err(..) => {
+                        respond!(
+                            ctx,
+                            "Expected: !quote, !quote <number>, !quote add, or !quote delete."
+                        );
+                        return Ok(());
+                    }
+                };
+
+                match quotes.get(ctx.channel(), number).await? {
+                    Some(quote) => self.show(ctx, quote).await?,
+                    None => respond!(ctx, "There is no quote #{}.", number),
+                }
+            }
+            None => match quotes.random(ctx.channel()).await? {
+                Some(quote) => self.show(ctx, quote).await?,
+                None => respond!(ctx, "There are no quotes yet."),
+            },
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "quote"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            handlers,
+            settings,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        let settings = settings.scoped("quote");
+        let default_template =
+            Template::compile("Quote #{{id}}: \"{{text}}\" - {{author}}, {{date}}")?;
+
+        handlers.insert(
+            "quote",
+            Handler {
+                enabled: settings.var("enabled", false).await?,
+                template: settings.var("template", default_template).await?,
+                quotes: injector.var().await,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Handler;
+    use crate::command;
+    use crate::db;
+    use crate::irc::mock::Mock;
+    use crate::prelude::*;
+    use crate::template::Template;
+    use anyhow::Result;
+    use chrono::Utc;
+
+    async fn setup() -> Result<(Mock, Handler)> {
+        let mock = Mock::new().await?;
+        mock.injector
+            .update(db::Quotes::load(db::Database::memory()?).await?)
+            .await;
+
+        let handler = Handler {
+            enabled: settings::Var::new(true),
+            template: settings::Var::new(Template::compile(
+                "#{{id}}: {{text}} ({{author}}, {{date}})",
+            )?),
+            quotes: mock.injector.var().await,
+        };
+
+        Ok((mock, handler))
+    }
+
+    fn respond_message(e: anyhow::Error) -> String {
+        match e.downcast::<command::Respond>() {
+            Ok(respond) => respond.0.to_string(),
+            Err(e) => panic!("expected respond error, got: {}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quotes() -> Result<()> {
+        let (mock, handler) = setup().await?;
+        mock.moderator("moderator");
+        let today = Utc::now().format("%Y-%m-%d").to_string();
+
+        mock.run(&handler, "viewer", "").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from("viewer -> There are no quotes yet.")]
+        );
+
+        let e = mock.run(&handler, "viewer", "add hello").await.unwrap_err();
+        assert_eq!(respond_message(e), "Do you think this is a democracy? LUL");
+
+        mock.run(&handler, "moderator", "add first quote").await?;
+        mock.run(&handler, "moderator", "add second quote").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("moderator -> Added quote #1."),
+                String::from("moderator -> Added quote #2."),
+            ]
+        );
+
+        mock.run(&handler, "viewer", "2").await?;
+        mock.run(&handler, "viewer", "3").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                format!("#2: second quote (moderator, {})", today),
+                String::from("viewer -> There is no quote #3."),
+            ]
+        );
+
+        // NB: deleting a quote doesn't renumber the ones after it.
+        mock.run(&handler, "moderator", "delete 1").await?;
+        mock.run(&handler, "viewer", "1").await?;
+        mock.run(&handler, "viewer", "").await?;
+        mock.run(&handler, "moderator", "add third quote").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("moderator -> Deleted quote #1."),
+                String::from("viewer -> There is no quote #1."),
+                format!("#2: second quote (moderator, {})", today),
+                String::from("moderator -> Added quote #3."),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::api;
+use crate::auth;
+use crate::command;
+use crate::db;
+use crate::module;
+use crate::prelude::*;
+use crate::template::Template;
+use anyhow::Result;
+
+/// Handler for the `!so` command.
+pub struct Shoutout {
+    enabled: settings::Var<bool>,
+    template: settings::Var<Template>,
+    no_game_template: settings::Var<Template>,
+    twitch: api::Twitch,
+}
+
+#[async_trait]
+impl command::Handler for Shoutout {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Shoutout)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let login = db::user_id(&ctx.next_str("<user>")?);
+
+        let user = match self.twitch.new_user_by_login(&login).await? {
+            Some(user) => user,
+            None => {
+                respond!(ctx, "No channel named `{}`", login);
+                return Ok(());
+            }
+        };
+
+        let game = self.twitch.new_last_game(&user.id).await?;
+        let url = format!("https://twitch.tv/{}", user.login);
+
+        let template = match game {
+            Some(..) => self.template.load().await,
+            None => self.no_game_template.load().await,
+        };
+
+        let response = template.render_to_string(Vars {
+            target: &user.display_name,
+            url: &url,
+            game: game.as_deref(),
+        })?;
+
+        ctx.privmsg(response).await;
+        return Ok(());
+
+        #[derive(serde::Serialize)]
+        struct Vars<'a> {
+            target: &'a str,
+            url: &'a str,
+            game: Option<&'a str>,
+        }
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "shoutout"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            settings,
+            twitch,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        let default_template = Template::compile(
+            "Go check out {{target}} at {{url}}, they were last playing {{game}}!",
+        )?;
+        let default_no_game_template = Template::compile("Go check out {{target}} at {{url}}!")?;
+
+        handlers.insert(
+            "so",
+            Shoutout {
+                enabled: settings.var("shoutout/enabled", true).await?,
+                template: settings.var("shoutout/template", default_template).await?,
+                no_game_template: settings
+                    .var("shoutout/no-game-template", default_no_game_template)
+                    .await?,
+                twitch: twitch.clone(),
+            },
+        );
+
+        Ok(())
+    }
+}
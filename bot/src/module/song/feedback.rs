@@ -1,21 +1,75 @@
 use crate::irc;
 use crate::irc::Sender;
-use crate::player::{Event, Player};
+use crate::player::{Event, Item, Player};
 use crate::prelude::*;
 use crate::settings;
+use crate::template::Template;
 use crate::utils::{Cooldown, Duration};
 use anyhow::{Context as _, Result};
+use std::time;
+
+/// The default template for announcing the song which is playing.
+pub(crate) const DEFAULT_NOW_PLAYING: &str =
+    "Now playing: {{what}}{{#if user}}, requested by {{user}}{{/if}}.";
+
+/// Announces in chat when the player switches to a new song.
+#[derive(Clone)]
+pub(crate) struct NowPlaying {
+    enabled: settings::Var<bool>,
+    template: settings::Var<Template>,
+    /// Announcements are skipped if the last one was more recent than this,
+    /// so that skipping songs quickly doesn't flood chat.
+    cooldown: settings::Var<Duration>,
+}
+
+impl NowPlaying {
+    pub(crate) async fn new(settings: &crate::Settings) -> Result<Self> {
+        Ok(Self {
+            enabled: settings.var("enabled", true).await?,
+            template: settings
+                .var("template", Template::compile(DEFAULT_NOW_PLAYING)?)
+                .await?,
+            cooldown: settings.var("cooldown", Duration::seconds(10)).await?,
+        })
+    }
+}
+
+/// Variables available to the now playing template.
+#[derive(serde::Serialize)]
+struct NowPlayingVars<'a> {
+    what: String,
+    name: String,
+    artists: Option<String>,
+    user: Option<&'a str>,
+}
+
+impl<'a> NowPlayingVars<'a> {
+    fn new(item: &'a Item) -> Self {
+        Self {
+            what: item.what(),
+            name: item.track.name(),
+            artists: item.track.artists(),
+            user: item.user.as_deref(),
+        }
+    }
+}
 
 /// Setup the task that sends chat feedback.
 pub(crate) async fn task(
     sender: Sender,
     injector: Injector,
     chat_feedback: settings::Var<bool>,
+    now_playing: NowPlaying,
 ) -> Result<()> {
     let (mut player_stream, player) = injector.stream::<Player>().await;
 
     let new_feedback_loop = move |new_player: Option<Player>| match new_player {
-        Some(player) => Fuse::new(feedback(player, sender.clone(), chat_feedback.clone())),
+        Some(player) => Fuse::new(feedback(
+            player,
+            sender.clone(),
+            chat_feedback.clone(),
+            now_playing.clone(),
+        )),
         None => Default::default(),
     };
 
@@ -41,8 +95,10 @@ async fn feedback(
     player: Player,
     sender: irc::Sender,
     chat_feedback: settings::Var<bool>,
+    now_playing: NowPlaying,
 ) -> Result<()> {
     let mut configured_cooldown = Cooldown::from_duration(Duration::seconds(10));
+    let mut last_now_playing = None::<time::Instant>;
     let mut rx = player.subscribe().await;
 
     loop {
@@ -54,19 +110,29 @@ async fn feedback(
                 sender.privmsg("Player is detached!").await;
             }
             Event::Playing(feedback, item) => {
-                if !feedback || !chat_feedback.load().await {
+                if !feedback || !chat_feedback.load().await || !now_playing.enabled.load().await {
                     continue;
                 }
 
+                let now = time::Instant::now();
+                let cooldown = now_playing.cooldown.load().await.as_std();
+
+                if let Some(last) = last_now_playing {
+                    if now.saturating_duration_since(last) < cooldown {
+                        log::trace!("skipping now playing announcement, on cooldown");
+                        continue;
+                    }
+                }
+
+                last_now_playing = Some(now);
+
                 if let Some(item) = item {
-                    let message = match item.user.as_ref() {
-                        Some(user) => {
-                            format!("Now playing: {}, requested by {}.", item.what(), user)
-                        }
-                        None => format!("Now playing: {}.", item.what(),),
-                    };
-
-                    sender.privmsg(message).await;
+                    let template = now_playing.template.load().await;
+
+                    match template.render_to_string(NowPlayingVars::new(&item)) {
+                        Ok(message) => sender.privmsg(message).await,
+                        Err(e) => log_error!(e, "failed to render now playing template"),
+                    }
                 } else {
                     sender.privmsg("Now playing.").await;
                 }
@@ -96,3 +162,40 @@ async fn feedback(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{NowPlayingVars, DEFAULT_NOW_PLAYING};
+    use crate::template::Template;
+    use anyhow::Result;
+
+    #[test]
+    fn test_now_playing_template() -> Result<()> {
+        let template = Template::compile(DEFAULT_NOW_PLAYING)?;
+
+        let vars = NowPlayingVars {
+            what: String::from("\"Bohemian Rhapsody\" by Queen"),
+            name: String::from("Bohemian Rhapsody"),
+            artists: Some(String::from("Queen")),
+            user: Some("setbac"),
+        };
+
+        assert_eq!(
+            template.render_to_string(vars)?,
+            "Now playing: \"Bohemian Rhapsody\" by Queen, requested by setbac."
+        );
+
+        let vars = NowPlayingVars {
+            what: String::from("\"Bohemian Rhapsody\" by Queen"),
+            name: String::from("Bohemian Rhapsody"),
+            artists: Some(String::from("Queen")),
+            user: None,
+        };
+
+        assert_eq!(
+            template.render_to_string(vars)?,
+            "Now playing: \"Bohemian Rhapsody\" by Queen."
+        );
+        Ok(())
+    }
+}
@@ -8,6 +8,7 @@ use crate::player::{AddTrackError, Item, PlayThemeError, Player};
 use crate::prelude::*;
 use crate::settings;
 use crate::utils::{self, Cooldown, Duration};
+use crate::web;
 use anyhow::Result;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -15,8 +16,15 @@ use tokio::sync::Mutex;
 mod feedback;
 mod redemption;
 mod requester;
+mod skip_vote;
 
 const EXAMPLE_SEARCH: &str = "queen we will rock you";
+/// How much `!song volume up` and `!song volume down` changes the volume by
+/// default.
+const DEFAULT_VOLUME_STEP: u32 = 10;
+/// How many users `!song requesters` lists by default, and at most.
+const DEFAULT_REQUESTERS: usize = 5;
+const MAX_REQUESTERS: usize = 10;
 
 /// Handler for the `!song` command.
 pub struct Handler {
@@ -25,9 +33,101 @@ pub struct Handler {
     request_help_cooldown: Mutex<Cooldown>,
     currency: injector::Ref<Currency>,
     requester: requester::SongRequester,
+    skip_vote: SkipVoteConfig,
+    skip_votes: Mutex<skip_vote::SkipVotes>,
+}
+
+/// Settings for voting to skip songs.
+struct SkipVoteConfig {
+    enabled: settings::Var<bool>,
+    /// Fixed number of votes needed.
+    threshold: settings::Var<u32>,
+    /// Percentage of active chatters whose votes are needed, which takes
+    /// precedence over the fixed threshold.
+    percentage: settings::Var<Option<u32>>,
+    /// How recently a user must have chatted to count as active.
+    active_window: settings::Var<Duration>,
+    active_chatters: injector::Ref<irc::chatters::ActiveChatters>,
 }
 
 impl Handler {
+    /// Vote to skip the current song.
+    async fn handle_skip_vote(&self, ctx: &mut command::Context, player: &Player) -> Result<()> {
+        ctx.check_scope(Scope::SongSkipVote).await?;
+
+        if !self.skip_vote.enabled.load().await {
+            respond!(ctx, "Voting to skip songs is not enabled.");
+            return Ok(());
+        }
+
+        let user = match ctx.user.real() {
+            Some(user) => user.name().to_string(),
+            None => {
+                respond!(ctx, "Only real users can vote to skip songs");
+                return Ok(());
+            }
+        };
+
+        let song = match player.current().await {
+            Some(song) => song,
+            None => {
+                respond!(ctx, "Nothing is playing right now.");
+                return Ok(());
+            }
+        };
+
+        let active = match self.skip_vote.active_chatters.load().await {
+            Some(active_chatters) => {
+                let window = self.skip_vote.active_window.load().await.as_std();
+                active_chatters
+                    .active(window, std::time::Instant::now())
+                    .len()
+            }
+            None => 0,
+        };
+
+        let required = skip_vote::required(
+            self.skip_vote.threshold.load().await,
+            self.skip_vote.percentage.load().await,
+            active,
+        );
+
+        let vote = self
+            .skip_votes
+            .lock()
+            .await
+            .vote(&song.item.track_id, &user);
+
+        if vote.votes() >= required {
+            self.skip_votes.lock().await.clear();
+            player.skip().await?;
+            respond!(ctx, "Vote passed, skipping {}.", song.item.what());
+            return Ok(());
+        }
+
+        match vote {
+            skip_vote::Vote::Duplicate { votes } => {
+                respond!(
+                    ctx,
+                    "You already voted to skip this song ({}/{} votes).",
+                    votes,
+                    required
+                );
+            }
+            skip_vote::Vote::Counted { votes } => {
+                respond!(
+                    ctx,
+                    "Voted to skip {} ({}/{} votes).",
+                    song.item.what(),
+                    votes,
+                    required
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     async fn handle_request(&self, ctx: &mut command::Context, player: &Player) -> Result<()> {
         let q = ctx.rest().trim().to_string();
 
@@ -138,7 +238,7 @@ impl command::Handler for Handler {
                     Err(PlayThemeError::Error(e)) => {
                         user.respond("There was a problem playing that theme :(")
                             .await;
-                        log_error!(e, "failed to add song");
+                        log_error!(e, "[{}] failed to add song", ctx.id());
                     }
                     Err(PlayThemeError::MissingAuth) => {
                         user.respond(
@@ -247,6 +347,57 @@ impl command::Handler for Handler {
                 respond!(ctx, "Song queue purged.");
             }
             // print when your next song will play.
+            Some("mine") => {
+                let user = match ctx.user.real() {
+                    Some(user) => user,
+                    None => {
+                        respond!(ctx, "Not a real user");
+                        return Ok(());
+                    }
+                };
+
+                let name = user.name().to_lowercase();
+                let played = player.played_count(&name).await?;
+
+                let queued = player
+                    .list()
+                    .await
+                    .into_iter()
+                    .filter(|item| item.user.as_ref().map(|u| *u == name).unwrap_or_default())
+                    .map(|item| item.what())
+                    .collect::<Vec<_>>();
+
+                let played = match played {
+                    1 => String::from("One of your songs has been played"),
+                    n => format!("{} of your songs have been played", n),
+                };
+
+                if queued.is_empty() {
+                    respond!(ctx, "{}, and you don't have any songs in queue.", played);
+                } else {
+                    respond!(ctx, "{}. In queue: {}.", played, queued.join(", "));
+                }
+            }
+            Some("requesters") => {
+                let limit = ctx
+                    .next_parse_optional::<usize>()?
+                    .unwrap_or(DEFAULT_REQUESTERS)
+                    .min(MAX_REQUESTERS);
+
+                let requesters = player.top_requesters(limit).await?;
+
+                if requesters.is_empty() {
+                    respond!(ctx, "No requested songs have been played yet.");
+                    return Ok(());
+                }
+
+                let requesters = requesters
+                    .into_iter()
+                    .map(|(user, count)| format!("{} ({})", user, count))
+                    .collect::<Vec<_>>();
+
+                respond!(ctx, "Top requesters: {}.", requesters.join(", "));
+            }
             Some("when") => {
                 let user = ctx.next();
 
@@ -340,8 +491,85 @@ impl command::Handler for Handler {
                     Some(item) => ctx.respond(format!("Removed: {}!", item.what())).await,
                 }
             }
+            Some("mood") => {
+                ctx.check_scope(Scope::SongEditQueue).await?;
+
+                let mood = match ctx.next().as_deref() {
+                    Some("off") => None,
+                    Some(mood) => match str::parse::<player::Mood>(mood) {
+                        Ok(mood) => Some(mood),
+                        Err(..) => {
+                            respond!(ctx, "Expected: chill, hype, or off.");
+                            return Ok(());
+                        }
+                    },
+                    None => {
+                        match player.mood().await {
+                            Some(mood) => {
+                                respond!(ctx, "Fallback songs lean towards {} tracks.", mood)
+                            }
+                            None => {
+                                respond!(ctx, "No mood set, fallback songs are picked at random.")
+                            }
+                        }
+
+                        return Ok(());
+                    }
+                };
+
+                player.set_mood(mood).await?;
+
+                match mood {
+                    Some(mood) => {
+                        respond!(ctx, "Fallback songs will now lean towards {} tracks.", mood)
+                    }
+                    None => respond!(ctx, "Mood cleared, fallback songs are picked at random."),
+                }
+            }
+            // remove your own most recent request.
+            Some("wrongsong") => {
+                let user = match ctx.user.real() {
+                    Some(user) => user,
+                    None => {
+                        respond!(ctx, "Only real users can delete their own songs");
+                        return Ok(());
+                    }
+                };
+
+                let name = user.name().to_lowercase();
+
+                match player.remove_last_by_user(&name).await? {
+                    Some(item) => respond!(ctx, "Removed your request: {}.", item.what()),
+                    None => respond!(ctx, "You don't have any songs in queue."),
+                }
+            }
             Some("volume") => {
                 match ctx.next().as_deref() {
+                    // relative volume adjustment
+                    Some(direction @ "up") | Some(direction @ "down") => {
+                        ctx.check_scope(Scope::SongVolume).await?;
+
+                        let step = ctx
+                            .next_parse_optional::<u32>()?
+                            .unwrap_or(DEFAULT_VOLUME_STEP);
+
+                        let volume = match direction {
+                            "up" => player::ModifyVolume::Increase(step),
+                            _ => player::ModifyVolume::Decrease(step),
+                        };
+
+                        match player.volume(volume).await {
+                            Some(volume) => {
+                                respond!(ctx, format!("Updated volume to {}.", volume));
+                            }
+                            None => {
+                                respond!(
+                                    ctx,
+                                    "Cannot update volume, nothing is playing right now."
+                                );
+                            }
+                        }
+                    }
                     // setting volume
                     Some(other) => {
                         ctx.check_scope(Scope::SongVolume).await?;
@@ -360,6 +588,11 @@ impl command::Handler for Handler {
                             }
                         };
 
+                        if diff.is_none() && argument > 100 {
+                            respond!(ctx, "Volume must be between 0 and 100.");
+                            return Ok(());
+                        }
+
                         let volume = match diff {
                             Some(true) => player::ModifyVolume::Increase(argument),
                             Some(false) => player::ModifyVolume::Decrease(argument),
@@ -386,10 +619,35 @@ impl command::Handler for Handler {
                     },
                 }
             }
-            Some("skip") => {
+            Some("seek") => {
                 ctx.check_scope(Scope::SongPlaybackControl).await?;
-                player.skip().await?;
+
+                let position = match ctx.next().map(|p| str::parse::<utils::Offset>(&p)) {
+                    Some(Ok(position)) => position.as_duration(),
+                    _ => {
+                        respond!(ctx, "Expected a position like 1:30 or 90");
+                        return Ok(());
+                    }
+                };
+
+                match player.seek(position).await? {
+                    Some(position) => {
+                        respond!(ctx, "Seeked to {}.", utils::digital_duration(position));
+                    }
+                    None => {
+                        respond!(ctx, "Cannot seek, nothing is playing right now.");
+                    }
+                }
             }
+            Some("skip") => match ctx.next().as_deref() {
+                Some("vote") => {
+                    self.handle_skip_vote(ctx, &player).await?;
+                }
+                _ => {
+                    ctx.check_scope(Scope::SongPlaybackControl).await?;
+                    player.skip().await?;
+                }
+            },
             Some("request") => {
                 self.handle_request(ctx, &player).await?;
             }
@@ -405,6 +663,55 @@ impl command::Handler for Handler {
                 ctx.check_scope(Scope::SongPlaybackControl).await?;
                 player.pause().await?;
             }
+            Some("device") => {
+                ctx.check_scope(Scope::SongPlaybackControl).await?;
+
+                let name = ctx.rest().trim();
+
+                if name.is_empty() {
+                    let current = player.current_device().await;
+                    let devices = player.list_devices().await?;
+
+                    if devices.is_empty() {
+                        respond!(ctx, "No devices available.");
+                        return Ok(());
+                    }
+
+                    let devices = devices
+                        .iter()
+                        .map(|d| match current.as_deref() {
+                            Some(current) if current == d.id => format!("{} (current)", d.name),
+                            _ => d.name.to_string(),
+                        })
+                        .collect::<Vec<_>>();
+
+                    respond!(ctx, "Devices: {}.", devices.join(", "));
+                    return Ok(());
+                }
+
+                let devices = player.list_devices().await?;
+
+                let device = match devices
+                    .into_iter()
+                    .find(|d| d.id == name || d.name.eq_ignore_ascii_case(name))
+                {
+                    Some(device) => device,
+                    None => {
+                        respond!(ctx, "No device named `{}`.", name);
+                        return Ok(());
+                    }
+                };
+
+                if player.transfer_device(device.id).await? {
+                    respond!(ctx, "Transferred playback to {}.", device.name);
+                } else {
+                    respond!(
+                        ctx,
+                        "Failed to transfer playback to {}, it never became the active device.",
+                        device.name
+                    );
+                }
+            }
             Some("length") => {
                 let (count, duration) = player.length().await;
 
@@ -439,11 +746,13 @@ impl command::Handler for Handler {
                     alts.push("close");
                     alts.push("open");
                     alts.push("purge");
+                    alts.push("mood");
                 } else {
                     alts.push("promote 🛇");
                     alts.push("close 🛇");
                     alts.push("open 🛇");
                     alts.push("purge 🛇");
+                    alts.push("mood 🛇");
                 }
 
                 if ctx.user.has_scope(Scope::SongVolume).await {
@@ -454,20 +763,31 @@ impl command::Handler for Handler {
 
                 if ctx.user.has_scope(Scope::SongPlaybackControl).await {
                     alts.push("skip");
+                    alts.push("seek");
                     alts.push("toggle");
                     alts.push("play");
                     alts.push("pause");
+                    alts.push("device");
                 } else {
                     alts.push("skip 🛇");
+                    alts.push("seek 🛇");
                     alts.push("toggle 🛇");
                     alts.push("play 🛇");
                     alts.push("pause 🛇");
+                    alts.push("device 🛇");
+                }
+
+                if ctx.user.has_scope(Scope::SongSkipVote).await {
+                    alts.push("skip vote");
                 }
 
                 alts.push("list");
                 alts.push("current");
                 alts.push("when");
+                alts.push("mine");
+                alts.push("requesters");
                 alts.push("delete");
+                alts.push("wrongsong");
                 alts.push("request");
                 alts.push("length");
                 respond!(ctx, format!("Expected argument: {}.", alts.join(", ")));
@@ -478,6 +798,38 @@ impl command::Handler for Handler {
     }
 }
 
+/// Handler for the `!songlist` command, which links to a page showing the
+/// song queue.
+pub struct SongList {
+    enabled: settings::Var<bool>,
+    server: injector::Ref<web::Server>,
+}
+
+#[async_trait]
+impl command::Handler for SongList {
+    fn scope(&self) -> Option<Scope> {
+        Some(Scope::Song)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            respond!(ctx, "Song requests are currently not enabled, sorry :(");
+            return Ok(());
+        }
+
+        let server = match self.server.load().await {
+            Some(server) => server,
+            None => {
+                respond!(ctx, "The song list is not available right now, sorry :(");
+                return Ok(());
+            }
+        };
+
+        respond!(ctx, "You can find the song queue at {}", server.queue_url());
+        Ok(())
+    }
+}
+
 pub struct Module;
 
 #[async_trait]
@@ -506,12 +858,41 @@ impl module::Module for Module {
         let enabled = settings.var("enabled", false).await?;
         let chat_feedback = settings.var("chat-feedback", true).await?;
         let request_reward = settings.var("request-reward", 0).await?;
+        let request_cost = settings.var("request-cost", 0).await?;
+        let cost_moderator_exempt = settings.var("cost-moderator-exempt", false).await?;
 
         let spotify = Constraint::build(&mut settings.scoped("spotify"), true, 0).await?;
         let youtube = Constraint::build(&mut settings.scoped("youtube"), false, 60).await?;
 
         let help_cooldown = Cooldown::from_duration(Duration::seconds(5));
-        let requester = requester::SongRequester::new(request_reward, spotify, youtube);
+        let allow_episodes = settings.var("spotify/allow-episodes", false).await?;
+
+        let requester = requester::SongRequester::new(
+            request_reward,
+            request_cost,
+            cost_moderator_exempt,
+            spotify,
+            youtube,
+            allow_episodes,
+        );
+
+        let skip_vote = SkipVoteConfig {
+            enabled: settings.var("skip-vote/enabled", false).await?,
+            threshold: settings.var("skip-vote/threshold", 3).await?,
+            percentage: settings.optional("skip-vote/threshold%").await?,
+            active_window: settings
+                .var("skip-vote/active-window", Duration::seconds(60 * 10))
+                .await?,
+            active_chatters: injector.var().await,
+        };
+
+        handlers.insert(
+            "songlist",
+            SongList {
+                enabled: enabled.clone(),
+                server: injector.var().await,
+            },
+        );
 
         handlers.insert(
             "song",
@@ -521,13 +902,18 @@ impl module::Module for Module {
                 player: injector.var().await,
                 currency,
                 requester: requester.clone(),
+                skip_vote,
+                skip_votes: Mutex::new(Default::default()),
             },
         );
 
+        let now_playing = feedback::NowPlaying::new(&settings.scoped("now-playing")).await?;
+
         futures.push(Box::pin(feedback::task(
             sender.clone(),
             injector.clone(),
             chat_feedback,
+            now_playing,
         )));
 
         futures.push(Box::pin(redemption::task(
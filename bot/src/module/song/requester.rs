@@ -2,7 +2,7 @@ use crate::auth::Scope;
 use crate::currency::Currency;
 use crate::irc::RealUser;
 use crate::module::song::Constraint;
-use crate::player::{AddTrackError, Player};
+use crate::player::{AddTrackError, Payment, Player};
 use crate::settings;
 use crate::track_id::{self, TrackId};
 use anyhow::Result;
@@ -19,21 +19,30 @@ pub(crate) enum RequestCurrency<'a> {
 #[derive(Clone)]
 pub(crate) struct SongRequester {
     request_reward: settings::Var<u32>,
+    request_cost: settings::Var<u32>,
+    cost_moderator_exempt: settings::Var<bool>,
     spotify: Constraint,
     youtube: Constraint,
+    allow_episodes: settings::Var<bool>,
 }
 
 impl SongRequester {
     /// Construct a new requester module.
     pub(crate) fn new(
         request_reward: settings::Var<u32>,
+        request_cost: settings::Var<u32>,
+        cost_moderator_exempt: settings::Var<bool>,
         spotify: Constraint,
         youtube: Constraint,
+        allow_episodes: settings::Var<bool>,
     ) -> Self {
         Self {
             request_reward,
+            request_cost,
+            cost_moderator_exempt,
             spotify,
             youtube,
+            allow_episodes,
         }
     }
 
@@ -55,28 +64,25 @@ impl SongRequester {
         let spotify = self.spotify.clone();
         let youtube = self.youtube.clone();
 
-        let track_id = match TrackId::parse_with_urls(&q) {
-            Ok(track_id) => Some(track_id),
-            Err(e) => {
-                match e {
-                    // NB: fall back to searching.
-                    track_id::ParseTrackIdError::MissingUriPrefix => (),
-                    // show other errors.
-                    e => {
-                        log::warn!("bad song request: {}", e);
-                        let e = format!("{} :(", e);
-                        return Err(RequestError::BadRequest(Some(e)));
-                    }
+        let track_id = match q.strip_prefix("isrc:") {
+            // NB: precise lookups by ISRC never fall back to a fuzzy search.
+            Some(isrc) => {
+                let isrc = isrc.trim();
+
+                if !is_isrc(isrc) {
+                    let e = format!(
+                        "`{}` is not a valid ISRC, expected something like `USUM71900001` :(",
+                        isrc
+                    );
+                    return Err(RequestError::BadRequest(Some(e)));
                 }
 
-                log::trace!("Failed to parse as URL/URI: {}: {}", q, e);
-                None
+                player
+                    .search_isrc(isrc)
+                    .await
+                    .map_err(RequestError::Error)?
             }
-        };
-
-        let track_id = match track_id {
-            Some(track_id) => Some(track_id),
-            None => player.search_track(q).await.map_err(RequestError::Error)?,
+            None => self.resolve(q, player).await?,
         };
 
         let track_id = match track_id {
@@ -86,8 +92,12 @@ impl SongRequester {
             }
         };
 
+        if track_id.is_episode() && !self.allow_episodes.load().await {
+            return Err(RequestError::EpisodesNotAllowed);
+        }
+
         let (what, scope, enabled) = match track_id {
-            TrackId::Spotify(..) => {
+            TrackId::Spotify(..) | TrackId::SpotifyEpisode(..) => {
                 let enabled = spotify.enabled.load().await;
                 ("Spotify", Scope::SongSpotify, enabled)
             }
@@ -112,12 +122,12 @@ impl SongRequester {
         };
 
         let max_duration = match track_id {
-            TrackId::Spotify(_) => spotify.max_duration.load().await,
+            TrackId::Spotify(_) | TrackId::SpotifyEpisode(_) => spotify.max_duration.load().await,
             TrackId::YouTube(_) => youtube.max_duration.load().await,
         };
 
         let min_currency = match track_id {
-            TrackId::Spotify(_) => spotify.min_currency.load().await,
+            TrackId::Spotify(_) | TrackId::SpotifyEpisode(_) => spotify.min_currency.load().await,
             TrackId::YouTube(_) => youtube.min_currency.load().await,
         };
 
@@ -144,8 +154,8 @@ impl SongRequester {
                             if balance.balance < min_currency {
                                 return Err(RequestError::NoBalance {
                                     currency: currency.name.clone(),
-                                    required: min_currency,
-                                    balance: balance.balance,
+                                    required: currency.format(min_currency),
+                                    balance: currency.format_balance(balance.balance),
                                 });
                             }
                         }
@@ -156,15 +166,58 @@ impl SongRequester {
             }
         }
 
+        let charge = match currency {
+            RequestCurrency::BotCurrency(currency) => {
+                self.charge(currency, channel, user, real_user).await?
+            }
+            RequestCurrency::Redemption => None,
+        };
+
+        let paid = charge.as_ref().map(|charge| Payment {
+            channel: channel.to_string(),
+            amount: charge.amount,
+        });
+
         let result = player
-            .add_track(user, track_id, has_bypass_constraints, max_duration)
+            .add_track(user, track_id, has_bypass_constraints, max_duration, paid)
             .await;
 
         let (pos, item) = match result {
             Ok((pos, item)) => (pos, item),
-            Err(e) => return Err(RequestError::AddTrackError(e)),
+            Err(e) => {
+                // NB: rejected requests don't cost anything.
+                if let Some(charge) = &charge {
+                    charge
+                        .currency
+                        .balance_add(channel, user, i64::from(charge.amount))
+                        .await
+                        .map_err(RequestError::Error)?;
+                }
+
+                return Err(RequestError::AddTrackError(e));
+            }
         };
 
+        if let Some(charge) = charge {
+            let cost = charge.currency.format(i64::from(charge.amount));
+            let balance = charge.currency.format_balance(charge.balance);
+
+            return Ok(if let Some(pos) = pos {
+                RequestOutcome::PaidAt {
+                    what: item.what(),
+                    pos: pos + 1,
+                    cost,
+                    balance,
+                }
+            } else {
+                RequestOutcome::Paid {
+                    what: item.what(),
+                    cost,
+                    balance,
+                }
+            });
+        }
+
         let currency = match currency {
             RequestCurrency::BotCurrency(Some(currency)) if request_reward > 0 => currency,
             _ => {
@@ -186,19 +239,104 @@ impl SongRequester {
 
         Ok(if let Some(pos) = pos {
             RequestOutcome::RewardedAt {
-                currency: currency.name.clone(),
-                amount: request_reward,
+                reward: currency.format(request_reward as i64),
                 what: item.what(),
                 pos: pos + 1,
             }
         } else {
             RequestOutcome::Rewarded {
-                currency: currency.name.clone(),
-                amount: request_reward,
+                reward: currency.format(request_reward as i64),
                 what: item.what(),
             }
         })
     }
+
+    /// Deduct the cost of a song request from the balance of the user, unless
+    /// requests are free for them.
+    async fn charge(
+        &self,
+        currency: Option<&Currency>,
+        channel: &str,
+        user: &str,
+        real_user: Option<&RealUser<'_>>,
+    ) -> Result<Option<Charge>, RequestError> {
+        let amount = self.request_cost.load().await;
+
+        if amount == 0 {
+            return Ok(None);
+        }
+
+        let moderator = real_user
+            .map(|u| u.is_moderator() || u.is_streamer())
+            .unwrap_or_default();
+
+        if moderator && self.cost_moderator_exempt.load().await {
+            return Ok(None);
+        }
+
+        let currency = currency.ok_or(RequestError::NoCurrency)?;
+        let cost = i64::from(amount);
+
+        let taken = currency
+            .balance_take(channel, user, cost)
+            .await
+            .map_err(RequestError::Error)?;
+
+        let balance = currency
+            .balance_of(channel, user)
+            .await
+            .map_err(RequestError::Error)?
+            .unwrap_or_default();
+
+        if !taken {
+            return Err(RequestError::NoBalance {
+                currency: currency.name.clone(),
+                required: currency.format(cost),
+                balance: currency.format_balance(balance.balance),
+            });
+        }
+
+        Ok(Some(Charge {
+            currency: currency.clone(),
+            amount,
+            balance: balance.balance,
+        }))
+    }
+
+    /// Resolve a track from a URL, URI, or by searching for it.
+    async fn resolve(&self, q: &str, player: &Player) -> Result<Option<TrackId>, RequestError> {
+        let track_id = match TrackId::parse_with_urls(q) {
+            Ok(track_id) => Some(track_id),
+            Err(e) => {
+                match e {
+                    // NB: fall back to searching.
+                    track_id::ParseTrackIdError::MissingUriPrefix => (),
+                    // show other errors.
+                    e => {
+                        log::warn!("bad song request: {}", e);
+                        let e = format!("{} :(", e);
+                        return Err(RequestError::BadRequest(Some(e)));
+                    }
+                }
+
+                log::trace!("Failed to parse as URL/URI: {}: {}", q, e);
+                None
+            }
+        };
+
+        match track_id {
+            Some(track_id) => Ok(Some(track_id)),
+            None => player.search_track(q).await.map_err(RequestError::Error),
+        }
+    }
+}
+
+/// Currency which has been deducted for a song request.
+struct Charge {
+    currency: Currency,
+    amount: u32,
+    /// The balance of the user after paying.
+    balance: i64,
 }
 
 pub(crate) enum RequestOutcome {
@@ -208,16 +346,25 @@ pub(crate) enum RequestOutcome {
     Added { what: String },
     /// Added the given track and gave the specified reward at the given position.
     RewardedAt {
-        currency: Arc<String>,
-        amount: u32,
+        reward: String,
         what: String,
         pos: usize,
     },
     /// Added the given track and gave the specified reward.
-    Rewarded {
-        currency: Arc<String>,
-        amount: u32,
+    Rewarded { reward: String, what: String },
+    /// Added the given track at the given position, which cost the user the
+    /// specified amount.
+    PaidAt {
         what: String,
+        pos: usize,
+        cost: String,
+        balance: String,
+    },
+    /// Added the given track, which cost the user the specified amount.
+    Paid {
+        what: String,
+        cost: String,
+        balance: String,
     },
 }
 
@@ -235,32 +382,49 @@ impl fmt::Display for RequestOutcome {
             RequestOutcome::Added { what } => {
                 write!(f, "Added {what}!", what = what)
             }
-            RequestOutcome::RewardedAt {
-                currency,
-                amount,
+            RequestOutcome::RewardedAt { reward, what, pos } => {
+                write!(
+                    f,
+                    "Added {what} at position #{pos}, here's your {reward}!",
+                    what = what,
+                    pos = pos,
+                    reward = reward,
+                )
+            }
+            RequestOutcome::Rewarded { reward, what } => {
+                write!(
+                    f,
+                    "Added {what}, here's your {reward}!",
+                    what = what,
+                    reward = reward,
+                )
+            }
+            RequestOutcome::PaidAt {
                 what,
                 pos,
+                cost,
+                balance,
             } => {
                 write!(
                     f,
-                    "Added {what} at position #{pos}, here's your {amount} {currency}!",
+                    "Added {what} at position #{pos} for {cost}, you have {balance} left!",
                     what = what,
                     pos = pos,
-                    amount = amount,
-                    currency = currency,
+                    cost = cost,
+                    balance = balance,
                 )
             }
-            RequestOutcome::Rewarded {
-                currency,
-                amount,
+            RequestOutcome::Paid {
                 what,
+                cost,
+                balance,
             } => {
                 write!(
                     f,
-                    "Added {what}, here's your {amount} {currency}!",
+                    "Added {what} for {cost}, you have {balance} left!",
                     what = what,
-                    amount = amount,
-                    currency = currency,
+                    cost = cost,
+                    balance = balance,
                 )
             }
         }
@@ -276,13 +440,15 @@ pub(crate) enum RequestError {
     NotEnabled(&'static str),
     /// User is not allowed to perform that kind of song request.
     NotAllowed(&'static str),
+    /// Requested a podcast episode, but they are not allowed.
+    EpisodesNotAllowed,
     /// No currency configured for stream.
     NoCurrency,
     /// Not enough stream currency balance.
     NoBalance {
         currency: Arc<String>,
-        required: i64,
-        balance: i64,
+        required: String,
+        balance: String,
     },
     /// Error raised when adding track.
     AddTrackError(AddTrackError),
@@ -317,6 +483,9 @@ impl fmt::Display for RequestError {
                     what = what
                 )
             }
+            RequestError::EpisodesNotAllowed => {
+                write!(f, "Podcast episodes can't be requested, sorry :(")
+            }
             RequestError::NoCurrency => {
                 write!(f, "No currency configured for stream, but it is required.")
             }
@@ -342,3 +511,32 @@ impl fmt::Display for RequestError {
         }
     }
 }
+
+/// Test if the given string is a well-formed ISRC, like `USUM71900001`.
+///
+/// An ISRC is a two letter country code, followed by a three character
+/// registrant code, two digits for the year, and five digits for the
+/// designation.
+fn is_isrc(s: &str) -> bool {
+    let b = s.as_bytes();
+
+    b.len() == 12
+        && b[..2].iter().all(u8::is_ascii_alphabetic)
+        && b[2..5].iter().all(u8::is_ascii_alphanumeric)
+        && b[5..].iter().all(u8::is_ascii_digit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_isrc;
+
+    #[test]
+    fn test_is_isrc() {
+        assert!(is_isrc("USUM71900001"));
+        assert!(is_isrc("gbaye0601498"));
+        assert!(!is_isrc("USUM7190000"));
+        assert!(!is_isrc("US-UM7-19-00001"));
+        assert!(!is_isrc("1SUM71900001"));
+        assert!(!is_isrc("USUM7A900001"));
+    }
+}
@@ -0,0 +1,103 @@
+//! Votes to skip the current song.
+
+use crate::track_id::TrackId;
+use std::collections::HashSet;
+
+/// The outcome of a single vote.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Vote {
+    /// The user has already voted to skip this song.
+    Duplicate { votes: usize },
+    /// The vote was counted.
+    Counted { votes: usize },
+}
+
+impl Vote {
+    /// The number of votes after this vote.
+    pub(super) fn votes(&self) -> usize {
+        match *self {
+            Vote::Duplicate { votes } | Vote::Counted { votes } => votes,
+        }
+    }
+}
+
+/// Unique voters to skip the current song.
+#[derive(Default)]
+pub(super) struct SkipVotes {
+    /// The song the votes are for.
+    track_id: Option<TrackId>,
+    voters: HashSet<String>,
+}
+
+impl SkipVotes {
+    /// Vote for the given user to skip the song with the given id.
+    ///
+    /// Votes for a previous song are discarded.
+    pub(super) fn vote(&mut self, track_id: &TrackId, user: &str) -> Vote {
+        if self.track_id.as_ref() != Some(track_id) {
+            self.track_id = Some(track_id.clone());
+            self.voters.clear();
+        }
+
+        if !self.voters.insert(user.to_lowercase()) {
+            return Vote::Duplicate {
+                votes: self.voters.len(),
+            };
+        }
+
+        Vote::Counted {
+            votes: self.voters.len(),
+        }
+    }
+
+    /// Clear all votes.
+    pub(super) fn clear(&mut self) {
+        self.track_id = None;
+        self.voters.clear();
+    }
+}
+
+/// Calculate how many votes are needed to skip a song.
+///
+/// If `percentage` is set, that percentage of the active chatters is needed,
+/// otherwise the fixed `threshold`. At least one vote is always needed.
+pub(super) fn required(threshold: u32, percentage: Option<u32>, active: usize) -> usize {
+    let required = match percentage {
+        Some(percentage) => (active * percentage as usize + 99) / 100,
+        None => threshold as usize,
+    };
+
+    usize::max(required, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{required, SkipVotes, Vote};
+    use crate::spotify_id::SpotifyId;
+    use crate::track_id::TrackId;
+
+    #[test]
+    fn test_skip_votes() {
+        let a = TrackId::Spotify(SpotifyId::from_base62("4uLU6hMCjMI75M1A2tKUQC").unwrap());
+        let b = TrackId::Spotify(SpotifyId::from_base62("0ofHAoxe9vBkTCp2UQIavz").unwrap());
+
+        let mut votes = SkipVotes::default();
+
+        assert_eq!(votes.vote(&a, "foo"), Vote::Counted { votes: 1 });
+        assert_eq!(votes.vote(&a, "Foo"), Vote::Duplicate { votes: 1 });
+        assert_eq!(votes.vote(&a, "bar"), Vote::Counted { votes: 2 });
+        // NB: a new song starts a new tally.
+        assert_eq!(votes.vote(&b, "foo"), Vote::Counted { votes: 1 });
+
+        votes.clear();
+        assert_eq!(votes.vote(&b, "foo"), Vote::Counted { votes: 1 });
+    }
+
+    #[test]
+    fn test_required() {
+        assert_eq!(required(3, None, 100), 3);
+        assert_eq!(required(0, None, 100), 1);
+        assert_eq!(required(3, Some(50), 7), 4);
+        assert_eq!(required(3, Some(50), 0), 1);
+    }
+}
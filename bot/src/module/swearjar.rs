@@ -67,8 +67,8 @@ impl command::Handler for Handler {
             .await?;
 
         user.sender().privmsg(format!(
-            "/me has taken {} {currency} from {streamer} and given it to the viewers for listening to their bad mouth!",
-            total_reward, currency = currency.name, streamer = user.streamer().display_name,
+            "/me has taken {} from {streamer} and given it to the viewers for listening to their bad mouth!",
+            currency.format(total_reward), streamer = user.streamer().display_name,
         )).await;
 
         Ok(())
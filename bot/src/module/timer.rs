@@ -0,0 +1,323 @@
+//! Module for timers, which post a message to chat at a fixed interval.
+
+use crate::auth;
+use crate::command;
+use crate::db;
+use crate::idle;
+use crate::irc;
+use crate::module;
+use crate::prelude::*;
+use crate::utils;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future;
+
+/// The shortest interval in seconds that a timer can be posted at.
+const MIN_INTERVAL: u64 = 60;
+
+/// Handler for the `!timer` command.
+pub struct Handler {
+    enabled: settings::Var<bool>,
+    timers: injector::Ref<db::Timers>,
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::TimerEdit)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let timers = match self.timers.load().await {
+            Some(timers) => timers,
+            None => return Ok(()),
+        };
+
+        match ctx.next().as_deref() {
+            Some("list") => {
+                let timers = timers.list(ctx.channel()).await;
+                ctx.respond_lines(timers, "No timers.").await;
+            }
+            Some("add") => {
+                let name = ctx.next_str("<name> <interval> <message...>")?;
+                let interval: utils::Duration = ctx.next_parse("<name> <interval> <message...>")?;
+                let template = ctx.rest_parse("<name> <interval> <message...>")?;
+
+                if interval.num_seconds() < MIN_INTERVAL {
+                    respond!(
+                        ctx,
+                        "Timers can't run more often than every {}.",
+                        utils::Duration::seconds(MIN_INTERVAL)
+                    );
+                    return Ok(());
+                }
+
+                if timers
+                    .edit(ctx.channel(), &name, interval, template)
+                    .await?
+                {
+                    respond!(ctx, "Added timer `{}`, running every {}.", name, interval);
+                } else {
+                    respond!(ctx, "Updated timer `{}`, running every {}.", name, interval);
+                }
+            }
+            Some("remove") => {
+                let name = ctx.next_str("<name>")?;
+
+                if timers.delete(ctx.channel(), &name).await? {
+                    respond!(ctx, "Removed timer `{}`.", name);
+                } else {
+                    respond!(ctx, "No timer named `{}`.", name);
+                }
+            }
+            Some("pause") => {
+                let name = ctx.next_str("<name>")?;
+
+                if timers.set_paused(ctx.channel(), &name, true).await? {
+                    respond!(ctx, "Paused timer `{}`.", name);
+                } else {
+                    respond!(ctx, "No timer named `{}`.", name);
+                }
+            }
+            Some("resume") => {
+                let name = ctx.next_str("<name>")?;
+
+                if timers.set_paused(ctx.channel(), &name, false).await? {
+                    respond!(ctx, "Resumed timer `{}`.", name);
+                } else {
+                    respond!(ctx, "No timer named `{}`.", name);
+                }
+            }
+            _ => {
+                respond!(ctx, "Expected: list, add, remove, pause, or resume.");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "timer"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            handlers,
+            futures,
+            sender,
+            settings,
+            idle,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        let (mut enabled_stream, mut enabled) =
+            settings.stream("timer/enabled").or_default().await?;
+
+        handlers.insert(
+            "timer",
+            Handler {
+                enabled: settings.var("timer/enabled", false).await?,
+                timers: injector.var().await,
+            },
+        );
+
+        let (mut timers_stream, mut timers) = injector.stream::<db::Timers>().await;
+        let sender = sender.clone();
+        let idle = idle.clone();
+
+        let future = async move {
+            let mut scheduler = Scheduler::new(sender, idle);
+
+            loop {
+                match timers.as_ref() {
+                    Some(timers) if enabled => scheduler.update(timers).await,
+                    _ => scheduler.clear(),
+                }
+
+                tokio::select! {
+                    update = timers_stream.recv() => {
+                        timers = update;
+                    }
+                    update = enabled_stream.recv() => {
+                        enabled = update;
+                    }
+                    _ = changed(timers.as_ref()) => {
+                    }
+                }
+            }
+        };
+
+        futures.push(Box::pin(future));
+        Ok(())
+    }
+}
+
+/// Wait until the given timers have changed.
+async fn changed(timers: Option<&db::Timers>) {
+    match timers {
+        Some(timers) => timers.changed().await,
+        None => future::pending().await,
+    }
+}
+
+/// Keeps one running task for each active timer.
+struct Scheduler {
+    sender: irc::Sender,
+    idle: idle::Idle,
+    tasks: HashMap<String, (Arc<db::Timer>, tokio::task::JoinHandle<()>)>,
+}
+
+impl Scheduler {
+    fn new(sender: irc::Sender, idle: idle::Idle) -> Self {
+        Self {
+            sender,
+            idle,
+            tasks: HashMap::new(),
+        }
+    }
+
+    /// Start, stop, or restart timer tasks to match the configured timers.
+    async fn update(&mut self, timers: &db::Timers) {
+        let mut active = timers
+            .list(self.sender.channel())
+            .await
+            .into_iter()
+            .filter(|t| !t.paused)
+            .map(|t| (t.key.name.clone(), t))
+            .collect::<HashMap<_, _>>();
+
+        self.tasks.retain(|name, (timer, task)| {
+            match active.get(name) {
+                // NB: timers are replaced when they are edited.
+                Some(current) if Arc::ptr_eq(current, timer) => {
+                    active.remove(name);
+                    true
+                }
+                _ => {
+                    log::trace!("stopping timer `{}`", name);
+                    task.abort();
+                    false
+                }
+            }
+        });
+
+        for (name, timer) in active {
+            log::trace!("starting timer `{}` every {}", name, timer.interval);
+            let task = tokio::spawn(run(timer.clone(), self.sender.clone(), self.idle.clone()));
+            self.tasks.insert(name, (timer, task));
+        }
+    }
+
+    /// Stop all running timer tasks.
+    fn clear(&mut self) {
+        for (_, (_, task)) in self.tasks.drain() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        // NB: timer tasks are detached, so they'd keep posting to a stale
+        // connection after a reconnect unless we stop them here.
+        self.clear();
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct TimerData<'a> {
+    channel: &'a str,
+}
+
+/// Post the given timer to chat every time its interval elapses.
+async fn run(timer: Arc<db::Timer>, sender: irc::Sender, idle: idle::Idle) {
+    let period = timer.interval.as_std();
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+
+    loop {
+        interval.tick().await;
+
+        if idle.is_idle().await {
+            log::trace!("channel is too idle to post timer `{}`", timer.key.name);
+            continue;
+        }
+
+        let channel = sender.channel();
+
+        match timer.render(&TimerData { channel }) {
+            Ok(text) => sender.privmsg(text).await,
+            Err(e) => log_error!(e, "failed to render timer `{}`", timer.key.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Handler;
+    use crate::db;
+    use crate::irc::mock::{Mock, CHANNEL};
+    use crate::prelude::*;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_timer_commands() -> Result<()> {
+        let mock = Mock::new().await?;
+        mock.moderator("moderator");
+
+        let db = mock.injector.get::<db::Database>().await.expect("database");
+        let timers = db::Timers::load(db).await?;
+        mock.injector.update(timers.clone()).await;
+
+        let handler = Handler {
+            enabled: settings::Var::new(true),
+            timers: mock.injector.var().await,
+        };
+
+        mock.run(&handler, "moderator", "add discord 10m Join the discord!")
+            .await?;
+        mock.run(&handler, "moderator", "add discord 15m Join our discord!")
+            .await?;
+        mock.run(&handler, "moderator", "add spam 5s Spam!").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("moderator -> Added timer `discord`, running every 10m."),
+                String::from("moderator -> Updated timer `discord`, running every 15m."),
+                String::from("moderator -> Timers can't run more often than every 1m."),
+            ]
+        );
+
+        mock.run(&handler, "moderator", "pause discord").await?;
+        let timer = timers.get(CHANNEL, "discord").await.expect("timer");
+        assert!(timer.paused);
+        assert_eq!(timer.template.source(), "Join our discord!");
+
+        mock.run(&handler, "moderator", "list").await?;
+        mock.run(&handler, "moderator", "remove discord").await?;
+        mock.run(&handler, "moderator", "remove discord").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("moderator -> Paused timer `discord`."),
+                String::from("moderator -> discord (every 15m, paused)"),
+                String::from("moderator -> Removed timer `discord`."),
+                String::from("moderator -> No timer named `discord`."),
+            ]
+        );
+
+        assert!(mock.run(&handler, "viewer", "list").await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use crate::auth;
+use crate::command;
+use crate::module;
+use crate::prelude::*;
+use crate::stream_info;
+use crate::utils;
+use anyhow::Result;
+use chrono::Utc;
+
+/// Handler for the `!uptime` command.
+pub struct Uptime {
+    enabled: settings::Var<bool>,
+    stream_info: stream_info::StreamInfo,
+}
+
+#[async_trait]
+impl command::Handler for Uptime {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Uptime)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let started_at = self
+            .stream_info
+            .data
+            .read()
+            .stream
+            .as_ref()
+            .map(|s| s.started_at);
+
+        let now = Utc::now();
+
+        match started_at {
+            // NB: very important to check that _now_ is after started at.
+            Some(ref started_at) if now > *started_at => {
+                let uptime = utils::long_duration((now - *started_at).to_std().unwrap_or_default());
+                respond!(ctx, "Stream has been live for {uptime}.", uptime = uptime);
+            }
+            Some(_) => {
+                respond!(ctx, "Stream is live, but start time is weird!");
+            }
+            None => {
+                respond!(ctx, "Stream is not live right now, try again later!");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "uptime"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            stream_info,
+            settings,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        handlers.insert(
+            "uptime",
+            Uptime {
+                enabled: settings.var("uptime/enabled", true).await?,
+                stream_info: stream_info.clone(),
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Uptime;
+    use crate::irc::mock::Mock;
+    use crate::prelude::*;
+    use anyhow::Result;
+    use chrono::{Duration, Utc};
+
+    #[tokio::test]
+    async fn test_uptime() -> Result<()> {
+        let mock = Mock::new().await?;
+
+        let handler = Uptime {
+            enabled: settings::Var::new(true),
+            stream_info: mock.stream_info.clone(),
+        };
+
+        mock.run(&handler, "viewer", "").await?;
+
+        mock.go_live(Utc::now() - Duration::hours(3) - Duration::minutes(12));
+        mock.run(&handler, "viewer", "").await?;
+
+        let sent = mock.take_sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            sent[0],
+            "viewer -> Stream is not live right now, try again later!"
+        );
+        assert!(sent[1].starts_with("viewer -> Stream has been live for three hours, 12 minutes"));
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use crate::auth;
+use crate::command;
+use crate::db;
+use crate::module;
+use crate::prelude::*;
+use crate::utils;
+use anyhow::Result;
+use std::convert::TryFrom as _;
+use std::time;
+
+/// Handler for the !watchtime command.
+///
+/// Watch time is read from the balances of the built-in currency, which is
+/// where it's credited.
+pub struct WatchTime {
+    enabled: settings::Var<bool>,
+    db: injector::Ref<Arc<db::Cached<db::Sqlite>>>,
+}
+
+#[async_trait]
+impl command::Handler for WatchTime {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::WatchTime)
+    }
+
+    async fn handle(&self, ctx: &mut command::Context) -> Result<()> {
+        if !self.enabled.load().await {
+            return Ok(());
+        }
+
+        let db = match self.db.load().await {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+
+        match ctx.next() {
+            Some(user) => {
+                ctx.check_scope(auth::Scope::WatchTimeOthers).await?;
+
+                let user = db::user_id(&user);
+                let watch_time = watch_time(&*db, ctx.channel(), &user).await?;
+                respond!(ctx, "{} has watched for {}.", user, watch_time);
+            }
+            None => {
+                let user = match ctx.user.real() {
+                    Some(user) => user,
+                    None => {
+                        respond!(ctx, "Only real users can check their watch time");
+                        return Ok(());
+                    }
+                };
+
+                let watch_time = watch_time(&*db, ctx.channel(), user.name()).await?;
+                respond!(ctx, "You have watched for {}.", watch_time);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the formatted watch time of the given user.
+async fn watch_time(db: &dyn db::Backend, channel: &str, user: &str) -> Result<String> {
+    let seconds = db
+        .balance_of(&db::channel_id(channel), &db::user_id(user))
+        .await?
+        .map(|balance| balance.watch_time)
+        .unwrap_or_default();
+
+    let seconds = u64::try_from(seconds).unwrap_or_default();
+    Ok(utils::compact_duration(time::Duration::from_secs(seconds)))
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "watchtime"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            injector,
+            settings,
+            ..
+        }: module::HookContext<'_>,
+    ) -> Result<()> {
+        handlers.insert(
+            "watchtime",
+            WatchTime {
+                enabled: settings.var("watchtime/enabled", false).await?,
+                db: injector.var().await,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WatchTime;
+    use crate::db::{self, Backend as _};
+    use crate::irc::mock::{Mock, CHANNEL};
+    use crate::prelude::*;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_watch_time() -> Result<()> {
+        let mock = Mock::new().await?;
+        mock.moderator("moderator");
+
+        let db = mock.injector.get::<db::Database>().await.expect("database");
+        let db = Arc::new(db::Cached::new(db::Sqlite::new(db)));
+        mock.injector.update(db.clone()).await;
+
+        db.balances_increment(CHANNEL, vec![String::from("viewer")], 0, 90 * 60)
+            .await?;
+        db.balances_increment(CHANNEL, vec![String::from("viewer")], 0, 5 * 60)
+            .await?;
+
+        let handler = WatchTime {
+            enabled: settings::Var::new(true),
+            db: mock.injector.var().await,
+        };
+
+        mock.run(&handler, "viewer", "").await?;
+        mock.run(&handler, "moderator", "@Viewer").await?;
+        assert!(mock.run(&handler, "viewer", "moderator").await.is_err());
+        assert_eq!(
+            mock.take_sent(),
+            vec![
+                String::from("viewer -> You have watched for 1h 35m."),
+                String::from("moderator -> viewer has watched for 1h 35m."),
+            ]
+        );
+
+        Ok(())
+    }
+}
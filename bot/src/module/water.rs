@@ -1,6 +1,7 @@
 use crate::auth;
 use crate::command;
 use crate::currency::Currency;
+use crate::db;
 use crate::module;
 use crate::prelude::*;
 use crate::stream_info;
@@ -11,28 +12,60 @@ use tokio::sync::Mutex;
 
 #[derive(Clone)]
 pub struct Reward {
+    /// The id of the persisted water, if it could be stored.
+    id: Option<i32>,
     user: String,
     amount: i64,
 }
 
+/// The chain of waters during a single stream.
+#[derive(Default)]
+pub struct History {
+    /// When the stream the waters belong to started.
+    started_at: Option<DateTime<Utc>>,
+    waters: Vec<(DateTime<Utc>, Option<Reward>)>,
+}
+
+impl History {
+    /// Load the history of the most recent stream in the given channel.
+    async fn load(waters: &db::Waters, channel: &str) -> Result<Self> {
+        let mut history = Self::default();
+
+        for water in waters.tail(channel).await? {
+            let started_at = DateTime::<Utc>::from_utc(water.stream_started_at, Utc);
+
+            if history.started_at.is_none() {
+                history.started_at = Some(started_at);
+                history.waters.push((started_at, None));
+            }
+
+            history.waters.push((
+                DateTime::<Utc>::from_utc(water.rewarded_at, Utc),
+                Some(Reward {
+                    id: Some(water.id),
+                    user: water.user,
+                    amount: water.amount,
+                }),
+            ));
+        }
+
+        Ok(history)
+    }
+}
+
 pub struct Handler {
     enabled: settings::Var<bool>,
     cooldown: settings::Var<utils::Cooldown>,
     currency: injector::Ref<Currency>,
-    waters: Mutex<Vec<(DateTime<Utc>, Option<Reward>)>>,
+    waters: injector::Ref<db::Waters>,
+    history: Mutex<History>,
     stream_info: stream_info::StreamInfo,
     reward_multiplier: settings::Var<u32>,
+    max_reward: settings::Var<Option<u32>>,
 }
 
 impl Handler {
-    async fn check_waters(
-        &self,
-        waters: &mut Vec<(DateTime<Utc>, Option<Reward>)>,
-    ) -> Result<(DateTime<Utc>, Option<Reward>)> {
-        if let Some((when, user)) = waters.last() {
-            return Ok((*when, user.clone()));
-        }
-
+    async fn check_waters(&self, history: &mut History) -> Result<(DateTime<Utc>, Option<Reward>)> {
         let started_at = self
             .stream_info
             .data
@@ -41,12 +74,19 @@ impl Handler {
             .as_ref()
             .map(|s| s.started_at);
 
-        let started_at = started_at.ok_or_else(|| {
-            respond_err!("Sorry, the !water command is currently not available :(")
-        })?;
+        // NB: a new stream starts a new chain of waters.
+        if let Some(started_at) = started_at {
+            if history.started_at != Some(started_at) {
+                history.started_at = Some(started_at);
+                history.waters = vec![(started_at, None)];
+            }
+        }
+
+        if let Some((when, user)) = history.waters.last() {
+            return Ok((*when, user.clone()));
+        }
 
-        waters.push((started_at, None));
-        Ok((started_at, None))
+        respond_bail!("Sorry, the !water command is currently not available :(");
     }
 }
 
@@ -78,10 +118,8 @@ impl command::Handler for Handler {
         match a.as_deref() {
             Some("undo") => {
                 ctx.check_scope(auth::Scope::WaterUndo).await?;
-                let mut waters = self.waters.lock().await;
-                let (_, reward) = self.check_waters(&mut waters).await?;
-
-                waters.pop();
+                let mut history = self.history.lock().await;
+                let (_, reward) = self.check_waters(&mut history).await?;
 
                 let reward = match reward {
                     Some(reward) => reward,
@@ -91,6 +129,24 @@ impl command::Handler for Handler {
                     }
                 };
 
+                history.waters.pop();
+
+                // NB: only refund the reward if we're the ones marking it as
+                // undone, so that it can't be refunded twice.
+                if let (Some(id), Some(waters)) = (reward.id, self.waters.load().await) {
+                    match waters.undo(id).await {
+                        Ok(true) => (),
+                        Ok(false) => {
+                            respond!(ctx, "That !water has already been undone.");
+                            return Ok(());
+                        }
+                        Err(e) => {
+                            log_error!(e, "[{}] failed to mark water as undone", ctx.id());
+                            return Ok(());
+                        }
+                    }
+                }
+
                 ctx.privmsg(format!(
                     "{user} issued a bad !water that is now being undone FeelsBadMan",
                     user = reward.user
@@ -101,12 +157,12 @@ impl command::Handler for Handler {
                     .balance_add(ctx.channel(), &reward.user, -reward.amount)
                     .await
                 {
-                    log::error!("failed to undo water from database: {}", e);
+                    log::error!("[{}] failed to undo water from database: {}", ctx.id(), e);
                 }
             }
             None => {
-                let mut waters = self.waters.lock().await;
-                let (last, _) = self.check_waters(&mut waters).await?;
+                let mut history = self.history.lock().await;
+                let (last, _) = self.check_waters(&mut history).await?;
 
                 let user = match ctx.user.real() {
                     Some(user) => user,
@@ -121,28 +177,59 @@ impl command::Handler for Handler {
                 let amount = i64::max(0i64, diff.num_minutes());
                 let amount = (amount * self.reward_multiplier.load().await as i64) / 100i64;
 
-                waters.push((
+                let (amount, capped) = match self.max_reward.load().await {
+                    Some(max) if amount > i64::from(max) => (i64::from(max), true),
+                    _ => (amount, false),
+                };
+
+                let id = match (history.started_at, self.waters.load().await) {
+                    (Some(started_at), Some(waters)) => {
+                        match waters
+                            .push(ctx.channel(), started_at, now, user.name(), amount)
+                            .await
+                        {
+                            Ok(id) => Some(id),
+                            Err(e) => {
+                                log_error!(e, "[{}] failed to store water", ctx.id());
+                                None
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+
+                history.waters.push((
                     now,
                     Some(Reward {
+                        id,
                         user: user.name().to_string(),
                         amount,
                     }),
                 ));
 
-                respond!(
-                    ctx,
-                    "{streamer}, DRINK SOME WATER! {user} has been rewarded {amount} {currency} for the reminder.",
-                    streamer = ctx.user.streamer().display_name,
-                    user = user.display_name(),
-                    amount = amount,
-                    currency = currency.name
-                );
+                if capped {
+                    respond!(
+                        ctx,
+                        "{streamer}, DRINK SOME WATER! {user} has been rewarded {amount} (capped) for the reminder.",
+                        streamer = ctx.user.streamer().display_name,
+                        user = user.display_name(),
+                        amount = currency.format(amount),
+                    );
+                } else {
+                    respond!(
+                        ctx,
+                        "{streamer}, DRINK SOME WATER! {user} has been rewarded {amount} for the reminder.",
+                        streamer = ctx.user.streamer().display_name,
+                        user = user.display_name(),
+                        amount = currency.format(amount),
+                    );
+                }
 
                 if let Err(e) = currency
                     .balance_add(ctx.channel(), user.name(), amount)
                     .await
                 {
-                    log::error!("failed to appply water balance: {}", e);
+                    log::error!("[{}] failed to appply water balance: {}", ctx.id(), e);
                 }
             }
             Some(_) => {
@@ -170,6 +257,7 @@ impl super::Module for Module {
             stream_info,
             settings,
             injector,
+            sender,
             ..
         }: module::HookContext<'_>,
     ) -> Result<()> {
@@ -181,6 +269,13 @@ impl super::Module for Module {
             )
             .await?;
         let reward_multiplier = settings.var("water/reward%", 100).await?;
+        let max_reward = settings.optional("water/max-reward").await?;
+        let waters = injector.var::<db::Waters>().await;
+
+        let history = match waters.load().await {
+            Some(waters) => History::load(&waters, sender.channel()).await?,
+            None => History::default(),
+        };
 
         handlers.insert(
             "water",
@@ -188,12 +283,224 @@ impl super::Module for Module {
                 enabled,
                 cooldown,
                 currency: injector.var().await,
-                waters: Mutex::new(Vec::new()),
+                waters,
+                history: Mutex::new(history),
                 stream_info: stream_info.clone(),
                 reward_multiplier,
+                max_reward,
             },
         );
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Handler, History};
+    use crate::command;
+    use crate::currency::Currency;
+    use crate::db;
+    use crate::irc::mock::{Mock, CHANNEL};
+    use crate::prelude::*;
+    use anyhow::Result;
+    use chrono::{Duration, Utc};
+    use tokio::sync::Mutex;
+
+    async fn setup(reward_multiplier: u32) -> Result<(Mock, Handler, Currency)> {
+        let mock = Mock::new().await?;
+
+        let currency = mock.currency().await?;
+
+        let waters = db::Waters::load(db::Database::memory()?).await?;
+        mock.injector.update(waters).await;
+
+        let handler = handler(&mock, reward_multiplier, History::default()).await;
+        Ok((mock, handler, currency))
+    }
+
+    async fn handler(mock: &Mock, reward_multiplier: u32, history: History) -> Handler {
+        Handler {
+            enabled: settings::Var::new(true),
+            cooldown: settings::Var::new(Default::default()),
+            currency: mock.injector.var().await,
+            waters: mock.injector.var().await,
+            history: Mutex::new(history),
+            stream_info: mock.stream_info.clone(),
+            reward_multiplier: settings::Var::new(reward_multiplier),
+            max_reward: settings::Var::new(None),
+        }
+    }
+
+    /// Construct a handler as if the bot had been restarted.
+    async fn restart(mock: &Mock) -> Result<Handler> {
+        let waters = mock.injector.get::<db::Waters>().await.expect("waters");
+        let history = History::load(&waters, CHANNEL).await?;
+        Ok(handler(mock, 100, history).await)
+    }
+
+    async fn balance(currency: &Currency, user: &str) -> Result<i64> {
+        let balance = currency.balance_of(CHANNEL, user).await?;
+        Ok(balance.map(|b| b.balance).unwrap_or_default())
+    }
+
+    fn respond_message(e: anyhow::Error) -> String {
+        match e.downcast::<command::Respond>() {
+            Ok(respond) => respond.0.to_string(),
+            Err(e) => panic!("expected respond error, got: {}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_water_reward() -> Result<()> {
+        let (mock, handler, currency) = setup(100).await?;
+        mock.go_live(Utc::now() - Duration::minutes(30));
+
+        mock.run(&handler, "viewer", "").await?;
+
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from(
+                "viewer -> Streamer, DRINK SOME WATER! viewer has been rewarded 30 points for the reminder."
+            )]
+        );
+        assert_eq!(balance(&currency, "viewer").await?, 30);
+
+        // Second water is only rewarded for the time since the last one.
+        mock.run(&handler, "other", "").await?;
+        assert_eq!(balance(&currency, "other").await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_water_reward_multiplier() -> Result<()> {
+        let (mock, handler, currency) = setup(50).await?;
+        mock.go_live(Utc::now() - Duration::minutes(30));
+
+        mock.run(&handler, "viewer", "").await?;
+        assert_eq!(balance(&currency, "viewer").await?, 15);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_water_max_reward() -> Result<()> {
+        let (mock, handler, currency) = setup(100).await?;
+        *handler.max_reward.write().await = Some(20);
+        mock.go_live(Utc::now() - Duration::minutes(30));
+
+        mock.run(&handler, "viewer", "").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from(
+                "viewer -> Streamer, DRINK SOME WATER! viewer has been rewarded 20 points (capped) for the reminder."
+            )]
+        );
+        assert_eq!(balance(&currency, "viewer").await?, 20);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_water_undo() -> Result<()> {
+        let (mock, handler, currency) = setup(100).await?;
+        mock.go_live(Utc::now() - Duration::minutes(30));
+        mock.moderator("moderator");
+
+        mock.run(&handler, "viewer", "").await?;
+        assert_eq!(balance(&currency, "viewer").await?, 30);
+        mock.take_sent();
+
+        // Regular viewers are not allowed to undo.
+        let e = mock.run(&handler, "other", "undo").await.unwrap_err();
+        assert_eq!(respond_message(e), "Do you think this is a democracy? LUL");
+        assert_eq!(balance(&currency, "viewer").await?, 30);
+
+        mock.run(&handler, "moderator", "undo").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from(
+                "viewer issued a bad !water that is now being undone FeelsBadMan"
+            )]
+        );
+        assert_eq!(balance(&currency, "viewer").await?, 0);
+
+        // Nothing left to undo.
+        mock.run(&handler, "moderator", "undo").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from(
+                "moderator -> No one has been rewarded for !water yet cmonBruh"
+            )]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_water_undo_after_restart() -> Result<()> {
+        let (mock, handler, currency) = setup(100).await?;
+        mock.go_live(Utc::now() - Duration::minutes(30));
+        mock.moderator("moderator");
+
+        mock.run(&handler, "viewer", "").await?;
+        assert_eq!(balance(&currency, "viewer").await?, 30);
+        mock.take_sent();
+
+        let restarted = restart(&mock).await?;
+        mock.run(&restarted, "moderator", "undo").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from(
+                "viewer issued a bad !water that is now being undone FeelsBadMan"
+            )]
+        );
+        assert_eq!(balance(&currency, "viewer").await?, 0);
+
+        // The original handler still remembers the water, but it must not be
+        // refunded twice.
+        mock.run(&handler, "moderator", "undo").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from(
+                "moderator -> That !water has already been undone."
+            )]
+        );
+        assert_eq!(balance(&currency, "viewer").await?, 0);
+
+        // Undone waters aren't loaded again.
+        let restarted = restart(&mock).await?;
+        mock.run(&restarted, "moderator", "undo").await?;
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from(
+                "moderator -> No one has been rewarded for !water yet cmonBruh"
+            )]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_water_offline() -> Result<()> {
+        let (mock, handler, _) = setup(100).await?;
+
+        let e = mock.run(&handler, "viewer", "").await.unwrap_err();
+        assert_eq!(
+            respond_message(e),
+            "Sorry, the !water command is currently not available :("
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_water_injected() -> Result<()> {
+        let (mock, handler, _) = setup(100).await?;
+        mock.go_live(Utc::now());
+
+        let mut ctx = mock.injected("");
+        command::Handler::handle(&handler, &mut ctx).await?;
+
+        assert_eq!(
+            mock.take_sent(),
+            vec![String::from("Can only get balance for real users.")]
+        );
+        Ok(())
+    }
+}
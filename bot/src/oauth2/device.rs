@@ -0,0 +1,256 @@
+//! The OAuth 2.0 device authorization flow.
+//!
+//! Instead of authorizing in a browser through the remote API, we print a code
+//! and an URL that can be visited from any device, and poll until the user has
+//! authorized the bot. This doesn't require a local web server that is
+//! reachable from a browser, which makes it useful on headless servers.
+//!
+//! This is only supported by Twitch.
+
+use crate::api::setbac::{Connection, Token};
+use crate::api::RequestBuilder;
+use anyhow::{bail, Result};
+use chrono::Utc;
+use reqwest::{header, Client, Method, StatusCode, Url};
+use std::time;
+
+const DEVICE_URL: &str = "https://id.twitch.tv/oauth2/device";
+const TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Scopes requested for the streamer account.
+const STREAMER_SCOPES: &[&str] = &[
+    "channel:edit:commercial",
+    "channel:manage:broadcast",
+    "channel:manage:redemptions",
+    "channel:read:redemptions",
+    "channel:read:subscriptions",
+    "clips:edit",
+    "moderation:read",
+];
+
+/// Scopes requested for the bot account.
+const BOT_SCOPES: &[&str] = &[
+    "channel:moderate",
+    "chat:edit",
+    "chat:read",
+    "whispers:edit",
+    "whispers:read",
+];
+
+/// How connections are authorized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum AuthFlow {
+    /// Authorize in the browser through the remote API.
+    #[serde(rename = "redirect")]
+    Redirect,
+    /// Authorize using the device flow.
+    #[serde(rename = "device")]
+    Device,
+}
+
+/// Get the scopes to request for the given flow, or `None` if the flow
+/// doesn't support device authorization.
+pub fn scopes(flow_id: &str) -> Option<&'static [&'static str]> {
+    match flow_id {
+        "twitch-streamer" => Some(STREAMER_SCOPES),
+        "twitch-bot" => Some(BOT_SCOPES),
+        _ => None,
+    }
+}
+
+/// Client for the device flow.
+#[derive(Debug, Clone)]
+pub struct Device {
+    client: Client,
+    client_id: String,
+    flow_id: &'static str,
+    what: &'static str,
+    scopes: &'static [&'static str],
+}
+
+impl Device {
+    /// Construct a new device flow client.
+    pub fn new(
+        client_id: String,
+        flow_id: &'static str,
+        what: &'static str,
+        scopes: &'static [&'static str],
+    ) -> Self {
+        Self {
+            client: Client::new(),
+            client_id,
+            flow_id,
+            what,
+            scopes,
+        }
+    }
+
+    /// Request a new connection, waiting until the user has authorized it.
+    ///
+    /// Returns `None` if the code expired before it was authorized.
+    pub async fn authorize(&self) -> Result<Option<Connection>> {
+        let scopes = self.scopes.join(" ");
+
+        let body = serde_urlencoded::to_string([
+            ("client_id", self.client_id.as_str()),
+            ("scopes", scopes.as_str()),
+        ])?;
+
+        let code = self
+            .form(DEVICE_URL, body)?
+            .execute()
+            .await?
+            .json::<DeviceCode>()?;
+
+        log::warn!(
+            "{}: Authorize the bot by visiting {} and entering the code `{}`",
+            self.what,
+            code.verification_uri,
+            code.user_code
+        );
+
+        let body = serde_urlencoded::to_string([
+            ("client_id", self.client_id.as_str()),
+            ("scopes", scopes.as_str()),
+            ("device_code", code.device_code.as_str()),
+            ("grant_type", DEVICE_CODE_GRANT),
+        ])?;
+
+        let expires_at = time::Instant::now() + time::Duration::from_secs(code.expires_in);
+        let interval = time::Duration::from_secs(code.interval.max(1));
+
+        while time::Instant::now() < expires_at {
+            tokio::time::sleep(interval).await;
+
+            // NB: a bad request means the user hasn't authorized us yet.
+            let token = self
+                .form(TOKEN_URL, body.clone())?
+                .execute()
+                .await?
+                .empty_on_status(StatusCode::BAD_REQUEST)
+                .json::<TokenResponse>()?;
+
+            if let Some(token) = token {
+                log::info!("{}: Authorized through the device flow", self.what);
+                let hash = format!("device-{}", Utc::now().timestamp());
+                return Ok(Some(self.connection(hash, token)));
+            }
+        }
+
+        log::warn!(
+            "{}: Device code expired before it was authorized",
+            self.what
+        );
+        Ok(None)
+    }
+
+    /// Refresh the given connection.
+    ///
+    /// Returns `None` if the connection can't be refreshed, in which case it
+    /// has to be authorized again.
+    pub async fn refresh(&self, connection: &Connection) -> Result<Option<Connection>> {
+        let refresh_token = match &connection.token.refresh_token {
+            Some(refresh_token) if connection.token.client_id == self.client_id => refresh_token,
+            _ => return Ok(None),
+        };
+
+        let body = serde_urlencoded::to_string([
+            ("client_id", self.client_id.as_str()),
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token.as_str()),
+        ])?;
+
+        let token = self
+            .form(TOKEN_URL, body)?
+            .execute()
+            .await?
+            .empty_on_status(StatusCode::BAD_REQUEST)
+            .json::<TokenResponse>()?;
+
+        Ok(token.map(|token| self.connection(connection.hash.clone(), token)))
+    }
+
+    /// Build a form request to the given url.
+    fn form(&self, url: &str, body: String) -> Result<RequestBuilder> {
+        let url = str::parse::<Url>(url)?;
+
+        Ok(RequestBuilder::new(self.client.clone(), Method::POST, url)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::ACCEPT, "application/json")
+            .body(body))
+    }
+
+    /// Convert a token response into a connection.
+    fn connection(&self, hash: String, token: TokenResponse) -> Connection {
+        Connection {
+            id: self.flow_id.to_string(),
+            title: self.what.to_string(),
+            description: String::from("Authorized through the device flow"),
+            hash,
+            token: Token {
+                client_id: self.client_id.clone(),
+                flow_id: self.flow_id.to_string(),
+                access_token: token.access_token,
+                refreshed_at: Utc::now(),
+                expires_in: token.expires_in,
+                scopes: token.scope,
+                refresh_token: Some(token.refresh_token),
+            },
+        }
+    }
+}
+
+/// Check that a client id is configured for the device flow.
+pub fn client_id(client_id: Option<String>, what: &str) -> Result<String> {
+    match client_id {
+        Some(client_id) if !client_id.trim().is_empty() => Ok(client_id),
+        _ => bail!(
+            "{}: the device flow requires `remote/device/client-id` to be set",
+            what
+        ),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[serde(default)]
+    scope: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Device, TokenResponse};
+
+    #[test]
+    fn test_connection() {
+        let device = Device::new(String::from("abc"), "twitch-bot", "Twitch Bot", &[]);
+
+        let token = serde_json::from_str::<TokenResponse>(
+            r#"{"access_token":"access","refresh_token":"refresh","expires_in":14400,"scope":["chat:read"],"token_type":"bearer"}"#,
+        )
+        .expect("token response");
+
+        let c = device.connection(String::from("device-1"), token);
+        assert_eq!(c.id, "twitch-bot");
+        assert_eq!(c.hash, "device-1");
+        assert_eq!(c.token.client_id, "abc");
+        assert_eq!(c.token.access_token, "access");
+        assert_eq!(c.token.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(c.token.expires_in, Some(14400));
+        assert_eq!(c.token.scopes, vec![String::from("chat:read")]);
+    }
+}
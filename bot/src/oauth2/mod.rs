@@ -4,16 +4,25 @@ use crate::api::{
 };
 use crate::injector::{Injector, Key};
 use crate::prelude::*;
+use crate::sys;
 use crate::utils::Duration;
 use crate::web;
 use anyhow::Error;
 use std::collections::VecDeque;
 use std::fmt;
+use std::future;
 use std::sync::Arc;
 use std::time;
 use thiserror::Error;
 use tokio::sync::{RwLock, RwLockReadGuard};
 
+mod device;
+
+/// The longest to wait before retrying after failing to refresh a connection.
+const MAX_RETRY_DELAY: time::Duration = time::Duration::from_secs(10 * 60);
+
+pub use self::device::AuthFlow;
+
 #[derive(Debug, Error)]
 #[error("Missing OAuth 2.0 Connection: {0}")]
 pub struct MissingTokenError(&'static str);
@@ -141,6 +150,16 @@ struct ConnectionFactory {
     key: Key<SyncToken>,
     server: web::Server,
     current_hash: Option<String>,
+    auth_flow: AuthFlow,
+    /// Client id used for the device flow.
+    device_client_id: Option<String>,
+    /// Backoff used when building the connection fails.
+    error_backoff: backoff::ExponentialBackoff,
+    /// Don't check the connection again until this time, since the last
+    /// attempt failed.
+    retry_at: Option<time::Instant>,
+    /// Authorization through the device flow which is waiting on the user.
+    authorization: Fuse<tokio::task::JoinHandle<Result<Option<Connection>, Error>>>,
 }
 
 enum Validation {
@@ -155,7 +174,13 @@ enum Validation {
 impl ConnectionFactory {
     /// Perform an update based on the existing state.
     pub async fn update(&mut self) -> Result<(), Error> {
-        match self.log_build().await {
+        let validation = self.log_build().await;
+        self.apply(validation).await
+    }
+
+    /// Apply the result of validating the connection.
+    async fn apply(&mut self, validation: Validation) -> Result<(), Error> {
+        match validation {
             Validation::Ok => (),
             Validation::Cleared => {
                 self.settings
@@ -165,6 +190,20 @@ impl ConnectionFactory {
 
                 if self.current_hash.is_some() {
                     self.injector.clear_key(&self.key).await;
+
+                    if let Some(notifier) = self.injector.get::<sys::Notifier>().await {
+                        notifier
+                            .notify(
+                                sys::Category::AuthExpired,
+                                format!("{} disconnected", self.what),
+                                format!(
+                                    "The connection to {} expired, connect it again in the bot.",
+                                    self.what
+                                ),
+                                sys::NotificationIcon::Warning,
+                            )
+                            .await;
+                    }
                 }
 
                 self.server.clear_connection(&self.flow_id).await;
@@ -196,14 +235,28 @@ impl ConnectionFactory {
         connection: Option<Connection>,
     ) -> Result<(), Error> {
         let was_none = self.connection.is_none();
+        // A connection stored by a previous run which we are picking up.
+        let stored = was_none && connection.is_some();
         self.connection = connection.clone();
 
         let connection = match self.log_build().await {
-            Validation::Ok => connection,
+            Validation::Ok => {
+                if stored {
+                    log::info!("{}: Using stored connection", self.what);
+                }
+
+                connection
+            }
             // already cleared, nothing to do.
             Validation::Cleared if was_none => return Ok(()),
             Validation::Cleared => None,
             Validation::Updated(connection) => {
+                // NB: persist the refreshed connection so that it's picked up
+                // as-is on the next start.
+                if stored {
+                    log::info!("{}: Refreshed stored connection", self.what);
+                }
+
                 self.settings
                     .set_silent("connection", Some(&connection))
                     .await?;
@@ -237,19 +290,87 @@ impl ConnectionFactory {
         Ok(())
     }
 
+    /// Test if we're waiting to retry after a failed attempt to build the
+    /// connection.
+    fn is_backing_off(&self) -> bool {
+        match self.retry_at {
+            Some(retry_at) => time::Instant::now() < retry_at,
+            None => false,
+        }
+    }
+
     /// Construct a new connection and log on failures.
+    ///
+    /// Failures are retried with a backoff by the periodic check.
     pub async fn log_build(&mut self) -> Validation {
+        use backoff::backoff::Backoff as _;
+
         match self.build().await {
-            Ok(connection) => connection,
+            Ok(connection) => {
+                self.error_backoff.reset();
+                self.retry_at = None;
+                connection
+            }
             Err(e) => {
-                log_error!(e, "{}: Failed to build connection", self.what);
+                self.back_off(e);
                 Validation::Ok
             }
         }
     }
 
+    /// Log a failure to build the connection and back off before retrying.
+    fn back_off(&mut self, e: Error) {
+        use backoff::backoff::Backoff as _;
+
+        let delay = self.error_backoff.next_backoff().unwrap_or(MAX_RETRY_DELAY);
+
+        log_error!(
+            e,
+            "{}: Failed to build connection, retrying in {:?}",
+            self.what,
+            delay
+        );
+
+        self.retry_at = Some(time::Instant::now() + delay);
+    }
+
+    /// Stop waiting for a pending authorization through the device flow.
+    fn cancel_authorization(&mut self) {
+        if let Some(task) = self.authorization.as_inner_ref() {
+            task.abort();
+        }
+
+        self.authorization.clear();
+    }
+
+    /// Handle the outcome of an authorization through the device flow.
+    async fn authorized(&mut self, result: Result<Option<Connection>, Error>) -> Result<(), Error> {
+        use backoff::backoff::Backoff as _;
+
+        match result {
+            Ok(Some(connection)) => {
+                self.error_backoff.reset();
+                self.retry_at = None;
+                self.connection = Some(connection.clone());
+                self.apply(Validation::Updated(connection)).await
+            }
+            // NB: the code expired, a new one is requested on the next check.
+            Ok(None) => Ok(()),
+            Err(e) => {
+                self.back_off(e);
+                Ok(())
+            }
+        }
+    }
+
     /// Construct a new connection.
     pub async fn build(&mut self) -> Result<Validation, Error> {
+        if let Some(device) = self.device()? {
+            return self.build_device(&device).await;
+        }
+
+        self.cancel_authorization();
+
         let setbac = match self.setbac.as_ref() {
             Some(setbac) => setbac,
             _ => {
@@ -312,6 +433,79 @@ impl ConnectionFactory {
         }
     }
 
+    /// Get the device flow client to use, if the device flow is enabled and
+    /// supported for this connection.
+    fn device(&self) -> Result<Option<device::Device>, Error> {
+        if self.auth_flow != AuthFlow::Device {
+            return Ok(None);
+        }
+
+        let scopes = match device::scopes(self.flow_id) {
+            Some(scopes) => scopes,
+            None => {
+                log::trace!("{}: Device flow not supported", self.what);
+                return Ok(None);
+            }
+        };
+
+        let client_id = device::client_id(self.device_client_id.clone(), self.what)?;
+
+        Ok(Some(device::Device::new(
+            client_id,
+            self.flow_id,
+            self.what,
+            scopes,
+        )))
+    }
+
+    /// Construct a new connection using the device flow.
+    async fn build_device(&mut self, device: &device::Device) -> Result<Validation, Error> {
+        let force_refresh = std::mem::take(&mut self.force_refresh);
+
+        if self.connection.is_some() {
+            self.cancel_authorization();
+        }
+
+        let connection = match self.connection.as_ref() {
+            Some(connection) => connection,
+            None => {
+                // NB: authorizing waits until the user has entered the code,
+                // so it runs in its own task and the connection loop picks up
+                // the result once it's done.
+                if self.authorization.is_empty() {
+                    log::trace!(
+                        "{}: Requesting new connection through device flow",
+                        self.what
+                    );
+
+                    let device = device.clone();
+                    self.authorization =
+                        Fuse::new(tokio::spawn(async move { device.authorize().await }));
+                }
+
+                return Ok(Validation::Ok);
+            }
+        };
+
+        if !force_refresh && !connection.token.expires_within(self.expires)? {
+            log::trace!("{}: Connection OK", self.what);
+            return Ok(Validation::Ok);
+        }
+
+        log::trace!("{}: Refreshing connection through device flow", self.what);
+
+        Ok(match device.refresh(connection).await? {
+            Some(connection) => {
+                self.connection = Some(connection.clone());
+                Validation::Updated(connection)
+            }
+            None => {
+                self.connection = None;
+                Validation::Cleared
+            }
+        })
+    }
+
     /// Request a new connection from the authentication flow.
     async fn request_new_connection(&self, setbac: &Setbac) -> Result<Option<Connection>, Error> {
         log::trace!("{}: Requesting new connection", self.what);
@@ -406,9 +600,25 @@ pub async fn build(
         .stream::<Duration>("remote/check-interval")
         .or_with(Duration::seconds(30))
         .await?;
+    let (mut auth_flow_stream, auth_flow) = parent
+        .stream::<AuthFlow>("remote/auth-flow")
+        .or_with(AuthFlow::Redirect)
+        .await?;
+    let (mut device_client_id_stream, device_client_id) = parent
+        .stream::<String>("remote/device/client-id")
+        .optional()
+        .await?;
 
     let sync_token = SyncToken::new(what, force_refresh);
 
+    let mut error_backoff = backoff::ExponentialBackoff {
+        initial_interval: check_interval.as_std(),
+        max_interval: MAX_RETRY_DELAY,
+        max_elapsed_time: None,
+        ..backoff::ExponentialBackoff::default()
+    };
+    backoff::backoff::Backoff::reset(&mut error_backoff);
+
     let mut builder = ConnectionFactory {
         setbac,
         flow_id,
@@ -422,6 +632,11 @@ pub async fn build(
         key,
         server,
         current_hash: None,
+        auth_flow,
+        device_client_id,
+        error_backoff,
+        retry_at: None,
+        authorization: Fuse::empty(),
     };
 
     // check for expirations.
@@ -451,15 +666,90 @@ pub async fn build(
                     }
                 }
                 _ = check_interval.tick() => {
+                    if builder.is_backing_off() {
+                        log::trace!("{}: Waiting to retry failed connection", what);
+                        continue;
+                    }
+
                     log::trace!("{}: Check for expiration", what);
                     builder.update().await?;
                 }
                 update = check_interval_stream.recv() => {
                     check_interval = tokio::time::interval(update.as_std());
                 }
+                update = auth_flow_stream.recv() => {
+                    builder.auth_flow = update;
+                    builder.update().await?;
+                }
+                update = device_client_id_stream.recv() => {
+                    builder.device_client_id = update;
+                    builder.update().await?;
+                }
+                result = &mut builder.authorization => {
+                    builder.authorized(result?).await?;
+                }
             }
         }
     };
 
     Ok((sync_token, future))
 }
+
+/// Warn about the given connections if they haven't been authenticated
+/// within `remote/auth-timeout`, pointing the user to the web interface at
+/// `url` where they can be authorized.
+///
+/// If `remote/auth-timeout-exit` is enabled the returned future completes
+/// once the timeout expires, which shuts down the bot. Otherwise we keep on
+/// waiting and warn again every time the timeout expires.
+pub async fn auth_timeout(
+    parent: &crate::Settings,
+    tokens: Vec<SyncToken>,
+    url: &str,
+) -> Result<impl Future<Output = Result<(), Error>>, Error> {
+    let url = url.to_string();
+    let timeout = parent
+        .var("remote/auth-timeout", Duration::seconds(5 * 60))
+        .await?;
+    let exit = parent.var("remote/auth-timeout-exit", false).await?;
+
+    Ok(async move {
+        loop {
+            let current = timeout.load().await;
+
+            if current.is_empty() {
+                break;
+            }
+
+            tokio::time::sleep(current.as_std()).await;
+
+            let mut missing = Vec::new();
+
+            for token in &tokens {
+                if !token.is_ready().await {
+                    missing.push(token.what);
+                }
+            }
+
+            if missing.is_empty() {
+                break;
+            }
+
+            for what in missing {
+                log::warn!(
+                    "{}: Not authenticated after {}, authorize it at {}",
+                    what,
+                    current,
+                    url
+                );
+            }
+
+            if exit.load().await {
+                log::warn!("Shutting down since `remote/auth-timeout-exit` is enabled");
+                return Ok(());
+            }
+        }
+
+        future::pending().await
+    })
+}
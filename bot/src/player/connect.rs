@@ -1,12 +1,17 @@
 use crate::api;
 use crate::player;
 use crate::prelude::*;
-use crate::track_id::SpotifyId;
+use crate::track_id::TrackId;
 use anyhow::{bail, Error, Result};
 use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 
+/// How many times to check if a device became active after a transfer.
+const TRANSFER_ATTEMPTS: usize = 5;
+/// How long to wait between each check for an active device.
+const TRANSFER_DELAY: Duration = Duration::from_millis(500);
+
 /// Setup a player.
 pub(super) async fn setup(
     spotify: Arc<api::Spotify>,
@@ -113,8 +118,19 @@ pub(super) struct ConnectPlayer {
 impl ConnectPlayer {
     /// Play the specified song. Or just starting playing if the id of the song
     /// is unspecified.
-    pub(super) async fn play(&self, id: Option<SpotifyId>, elapsed: Option<Duration>) {
-        let track_uri = id.map(|id| format!("spotify:track:{}", id.to_base62()));
+    ///
+    /// The track must be a Spotify track or episode.
+    pub(super) async fn play(&self, track_id: Option<&TrackId>, elapsed: Option<Duration>) {
+        warn_on_error(self.try_play(track_id, elapsed).await);
+    }
+
+    /// Same as `play`, except that errors are returned instead of logged.
+    pub(super) async fn try_play(
+        &self,
+        track_id: Option<&TrackId>,
+        elapsed: Option<Duration>,
+    ) -> Result<(), ConnectError> {
+        let track_uri = track_id.map(TrackId::to_string);
         let elapsed = elapsed.map(|elapsed| elapsed.as_millis() as u64);
         let device_id = self.device.load().await;
 
@@ -123,7 +139,7 @@ impl ConnectPlayer {
             .me_player_play(device_id.as_deref(), track_uri.as_deref(), elapsed)
             .await;
 
-        warn_on_error(ConnectError::handle(result, "play"));
+        ConnectError::handle(result, "play")
     }
 
     /// Play the next song.
@@ -133,6 +149,19 @@ impl ConnectPlayer {
         warn_on_error(ConnectError::handle(result, "skip"));
     }
 
+    /// Seek to the given position in the current song.
+    pub(super) async fn seek(&self, position: Duration) {
+        let device_id = self.device.load().await;
+        let position = position.as_millis() as u64;
+
+        warn_on_error(ConnectError::handle(
+            self.spotify
+                .me_player_seek(device_id.as_deref(), position)
+                .await,
+            "seek",
+        ));
+    }
+
     /// Pause playback.
     pub(super) async fn pause(&self) {
         let device_id = self.device.load().await;
@@ -166,8 +195,10 @@ impl ConnectPlayer {
     }
 
     /// Enqueue the specified song to play next.
-    pub(super) async fn queue(&self, id: SpotifyId) -> Result<(), ConnectError> {
-        let track_uri = format!("spotify:track:{}", id.to_base62());
+    ///
+    /// The track must be a Spotify track or episode.
+    pub(super) async fn queue(&self, track_id: &TrackId) -> Result<(), ConnectError> {
+        let track_uri = track_id.to_string();
         let device_id = self.device.load().await;
 
         let result = self
@@ -260,6 +291,30 @@ impl ConnectDevice {
         self.spotify.my_player_devices().await
     }
 
+    /// Transfer playback to the given device and wait until it's reported as
+    /// the active device.
+    ///
+    /// Returns `false` if the device never became active.
+    pub(super) async fn transfer_device(&self, device: String) -> Result<bool> {
+        self.settings.set("device", Some(&device)).await?;
+
+        if !self.spotify.me_player_transfer(&device).await? {
+            return Ok(false);
+        }
+
+        for _ in 0..TRANSFER_ATTEMPTS {
+            tokio::time::sleep(TRANSFER_DELAY).await;
+
+            let devices = self.spotify.my_player_devices().await?;
+
+            if devices.iter().any(|d| d.id == device && d.is_active) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
     /// Set which device to perform playback from.
     pub(super) async fn set_device(&self, device: Option<String>) -> Result<()> {
         self.settings.set("device", device).await?;
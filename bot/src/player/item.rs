@@ -9,6 +9,18 @@ pub struct Item {
     pub track: Track,
     pub user: Option<String>,
     pub duration: Duration,
+    /// Currency the user paid to request the item, which is refunded if the
+    /// item is removed before it plays.
+    pub paid: Option<Payment>,
+}
+
+/// Currency paid to request a song.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payment {
+    /// The channel whose currency was paid.
+    pub channel: String,
+    /// The amount paid.
+    pub amount: u32,
 }
 
 impl Item {
@@ -22,6 +34,9 @@ impl Item {
                     format!("\"{}\"", track.name)
                 }
             }
+            Track::SpotifyEpisode { episode } => {
+                format!("\"{}\" from \"{}\"", episode.name, episode.show.name)
+            }
             Track::YouTube { video } => match video.snippet.as_ref() {
                 Some(snippet) => match snippet.channel_title.as_ref() {
                     Some(channel_title) => {
@@ -38,6 +53,7 @@ impl Item {
     pub fn is_playable(&self) -> bool {
         match &self.track {
             Track::Spotify { track } => track.is_playable.unwrap_or(true),
+            Track::SpotifyEpisode { episode } => episode.is_playable.unwrap_or(true),
             Track::YouTube { video: _ } => true,
         }
     }
@@ -1,12 +1,16 @@
 use crate::api;
 use crate::db;
-use crate::player::{convert_item, Item, Song};
+use crate::player::mood::{Features, Mood};
+use crate::player::{convert_item, Item, Payment, Song};
+use crate::spotify_id::SpotifyId;
 use crate::track_id::TrackId;
 use crate::utils;
 use anyhow::Result;
 use chrono::Utc;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom as _;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Mixer decides what song to play next.
 pub(super) struct Mixer {
@@ -20,6 +24,10 @@ pub(super) struct Mixer {
     fallback_items: Vec<Arc<Item>>,
     /// Items ordered in the reverse way they are meant to be played.
     fallback_queue: VecDeque<Arc<Item>>,
+    /// The mood to bias fallback items towards.
+    mood: Option<Mood>,
+    /// Cached audio features of fallback items.
+    features: HashMap<SpotifyId, Features>,
 }
 
 impl Mixer {
@@ -34,6 +42,8 @@ impl Mixer {
             sidelined: Default::default(),
             fallback_items: Default::default(),
             fallback_queue: Default::default(),
+            mood: None,
+            features: Default::default(),
         }
     }
 
@@ -42,11 +52,8 @@ impl Mixer {
         &mut self,
         spotify: &api::Spotify,
         youtube: &api::YouTube,
+        market: &str,
     ) -> Result<()> {
-        // TODO: cache this value
-        let streamer = spotify.me().await?;
-        let market = streamer.country.as_deref();
-
         // Add tracks from database.
         for song in self.db.player_list().await? {
             let item = convert_item(
@@ -55,11 +62,12 @@ impl Mixer {
                 song.user.as_deref(),
                 &song.track_id,
                 None,
-                market,
+                Some(market),
             )
             .await;
 
-            if let Ok(Some(item)) = item {
+            if let Ok(Some(mut item)) = item {
+                item.paid = payment_from_db(song.paid_channel.as_deref(), song.paid);
                 self.queue.push_back(Arc::new(item));
             } else {
                 log::warn!("failed to convert db item: {:?}", song);
@@ -79,6 +87,11 @@ impl Mixer {
         self.queue.len()
     }
 
+    /// Get the total duration of the queue in the mixer.
+    pub(super) fn duration(&self) -> Duration {
+        self.queue.iter().map(|item| item.duration).sum()
+    }
+
     /// Push item to back of queue.
     pub(super) async fn push_back(&mut self, item: Arc<Item>) -> Result<()> {
         self.db
@@ -86,6 +99,11 @@ impl Mixer {
                 track_id: item.track_id.clone(),
                 added_at: Utc::now().naive_utc(),
                 user: item.user.clone(),
+                paid_channel: item.paid.as_ref().map(|paid| paid.channel.clone()),
+                paid: item
+                    .paid
+                    .as_ref()
+                    .map(|paid| i32::try_from(paid.amount).unwrap_or(i32::MAX)),
             })
             .await?;
 
@@ -135,6 +153,16 @@ impl Mixer {
         Ok(None)
     }
 
+    /// Count the requested songs by the given user which have been played.
+    pub(super) async fn played_count(&self, user: &str) -> Result<i64> {
+        self.db.player_played_count(user).await
+    }
+
+    /// List the users who have had the most requested songs played.
+    pub(super) async fn top_requesters(&self, limit: usize) -> Result<Vec<(String, i64)>> {
+        self.db.player_top_requesters(limit as i64).await
+    }
+
     /// Remove the last requested song matching the given user.
     pub(super) async fn remove_last_by_user(&mut self, user: &str) -> Result<Option<Arc<Item>>> {
         if self.queue.is_empty() {
@@ -205,10 +233,60 @@ impl Mixer {
             self.fallback_queue.extend(extension);
         }
 
-        let item = self.fallback_queue.pop_front()?;
+        let item = match self.mood_position() {
+            Some(position) => self.fallback_queue.remove(position)?,
+            None => self.fallback_queue.pop_front()?,
+        };
+
         Some(Song::new(item, Default::default()))
     }
 
+    /// Find the position of the first fallback item which matches the current
+    /// mood.
+    fn mood_position(&self) -> Option<usize> {
+        let mood = self.mood?;
+
+        self.fallback_queue
+            .iter()
+            .position(|item| match &item.track_id {
+                TrackId::Spotify(id) => self
+                    .features
+                    .get(id)
+                    .map(|features| mood.matches(features))
+                    .unwrap_or_default(),
+                _ => false,
+            })
+    }
+
+    /// Get the current mood.
+    pub(super) fn mood(&self) -> Option<Mood> {
+        self.mood
+    }
+
+    /// Set the mood to bias fallback items towards.
+    pub(super) fn set_mood(&mut self, mood: Option<Mood>) {
+        self.mood = mood;
+    }
+
+    /// List the fallback items which we don't have audio features for.
+    pub(super) fn missing_features(&self) -> Vec<SpotifyId> {
+        self.fallback_items
+            .iter()
+            .filter_map(|item| match &item.track_id {
+                TrackId::Spotify(id) if !self.features.contains_key(id) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Cache the given audio features.
+    pub(super) fn insert_features(
+        &mut self,
+        features: impl IntoIterator<Item = (SpotifyId, Features)>,
+    ) {
+        self.features.extend(features);
+    }
+
     /// Get the next song that should be played.
     ///
     /// This takes into account:
@@ -256,3 +334,13 @@ impl Mixer {
         self.fallback_queue.clear();
     }
 }
+
+/// Convert the payment for a song as stored in the database.
+fn payment_from_db(channel: Option<&str>, amount: Option<i32>) -> Option<Payment> {
+    let amount = u32::try_from(amount?).ok().filter(|amount| *amount > 0)?;
+
+    Some(Payment {
+        channel: channel?.to_string(),
+        amount,
+    })
+}
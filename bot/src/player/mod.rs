@@ -18,13 +18,19 @@ use tracing_futures::Instrument as _;
 pub(self) use self::connect::{ConnectDevice, ConnectPlayer, ConnectStream};
 pub(self) use self::mixer::Mixer;
 pub(self) use self::playback_future::PlaybackFuture;
-pub(self) use self::player_internal::PlayerInternal;
+pub(self) use self::player_internal::{PlayerEvents, PlayerInternal};
 pub(self) use self::youtube::YouTubePlayer;
-pub use self::{item::Item, song::Song, track::Track};
+pub use self::{
+    item::{Item, Payment},
+    mood::Mood,
+    song::Song,
+    track::Track,
+};
 
 mod connect;
 mod item;
 mod mixer;
+mod mood;
 mod playback_future;
 mod player_internal;
 mod song;
@@ -80,6 +86,21 @@ pub(self) enum PlaybackMode {
     Queue,
 }
 
+/// The service to search for songs which are requested by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub(self) enum SearchService {
+    #[serde(rename = "spotify")]
+    Spotify,
+    #[serde(rename = "youtube")]
+    YouTube,
+}
+
+impl Default for SearchService {
+    fn default() -> Self {
+        Self::Spotify
+    }
+}
+
 impl Default for PlaybackMode {
     fn default() -> Self {
         Self::Default
@@ -108,6 +129,25 @@ impl ModifyVolume {
     }
 }
 
+/// The market which makes Spotify use the market associated with the
+/// streamer's token.
+const MARKET_FROM_TOKEN: &str = "from_token";
+
+/// Normalize the configured market, falling back to the market associated
+/// with the token.
+pub(self) fn market(market: Option<String>) -> String {
+    let market = match market.as_deref().map(str::trim) {
+        Some(market) if !market.is_empty() => market,
+        _ => return String::from(MARKET_FROM_TOKEN),
+    };
+
+    if market.eq_ignore_ascii_case(MARKET_FROM_TOKEN) {
+        return String::from(MARKET_FROM_TOKEN);
+    }
+
+    market.to_uppercase()
+}
+
 /// Converts a track into an Item.
 ///
 /// Returns `None` if the service required to convert the item is not
@@ -132,6 +172,16 @@ pub(self) async fn convert_item(
 
             (Track::Spotify { track }, duration)
         }
+        TrackId::SpotifyEpisode(id) => {
+            if !spotify.token.is_ready().await {
+                return Ok(None);
+            }
+
+            let episode = spotify.episode(id.to_base62(), market).await?;
+            let duration = Duration::from_millis(episode.duration_ms.into());
+
+            (Track::SpotifyEpisode { episode }, duration)
+        }
         TrackId::YouTube(id) => {
             if !youtube.token.is_ready().await {
                 return Ok(None);
@@ -164,6 +214,7 @@ pub(self) async fn convert_item(
         track,
         user: user.map(|user| user.to_string()),
         duration,
+        paid: None,
     }))
 }
 
@@ -218,9 +269,15 @@ pub async fn run(
     let duplicate_duration = settings
         .var("duplicate-duration", utils::Duration::default())
         .await?;
+    let reject_duplicates = settings.var("reject-duplicates", true).await?;
     let song_switch_feedback = settings.var("song-switch-feedback", true).await?;
     let max_songs_per_user = settings.var("max-songs-per-user", 2).await?;
     let max_queue_length = settings.var("max-queue-length", 30).await?;
+    let max_queue_duration = settings.optional("max-queue-duration").await?;
+    let market = settings.optional("spotify/market").await?;
+    let search_service = settings
+        .var("search-service", SearchService::default())
+        .await?;
 
     let mixer = Mixer::new(db.clone());
 
@@ -242,12 +299,17 @@ pub async fn run(
         mixer,
         bus,
         global_bus,
+        events: PlayerEvents::new(&settings.scoped("events")).await?,
         song_switch_feedback,
 
         device,
         max_queue_length,
+        max_queue_duration,
         max_songs_per_user,
         duplicate_duration,
+        reject_duplicates,
+        market,
+        search_service,
 
         themes: injector.var().await,
         closed: None,
@@ -348,6 +410,14 @@ impl Player {
         inner.device.set_device(Some(device)).await
     }
 
+    /// Transfer playback to the given device.
+    ///
+    /// Returns `false` if the device didn't become active after the transfer.
+    pub async fn transfer_device(&self, device: String) -> Result<bool> {
+        let inner = self.inner.read().await;
+        inner.device.transfer_device(device).await
+    }
+
     /// Clear the current device.
     pub async fn clear_device(&self) -> Result<()> {
         let inner = self.inner.read().await;
@@ -367,6 +437,11 @@ impl Player {
             .collect()
     }
 
+    /// Get the songs queued up after the current song.
+    pub async fn queue(&self) -> Vec<Arc<Item>> {
+        self.inner.read().await.mixer.list().cloned().collect()
+    }
+
     /// Promote the given song to the head of the queue.
     pub async fn promote_song(&self, user: Option<&str>, n: usize) -> Result<Option<Arc<Item>>> {
         let mut inner = self.inner.write().await;
@@ -400,6 +475,15 @@ impl Player {
         Ok(())
     }
 
+    /// Seek to the given position in the current song.
+    ///
+    /// Positions past the end of the song are clamped to its length. Returns
+    /// the position seeked to, or `None` if nothing is playing.
+    pub async fn seek(&self, position: Duration) -> Result<Option<Duration>> {
+        let mut inner = self.inner.write().await;
+        inner.seek(Source::Manual, position).await
+    }
+
     /// Skip the current song.
     pub async fn skip(&self) -> Result<()> {
         let mut inner = self.inner.write().await;
@@ -421,7 +505,9 @@ impl Player {
             .clone();
 
         match track_id {
-            TrackId::Spotify(..) => Some(inner.connect_player.current_volume().await),
+            TrackId::Spotify(..) | TrackId::SpotifyEpisode(..) => {
+                Some(inner.connect_player.current_volume().await)
+            }
             TrackId::YouTube(..) => Some(inner.youtube_player.current_volume().await),
         }
     }
@@ -438,7 +524,9 @@ impl Player {
         };
 
         Some(match track_id {
-            TrackId::Spotify(..) => inner.connect_player.volume(modify).await,
+            TrackId::Spotify(..) | TrackId::SpotifyEpisode(..) => {
+                inner.connect_player.volume(modify).await
+            }
             TrackId::YouTube(..) => inner.youtube_player.volume(modify).await,
         })
     }
@@ -454,11 +542,21 @@ impl Player {
     }
 
     /// Search for a track.
+    ///
+    /// The query can be prefixed with `youtube:` or `spotify:` to pick the
+    /// service to search, otherwise the configured search service is used.
     pub async fn search_track(&self, q: &str) -> Result<Option<TrackId>> {
         let inner = self.inner.read().await;
 
-        if q.starts_with("youtube:") {
-            let q = q.trim_start_matches("youtube:");
+        let (service, q) = if let Some(q) = q.strip_prefix("youtube:") {
+            (SearchService::YouTube, q)
+        } else if let Some(q) = q.strip_prefix("spotify:") {
+            (SearchService::Spotify, q)
+        } else {
+            (inner.search_service.load().await, q)
+        };
+
+        if let SearchService::YouTube = service {
             let results = inner.youtube.search(q).await?;
 
             let result = results.items.into_iter().filter(|r| match r.id.kind {
@@ -470,13 +568,8 @@ impl Player {
             return Ok(result.next().map(TrackId::YouTube));
         }
 
-        let q = if q.starts_with("spotify:") {
-            q.trim_start_matches("spotify:")
-        } else {
-            q
-        };
-
-        let page = inner.spotify.search_track(q).await?;
+        let market = market(inner.market.load().await);
+        let page = inner.spotify.search_track(q, Some(&market)).await?;
 
         match page.items.into_iter().next().and_then(|t| t.id) {
             Some(track_id) => match SpotifyId::from_base62(&track_id) {
@@ -487,6 +580,33 @@ impl Player {
         }
     }
 
+    /// Search for a track by its exact ISRC (International Standard Recording
+    /// Code).
+    pub async fn search_isrc(&self, isrc: &str) -> Result<Option<TrackId>> {
+        let inner = self.inner.read().await;
+
+        let market = market(inner.market.load().await);
+        let q = format!("isrc:{}", isrc);
+        let page = inner.spotify.search_track(&q, Some(&market)).await?;
+
+        // NB: the search filter is fuzzy, so make sure we pick a track with a
+        // matching ISRC.
+        let track = page.items.into_iter().find(|t| {
+            t.external_ids
+                .get("isrc")
+                .map(|id| id.eq_ignore_ascii_case(isrc))
+                .unwrap_or_default()
+        });
+
+        match track.and_then(|t| t.id) {
+            Some(track_id) => match SpotifyId::from_base62(&track_id) {
+                Ok(track_id) => Ok(Some(TrackId::Spotify(track_id))),
+                Err(_) => bail!("search result returned malformed id"),
+            },
+            None => Ok(None),
+        }
+    }
+
     /// Play a theme track.
     pub async fn play_theme(&self, channel: &str, name: &str) -> Result<(), PlayThemeError> {
         let mut inner = self.inner.write().await;
@@ -502,6 +622,7 @@ impl Player {
         };
 
         let duration = theme.end.clone().map(|o| o.as_duration());
+        let market = market(inner.market.load().await);
 
         let item = convert_item(
             &*inner.spotify,
@@ -509,7 +630,7 @@ impl Player {
             None,
             &theme.track_id,
             duration,
-            None,
+            Some(&market),
         )
         .await
         .map_err(PlayThemeError::Error)?;
@@ -529,7 +650,8 @@ impl Player {
         Ok(())
     }
 
-    /// Add the given track to the queue.
+    /// Add the given track to the queue, where `paid` is what the user paid to
+    /// request it.
     ///
     /// Returns the item added.
     pub async fn add_track(
@@ -538,10 +660,11 @@ impl Player {
         track_id: TrackId,
         bypass_constraints: bool,
         max_duration: Option<utils::Duration>,
+        paid: Option<Payment>,
     ) -> Result<(Option<usize>, Arc<Item>), AddTrackError> {
         let mut inner = self.inner.write().await;
         inner
-            .add_track(user, track_id, bypass_constraints, max_duration)
+            .add_track(user, track_id, bypass_constraints, max_duration, paid)
             .await
     }
 
@@ -553,6 +676,8 @@ impl Player {
             inner.modified(Source::Manual).await?;
         }
 
+        inner.refund(&purged).await;
+
         Ok(purged)
     }
 
@@ -561,8 +686,9 @@ impl Player {
         let mut inner = self.inner.write().await;
         let removed = inner.mixer.remove_at(n).await?;
 
-        if removed.is_some() {
+        if let Some(item) = &removed {
             inner.modified(Source::Manual).await?;
+            inner.refund(std::slice::from_ref(item)).await;
         }
 
         Ok(removed)
@@ -573,25 +699,52 @@ impl Player {
         let mut inner = self.inner.write().await;
         let removed = inner.mixer.remove_last().await?;
 
-        if removed.is_some() {
+        if let Some(item) = &removed {
             inner.modified(Source::Manual).await?;
+            inner.refund(std::slice::from_ref(item)).await;
         }
 
         Ok(removed)
     }
 
+    /// Count the songs requested by the given user which have been played.
+    pub async fn played_count(&self, user: &str) -> Result<i64> {
+        let inner = self.inner.read().await;
+        inner.mixer.played_count(user).await
+    }
+
+    /// List the users who have had the most requested songs played, and how
+    /// many.
+    pub async fn top_requesters(&self, limit: usize) -> Result<Vec<(String, i64)>> {
+        let inner = self.inner.read().await;
+        inner.mixer.top_requesters(limit).await
+    }
+
     /// Remove the last track by the given user.
     pub async fn remove_last_by_user(&self, user: &str) -> Result<Option<Arc<Item>>> {
         let mut inner = self.inner.write().await;
         let removed = inner.mixer.remove_last_by_user(user).await?;
 
-        if removed.is_some() {
+        if let Some(item) = &removed {
             inner.modified(Source::Manual).await?;
+            inner.refund(std::slice::from_ref(item)).await;
         }
 
         Ok(removed)
     }
 
+    /// Get the mood that fallback songs are biased towards.
+    pub async fn mood(&self) -> Option<Mood> {
+        self.inner.read().await.mixer.mood()
+    }
+
+    /// Set the mood to bias fallback songs towards, or `None` to pick them at
+    /// random.
+    pub async fn set_mood(&self, mood: Option<Mood>) -> Result<()> {
+        self.inner.write().await.mixer.set_mood(mood);
+        mood::fetch_missing_features(&self.inner).await
+    }
+
     /// Find the next item that matches the given predicate and how long until it plays.
     pub async fn find(
         &self,
@@ -620,6 +773,11 @@ impl Player {
         None
     }
 
+    /// Get the total duration of the songs queued up after the current song.
+    pub async fn queued_duration(&self) -> Duration {
+        self.inner.read().await.mixer.duration()
+    }
+
     /// Get the length in number of items and total number of seconds in queue.
     pub async fn length(&self) -> (usize, Duration) {
         let inner = self.inner.read().await;
@@ -662,10 +820,17 @@ pub enum PlayThemeError {
 pub enum AddTrackError {
     /// Queue is full.
     QueueFull,
-    /// Queue already contains track.
-    QueueContainsTrack(usize),
-    /// Too many user tracks.
-    TooManyUserTracks(u32),
+    /// Adding the track would make the queue longer than the maximum queue
+    /// duration, with how much time is left in the queue.
+    QueueTooLong { remaining: Duration },
+    /// Queue already contains track at the given position, requested by the
+    /// given user.
+    QueueContainsTrack {
+        pos: usize,
+        duplicate_by: DuplicateBy,
+    },
+    /// Too many user tracks, with how many the user has queued and the limit.
+    TooManyUserTracks { queued: u32, limit: u32 },
     /// Player has been closed from adding more tracks to the queue with an optional reason.
     PlayerClosed(Option<Arc<String>>),
     /// Duplicate song that was added at the specified time by the specified user.
@@ -678,8 +843,8 @@ pub enum AddTrackError {
     MissingAuth,
     /// Playback mode is not supported for the given track.
     UnsupportedPlaybackMode,
-    /// Song cannot be played in the streamer's region
-    NotPlayable,
+    /// Song cannot be played in the given market.
+    NotPlayable(String),
     /// Other generic error happened.
     Error(anyhow::Error),
 }
@@ -701,35 +866,74 @@ impl fmt::Display for AddTrackError {
                     write!(f, "Player is closed from further requests, sorry :(")
                 }
             },
-            AddTrackError::QueueContainsTrack(pos) => {
+            AddTrackError::QueueContainsTrack { pos, duplicate_by } => match duplicate_by {
+                DuplicateBy::Requester => {
+                    write!(
+                        f,
+                        "You already requested that track (position #{pos}).",
+                        pos = pos + 1,
+                    )
+                }
+                DuplicateBy::Other(who) => {
+                    write!(
+                        f,
+                        "That track is already queued by {who} (position #{pos}).",
+                        who = who,
+                        pos = pos + 1,
+                    )
+                }
+                DuplicateBy::Unknown => {
+                    write!(
+                        f,
+                        "Player already contains that track (position #{pos}).",
+                        pos = pos + 1,
+                    )
+                }
+            },
+            AddTrackError::TooManyUserTracks { queued, limit } => match (queued, limit) {
+                (_, 0) => {
+                    write!(
+                        f,
+                        "Unfortunately you are not allowed to add tracks (track limit is zero) :("
+                    )
+                }
+                (1, _) => {
+                    write!(
+                        f,
+                        "<3 your enthusiasm, but you already have a track in the queue.",
+                    )
+                }
+                (queued, 1) => {
+                    write!(
+                        f,
+                        "<3 your enthusiasm, but you already have {queued} tracks in the queue and the limit is one.",
+                        queued = queued,
+                    )
+                }
+                (queued, limit) => {
+                    write!(
+                        f,
+                        "<3 your enthusiasm, but you already have {queued} tracks in the queue and the limit is {limit}.",
+                        queued = queued,
+                        limit = limit,
+                    )
+                }
+            },
+            AddTrackError::QueueFull => {
+                write!(f, "Player is full, try again later!")
+            }
+            AddTrackError::QueueTooLong { remaining } if remaining.as_secs() == 0 => {
                 write!(
                     f,
-                    "Player already contains that track (position #{pos}).",
-                    pos = pos + 1,
+                    "There's no more room for music in the queue, try again later!"
                 )
             }
-            AddTrackError::TooManyUserTracks(count) => {
-                match count {
-                    0 => {
-                        write!(f, "Unfortunately you are not allowed to add tracks (track limit is zero) :(")
-                    }
-                    1 => {
-                        write!(
-                            f,
-                            "<3 your enthusiasm, but you already have a track in the queue.",
-                        )
-                    }
-                    count => {
-                        write!(
-                            f,
-                            "<3 your enthusiasm, but you already have {count} tracks in the queue.",
-                            count = count,
-                        )
-                    }
-                }
-            }
-            AddTrackError::QueueFull => {
-                write!(f, "Player is full, try again later!")
+            AddTrackError::QueueTooLong { remaining } => {
+                write!(
+                    f,
+                    "That song is too long, there's only {} of room left in the queue.",
+                    utils::compact_duration(*remaining),
+                )
             }
             AddTrackError::Duplicate {
                 duplicate_by,
@@ -758,9 +962,16 @@ impl fmt::Display for AddTrackError {
                     "Cannot add the given song because the service has not been authenticated by the streamer!",
                 )
             }
-            AddTrackError::NotPlayable => {
+            AddTrackError::NotPlayable(market) if market == MARKET_FROM_TOKEN => {
                 write!(f, "This song is not available in the streamer's region :(")
             }
+            AddTrackError::NotPlayable(market) => {
+                write!(
+                    f,
+                    "This song is not available in the configured market ({}) :(",
+                    market
+                )
+            }
             AddTrackError::Error(e) => {
                 write!(f, "{}", e)
             }
@@ -777,6 +988,17 @@ pub enum DuplicateBy {
     Unknown,
 }
 
+impl DuplicateBy {
+    /// Describe who requested a song, compared to the user requesting it again.
+    fn new(who: Option<String>, user: &str) -> Self {
+        match who {
+            Some(who) if who == user => DuplicateBy::Requester,
+            Some(who) => DuplicateBy::Other(who),
+            None => DuplicateBy::Unknown,
+        }
+    }
+}
+
 impl fmt::Display for DuplicateBy {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -0,0 +1,140 @@
+use crate::api;
+use crate::player::PlayerInternal;
+use crate::spotify_id::SpotifyId;
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str;
+use tokio::sync::RwLock;
+
+/// The maximum number of tracks we can ask for audio features at a time.
+const AUDIO_FEATURES_LIMIT: usize = 100;
+
+/// A mood to bias fallback songs towards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mood {
+    /// Low energy and slow tracks.
+    Chill,
+    /// High energy and fast tracks.
+    Hype,
+}
+
+impl Mood {
+    /// Test if the given audio features match the mood.
+    pub(super) fn matches(self, features: &Features) -> bool {
+        match self {
+            Mood::Chill => features.energy <= 0.5 && features.tempo <= 110.0,
+            Mood::Hype => features.energy >= 0.7 && features.tempo >= 120.0,
+        }
+    }
+}
+
+impl str::FromStr for Mood {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chill" => Ok(Mood::Chill),
+            "hype" => Ok(Mood::Hype),
+            other => bail!("bad mood `{}`, expected chill or hype", other),
+        }
+    }
+}
+
+impl fmt::Display for Mood {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mood::Chill => "chill".fmt(fmt),
+            Mood::Hype => "hype".fmt(fmt),
+        }
+    }
+}
+
+/// The audio features of a track that we use to match moods.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Features {
+    /// Energy, from 0.0 to 1.0.
+    energy: f32,
+    /// Tempo in beats per minute.
+    tempo: f32,
+}
+
+/// Fetch and cache audio features for any fallback items which are missing
+/// them, if a mood is set.
+pub(super) async fn fetch_missing_features(internal: &RwLock<PlayerInternal>) -> Result<()> {
+    let (spotify, missing) = {
+        let inner = internal.read().await;
+
+        if inner.mixer.mood().is_none() {
+            return Ok(());
+        }
+
+        (inner.spotify.clone(), inner.mixer.missing_features())
+    };
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    log::trace!("Fetching audio features for {} tracks", missing.len());
+
+    let mut out = Vec::new();
+
+    for chunk in missing.chunks(AUDIO_FEATURES_LIMIT) {
+        let ids = chunk.iter().map(|id| id.to_base62()).collect::<Vec<_>>();
+
+        for features in spotify.audio_features(&ids).await? {
+            if let Some(entry) = convert(&features) {
+                out.push(entry);
+            }
+        }
+    }
+
+    internal.write().await.mixer.insert_features(out);
+    Ok(())
+}
+
+/// Convert audio features from the Spotify API.
+fn convert(features: &api::spotify::AudioFeatures) -> Option<(SpotifyId, Features)> {
+    let id = SpotifyId::from_base62(&features.id).ok()?;
+
+    Some((
+        id,
+        Features {
+            energy: features.energy,
+            tempo: features.tempo,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Features, Mood};
+
+    #[test]
+    fn test_matches() {
+        let slow = Features {
+            energy: 0.3,
+            tempo: 80.0,
+        };
+
+        let fast = Features {
+            energy: 0.9,
+            tempo: 140.0,
+        };
+
+        let between = Features {
+            energy: 0.6,
+            tempo: 115.0,
+        };
+
+        assert!(Mood::Chill.matches(&slow));
+        assert!(!Mood::Chill.matches(&fast));
+        assert!(!Mood::Chill.matches(&between));
+        assert!(Mood::Hype.matches(&fast));
+        assert!(!Mood::Hype.matches(&slow));
+        assert!(!Mood::Hype.matches(&between));
+
+        assert_eq!("hype".parse::<Mood>().ok(), Some(Mood::Hype));
+        assert!("sad".parse::<Mood>().is_err());
+    }
+}
@@ -100,20 +100,40 @@ impl PlaybackFuture {
             let task = retry_until_ok! {
                 "Loading fallback items", {
                     let task = internal.read().await.load_fallback_items(fallback.as_ref());
-                    let (what, items) = task.await?;
 
-                    log::info!(
-                        "Updated fallback queue with {} items from {}.",
-                        items.len(),
-                        what
-                    );
+                    let (what, items) = match task.await {
+                        Ok(output) => output,
+                        Err(e) => {
+                            // NB: don't keep playing songs from a fallback
+                            // which is no longer configured or available.
+                            internal.write().await.update_fallback_items(Vec::new()).await;
+                            return Err(e);
+                        }
+                    };
+
+                    if items.is_empty() {
+                        log::warn!(
+                            "No fallback items in {}, the player will pause when the queue is empty.",
+                            what
+                        );
+                    } else {
+                        log::info!(
+                            "Updated fallback queue with {} items from {}.",
+                            items.len(),
+                            what
+                        );
+                    }
 
                     internal.write().await.update_fallback_items(items).await;
                     Ok(())
                 }
             };
 
-            task.await
+            task.await;
+
+            if let Err(e) = super::mood::fetch_missing_features(internal).await {
+                log_error!(e, "Failed to fetch audio features for fallback items");
+            }
         }
     }
 }
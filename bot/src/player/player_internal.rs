@@ -1,12 +1,13 @@
 use crate::api;
-use crate::api::spotify::{FullTrack, PrivateUser};
+use crate::api::spotify::FullTrack;
 use crate::bus;
+use crate::currency::Currency;
 use crate::db;
 use crate::injector;
 use crate::player::{
     convert_item, AddTrackError, ConnectDevice, ConnectPlayer, DuplicateBy, Event,
-    IntegrationEvent, Item, Mixer, PlaybackMode, PlayerKind, Song, Source, State, Track,
-    YouTubePlayer,
+    IntegrationEvent, Item, Mixer, Payment, PlaybackMode, PlayerKind, SearchService, Song, Source,
+    State, Track, YouTubePlayer,
 };
 use crate::prelude::*;
 use crate::settings;
@@ -25,6 +26,24 @@ pub(super) struct Initialized {
     playback_state: bool,
 }
 
+/// Settings for which player events are published on the global bus, so that
+/// external integrations like overlays can react to them.
+pub(super) struct PlayerEvents {
+    pub(super) track_change: settings::Var<bool>,
+    pub(super) queue_empty: settings::Var<bool>,
+    pub(super) playback_error: settings::Var<bool>,
+}
+
+impl PlayerEvents {
+    pub(super) async fn new(settings: &crate::Settings) -> Result<Self> {
+        Ok(Self {
+            track_change: settings.var("track-change", false).await?,
+            queue_empty: settings.var("queue-empty", false).await?,
+            playback_error: settings.var("playback-error", false).await?,
+        })
+    }
+}
+
 pub(super) struct PlayerInternal {
     pub(super) initialized: Initialized,
     pub(super) injector: Injector,
@@ -49,12 +68,22 @@ pub(super) struct PlayerInternal {
     pub(super) bus: bus::Bus<Event>,
     /// Notifier to use when sending song updates.
     pub(super) global_bus: bus::Bus<bus::Global>,
+    /// Which player events to publish on the global bus.
+    pub(super) events: PlayerEvents,
     /// Song config.
     pub(super) song_switch_feedback: settings::Var<bool>,
     pub(super) device: ConnectDevice,
     pub(super) max_queue_length: settings::Var<u32>,
+    /// The maximum total duration of queued songs.
+    pub(super) max_queue_duration: settings::Var<Option<utils::Duration>>,
     pub(super) max_songs_per_user: settings::Var<u32>,
     pub(super) duplicate_duration: settings::Var<utils::Duration>,
+    /// If songs which are already in the queue can't be requested again.
+    pub(super) reject_duplicates: settings::Var<bool>,
+    /// The Spotify market to use when looking up tracks.
+    pub(super) market: settings::Var<Option<String>>,
+    /// The service to search for songs requested by name.
+    pub(super) search_service: settings::Var<SearchService>,
     /// Theme songs.
     pub(super) themes: injector::Ref<db::Themes>,
     /// Player is closed for more requests.
@@ -89,8 +118,10 @@ impl PlayerInternal {
         }
 
         if !self.initialized.queue {
+            let market = super::market(self.market.load().await);
+
             self.mixer
-                .initialize_queue(&*self.spotify, &*self.youtube)
+                .initialize_queue(&self.spotify, &self.youtube, &market)
                 .await?;
 
             self.initialized.queue = true;
@@ -125,6 +156,10 @@ impl PlayerInternal {
         } else {
             self.bus.send_sync(Event::Empty);
             self.notify_song_change(None).await?;
+
+            if self.events.queue_empty.load().await {
+                self.global_bus.send(bus::Global::SongQueueEmpty).await;
+            }
         }
 
         Ok(())
@@ -175,10 +210,22 @@ impl PlayerInternal {
     /// Play the given song.
     async fn send_play_command(&mut self, song: &Song) {
         match song.item.track_id.clone() {
-            TrackId::Spotify(id) => {
-                self.connect_player
-                    .play(Some(id), Some(song.elapsed()))
+            track_id @ TrackId::Spotify(..) | track_id @ TrackId::SpotifyEpisode(..) => {
+                let result = self
+                    .connect_player
+                    .try_play(Some(&track_id), Some(song.elapsed()))
                     .await;
+
+                if let Err(e) = result {
+                    let message = e.to_string();
+                    log_warn!(e, "failed to issue connect command");
+
+                    if self.events.playback_error.load().await {
+                        self.global_bus
+                            .send(bus::Global::SongPlaybackError { track_id, message })
+                            .await;
+                    }
+                }
             }
             TrackId::YouTube(id) => {
                 self.youtube_player
@@ -188,6 +235,21 @@ impl PlayerInternal {
         }
     }
 
+    /// Notify external integrations that we switched to the given song.
+    async fn notify_track_change(&self, song: &Song) {
+        if !self.events.track_change.load().await {
+            return;
+        }
+
+        self.global_bus
+            .send(bus::Global::SongTrackChange {
+                track_id: song.item.track_id.clone(),
+                track: song.item.track.clone(),
+                user: song.item.user.clone(),
+            })
+            .await;
+    }
+
     /// Switch the player to the specified song without changing its state.
     async fn switch_to_song(&mut self, mut song: Option<Song>) -> Result<()> {
         if let Some(song) = song.as_mut() {
@@ -213,6 +275,7 @@ impl PlayerInternal {
         self.send_play_command(&song).await;
         self.switch_current_player(song.player()).await?;
         self.notify_song_change(Some(&song)).await?;
+        self.notify_track_change(&song).await;
 
         if let Source::Manual = source {
             let feedback = self.song_switch_feedback.load().await;
@@ -368,6 +431,50 @@ impl PlayerInternal {
         Ok(())
     }
 
+    /// Seek to the given position in the current song, clamped to its length.
+    pub(super) async fn seek(
+        &mut self,
+        source: Source,
+        position: Duration,
+    ) -> Result<Option<Duration>> {
+        if self.detached {
+            if let Source::Manual = source {
+                self.bus.send_sync(Event::Detached);
+            }
+
+            return Ok(None);
+        }
+
+        let song = match self.injector.get::<Song>().await {
+            Some(song) => song,
+            None => return Ok(None),
+        };
+
+        let state = self.injector.get::<State>().await.unwrap_or_default();
+        let position = Duration::min(position, song.duration());
+        let mut song = Song::new(song.item.clone(), position);
+
+        // NB: a paused song picks up the new position when it is resumed.
+        if let State::Playing = state {
+            match &song.item.track_id {
+                TrackId::Spotify(..) | TrackId::SpotifyEpisode(..) => {
+                    self.connect_player.seek(position).await;
+                }
+                TrackId::YouTube(id) => {
+                    self.youtube_player
+                        .play(position, song.duration(), id.clone())
+                        .await;
+                }
+            }
+
+            song.play();
+        }
+
+        self.injector.update(song.clone()).await;
+        self.notify_song_change(Some(&song)).await?;
+        Ok(Some(position))
+    }
+
     pub(super) async fn skip(&mut self, source: Source) -> Result<()> {
         if self.detached {
             if let Source::Manual = source {
@@ -428,6 +535,28 @@ impl PlayerInternal {
         Ok(())
     }
 
+    /// Give back what was paid to request the given items, which were removed
+    /// before they played.
+    pub(super) async fn refund(&self, items: &[Arc<Item>]) {
+        let currency = match self.injector.get::<Currency>().await {
+            Some(currency) => currency,
+            None => return,
+        };
+
+        for item in items {
+            let (paid, user) = match (&item.paid, &item.user) {
+                (Some(paid), Some(user)) => (paid, user),
+                _ => continue,
+            };
+
+            let amount = i64::from(paid.amount);
+
+            if let Err(e) = currency.balance_add(&paid.channel, user, amount).await {
+                log_error!(e, "failed to refund song request by {}", user);
+            }
+        }
+    }
+
     /// Mark the queue as modified and load and notify resources appropriately.
     pub(super) async fn modified(&mut self, source: Source) -> Result<()> {
         if self.detached {
@@ -485,8 +614,8 @@ impl PlayerInternal {
                 self.play_song(source, Song::new(item, offset)).await?;
             }
             PlaybackMode::Queue => match &item.track_id {
-                &TrackId::Spotify(id) => {
-                    self.connect_player.play(Some(id), Some(offset)).await;
+                track_id @ TrackId::Spotify(..) | track_id @ TrackId::SpotifyEpisode(..) => {
+                    self.connect_player.play(Some(track_id), Some(offset)).await;
                     self.injector.update(State::Playing).await;
                 }
                 _ => {
@@ -509,8 +638,11 @@ impl PlayerInternal {
         uri: Option<&'a Uri>,
     ) -> impl Future<Output = Result<(String, Vec<Arc<Item>>)>> + 'a {
         let spotify = self.spotify.clone();
+        let market = self.market.clone();
 
         async move {
+            let market = super::market(market.load().await);
+
             let (what, items) = match uri {
                 Some(uri) => {
                     let id = match uri {
@@ -523,7 +655,7 @@ impl PlayerInternal {
                         }
                     };
 
-                    let (name, items) = download_spotify_playlist(&spotify, *id).await?;
+                    let (name, items) = download_spotify_playlist(&spotify, *id, &market).await?;
                     let items = convert(items).await?;
                     (Some(name), items)
                 }
@@ -568,6 +700,7 @@ impl PlayerInternal {
                         track: Track::Spotify { track },
                         user: None,
                         duration,
+                        paid: None,
                     };
 
                     if item.is_playable() {
@@ -582,11 +715,9 @@ impl PlayerInternal {
             async fn download_spotify_playlist(
                 spotify: &api::Spotify,
                 playlist: SpotifyId,
+                market: &str,
             ) -> Result<(String, impl stream::Stream<Item = Result<FullTrack>>)> {
-                let streamer = spotify.me().await?;
-                let playlist = spotify
-                    .playlist(playlist, streamer.country.as_deref())
-                    .await?;
+                let playlist = spotify.playlist(playlist, Some(market)).await?;
 
                 let name = playlist.name.to_string();
                 let mut playlist_tracks = spotify.page_as_stream(playlist.tracks);
@@ -670,8 +801,10 @@ impl PlayerInternal {
 
                 // TODO: how do we deal with playback mode on a device transfer?
                 match track_id {
-                    TrackId::Spotify(id) => {
-                        self.connect_player.play(Some(id), Some(elapsed)).await;
+                    track_id @ TrackId::Spotify(..) | track_id @ TrackId::SpotifyEpisode(..) => {
+                        self.connect_player
+                            .play(Some(&track_id), Some(elapsed))
+                            .await;
                         self.switch_current_player(PlayerKind::Spotify).await?;
                         self.injector.update(State::Playing).await;
                     }
@@ -737,19 +870,32 @@ impl PlayerInternal {
         track_id: TrackId,
         bypass_constraints: bool,
         max_duration: Option<utils::Duration>,
+        paid: Option<Payment>,
     ) -> Result<(Option<usize>, Arc<Item>), AddTrackError> {
-        // TODO: cache this value
-        let streamer: PrivateUser = self.spotify.me().await.map_err(AddTrackError::Error)?;
-        let market = streamer.country.as_deref();
+        let market = super::market(self.market.load().await);
 
         match self.playback_mode {
             PlaybackMode::Default => {
-                self.default_add_track(user, track_id, bypass_constraints, max_duration, market)
-                    .await
+                self.default_add_track(
+                    user,
+                    track_id,
+                    bypass_constraints,
+                    max_duration,
+                    market,
+                    paid,
+                )
+                .await
             }
             PlaybackMode::Queue => {
-                self.queue_add_track(user, track_id, bypass_constraints, max_duration, market)
-                    .await
+                self.queue_add_track(
+                    user,
+                    track_id,
+                    bypass_constraints,
+                    max_duration,
+                    market,
+                    paid,
+                )
+                .await
             }
         }
     }
@@ -761,7 +907,8 @@ impl PlayerInternal {
         track_id: TrackId,
         bypass_constraints: bool,
         max_duration: Option<utils::Duration>,
-        market: Option<&str>,
+        market: String,
+        paid: Option<Payment>,
     ) -> Result<(Option<usize>, Arc<Item>), AddTrackError> {
         let (user_count, len) = {
             if !bypass_constraints {
@@ -789,14 +936,8 @@ impl PlayerInternal {
                         let duration_since =
                             Utc::now().signed_duration_since(added_at).to_std().ok();
 
-                        let duplicate_by = match last.user {
-                            Some(who) if who == user => DuplicateBy::Requester,
-                            Some(who) => DuplicateBy::Other(who),
-                            None => DuplicateBy::Unknown,
-                        };
-
                         return Err(AddTrackError::Duplicate {
-                            duplicate_by,
+                            duplicate_by: DuplicateBy::new(last.user, user),
                             duration_since,
                             duplicate_duration: duplicate_duration.as_std(),
                         });
@@ -804,14 +945,18 @@ impl PlayerInternal {
                 }
             }
 
+            let reject_duplicates = self.reject_duplicates.load().await;
             let mut user_count = 0;
             let mut len = 0;
 
             for (index, i) in self.mixer.list().enumerate() {
                 len += 1;
 
-                if i.track_id == track_id {
-                    return Err(AddTrackError::QueueContainsTrack(index));
+                if i.track_id == track_id && reject_duplicates {
+                    return Err(AddTrackError::QueueContainsTrack {
+                        pos: index,
+                        duplicate_by: DuplicateBy::new(i.user.clone(), user),
+                    });
                 }
 
                 if i.user.as_ref().map(|u| *u == user).unwrap_or_default() {
@@ -826,7 +971,10 @@ impl PlayerInternal {
 
         // NB: moderator is allowed to add more songs.
         if !bypass_constraints && user_count >= max_songs_per_user {
-            return Err(AddTrackError::TooManyUserTracks(max_songs_per_user));
+            return Err(AddTrackError::TooManyUserTracks {
+                queued: user_count,
+                limit: max_songs_per_user,
+            });
         }
 
         let item = convert_item(
@@ -835,7 +983,7 @@ impl PlayerInternal {
             Some(user),
             &track_id,
             None,
-            market,
+            Some(&market),
         )
         .await
         .map_err(AddTrackError::Error)?;
@@ -846,7 +994,7 @@ impl PlayerInternal {
         };
 
         if !item.is_playable() {
-            return Err(AddTrackError::NotPlayable);
+            return Err(AddTrackError::NotPlayable(market));
         }
 
         if let Some(max_duration) = max_duration {
@@ -857,6 +1005,21 @@ impl PlayerInternal {
             }
         }
 
+        // NB: moderator is allowed to violate max queue duration.
+        if !bypass_constraints {
+            if let Some(max_queue_duration) = self.max_queue_duration.load().await {
+                let max_queue_duration = max_queue_duration.as_std();
+                let queued = self.mixer.duration();
+
+                if queued + item.duration > max_queue_duration {
+                    return Err(AddTrackError::QueueTooLong {
+                        remaining: max_queue_duration.checked_sub(queued).unwrap_or_default(),
+                    });
+                }
+            }
+        }
+
+        item.paid = paid;
         let item = Arc::new(item);
 
         self.mixer
@@ -878,7 +1041,8 @@ impl PlayerInternal {
         track_id: TrackId,
         _bypass_constraints: bool,
         _max_duration: Option<utils::Duration>,
-        market: Option<&str>,
+        market: String,
+        paid: Option<Payment>,
     ) -> Result<(Option<usize>, Arc<Item>), AddTrackError> {
         let item = convert_item(
             &*self.spotify,
@@ -886,20 +1050,26 @@ impl PlayerInternal {
             Some(user),
             &track_id,
             None,
-            market,
+            Some(&market),
         )
         .await
         .map_err(AddTrackError::Error)?;
 
-        let item = match item {
+        let mut item = match item {
             Some(item) => item,
             None => return Err(AddTrackError::MissingAuth),
         };
 
+        if !item.is_playable() {
+            return Err(AddTrackError::NotPlayable(market));
+        }
+
+        item.paid = paid;
+
         match track_id {
-            TrackId::Spotify(id) => {
+            TrackId::Spotify(..) | TrackId::SpotifyEpisode(..) => {
                 self.connect_player
-                    .queue(id)
+                    .queue(&track_id)
                     .await
                     .map_err(|e| AddTrackError::Error(e.into()))?;
             }
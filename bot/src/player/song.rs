@@ -84,6 +84,7 @@ impl Song {
             track: Track::Spotify { track },
             user: None,
             duration,
+            paid: None,
         });
 
         let mut song = Song::new(item, elapsed);
@@ -162,7 +163,7 @@ impl Song {
     /// Get the player kind for the current song.
     pub fn player(&self) -> PlayerKind {
         match self.item.track_id {
-            TrackId::Spotify(..) => PlayerKind::Spotify,
+            TrackId::Spotify(..) | TrackId::SpotifyEpisode(..) => PlayerKind::Spotify,
             TrackId::YouTube(..) => PlayerKind::YouTube,
         }
     }
@@ -8,6 +8,8 @@ use anyhow::Result;
 pub enum Track {
     #[serde(rename = "spotify")]
     Spotify { track: api::spotify::FullTrack },
+    #[serde(rename = "spotify-episode")]
+    SpotifyEpisode { episode: api::spotify::FullEpisode },
     #[serde(rename = "youtube")]
     YouTube { video: api::youtube::Video },
 }
@@ -17,6 +19,7 @@ impl Track {
     pub fn artists(&self) -> Option<String> {
         match *self {
             Self::Spotify { ref track } => utils::human_artists(&track.artists),
+            Self::SpotifyEpisode { ref episode } => Some(episode.show.name.clone()),
             Self::YouTube { ref video } => {
                 video.snippet.as_ref().and_then(|s| s.channel_title.clone())
             }
@@ -27,6 +30,7 @@ impl Track {
     pub fn name(&self) -> String {
         match *self {
             Self::Spotify { ref track } => track.name.to_string(),
+            Self::SpotifyEpisode { ref episode } => episode.name.to_string(),
             Self::YouTube { ref video } => video
                 .snippet
                 .as_ref()
@@ -41,6 +45,7 @@ impl Track {
     pub fn to_json(&self) -> Result<serde_json::Value> {
         let json = match *self {
             Self::Spotify { ref track } => serde_json::to_value(&track)?,
+            Self::SpotifyEpisode { ref episode } => serde_json::to_value(episode)?,
             Self::YouTube { ref video } => serde_json::to_value(&video)?,
         };
 
@@ -26,6 +26,10 @@ impl Scripts {
         None
     }
 
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::empty()
+    }
+
     pub(crate) fn reload(&mut self, path: &Path) -> Result<()> {
         log::trace!("reload: {}", path.display());
         Ok(())
@@ -169,6 +169,11 @@ impl Scripts {
         })
     }
 
+    /// Iterate over the names of all commands provided by scripts.
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+
     /// Same as `load`, except that it removes the old handles before loading
     /// them again.
     pub(crate) fn reload(&mut self, path: &Path) -> Result<()> {
@@ -176,6 +176,16 @@ impl From<tokio::task::JoinError> for Error {
     }
 }
 
+impl db::TaskError for Error {
+    fn is_transient(&self) -> bool {
+        match self {
+            Error::Diesel(e) => db::is_transient(e),
+            Error::Error(e) => e.is_transient(),
+            _ => false,
+        }
+    }
+}
+
 /// Update events for a given key.
 #[derive(Debug, Clone)]
 pub enum Event<T> {
@@ -264,6 +274,22 @@ where
     /// A human-readable title for the setting.
     #[serde(default)]
     pub title: Option<String>,
+    /// If the setting is safe to show to operators with `!config show`.
+    ///
+    /// Feature toggles can always be shown.
+    #[serde(default)]
+    pub inspect: bool,
+}
+
+impl<S> SchemaType<S>
+where
+    S: Scope,
+{
+    /// Test if the setting can be shown to operators, which is never the case
+    /// for secrets.
+    pub fn is_inspectable(&self) -> bool {
+        !self.secret && (self.feature || self.inspect)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -590,6 +616,23 @@ where
         Ok(Some(SettingRef { schema, key, value }))
     }
 
+    /// Get the effective value of a setting which can be shown to operators.
+    ///
+    /// Returns `None` if there is no such setting, or if it can't be shown.
+    pub async fn inspect(&self, key: &str) -> Result<Option<Setting<S>>, Error> {
+        let setting = match self.setting::<serde_json::Value>(key).await? {
+            Some(setting) => setting,
+            None => return Ok(None),
+        };
+
+        // NB: security issue if this was shown.
+        if setting.key().starts_with("secrets/") || !setting.schema().is_inspectable() {
+            return Ok(None);
+        }
+
+        Ok(Some(setting.to_owned()))
+    }
+
     /// Test if the given key exists in the database.
     pub async fn has(&self, key: &str) -> Result<bool, Error> {
         let key = self.key(key);
@@ -648,30 +691,32 @@ where
             .inner
             .db
             .asyncify(move |c| {
-                let filter = dsl::settings.filter(dsl::key.eq(&key));
-
-                let b = filter
-                    .clone()
-                    .select((dsl::key, dsl::value))
-                    .first::<(String, String)>(c)
-                    .optional()?;
-
-                let json = serde_json::to_string(&value)?;
-
-                match b {
-                    None => {
-                        diesel::insert_into(dsl::settings)
-                            .values((dsl::key.eq(&key), dsl::value.eq(json)))
-                            .execute(c)?;
-                    }
-                    Some(_) => {
-                        diesel::update(filter)
-                            .set((dsl::key.eq(&key), dsl::value.eq(json)))
-                            .execute(c)?;
+                c.transaction(|| {
+                    let filter = dsl::settings.filter(dsl::key.eq(&key));
+
+                    let b = filter
+                        .clone()
+                        .select((dsl::key, dsl::value))
+                        .first::<(String, String)>(c)
+                        .optional()?;
+
+                    let json = serde_json::to_string(&value)?;
+
+                    match b {
+                        None => {
+                            diesel::insert_into(dsl::settings)
+                                .values((dsl::key.eq(&key), dsl::value.eq(json)))
+                                .execute(c)?;
+                        }
+                        Some(_) => {
+                            diesel::update(filter)
+                                .set((dsl::key.eq(&key), dsl::value.eq(json)))
+                                .execute(c)?;
+                        }
                     }
-                }
 
-                Ok::<_, Error>((key, value))
+                    Ok::<_, Error>((key.clone(), value.clone()))
+                })
             })
             .await?;
 
@@ -736,7 +781,7 @@ where
         self.inner
             .db
             .asyncify(move |c| {
-                let count = diesel::delete(dsl::settings.filter(dsl::key.eq(key))).execute(c)?;
+                let count = diesel::delete(dsl::settings.filter(dsl::key.eq(&key))).execute(c)?;
                 Ok(count == 1)
             })
             .await
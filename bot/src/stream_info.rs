@@ -1,12 +1,16 @@
 use crate::api;
 use crate::api::twitch;
 use crate::prelude::*;
+use crate::utils::Duration;
 use anyhow::{anyhow, Result};
 use parking_lot::RwLock;
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time;
 
+/// The scope the streamer's token needs to list subscribers.
+const SUBSCRIPTIONS_SCOPE: &str = "channel:read:subscriptions";
+
 #[derive(Debug, Default)]
 pub struct Data {
     pub stream: Option<twitch::new::Stream>,
@@ -14,6 +18,13 @@ pub struct Data {
     pub game: Option<String>,
     pub subs: Vec<twitch::new::Subscription>,
     pub subs_set: HashSet<String>,
+    /// The number of followers, if known.
+    pub followers: Option<u64>,
+    /// The number of subscribers, if known.
+    ///
+    /// This is unknown if the streamer's token doesn't permit listing
+    /// subscribers.
+    pub subscribers: Option<u64>,
 }
 
 /// Notify on changes in stream state.
@@ -22,6 +33,28 @@ pub enum StreamState {
     Stopped,
 }
 
+/// Debounces the stream going offline, so that a stream which briefly
+/// reports as offline isn't treated as having ended.
+#[derive(Debug, Default)]
+struct OfflineDebounce {
+    /// When the stream was first seen as offline.
+    since: Option<time::Instant>,
+}
+
+impl OfflineDebounce {
+    /// Test if the stream which was seen as offline at `now` has been offline
+    /// for at least `period`.
+    fn is_offline(&mut self, now: time::Instant, period: time::Duration) -> bool {
+        let since = *self.since.get_or_insert(now);
+        now.saturating_duration_since(since) >= period
+    }
+
+    /// Reset the debounce, since the stream has been seen as online.
+    fn reset(&mut self) {
+        self.since = None;
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StreamInfo {
     pub user: Arc<twitch::v5::User>,
@@ -35,11 +68,30 @@ impl StreamInfo {
     }
 
     /// Refresh the known list of subscribers.
+    ///
+    /// Returns `false` without refreshing if the streamer's token doesn't
+    /// permit listing subscribers.
     pub async fn refresh_subs(
         &self,
         twitch: &api::Twitch,
         streamer: &twitch::v5::User,
-    ) -> Result<()> {
+    ) -> Result<bool> {
+        let has_scope = twitch
+            .token
+            .read()
+            .await?
+            .scopes
+            .iter()
+            .any(|s| s == SUBSCRIPTIONS_SCOPE);
+
+        if !has_scope {
+            let mut info = self.data.write();
+            info.subs.clear();
+            info.subs_set.clear();
+            info.subscribers = None;
+            return Ok(false);
+        }
+
         let subs = {
             let mut out = Vec::new();
 
@@ -59,7 +111,18 @@ impl StreamInfo {
             .iter()
             .map(|s| s.user_name.to_lowercase())
             .collect();
+        info.subscribers = Some(info.subs.len() as u64);
+        Ok(true)
+    }
 
+    /// Refresh the number of followers.
+    pub async fn refresh_followers(
+        &self,
+        twitch: &api::Twitch,
+        streamer: &twitch::v5::User,
+    ) -> Result<()> {
+        let followers = twitch.new_followers_total(&streamer.id).await?;
+        self.data.write().followers = Some(followers);
         Ok(())
     }
 
@@ -84,11 +147,16 @@ impl StreamInfo {
     }
 
     /// Refresh the stream info.
-    pub async fn refresh_stream<'a>(
+    ///
+    /// A stream going offline is only reported once it has been offline for
+    /// `debounce_period`. Until then the last known stream is kept.
+    async fn refresh_stream<'a>(
         &'a self,
         twitch: &'a api::Twitch,
         streamer: &'a twitch::v5::User,
         stream_state_tx: &'a mut mpsc::Sender<StreamState>,
+        debounce: &'a mut OfflineDebounce,
+        debounce_period: time::Duration,
     ) -> Result<()> {
         let stream = match twitch.new_stream_by_id(&streamer.id).await {
             Ok(stream) => stream,
@@ -100,6 +168,15 @@ impl StreamInfo {
 
         let stream_is_some = self.data.read().stream.is_some();
 
+        if stream_is_some && stream.is_none() {
+            if !debounce.is_offline(time::Instant::now(), debounce_period) {
+                log::trace!("stream reported as offline, waiting before treating it as ended");
+                return Ok(());
+            }
+        } else {
+            debounce.reset();
+        }
+
         let update = match (stream_is_some, stream.is_some()) {
             (true, false) => Some(StreamState::Stopped),
             (false, true) => Some(StreamState::Started),
@@ -120,14 +197,24 @@ impl StreamInfo {
 }
 
 /// Set up a stream information loop.
-pub fn setup(
+pub async fn setup(
     streamer: Arc<twitch::v5::User>,
     twitch: api::Twitch,
-) -> (
+    settings: &crate::Settings,
+) -> Result<(
     StreamInfo,
     mpsc::Receiver<StreamState>,
     impl Future<Output = Result<()>>,
-) {
+)> {
+    let offline_debounce = settings
+        .var("stream-info/offline-debounce", Duration::seconds(2 * 60))
+        .await?;
+
+    let (mut counts_interval_stream, counts_interval) = settings
+        .stream("stream-info/counts-interval")
+        .or_with(Duration::seconds(60 * 10))
+        .await?;
+
     let (mut stream_state_tx, stream_state_rx) = mpsc::channel(64);
 
     let stream_info = StreamInfo {
@@ -136,23 +223,53 @@ pub fn setup(
     };
 
     let mut stream_interval = tokio::time::interval(time::Duration::from_secs(30));
-    let mut subs_interval = tokio::time::interval(time::Duration::from_secs(60 * 10));
+    let mut counts_interval = tokio::time::interval(counts_interval.as_std());
 
     let future_info = stream_info.clone();
 
     let future = async move {
         twitch.token.wait_until_ready().await?;
 
+        let mut debounce = OfflineDebounce::default();
+        // NB: only warn once about the missing scope, since it's polled.
+        let mut warned_missing_scope = false;
+
         loop {
             tokio::select! {
-                _ = subs_interval.tick() => {
-                    if let Err(e) = future_info.refresh_subs(&twitch, &*streamer).await {
-                        log_error!(e, "failed to refresh subscriptions");
+                update = counts_interval_stream.recv() => {
+                    counts_interval = tokio::time::interval(update.as_std());
+                }
+                _ = counts_interval.tick() => {
+                    match future_info.refresh_subs(&twitch, &streamer).await {
+                        Ok(true) => warned_missing_scope = false,
+                        Ok(false) => {
+                            if !warned_missing_scope {
+                                log::warn!(
+                                    "Streamer token is missing the `{}` scope, subscribers won't be available. Re-authenticate the streamer account to fix this.",
+                                    SUBSCRIPTIONS_SCOPE
+                                );
+                                warned_missing_scope = true;
+                            }
+                        }
+                        Err(e) => {
+                            log_error!(e, "failed to refresh subscriptions");
+                        }
+                    }
+
+                    if let Err(e) = future_info.refresh_followers(&twitch, &streamer).await {
+                        log_error!(e, "failed to refresh followers");
                     }
                 }
                 _ = stream_interval.tick() => {
-                    let stream = future_info
-                        .refresh_stream(&twitch, &*streamer, &mut stream_state_tx);
+                    let debounce_period = offline_debounce.load().await.as_std();
+
+                    let stream = future_info.refresh_stream(
+                        &twitch,
+                        &streamer,
+                        &mut stream_state_tx,
+                        &mut debounce,
+                        debounce_period,
+                    );
 
                     let channel = future_info
                         .refresh_channel(&twitch, &*streamer);
@@ -163,5 +280,31 @@ pub fn setup(
         }
     };
 
-    (stream_info, stream_state_rx, future)
+    Ok((stream_info, stream_state_rx, future))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OfflineDebounce;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_offline_debounce() {
+        let period = Duration::from_secs(120);
+        let start = Instant::now();
+
+        let mut debounce = OfflineDebounce::default();
+        assert!(!debounce.is_offline(start, period));
+        assert!(!debounce.is_offline(start + Duration::from_secs(60), period));
+
+        // stream came back, so we start over.
+        debounce.reset();
+        assert!(!debounce.is_offline(start + Duration::from_secs(90), period));
+        assert!(!debounce.is_offline(start + Duration::from_secs(200), period));
+        assert!(debounce.is_offline(start + Duration::from_secs(210), period));
+
+        // no debounce configured.
+        let mut debounce = OfflineDebounce::default();
+        assert!(debounce.is_offline(start, Duration::default()));
+    }
 }
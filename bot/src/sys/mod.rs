@@ -8,6 +8,7 @@ mod imp;
 #[cfg(target_os = "windows")]
 #[path = "windows/mod.rs"]
 mod imp;
+mod notifier;
 
 #[derive(Debug, Clone, Copy)]
 pub enum NotificationIcon {
@@ -106,3 +107,4 @@ impl Notification {
 }
 
 pub use self::imp::{setup, System};
+pub use self::notifier::{Category, Notifier};
@@ -0,0 +1,57 @@
+use crate::settings;
+use crate::sys::{Notification, NotificationIcon, System};
+use anyhow::Result;
+
+/// A category of desktop notifications, which can be muted separately.
+#[derive(Debug, Clone, Copy)]
+pub enum Category {
+    /// Someone subscribed to the channel.
+    Subscription,
+    /// The bot lost its connection to chat.
+    IrcDisconnected,
+    /// A connection to a service like Spotify expired and needs to be set up
+    /// again.
+    AuthExpired,
+}
+
+/// Sends desktop notifications for categories which are enabled.
+#[derive(Clone)]
+pub struct Notifier {
+    system: System,
+    subscription: settings::Var<bool>,
+    irc_disconnected: settings::Var<bool>,
+    auth_expired: settings::Var<bool>,
+}
+
+impl Notifier {
+    /// Construct a new notifier, where `settings` is the scope the toggles of
+    /// each category are read from.
+    pub async fn new(settings: &crate::Settings, system: System) -> Result<Self> {
+        Ok(Self {
+            system,
+            subscription: settings.var("subscription", true).await?,
+            irc_disconnected: settings.var("irc-disconnected", true).await?,
+            auth_expired: settings.var("auth-expired", true).await?,
+        })
+    }
+
+    /// Show a desktop notification, unless its category is muted.
+    pub async fn notify<T, B>(&self, category: Category, title: T, body: B, level: NotificationIcon)
+    where
+        T: AsRef<str>,
+        B: AsRef<str>,
+    {
+        let enabled = match category {
+            Category::Subscription => &self.subscription,
+            Category::IrcDisconnected => &self.irc_disconnected,
+            Category::AuthExpired => &self.auth_expired,
+        };
+
+        if !enabled.load().await {
+            return;
+        }
+
+        let notification = Notification::new(body).title(title).icon(level);
+        self.system.notification(notification);
+    }
+}
@@ -1,16 +1,50 @@
-use std::collections::HashSet;
+//! Templates used for responses in chat, based on handlebars.
+//!
+//! Besides the variables each template is rendered with, templates can use
+//! the following helpers:
+//!
+//! * `{{upper value}}` - `value` in uppercase.
+//! * `{{lower value}}` - `value` in lowercase.
+//! * `{{random a b c}}` - One of its arguments, picked at random.
+//! * `{{number value}}` - The number `value` with thousands separators, like
+//!   `1,234`.
+//! * `{{ordinal value}}` - The number `value` as an ordinal, like `2nd`.
+//!
+//! Additional helpers can be added with [register_helper].
+
+use crate::currency;
+use handlebars::template::{HelperTemplate, Parameter, TemplateElement};
+use handlebars::{
+    Context, Handlebars, Helper, HelperResult, JsonRender as _, Output, RenderContext, RenderError,
+};
+use std::collections::{HashSet, VecDeque};
 use std::fmt;
 use std::io;
 use std::string;
 
 lazy_static::lazy_static! {
-    static ref REGISTRY: handlebars::Handlebars<'static> = {
-        let mut reg = handlebars::Handlebars::new();
+    static ref REGISTRY: parking_lot::RwLock<Handlebars<'static>> = {
+        let mut reg = Handlebars::new();
         reg.register_escape_fn(|s| s.to_string());
-        reg
+        reg.register_helper("helperMissing", Box::new(helper_missing));
+        reg.register_helper("upper", Box::new(upper));
+        reg.register_helper("lower", Box::new(lower));
+        reg.register_helper("random", Box::new(random));
+        reg.register_helper("number", Box::new(number));
+        reg.register_helper("ordinal", Box::new(ordinal));
+        parking_lot::RwLock::new(reg)
     };
 }
 
+/// Register a helper which can be used by all templates, replacing any
+/// existing helper with the same name.
+pub fn register_helper<H>(name: &str, helper: H)
+where
+    H: handlebars::HelperDef + Send + Sync + 'static,
+{
+    REGISTRY.write().register_helper(name, Box::new(helper));
+}
+
 #[derive(Debug, Clone)]
 pub struct Template {
     source: String,
@@ -47,7 +81,8 @@ impl<'de> serde::Deserialize<'de> for Template {
             TemplateData::String(s) => s,
         };
 
-        let template = handlebars::Template::compile(&s).map_err(serde::de::Error::custom)?;
+        let template =
+            handlebars::Template::compile2(&s, true).map_err(serde::de::Error::custom)?;
 
         return Ok(Template {
             source: s,
@@ -78,7 +113,7 @@ impl Template {
     pub fn compile(s: &str) -> Result<Template, anyhow::Error> {
         Ok(Template {
             source: s.to_string(),
-            template: handlebars::Template::compile(s)?,
+            template: handlebars::Template::compile2(s, true)?,
         })
     }
 
@@ -99,66 +134,67 @@ impl Template {
         output.into_string().map_err(Into::into)
     }
 
-    /// Test if the template has the given variable.
+    /// All variables used by the template, including the ones only used in
+    /// blocks like `{{#if}}`.
     pub fn vars(&self) -> HashSet<String> {
-        use handlebars::template::{HelperTemplate, Parameter, TemplateElement};
-        use std::collections::VecDeque;
-
-        let mut out = HashSet::new();
-
-        for e in &self.template.elements {
-            collect_element(&mut out, e);
-        }
+        self.collect_vars(true)
+    }
 
-        return out;
+    /// Variables which are always used by the template, which are the ones
+    /// used outside of blocks like `{{#if}}`.
+    pub fn required_vars(&self) -> HashSet<String> {
+        self.collect_vars(false)
+    }
 
-        /// Helper to collect all expressions without recursing.
-        fn collect_element<'e>(out: &mut HashSet<String>, e: &'e TemplateElement) {
-            let mut queue = VecDeque::new();
+    /// Validate that the template only uses variables for which `is_known`
+    /// returns `true`.
+    ///
+    /// Paths like `{{args.0}}` are checked by their first segment, and
+    /// variables like `{{this}}` or `{{@index}}` which are provided by blocks
+    /// are always allowed.
+    pub fn validate(&self, is_known: impl Fn(&str) -> bool) -> Result<(), anyhow::Error> {
+        for (index, e) in self.template.elements.iter().enumerate() {
+            let mut vars = HashSet::new();
+            collect_element(&mut vars, e, true);
+
+            let mut vars = vars.into_iter().collect::<Vec<_>>();
+            vars.sort();
+
+            for var in vars {
+                let root = var.trim_start_matches("../");
+                let root = root.split(|c| c == '.' || c == '[').next().unwrap_or(root);
+
+                if root == "this" || root.starts_with('@') || is_known(root) {
+                    continue;
+                }
 
-            queue.push_back(e);
+                let mapping = self.template.mapping.as_ref().and_then(|m| m.get(index));
 
-            while let Some(e) = queue.pop_front() {
-                match e {
-                    TemplateElement::Expression(helper) => {
-                        collect_helper(out, &mut queue, &*helper);
-                    }
-                    TemplateElement::HTMLExpression(param) => {
-                        collect_parameter(out, &mut queue, &*param);
-                    }
-                    _ => (),
+                match mapping {
+                    Some(handlebars::template::TemplateMapping(line, column)) => anyhow::bail!(
+                        "unknown variable `{}` at line {}, column {}",
+                        var,
+                        line,
+                        column
+                    ),
+                    None => anyhow::bail!("unknown variable `{}`", var),
                 }
             }
         }
 
-        fn collect_parameter<'e>(
-            out: &mut HashSet<String>,
-            queue: &mut VecDeque<&'e TemplateElement>,
-            p: &'e Parameter,
-        ) {
-            match p {
-                Parameter::Subexpression(ref e) => {
-                    queue.push_back(&*e.element);
-                }
-                p => {
-                    if let Some(name) = p.as_name() {
-                        out.insert(name.to_string());
-                    }
-                }
-            }
-        }
+        Ok(())
+    }
 
-        fn collect_helper<'e>(
-            out: &mut HashSet<String>,
-            queue: &mut VecDeque<&'e TemplateElement>,
-            e: &'e HelperTemplate,
-        ) {
-            collect_parameter(out, queue, &e.name);
+    /// Collect variables used in the template, optionally including the ones
+    /// used in blocks.
+    fn collect_vars(&self, blocks: bool) -> HashSet<String> {
+        let mut out = HashSet::new();
 
-            for p in &e.params {
-                collect_parameter(out, queue, p);
-            }
+        for e in &self.template.elements {
+            collect_element(&mut out, e, blocks);
         }
+
+        out
     }
 
     /// Access the source of the template.
@@ -177,11 +213,205 @@ impl Template {
         let ctx = handlebars::Context::wraps(data)?;
         let mut render_context = handlebars::RenderContext::new(None);
         self.template
-            .render(&*REGISTRY, &ctx, &mut render_context, output)
+            .render(&REGISTRY.read(), &ctx, &mut render_context, output)
             .map_err(Into::into)
     }
 }
 
+/// Helper to collect all expressions without recursing.
+fn collect_element(out: &mut HashSet<String>, e: &TemplateElement, blocks: bool) {
+    let mut queue = VecDeque::new();
+
+    queue.push_back(e);
+
+    while let Some(e) = queue.pop_front() {
+        match e {
+            TemplateElement::Expression(helper) => {
+                collect_helper(out, &mut queue, helper);
+            }
+            TemplateElement::HTMLExpression(param) => {
+                collect_parameter(out, &mut queue, param);
+            }
+            TemplateElement::HelperBlock(helper) if blocks => {
+                for p in helper.params.iter().chain(helper.hash.values()) {
+                    collect_parameter(out, &mut queue, p);
+                }
+
+                for t in helper.template.iter().chain(helper.inverse.iter()) {
+                    queue.extend(&t.elements);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn collect_parameter<'e>(
+    out: &mut HashSet<String>,
+    queue: &mut VecDeque<&'e TemplateElement>,
+    p: &'e Parameter,
+) {
+    match p {
+        Parameter::Subexpression(ref e) => {
+            queue.push_back(&*e.element);
+        }
+        p => {
+            if let Some(name) = p.as_name() {
+                out.insert(name.to_string());
+            }
+        }
+    }
+}
+
+fn collect_helper<'e>(
+    out: &mut HashSet<String>,
+    queue: &mut VecDeque<&'e TemplateElement>,
+    e: &'e HelperTemplate,
+) {
+    // NB: an expression with arguments is a call to a helper, so its
+    // name isn't a variable.
+    if e.params.is_empty() && e.hash.is_empty() {
+        collect_parameter(out, queue, &e.name);
+    }
+
+    for p in &e.params {
+        collect_parameter(out, queue, p);
+    }
+}
+
+/// Called for expressions which don't match a variable or a helper.
+///
+/// Missing variables render as nothing, but calling a helper which doesn't
+/// exist is an error.
+fn helper_missing(
+    h: &Helper<'_, '_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    _: &mut dyn Output,
+) -> HelperResult {
+    if h.params().is_empty() && h.hash().is_empty() {
+        return Ok(());
+    }
+
+    Err(RenderError::new(format!(
+        "no template helper named `{}`",
+        h.name()
+    )))
+}
+
+/// Get the argument at the given index of a helper.
+fn param<'a>(h: &'a Helper<'_, '_>, index: usize) -> Result<&'a serde_json::Value, RenderError> {
+    match h.param(index) {
+        Some(param) => Ok(param.value()),
+        None => Err(RenderError::new(format!(
+            "template helper `{}` is missing argument #{}",
+            h.name(),
+            index + 1
+        ))),
+    }
+}
+
+/// Get the argument at the given index of a helper as an integer.
+fn integer_param(h: &Helper<'_, '_>, index: usize) -> Result<i64, RenderError> {
+    let value = param(h, index)?;
+
+    let number = match value {
+        serde_json::Value::Number(n) => n.as_i64(),
+        serde_json::Value::String(s) => str::parse::<i64>(s.trim()).ok(),
+        _ => None,
+    };
+
+    number.ok_or_else(|| {
+        RenderError::new(format!(
+            "template helper `{}` expected a number, but got `{}`",
+            h.name(),
+            value.render()
+        ))
+    })
+}
+
+fn upper(
+    h: &Helper<'_, '_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(&param(h, 0)?.render().to_uppercase())?;
+    Ok(())
+}
+
+fn lower(
+    h: &Helper<'_, '_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(&param(h, 0)?.render().to_lowercase())?;
+    Ok(())
+}
+
+fn random(
+    h: &Helper<'_, '_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    use rand::Rng as _;
+
+    let params = h.params();
+
+    if params.is_empty() {
+        return Err(RenderError::new(
+            "template helper `random` needs something to pick from",
+        ));
+    }
+
+    let index = rand::thread_rng().gen_range(0..params.len());
+    out.write(&params[index].value().render())?;
+    Ok(())
+}
+
+fn number(
+    h: &Helper<'_, '_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let format = currency::Format {
+        separators: true,
+        ..currency::Format::default()
+    };
+
+    out.write(&format.number(integer_param(h, 0)?))?;
+    Ok(())
+}
+
+fn ordinal(
+    h: &Helper<'_, '_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let n = integer_param(h, 0)?;
+
+    let suffix = match (n.unsigned_abs() % 10, n.unsigned_abs() % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+
+    out.write(&format!("{}{}", n, suffix))?;
+    Ok(())
+}
+
 impl std::str::FromStr for Template {
     type Err = anyhow::Error;
 
@@ -262,4 +492,178 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    pub fn test_template_vars_with_helpers() -> Result<(), Error> {
+        assert_eq!(
+            vec!["name", "count"]
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect::<HashSet<String>>(),
+            Template::compile("{{upper name}} is {{ordinal count}}")?.vars()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_conditionals() -> Result<(), Error> {
+        let template = Template::compile(
+            "{{#if count}}{{count}} {{#if plural}}times{{else}}time{{/if}}{{else}}never{{/if}}",
+        )?;
+
+        assert_eq!(
+            template.render_to_string(Vars {
+                count: 0,
+                plural: false,
+                name: ""
+            })?,
+            "never"
+        );
+        assert_eq!(
+            template.render_to_string(Vars {
+                count: 1,
+                plural: false,
+                name: ""
+            })?,
+            "1 time"
+        );
+        assert_eq!(
+            template.render_to_string(Vars {
+                count: 2,
+                plural: true,
+                name: ""
+            })?,
+            "2 times"
+        );
+
+        let template =
+            Template::compile("{{#if name}}hi {{name}}{{else}}hi{{/if}}{{#if missing}}!{{/if}}")?;
+        assert_eq!(
+            template.render_to_string(Vars {
+                count: 0,
+                plural: false,
+                name: ""
+            })?,
+            "hi"
+        );
+        assert_eq!(
+            template.render_to_string(Vars {
+                count: 0,
+                plural: false,
+                name: "foo"
+            })?,
+            "hi foo"
+        );
+
+        assert!(Template::compile("{{#if count}}unclosed").is_err());
+        assert!(Template::compile("{{#if count}}mismatched{{/each}}").is_err());
+        return Ok(());
+
+        #[derive(serde::Serialize)]
+        struct Vars {
+            count: u32,
+            plural: bool,
+            name: &'static str,
+        }
+    }
+
+    #[test]
+    pub fn test_template_vars_in_blocks() -> Result<(), Error> {
+        let template = Template::compile(
+            "{{#if arg1}}{{sender}} hugs {{arg1}}{{else}}{{rest}}{{/if}} {{count}}",
+        )?;
+
+        assert_eq!(
+            vec!["arg1", "sender", "rest", "count"]
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect::<HashSet<String>>(),
+            template.vars()
+        );
+
+        assert_eq!(
+            vec!["count"]
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect::<HashSet<String>>(),
+            template.required_vars()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_validate() -> Result<(), Error> {
+        let is_known = |var: &str| matches!(var, "name" | "args" | "1");
+
+        Template::compile("{{name}} {{upper name}} {{args.0}} {{1}}")?.validate(is_known)?;
+        Template::compile("{{#each args}}{{this}} {{@index}}{{/each}}")?.validate(is_known)?;
+
+        let e = Template::compile("hi {{name}}\n{{#if name}}{{nmae}}{{/if}}")?
+            .validate(is_known)
+            .unwrap_err();
+        assert_eq!(e.to_string(), "unknown variable `nmae` at line 2, column 1");
+
+        assert!(Template::compile("{{random nmae}}")?
+            .validate(is_known)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_helpers() -> Result<(), Error> {
+        let render = |s: &str| {
+            Template::compile(s)?.render_to_string(Vars {
+                name: "Foo",
+                count: 1234,
+            })
+        };
+
+        assert_eq!(render("{{upper name}} {{lower name}}")?, "FOO foo");
+        assert_eq!(render("{{number count}}")?, "1,234");
+        assert_eq!(
+            render("{{ordinal 1}} {{ordinal 2}} {{ordinal 3}} {{ordinal 4}}")?,
+            "1st 2nd 3rd 4th"
+        );
+        assert_eq!(
+            render("{{ordinal 11}} {{ordinal 112}} {{ordinal 121}}")?,
+            "11th 112th 121st"
+        );
+        assert_eq!(render("{{random name}}")?, "Foo");
+        assert!(["a", "b"].contains(&render("{{random \"a\" \"b\"}}")?.as_str()));
+        // missing variables render as nothing.
+        assert_eq!(render("{{missing}}")?, "");
+        assert!(render("{{missing name}}").is_err());
+        assert!(render("{{number name}}").is_err());
+        assert!(render("{{random}}").is_err());
+        return Ok(());
+
+        #[derive(serde::Serialize)]
+        struct Vars {
+            name: &'static str,
+            count: u32,
+        }
+    }
+
+    #[test]
+    pub fn test_register_helper() -> Result<(), Error> {
+        use handlebars::{Context, Handlebars, Helper, HelperResult, Output, RenderContext};
+
+        fn shout(
+            _: &Helper<'_, '_>,
+            _: &Handlebars<'_>,
+            _: &Context,
+            _: &mut RenderContext<'_, '_>,
+            out: &mut dyn Output,
+        ) -> HelperResult {
+            out.write("HEY")?;
+            Ok(())
+        }
+
+        super::register_helper("test-shout", shout);
+        let template = Template::compile("{{test-shout 1}}!")?;
+        assert_eq!(template.render_to_string(())?, "HEY!");
+        Ok(())
+    }
 }
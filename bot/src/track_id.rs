@@ -4,6 +4,7 @@ use thiserror::Error;
 
 static YOUTUBE_URL: &str = "https://youtu.be";
 static SPOTIFY_URL: &str = "https://open.spotify.com/track";
+static SPOTIFY_EPISODE_URL: &str = "https://open.spotify.com/episode";
 
 #[derive(
     Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, diesel::FromSqlRow, diesel::AsExpression,
@@ -12,6 +13,8 @@ static SPOTIFY_URL: &str = "https://open.spotify.com/track";
 pub enum TrackId {
     /// A Spotify track.
     Spotify(SpotifyId),
+    /// A Spotify podcast episode.
+    SpotifyEpisode(SpotifyId),
     /// A YouTube track.
     YouTube(String),
 }
@@ -24,6 +27,7 @@ pub enum ParseTrackIdError {
     #[error(
         "bad URL, expected: \
                        https://open.spotify.com/track/<id>, \
+                       https://open.spotify.com/episode/<id>, \
                        https://youtube.com/watch?v=<id>, or \
                        https://youtu.be/<id>"
     )]
@@ -49,24 +53,34 @@ impl std::str::FromStr for TrackId {
         }
 
         if s.starts_with("spotify:track:") {
-            let mut id = s.trim_start_matches("spotify:track:");
-            //Trim parameters
-            if let Some(index) = id.find('?') {
-                id = &id[..index];
-            }
-            let id = SpotifyId::from_base62(id)
-                .map_err(|_| ParseTrackIdError::BadBase62(id.to_string()))?;
+            let id = parse_spotify_id(s.trim_start_matches("spotify:track:"))?;
             return Ok(TrackId::Spotify(id));
         }
 
+        if s.starts_with("spotify:episode:") {
+            let id = parse_spotify_id(s.trim_start_matches("spotify:episode:"))?;
+            return Ok(TrackId::SpotifyEpisode(id));
+        }
+
         Err(ParseTrackIdError::MissingUriPrefix)
     }
 }
 
+/// Parse the id part of a Spotify URI.
+fn parse_spotify_id(mut id: &str) -> Result<SpotifyId, ParseTrackIdError> {
+    //Trim parameters
+    if let Some(index) = id.find('?') {
+        id = &id[..index];
+    }
+
+    SpotifyId::from_base62(id).map_err(|_| ParseTrackIdError::BadBase62(id.to_string()))
+}
+
 impl fmt::Display for TrackId {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             TrackId::Spotify(ref id) => write!(fmt, "spotify:track:{}", id.to_base62()),
+            TrackId::SpotifyEpisode(ref id) => write!(fmt, "spotify:episode:{}", id.to_base62()),
             TrackId::YouTube(ref id) => write!(fmt, "youtube:video:{}", id),
         }
     }
@@ -81,10 +95,18 @@ impl TrackId {
         }
     }
 
+    /// Test if this is a Spotify podcast episode.
+    pub fn is_episode(&self) -> bool {
+        matches!(self, TrackId::SpotifyEpisode(..))
+    }
+
     /// Get the URL for this track.
     pub fn url(&self) -> String {
         match *self {
             TrackId::Spotify(ref id) => format!("{}/{}", SPOTIFY_URL, id.to_base62()),
+            TrackId::SpotifyEpisode(ref id) => {
+                format!("{}/{}", SPOTIFY_EPISODE_URL, id.to_base62())
+            }
             TrackId::YouTube(ref id) => format!("{}/{}", YOUTUBE_URL, id),
         }
     }
@@ -109,13 +131,11 @@ impl TrackId {
                 Some(ref host) if *host == url::Host::Domain("open.spotify.com") => {
                     let parts = url.path().split('/').collect::<Vec<_>>();
 
-                    let id = match parts.as_slice() {
-                        ["", "track", id] => SpotifyId::from_base62(id)
-                            .map_err(|_| ParseTrackIdError::BadBase62((*id).to_string()))?,
-                        _ => return Err(ParseTrackIdError::BadUrl(url.to_string())),
+                    return match parts.as_slice() {
+                        ["", "track", id] => Ok(TrackId::Spotify(parse_spotify_id(id)?)),
+                        ["", "episode", id] => Ok(TrackId::SpotifyEpisode(parse_spotify_id(id)?)),
+                        _ => Err(ParseTrackIdError::BadUrl(url.to_string())),
                     };
-
-                    return Ok(TrackId::Spotify(id));
                 }
                 Some(ref host) if is_long_youtube(host) => {
                     let parts = url.path().split('/').collect::<Vec<_>>();
@@ -217,3 +237,31 @@ impl<'de> serde::Deserialize<'de> for TrackId {
         TrackId::parse_with_prefix_fallback(&s).map_err(serde::de::Error::custom)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SpotifyId, TrackId};
+
+    #[test]
+    fn test_parse_spotify_episode() {
+        let id = SpotifyId::from_base62("512ojhOuo1ktJprKbVcKyQ").unwrap();
+
+        let track_id =
+            TrackId::parse_with_urls("https://open.spotify.com/episode/512ojhOuo1ktJprKbVcKyQ")
+                .unwrap();
+        assert_eq!(track_id, TrackId::SpotifyEpisode(id));
+        assert!(track_id.is_episode());
+
+        let track_id = str::parse::<TrackId>("spotify:episode:512ojhOuo1ktJprKbVcKyQ").unwrap();
+        assert_eq!(track_id, TrackId::SpotifyEpisode(id));
+        assert_eq!(
+            track_id.to_string(),
+            "spotify:episode:512ojhOuo1ktJprKbVcKyQ"
+        );
+
+        let track_id =
+            TrackId::parse_with_urls("https://open.spotify.com/track/512ojhOuo1ktJprKbVcKyQ")
+                .unwrap();
+        assert!(!track_id.is_episode());
+    }
+}
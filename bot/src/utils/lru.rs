@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// A cache which evicts the least recently used entry when it's full, and
+/// entries which are older than its time to live, if it has one.
+#[derive(Debug)]
+pub(crate) struct Lru<K, V> {
+    capacity: usize,
+    ttl: Option<Duration>,
+    /// Incremented on every access to keep track of how recently entries
+    /// were used.
+    clock: u64,
+    entries: HashMap<K, Entry<V>>,
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    inserted_at: Instant,
+    used: u64,
+}
+
+impl<K, V> Lru<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    /// Construct a new cache holding at most `capacity` entries.
+    pub(crate) fn new(capacity: usize, ttl: Option<Duration>) -> Self {
+        Self {
+            capacity,
+            ttl,
+            clock: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The number of cached entries.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Set how many entries are cached, where `0` disables the cache.
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        while self.entries.len() > self.capacity {
+            self.evict();
+        }
+    }
+
+    /// Set how long entries are cached for.
+    pub(crate) fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
+
+    /// Get the entry for the given key, if it's cached and hasn't expired.
+    pub(crate) fn get(&mut self, key: &K, now: Instant) -> Option<V> {
+        let expired = match (self.entries.get(key), self.ttl) {
+            (None, _) => return None,
+            (Some(e), Some(ttl)) => now.saturating_duration_since(e.inserted_at) >= ttl,
+            (Some(..), None) => false,
+        };
+
+        if expired {
+            self.entries.remove(key);
+            return None;
+        }
+
+        self.clock += 1;
+        let e = self.entries.get_mut(key)?;
+        e.used = self.clock;
+        Some(e.value.clone())
+    }
+
+    /// Cache the given entry, evicting the least recently used one if the
+    /// cache is full.
+    pub(crate) fn insert(&mut self, key: K, value: V, now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            self.evict();
+        }
+
+        self.clock += 1;
+
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                inserted_at: now,
+                used: self.clock,
+            },
+        );
+    }
+
+    /// Drop the entry for the given key.
+    pub(crate) fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    /// Drop all entries.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Evict the least recently used entry.
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, e)| e.used)
+            .map(|(k, _)| k.clone());
+
+        if let Some(oldest) = oldest {
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lru;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_lru() {
+        let now = Instant::now();
+        let mut lru = Lru::new(2, Some(Duration::from_secs(60)));
+
+        lru.insert("a", 1, now);
+        lru.insert("b", 2, now);
+        assert_eq!(lru.get(&"a", now), Some(1));
+
+        // NB: "b" is the least recently used.
+        lru.insert("c", 3, now);
+        assert_eq!(lru.get(&"b", now), None);
+        assert_eq!(lru.get(&"a", now), Some(1));
+        assert_eq!(lru.get(&"c", now), Some(3));
+
+        let later = now + Duration::from_secs(60);
+        assert_eq!(lru.get(&"a", later), None);
+        assert_eq!(lru.len(), 1);
+
+        lru.set_ttl(None);
+        assert_eq!(lru.get(&"c", later + Duration::from_secs(3600)), Some(3));
+
+        lru.set_capacity(0);
+        lru.insert("d", 4, later);
+        assert_eq!(lru.len(), 0);
+    }
+}
@@ -2,17 +2,20 @@ use crate::api;
 use crate::prelude::*;
 use percent_encoding::PercentDecode;
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
 use std::mem;
 use std::ops;
 use std::sync::Arc;
 use std::time;
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 
 mod duration;
+mod lru;
 mod respond;
 
 pub(crate) use self::duration::Duration;
+pub(crate) use self::lru::Lru;
 pub(crate) use self::respond::respond;
 
 /// Collection of boxed futures to drive.
@@ -423,6 +426,12 @@ pub fn long_duration(duration: time::Duration) -> String {
 
     let p = partition(duration);
 
+    parts.extend(match p.days {
+        0 => None,
+        1 => Some("one day".to_string()),
+        n => Some(format!("{} days", english_num(n))),
+    });
+
     parts.extend(match p.hours {
         0 => None,
         1 => Some("one hour".to_string()),
@@ -539,6 +548,27 @@ pub fn human_list(list: &[String]) -> Option<String> {
     Some(list)
 }
 
+/// Calculate the edit (Levenshtein) distance between two strings, which is
+/// the number of characters that have to be inserted, removed, or replaced to
+/// turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, b) in b.iter().enumerate() {
+            let replace = diagonal + if a == *b { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = replace.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
 /// Test if character is a URL character.
 fn is_url_character(c: char) -> bool {
     match c {
@@ -641,15 +671,30 @@ impl fmt::Display for Offset {
 #[derive(Debug, Clone, Default)]
 pub struct Cooldown {
     last_action_at: Option<time::Instant>,
+    /// Random extra time added to the cooldown since the last action.
+    extra: time::Duration,
     pub cooldown: Duration,
+    /// The upper bound of random extra time to add to the cooldown each time
+    /// it's poked.
+    pub jitter: Duration,
 }
 
 impl Cooldown {
     /// Create a cooldown from the given duration.
     pub fn from_duration(duration: Duration) -> Self {
+        Self::with_jitter(duration, Duration::default())
+    }
+
+    /// Create a cooldown from the given duration, where each time it's poked
+    /// a random amount of time up to `jitter` is added to it.
+    ///
+    /// This avoids many actions with the same cooldown all happening at once.
+    pub fn with_jitter(duration: Duration, jitter: Duration) -> Self {
         Self {
             last_action_at: None,
+            extra: time::Duration::default(),
             cooldown: duration,
+            jitter,
         }
     }
 
@@ -670,7 +715,7 @@ impl Cooldown {
     pub fn check(&mut self, now: time::Instant) -> Option<time::Duration> {
         if let Some(last_action_at) = self.last_action_at.as_ref() {
             let since_last_action = now - *last_action_at;
-            let cooldown = self.cooldown.as_std();
+            let cooldown = self.cooldown.as_std() + self.extra;
 
             if since_last_action < cooldown {
                 return Some(cooldown - since_last_action);
@@ -682,7 +727,154 @@ impl Cooldown {
 
     /// Poke the cooldown with the current time
     pub fn poke(&mut self, now: time::Instant) {
+        use rand::Rng as _;
+
         self.last_action_at = Some(now);
+
+        let jitter = self.jitter.as_std();
+
+        self.extra = if jitter.is_zero() {
+            time::Duration::default()
+        } else {
+            rand::thread_rng().gen_range(time::Duration::default()..=jitter)
+        };
+    }
+
+    /// Poke the cooldown with the current time, and construct a timer which
+    /// completes once it's open again.
+    ///
+    /// This is used to drive periodic actions, so that they get the same
+    /// jitter as everything else using a cooldown.
+    pub fn sleep(&mut self) -> tokio::time::Sleep {
+        self.poke(time::Instant::now());
+        tokio::time::sleep(self.cooldown.as_std() + self.extra)
+    }
+}
+
+/// A registry of named cooldowns, which are shared by everything using the
+/// same name.
+#[derive(Debug, Clone, Default)]
+pub struct Cooldowns {
+    inner: Arc<parking_lot::Mutex<HashMap<String, Cooldown>>>,
+}
+
+impl Cooldowns {
+    /// Lock the registry to access the cooldowns in it.
+    pub fn lock(&self) -> parking_lot::MutexGuard<'_, HashMap<String, Cooldown>> {
+        self.inner.lock()
+    }
+}
+
+/// A cooldown which is tracked separately for each user, so that one user
+/// performing an action doesn't prevent everyone else from doing it.
+///
+/// Users are forgotten once their cooldown is open again, which bounds the
+/// memory used to the number of users currently on cooldown.
+#[derive(Debug, Clone, Default)]
+pub struct PerUserCooldown {
+    users: HashMap<String, Cooldown>,
+    pub cooldown: Duration,
+    /// The upper bound of random extra time to add to the cooldown of a user
+    /// each time it's poked.
+    pub jitter: Duration,
+}
+
+impl PerUserCooldown {
+    /// Create a per-user cooldown from the given duration.
+    pub fn from_duration(duration: Duration) -> Self {
+        Self {
+            users: HashMap::new(),
+            cooldown: duration,
+            jitter: Duration::default(),
+        }
+    }
+
+    /// Test if the given user is allowed to perform the action, and start
+    /// their cooldown if they are.
+    pub fn is_open(&mut self, user: &str) -> bool {
+        let now = time::Instant::now();
+
+        match self.check(user, now) {
+            None => {
+                self.poke(user, now);
+                true
+            }
+            Some(..) => false,
+        }
+    }
+
+    /// Test how much time remains until the cooldown is open for the given
+    /// user.
+    ///
+    /// Users whose cooldown is open are pruned.
+    pub fn check(&mut self, user: &str, now: time::Instant) -> Option<time::Duration> {
+        let cooldown = self.cooldown;
+
+        self.users.retain(|_, c| {
+            c.cooldown = cooldown;
+            c.check(now).is_some()
+        });
+
+        self.users.get_mut(user)?.check(now)
+    }
+
+    /// Poke the cooldown of the given user with the current time.
+    pub fn poke(&mut self, user: &str, now: time::Instant) {
+        let c = self.users.entry(user.to_string()).or_default();
+
+        c.cooldown = self.cooldown;
+        c.jitter = self.jitter;
+        c.poke(now);
+    }
+
+    /// Clear the cooldown of the given user.
+    pub fn clear(&mut self, user: &str) {
+        self.users.remove(user);
+    }
+}
+
+/// Limits how many times an action can be performed within a session, like
+/// a stream.
+///
+/// Uses are counted against a session key, and the count starts over when the
+/// key changes.
+#[derive(Debug, Clone)]
+pub struct UsageLimit<K> {
+    session: Option<K>,
+    used: u32,
+    pub limit: u32,
+}
+
+impl<K> UsageLimit<K>
+where
+    K: PartialEq,
+{
+    /// Create a usage limit which allows `limit` uses per session.
+    pub fn new(limit: u32) -> Self {
+        Self {
+            session: None,
+            used: 0,
+            limit,
+        }
+    }
+
+    /// Test if another use is allowed in the given session.
+    pub fn check(&self, session: &K) -> bool {
+        if self.session.as_ref() != Some(session) {
+            return self.limit > 0;
+        }
+
+        self.used < self.limit
+    }
+
+    /// Count a use in the given session.
+    pub fn poke(&mut self, session: K) {
+        if self.session.as_ref() != Some(&session) {
+            self.session = Some(session);
+            self.used = 0;
+        }
+
+        self.used += 1;
     }
 }
 
@@ -735,6 +927,53 @@ impl Restart {
     }
 }
 
+/// Signal that the bot is shutting down, so that components get a chance to
+/// wind down gracefully.
+#[derive(Clone)]
+pub struct Shutdown {
+    tx: Arc<watch::Sender<bool>>,
+    rx: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Construct a new shutdown signal.
+    pub fn new() -> Self {
+        let (tx, rx) = watch::channel(false);
+
+        Self {
+            tx: Arc::new(tx),
+            rx,
+        }
+    }
+
+    /// Trigger the shutdown.
+    pub fn trigger(&self) {
+        let _ = self.tx.send(true);
+    }
+
+    /// Test if the shutdown has been triggered.
+    pub fn is_triggered(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Wait until the shutdown has been triggered.
+    pub async fn wait(&self) {
+        let mut rx = self.rx.clone();
+
+        while !*rx.borrow() {
+            if rx.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// PT-formatted duration.
 #[derive(Debug, Clone)]
 pub struct PtDuration(time::Duration);
@@ -845,7 +1084,11 @@ impl serde::Serialize for PtDuration {
 
 #[cfg(test)]
 mod tests {
-    use super::{Offset, TrimmedWords, Urls, Words};
+    use super::{
+        edit_distance, Cooldown, Duration, Offset, PerUserCooldown, Shutdown, TrimmedWords, Urls,
+        UsageLimit, Words,
+    };
+    use std::time;
 
     #[test]
     pub fn test_offset() -> Result<(), anyhow::Error> {
@@ -859,6 +1102,88 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    pub fn test_cooldown_jitter() {
+        let now = time::Instant::now();
+        let mut cooldown = Cooldown::with_jitter(Duration::seconds(10), Duration::seconds(5));
+        assert!(cooldown.check(now).is_none());
+        cooldown.poke(now);
+
+        let remaining = cooldown.check(now).unwrap();
+        assert!(remaining >= time::Duration::from_secs(10));
+        assert!(remaining <= time::Duration::from_secs(15));
+        assert!(cooldown
+            .check(now + time::Duration::from_secs(15))
+            .is_none());
+
+        // no jitter is the same as a plain cooldown.
+        let mut cooldown = Cooldown::from_duration(Duration::seconds(10));
+        cooldown.poke(now);
+        assert_eq!(cooldown.check(now), Some(time::Duration::from_secs(10)));
+    }
+
+    #[test]
+    pub fn test_per_user_cooldown() {
+        let now = time::Instant::now();
+        let mut cooldown = PerUserCooldown::from_duration(Duration::seconds(10));
+        assert!(cooldown.check("foo", now).is_none());
+        cooldown.poke("foo", now);
+
+        assert_eq!(
+            cooldown.check("foo", now),
+            Some(time::Duration::from_secs(10))
+        );
+        assert!(cooldown.check("bar", now).is_none());
+
+        let later = now + time::Duration::from_secs(5);
+        cooldown.poke("bar", later);
+        assert!(cooldown.check("bar", later).is_some());
+
+        // NB: a change in duration applies to users already on cooldown.
+        cooldown.cooldown = Duration::seconds(20);
+        assert_eq!(
+            cooldown.check("foo", later),
+            Some(time::Duration::from_secs(15))
+        );
+
+        // users are pruned once their cooldown is open.
+        let later = now + time::Duration::from_secs(20);
+        assert!(cooldown.check("foo", later).is_none());
+        assert_eq!(cooldown.users.len(), 1);
+        assert!(cooldown.users.contains_key("bar"));
+
+        cooldown.clear("bar");
+        assert!(cooldown.check("bar", later).is_none());
+    }
+
+    #[test]
+    pub fn test_usage_limit() {
+        let mut limit = UsageLimit::new(2);
+        assert!(limit.check(&1));
+        limit.poke(1);
+        assert!(limit.check(&1));
+        limit.poke(1);
+        assert!(!limit.check(&1));
+
+        // a new session starts over.
+        assert!(limit.check(&2));
+        limit.poke(2);
+        assert!(limit.check(&2));
+
+        assert!(!UsageLimit::new(0).check(&1));
+    }
+
+    #[test]
+    pub fn test_edit_distance() {
+        assert_eq!(edit_distance("!song", "!song"), 0);
+        assert_eq!(edit_distance("!sogn", "!song"), 2);
+        assert_eq!(edit_distance("!son", "!song"), 1);
+        assert_eq!(edit_distance("!songs", "!song"), 1);
+        assert_eq!(edit_distance("", "!song"), 5);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("👌🏻", "👌"), 1);
+    }
+
     #[test]
     pub fn test_trimmed_words() {
         let out = TrimmedWords::new("hello, do you feel alive?").collect::<Vec<_>>();
@@ -921,4 +1246,19 @@ mod tests {
             ],
         );
     }
+
+    #[tokio::test]
+    pub async fn test_shutdown() {
+        let shutdown = Shutdown::new();
+        let other = shutdown.clone();
+        assert!(!other.is_triggered());
+
+        let waiting = tokio::spawn(async move { other.wait().await });
+        shutdown.trigger();
+        waiting.await.unwrap();
+
+        assert!(shutdown.is_triggered());
+        // NB: completes right away once triggered.
+        shutdown.wait().await;
+    }
 }
@@ -27,7 +27,21 @@ mod settings;
 
 use self::{cache::Cache, chat::Chat, settings::Settings};
 
-pub const URL: &str = "http://localhost:12345";
+/// The address the web server binds to by default.
+pub const DEFAULT_HOST: &str = "0.0.0.0";
+/// The port the web server binds to by default.
+pub const DEFAULT_PORT: u16 = 12345;
+
+/// Construct the URL the web server can be reached on locally, when bound to
+/// the given host and port.
+pub fn url(host: &str, port: u16) -> String {
+    let host = match str::parse::<std::net::IpAddr>(host) {
+        Ok(ip) if ip.is_unspecified() || ip.is_loopback() => "localhost",
+        _ => host,
+    };
+
+    format!("http://{}:{}", host, port)
+}
 
 mod assets {
     #[derive(rust_embed::RustEmbed)]
@@ -259,6 +273,38 @@ impl Commands {
                 }
             });
 
+        let edit_limits = warp::put()
+            .and(path!("commands" / Fragment / Fragment / "limits").and(path::end()))
+            .and(body::json())
+            .and_then({
+                let api = api.clone();
+                move |channel: Fragment, name: Fragment, body: db::CommandLimits| {
+                    let api = api.clone();
+
+                    async move {
+                        api.edit_limits(channel.as_str(), name.as_str(), body)
+                            .await
+                            .map_err(custom_reject)
+                    }
+                }
+            });
+
+        let edit_help = warp::put()
+            .and(path!("commands" / Fragment / Fragment / "help").and(path::end()))
+            .and(body::json())
+            .and_then({
+                let api = api.clone();
+                move |channel: Fragment, name: Fragment, body: PutHelp| {
+                    let api = api.clone();
+
+                    async move {
+                        api.edit_help(channel.as_str(), name.as_str(), body)
+                            .await
+                            .map_err(custom_reject)
+                    }
+                }
+            });
+
         let edit = warp::put()
             .and(path!("commands" / Fragment / Fragment).and(path::end()))
             .and(body::json())
@@ -273,7 +319,13 @@ impl Commands {
                 }
             });
 
-        return list.or(delete).or(edit).or(edit_disabled).boxed();
+        return list
+            .or(delete)
+            .or(edit)
+            .or(edit_disabled)
+            .or(edit_limits)
+            .or(edit_help)
+            .boxed();
 
         #[derive(serde::Deserialize)]
         pub struct PutCommand {
@@ -324,6 +376,53 @@ impl Commands {
         Ok(warp::reply::json(&EMPTY))
     }
 
+    /// Edit the limits on how often the given command can be used.
+    async fn edit_limits(
+        &self,
+        channel: &str,
+        name: &str,
+        limits: db::CommandLimits,
+    ) -> Result<impl warp::Reply> {
+        if !self
+            .commands()
+            .await?
+            .edit_limits(channel, name, limits)
+            .await?
+        {
+            bail!("no such command");
+        }
+
+        Ok(warp::reply::json(&EMPTY))
+    }
+
+    /// Edit the description and usage of the given command, as shown by
+    /// `!help`.
+    async fn edit_help(
+        &self,
+        channel: &str,
+        name: &str,
+        help: PutHelp,
+    ) -> Result<impl warp::Reply> {
+        let commands = self.commands().await?;
+
+        if !commands
+            .edit_description(channel, name, non_empty(help.description))
+            .await?
+        {
+            bail!("no such command");
+        }
+
+        commands
+            .edit_usage(channel, name, non_empty(help.usage))
+            .await?;
+
+        return Ok(warp::reply::json(&EMPTY));
+
+        fn non_empty(s: Option<String>) -> Option<String> {
+            s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+        }
+    }
+
     /// Delete the given command by key.
     async fn delete(&self, channel: &str, name: &str) -> Result<impl warp::Reply> {
         self.commands().await?.delete(channel, name).await?;
@@ -331,6 +430,15 @@ impl Commands {
     }
 }
 
+/// The description and usage of a command.
+#[derive(serde::Deserialize)]
+struct PutHelp {
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    usage: Option<String>,
+}
+
 /// Promotions endpoint.
 #[derive(Clone)]
 struct Promotions(injector::Ref<db::Promotions>);
@@ -588,6 +696,8 @@ struct Auth {
     active_connections: Arc<RwLock<HashMap<String, ConnectionMeta>>>,
     auth: auth::Auth,
     settings: injector::Ref<crate::Settings>,
+    /// The URL the web interface is reached on.
+    url: String,
 }
 
 #[derive(serde::Deserialize)]
@@ -601,11 +711,13 @@ impl Auth {
         auth: auth::Auth,
         active_connections: Arc<RwLock<HashMap<String, ConnectionMeta>>>,
         settings: injector::Ref<crate::Settings>,
+        url: String,
     ) -> filters::BoxedFilter<(impl warp::Reply,)> {
         let api = Auth {
             auth,
             active_connections,
             settings,
+            url,
         };
 
         let route = warp::get()
@@ -764,7 +876,7 @@ impl Auth {
             settings.set("remote/secret-key", key.as_str()).await?;
         }
 
-        let mut parts = URL.parse::<warp::http::Uri>()?.into_parts();
+        let mut parts = self.url.parse::<warp::http::Uri>()?.into_parts();
         parts.path_and_query = Some(warp::http::uri::PathAndQuery::from_static(
             "?received-key=true",
         ));
@@ -778,8 +890,11 @@ impl Auth {
 #[derive(Clone)]
 struct Api {
     player: injector::Ref<player::Player>,
+    settings: injector::Ref<crate::Settings>,
+    channel: injector::Ref<String>,
     after_streams: injector::Ref<db::AfterStreams>,
     currency: injector::Ref<Currency>,
+    spotify: injector::Ref<Arc<api::Spotify>>,
     latest: crate::settings::Var<Option<api::github::Release>>,
 }
 
@@ -874,6 +989,72 @@ impl Api {
         }
     }
 
+    /// Get the player, or `None` if the song feature is disabled.
+    async fn song_player(&self) -> Result<Option<player::Player>> {
+        let enabled = match self.settings.read().await.as_ref() {
+            Some(settings) => settings.get::<bool>("song/enabled").await?,
+            None => None,
+        };
+
+        if !enabled.unwrap_or_default() {
+            return Ok(None);
+        }
+
+        Ok(self.player.load().await)
+    }
+
+    /// Get the currently playing song.
+    async fn current_song(self) -> Result<warp::reply::Response, Error> {
+        use warp::Reply as _;
+
+        let player = match self.song_player().await? {
+            Some(player) => player,
+            None => return Ok(no_content()),
+        };
+
+        let song = match player.current().await {
+            Some(song) => song,
+            None => return Ok(no_content()),
+        };
+
+        let data = song.data(song.state())?;
+        Ok(warp::reply::json(&data).into_response())
+    }
+
+    /// Get the songs queued up after the current song.
+    async fn queue(self) -> Result<warp::reply::Response, Error> {
+        use warp::Reply as _;
+
+        let player = match self.song_player().await? {
+            Some(player) => player,
+            None => return Ok(no_content()),
+        };
+
+        let items = player.queue().await;
+
+        let queue = items
+            .iter()
+            .map(|item| QueueItem {
+                track_id: &item.track_id,
+                name: item.track.name(),
+                artists: item.track.artists(),
+                user: item.user.as_deref(),
+                duration: utils::digital_duration(item.duration),
+            })
+            .collect::<Vec<_>>();
+
+        return Ok(warp::reply::json(&queue).into_response());
+
+        #[derive(serde::Serialize)]
+        struct QueueItem<'a> {
+            track_id: &'a TrackId,
+            name: String,
+            artists: Option<String>,
+            user: Option<&'a str>,
+            duration: String,
+        }
+    }
+
     /// Access underlying after streams abstraction.
     async fn after_streams(&self) -> Result<RwLockReadGuard<'_, db::AfterStreams>> {
         match self.after_streams.read().await {
@@ -924,6 +1105,37 @@ impl Api {
         Ok(warp::reply::json(&balances))
     }
 
+    /// Get the highest balances in the current channel.
+    async fn leaderboard(self) -> Result<impl warp::Reply, Error> {
+        let currency = self.currency.load().await.ok_or(Error::NotFound)?;
+
+        let channel = match self.channel.load().await {
+            Some(channel) => channel,
+            None => return Ok(warp::reply::json(&Vec::<Balance>::new())),
+        };
+
+        let limit = match self.settings.read().await.as_ref() {
+            Some(settings) => settings
+                .get::<u32>("currency/top/limit")
+                .await
+                .map_err(anyhow::Error::from)?,
+            None => None,
+        };
+
+        let balances = currency
+            .top_balances(&channel, limit.unwrap_or(5) as usize)
+            .await?
+            .into_iter()
+            .map(|b| Balance {
+                name: b.user,
+                balance: b.amount,
+                watch_time: b.watch_time,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(warp::reply::json(&balances))
+    }
+
     /// Get version information.
     async fn version(&self) -> Result<impl warp::Reply, Error> {
         let info = Version {
@@ -968,6 +1180,24 @@ impl Api {
             Latest { version, asset }
         }
     }
+
+    /// Get metrics on how the bot uses external APIs.
+    async fn metrics(&self) -> Result<impl warp::Reply, Error> {
+        let spotify = self.spotify.read().await;
+        let spotify_track_cache = spotify.as_ref().map(|s| s.track_cache().stats());
+        let spotify = spotify.as_ref().map(|s| s.limiter().stats());
+
+        return Ok(warp::reply::json(&Metrics {
+            spotify,
+            spotify_track_cache,
+        }));
+
+        #[derive(serde::Serialize)]
+        struct Metrics {
+            spotify: Option<api::LimiterStats>,
+            spotify_track_cache: Option<api::spotify::TrackCacheStats>,
+        }
+    }
 }
 
 /// Set up the web endpoint.
@@ -980,8 +1210,15 @@ pub async fn setup(
     command_bus: bus::Bus<bus::Command>,
     auth: auth::Auth,
     latest: crate::settings::Var<Option<api::github::Release>>,
+    host: &str,
+    port: u16,
 ) -> Result<(Server, impl Future<Output = ()>)> {
-    let addr: SocketAddr = str::parse("0.0.0.0:12345")?;
+    let addr: SocketAddr = match str::parse::<std::net::IpAddr>(host) {
+        Ok(ip) => SocketAddr::new(ip, port),
+        Err(..) => bail!("bad `web/host`, expected an IP address: {}", host),
+    };
+
+    let public_url = url(host, port);
 
     let channel = injector
         .var_key(Key::<String>::tagged(tags::Globals::Channel)?)
@@ -992,8 +1229,11 @@ pub async fn setup(
 
     let api = Api {
         player: player.clone(),
+        settings: injector.var().await,
+        channel: channel.clone(),
         after_streams: injector.var().await,
         currency: injector.var().await,
+        spotify: injector.var().await,
         latest,
     };
 
@@ -1019,6 +1259,50 @@ pub async fn setup(
             }))
             .boxed();
 
+        let route = route
+            .or(warp::get().and(warp::path("metrics")).and_then({
+                let api = api.clone();
+                move || {
+                    let api = api.clone();
+                    async move { api.metrics().await.map_err(custom_reject) }
+                }
+            }))
+            .boxed();
+
+        let route = route
+            .or(warp::get()
+                .and(path!("current-song").and(path::end()))
+                .and_then({
+                    let api = api.clone();
+                    move || {
+                        let api = api.clone();
+                        async move { api.current_song().await.map_err(custom_reject) }
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get().and(path!("queue").and(path::end())).and_then({
+                let api = api.clone();
+                move || {
+                    let api = api.clone();
+                    async move { api.queue().await.map_err(custom_reject) }
+                }
+            }))
+            .boxed();
+
+        let route = route
+            .or(warp::get()
+                .and(path!("leaderboard").and(path::end()))
+                .and_then({
+                    let api = api.clone();
+                    move || {
+                        let api = api.clone();
+                        async move { api.leaderboard().await.map_err(custom_reject) }
+                    }
+                }))
+            .boxed();
+
         let route = route
             .or(warp::get().and(warp::path("devices")).and_then({
                 let api = api.clone();
@@ -1083,6 +1367,7 @@ pub async fn setup(
                 auth,
                 active_connections.clone(),
                 injector.var().await,
+                public_url.clone(),
             ))
             .boxed());
         let route = route.or(Aliases::route(injector.var().await));
@@ -1148,6 +1433,7 @@ pub async fn setup(
     let server = Server {
         player,
         active_connections,
+        url: public_url,
     };
 
     return Ok((server, server_future));
@@ -1263,9 +1549,21 @@ pub struct Server {
     player: injector::Ref<player::Player>,
     /// Callbacks for when we have received a token.
     active_connections: Arc<RwLock<HashMap<String, ConnectionMeta>>>,
+    /// The URL the server can be reached on.
+    url: String,
 }
 
 impl Server {
+    /// The URL the server can be reached on.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The URL of the page showing the song queue.
+    pub fn queue_url(&self) -> String {
+        format!("{}/queue", self.url)
+    }
+
     pub async fn update_connection(&self, id: &str, connection: ConnectionMeta) {
         self.active_connections
             .write()
@@ -1284,6 +1582,12 @@ pub struct ReceivedToken {
     pub state: String,
 }
 
+/// An empty response, used when there's nothing to show.
+fn no_content() -> warp::reply::Response {
+    use warp::Reply as _;
+    warp::reply::with_status(warp::reply(), warp::http::StatusCode::NO_CONTENT).into_response()
+}
+
 /// Connecting a bus to a websocket connection.
 fn send_bus<T>(bus: bus::Bus<T>) -> filters::BoxedFilter<(impl warp::Reply,)>
 where
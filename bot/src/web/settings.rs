@@ -59,6 +59,23 @@ impl Settings {
             }))
             .boxed();
 
+        let config = warp::get()
+            .and(warp::path("config").and(path::tail()).and_then({
+                let api = api.clone();
+                move |key: path::Tail| {
+                    let api = api.clone();
+
+                    async move {
+                        let key =
+                            str::parse::<Fragment>(key.as_str()).map_err(super::custom_reject)?;
+                        api.get_config(key.as_str())
+                            .await
+                            .map_err(super::custom_reject)
+                    }
+                }
+            }))
+            .boxed();
+
         let delete = warp::delete()
             .and(warp::path("settings").and(path::tail()).and_then({
                 let api = api.clone();
@@ -97,7 +114,7 @@ impl Settings {
             )
             .boxed();
 
-        list.or(get).or(delete).or(edit).boxed()
+        list.or(get).or(config).or(delete).or(edit).boxed()
     }
 
     /// Access underlying settings abstraction.
@@ -173,6 +190,14 @@ impl Settings {
         Ok(warp::reply::json(&setting))
     }
 
+    /// Get the effective value of the given setting, as long as it's safe to
+    /// show.
+    async fn get_config(&self, key: &str) -> Result<impl warp::Reply> {
+        let settings = self.settings().await?;
+        let setting = settings.inspect(key).await?;
+        Ok(warp::reply::json(&setting))
+    }
+
     /// Delete the given setting by key.
     async fn edit_setting(&self, key: &str, value: serde_json::Value) -> Result<impl warp::Reply> {
         let settings = self.settings().await?;